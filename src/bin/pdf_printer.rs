@@ -32,7 +32,7 @@ use std::fs;
 use std::io::Write;
 use std::path::Path;
 use std::process;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use env_logger::Builder;
 use log::{debug, error, log, Level, LevelFilter};
@@ -41,16 +41,23 @@ use clap::{App, Arg};
 use serde_json::Value;
 
 use parsley_rust::pcore::parsebuffer::{
-    LocatedVal, Location, ParseBuffer, ParseResult, ParsleyParser, StreamBufferT,
+    LocatedVal, Location, ParseBuffer, ParseBufferT, ParseResult, ParsleyParser, StreamBufferT,
 };
+use parsley_rust::pcore::transforms::{BufferTransformT, RestrictView};
 use parsley_rust::pdf_lib::catalog::catalog_type;
 use parsley_rust::pdf_lib::pdf_content_streams::{TextExtractor, TextToken};
+use parsley_rust::pdf_lib::pdf_linearization::check_linearization;
 use parsley_rust::pdf_lib::pdf_obj::{ObjectId, PDFObjContext, PDFObjT};
 use parsley_rust::pdf_lib::pdf_page_dom::Resources;
 use parsley_rust::pdf_lib::pdf_page_dom::{to_page_dom, FeaturePresence, PageKid};
-use parsley_rust::pdf_lib::pdf_streams::decode_stream;
-use parsley_rust::pdf_lib::pdf_traverse_xref::{parse_file, FileInfo};
+use parsley_rust::pdf_lib::pdf_sanitize::{removals_to_json, sanitize};
+use parsley_rust::pdf_lib::pdf_signatures::check_signatures;
+use parsley_rust::pdf_lib::pdf_streams::decode_stream_with_budget;
+use parsley_rust::pdf_lib::pdf_traverse_xref::{
+    parse_buffer_revisions, parse_file_with_options, FileInfo, ParseOptions,
+};
 use parsley_rust::pdf_lib::pdf_type_check::{check_type, TypeCheckContext};
+use parsley_rust::pdf_lib::pdf_xref_check::check_xref;
 
 #[cfg(feature = "kuduafl")]
 use parsley_rust::pdf_lib::pdf_traverse_xref::parse_data;
@@ -92,7 +99,7 @@ macro_rules! exit_log {
 
 // Perform a breadth-first traversal of the root object, logging
 // each object type and location as we go.
-fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFObjT>>) {
+fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Arc<LocatedVal<PDFObjT>>) {
     if false {
         debug!("Beginning breadth-first traversal of root object:");
     }
@@ -112,9 +119,9 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
     };
 
     let mut obj_queue = VecDeque::new();
-    obj_queue.push_back((Rc::clone(root_obj), 0)); // depth 0
+    obj_queue.push_back((Arc::clone(root_obj), 0)); // depth 0
     let mut processed = BTreeSet::new();
-    processed.insert(Rc::clone(root_obj));
+    processed.insert(Arc::clone(root_obj));
     while !obj_queue.is_empty() {
         let o = obj_queue.pop_front();
         if o.is_none() {
@@ -127,8 +134,8 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
                 log_obj("array", o.as_ref() as &dyn Location, depth);
                 for elem in a.objs() {
                     if !processed.contains(elem) {
-                        obj_queue.push_back((Rc::clone(elem), depth + 1));
-                        processed.insert(Rc::clone(elem));
+                        obj_queue.push_back((Arc::clone(elem), depth + 1));
+                        processed.insert(Arc::clone(elem));
                     }
                 }
             },
@@ -136,8 +143,8 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
                 log_obj("dict", o.as_ref() as &dyn Location, depth);
                 for (_, v) in d.map().iter() {
                     if !processed.contains(v) {
-                        obj_queue.push_back((Rc::clone(v), depth + 1));
-                        processed.insert(Rc::clone(v));
+                        obj_queue.push_back((Arc::clone(v), depth + 1));
+                        processed.insert(Arc::clone(v));
                     }
                 }
             },
@@ -146,20 +153,18 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
                 for (_, v) in s.dict().val().map().iter() {
                     // TODO: print key names
                     if !processed.contains(v) {
-                        obj_queue.push_back((Rc::clone(v), depth + 1));
-                        processed.insert(Rc::clone(v));
+                        obj_queue.push_back((Arc::clone(v), depth + 1));
+                        processed.insert(Arc::clone(v));
                     }
                 }
-                if !ctxt.is_encrypted() {
-                    match decode_stream(s) {
-                        Ok(_) => (),
-                        Err(e) => ta3_log!(
-                            Level::Warn,
-                            fi.file_offset(o.start()),
-                            " error decoding stream: {:?}",
-                            e
-                        ),
-                    }
+                match decode_stream_with_budget(s, ctxt.decode_budget()) {
+                    Ok(_) => (),
+                    Err(e) => ta3_log!(
+                        Level::Warn,
+                        fi.file_offset(o.start()),
+                        " error decoding stream: {:?}",
+                        e
+                    ),
                 }
             },
             PDFObjT::Reference(r) => {
@@ -168,8 +173,8 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
                 match ctxt.lookup_obj(r.id()) {
                     Some(obj) => {
                         if !processed.contains(obj) {
-                            obj_queue.push_back((Rc::clone(obj), depth + 1));
-                            processed.insert(Rc::clone(obj));
+                            obj_queue.push_back((Arc::clone(obj), depth + 1));
+                            processed.insert(Arc::clone(obj));
                         }
                     },
                     None => ta3_log!(
@@ -182,7 +187,7 @@ fn dump_root(fi: &FileInfo, ctxt: &PDFObjContext, root_obj: &Rc<LocatedVal<PDFOb
                 }
             },
             PDFObjT::Boolean(_) => log_obj("boolean", o.as_ref() as &dyn Location, depth),
-            PDFObjT::String(_) => log_obj("string", o.as_ref() as &dyn Location, depth),
+            PDFObjT::String(_, _) => log_obj("string", o.as_ref() as &dyn Location, depth),
             PDFObjT::Name(_) => log_obj("name", o.as_ref() as &dyn Location, depth),
             PDFObjT::Null(_) => log_obj("null", o.as_ref() as &dyn Location, depth),
             PDFObjT::Comment(_) => log_obj("comment", o.as_ref() as &dyn Location, depth),
@@ -231,7 +236,7 @@ fn extract_text(
 
 fn dump_file(fi: &FileInfo, ctxt: &mut PDFObjContext, root_id: ObjectId) {
     // TODO: this constraint should be enforced in the library.
-    let root_obj: &Rc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
+    let root_obj: &Arc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
         Some(obj) => obj,
         None => exit_log!(0, "Root object {:?} not found!", root_id),
     };
@@ -241,14 +246,14 @@ fn dump_file(fi: &FileInfo, ctxt: &mut PDFObjContext, root_id: ObjectId) {
 }
 
 fn type_check_file(fi: &FileInfo, ctxt: &mut PDFObjContext, root_id: ObjectId) {
-    let root_obj: &Rc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
+    let root_obj: &Arc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
         Some(obj) => obj,
         None => exit_log!(0, "Root object {:?} not found!", root_id),
     };
 
     let mut tctx = TypeCheckContext::new();
     let typ = catalog_type(&mut tctx);
-    if let Some(err) = check_type(ctxt, &tctx, Rc::clone(root_obj), typ) {
+    if let Some(err) = check_type(ctxt, &tctx, Arc::clone(root_obj), typ) {
         exit_log!(
             fi.file_offset(err.loc_start()),
             "Type Check Error: {:?}",
@@ -260,7 +265,7 @@ fn type_check_file(fi: &FileInfo, ctxt: &mut PDFObjContext, root_id: ObjectId) {
 fn file_extract_text(
     ctxt: &mut PDFObjContext, root_id: ObjectId, text_dump_file: &mut Option<fs::File>,
 ) {
-    let root_obj: &Rc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
+    let root_obj: &Arc<LocatedVal<PDFObjT>> = match ctxt.lookup_obj(root_id) {
         Some(obj) => obj,
         None => exit_log!(0, "Root object {:?} not found!", root_id),
     };
@@ -293,22 +298,24 @@ fn file_extract_text(
                 let mut buf = ParseBuffer::new(Vec::new());
                 '_content_loop: for c in l.contents() {
                     match c.val() {
-                        PDFObjT::Stream(s) => match decode_stream(s) {
-                            Ok(cs) => {
-                                buf.append(b" ");
-                                buf.append(cs.content());
-                            },
-                            Err(e) => {
-                                ta3_log!(
-                                    Level::Warn,
-                                    0,
-                                    " collecting error when decoding stream in page {:?}: {:?}",
-                                    pid,
-                                    e
-                                );
-                                // go to the next page
-                                continue 'page_loop
-                            },
+                        PDFObjT::Stream(s) => {
+                            match decode_stream_with_budget(s, ctxt.decode_budget()) {
+                                Ok(cs) => {
+                                    buf.append(b" ");
+                                    buf.append(cs.content());
+                                },
+                                Err(e) => {
+                                    ta3_log!(
+                                        Level::Warn,
+                                        0,
+                                        " collecting error when decoding stream in page {:?}: {:?}",
+                                        pid,
+                                        e
+                                    );
+                                    // go to the next page
+                                    continue 'page_loop
+                                },
+                            }
                         },
                         _ => {
                             ta3_log!(
@@ -341,6 +348,122 @@ fn file_extract_text(
     }
 }
 
+fn sanitize_file(
+    test_file: &str, ctxt: &PDFObjContext, root_id: ObjectId, out_file: &str,
+    log_file: Option<&str>,
+) {
+    let data = match fs::read(test_file) {
+        Ok(d) => d,
+        Err(e) => exit_log!(0, "Could not read {}: {}", test_file, e),
+    };
+    let (pdf, log) = match sanitize(ctxt, root_id, &data) {
+        Ok(v) => v,
+        Err(e) => exit_log!(0, "Could not sanitize file: {}", e),
+    };
+    if let Err(e) = fs::write(out_file, pdf) {
+        exit_log!(0, "Could not write sanitized file at {}: {}", out_file, e)
+    }
+    match log_file {
+        Some(f) => {
+            let json = removals_to_json(&log);
+            if let Err(e) = fs::write(f, format!("{:#}\n", json)) {
+                exit_log!(0, "Could not write sanitizer log at {}: {}", f, e)
+            }
+        },
+        None => {
+            for r in &log {
+                ta3_log!(Level::Info, 0, "sanitizer removed {}", r.to_json())
+            }
+        },
+    }
+}
+
+// Maps the file of the parsed document, so that the checks that
+// need its data do not read another copy of it.
+fn map_file(fi: &FileInfo) -> ParseBuffer {
+    let file = match fs::File::open(fi.path()) {
+        Ok(f) => f,
+        Err(e) => exit_log!(0, "Could not open {}: {}", fi.path().display(), e),
+    };
+    match ParseBuffer::map_file(&file) {
+        Ok(pb) => pb,
+        Err(e) => exit_log!(0, "Could not read {}: {}", fi.path().display(), e),
+    }
+}
+
+fn check_file_signatures(fi: &FileInfo, ctxt: &PDFObjContext, root_id: ObjectId) {
+    let pb = map_file(fi);
+    let view = RestrictView::new(0, pb.size()).transform(&pb).unwrap();
+    let revs = match parse_buffer_revisions(fi.path(), view) {
+        Ok((_, revs)) => revs,
+        Err(e) => exit_log!(e.offset(), "Cannot parse revisions: {}", e.msg()),
+    };
+    for r in check_signatures(fi, ctxt, root_id, pb.buf(), &revs) {
+        let (num, gen) = r.field();
+        for e in r.errors() {
+            ta3_log!(Level::Error, 0, "signature field ({},{}): {}", num, gen, e)
+        }
+        for c in r.changes() {
+            ta3_log!(
+                Level::Warn,
+                0,
+                "signature field ({},{}): object ({},{}) {:?} in later revision {}",
+                num,
+                gen,
+                c.object().0,
+                c.object().1,
+                c.kind(),
+                c.revision()
+            )
+        }
+        if r.is_valid() && !r.is_modified() {
+            ta3_log!(
+                Level::Info,
+                0,
+                "signature field ({},{}) covers the whole file",
+                num,
+                gen
+            )
+        }
+    }
+}
+
+fn check_file_linearization(fi: &FileInfo, opts: &ParseOptions) {
+    let pb = map_file(fi);
+    match check_linearization(fi.path(), pb.buf(), opts) {
+        Ok(Some(r)) => {
+            for e in r.errors() {
+                ta3_log!(Level::Error, 0, "linearization: {}", e)
+            }
+            if r.is_valid() {
+                ta3_log!(Level::Info, 0, "linearization is consistent with the file")
+            }
+        },
+        Ok(None) => ta3_log!(Level::Info, 0, "file is not linearized"),
+        Err(e) => exit_log!(e.offset(), "Cannot check linearization: {}", e.msg()),
+    }
+}
+
+fn check_file_xref(fi: &FileInfo) {
+    let pb = map_file(fi);
+    match check_xref(fi.path(), pb.buf()) {
+        Ok(r) => {
+            for i in r.issues() {
+                ta3_log!(Level::Warn, 0, "xref inconsistency: {:?}", i)
+            }
+            if r.is_consistent() {
+                ta3_log!(
+                    Level::Info,
+                    0,
+                    "xref is consistent with the {} objects it lists",
+                    r.ents().len()
+                )
+            }
+        },
+        Err(e) => exit_log!(e.offset(), "Cannot check xref: {}", e.msg()),
+    }
+}
+
 fn process_file(
     fi: &FileInfo, ctxt: &mut PDFObjContext, root_id: ObjectId,
     text_dump_file: &mut Option<fs::File>,
@@ -395,6 +518,86 @@ fn main() {
                 .takes_value(true)
                 .help("output file where to store extracted text"),
         )
+        .arg(
+            Arg::with_name("recover")
+                .short("r")
+                .long("recover")
+                .help("rebuild the xref by scanning the file if it is damaged"),
+        )
+        .arg(
+            Arg::with_name("lenient_length")
+                .long("lenient-length")
+                .help("find the end of a stream by scanning if its /Length is wrong"),
+        )
+        .arg(
+            Arg::with_name("lazy")
+                .long("lazy")
+                .help("parse each object when it is first used"),
+        )
+        .arg(
+            Arg::with_name("parallel")
+                .long("parallel")
+                .help("parse the objects on multiple threads"),
+        )
+        .arg(
+            Arg::with_name("password")
+                .short("p")
+                .long("password")
+                .value_name("PASSWORD")
+                .takes_value(true)
+                .help("user or owner password for encrypted files"),
+        )
+        .arg(
+            Arg::with_name("sanitize")
+                .long("sanitize")
+                .value_name("PDF_FILE")
+                .takes_value(true)
+                .help("output file where to write the file without active content"),
+        )
+        .arg(
+            Arg::with_name("sanitize_log")
+                .long("sanitize-log")
+                .value_name("JSON_FILE")
+                .takes_value(true)
+                .requires("sanitize")
+                .help("output file where to write the JSON log of the sanitizer's removals"),
+        )
+        .arg(
+            Arg::with_name("check_signatures")
+                .long("check-signatures")
+                .help("check the structure of the signatures and the updates made after them"),
+        )
+        .arg(
+            Arg::with_name("check_linearization")
+                .long("check-linearization")
+                .help("check the linearization dictionary and hint tables against the file"),
+        )
+        .arg(
+            Arg::with_name("check_xref")
+                .long("check-xref")
+                .help("check the xref entries against the objects in the file"),
+        )
+        .arg(
+            Arg::with_name("max_stream_size")
+                .long("max-stream-size")
+                .value_name("BYTES")
+                .takes_value(true)
+                .help("maximum decoded size of a stream"),
+        )
+        .arg(
+            Arg::with_name("max_expansion_ratio")
+                .long("max-expansion-ratio")
+                .value_name("RATIO")
+                .takes_value(true)
+                .help("maximum ratio of decoded to encoded size of a stream"),
+        )
+        .arg(
+            Arg::with_name("max_decoded_total")
+                .long("max-decoded-total")
+                .value_name("BYTES")
+                .takes_value(true)
+                .help("maximum total decoded size of the streams in the file"),
+        )
         .get_matches();
 
     // set logging level based on -v:
@@ -461,8 +664,65 @@ fn main() {
     };
 
     let test_file = matches.value_of("pdf_file").unwrap();
-    let (fi, mut ctxt, root_id) = parse_file(test_file);
+    let mut opts = ParseOptions::new();
+    opts.set_recover_xref(matches.is_present("recover"));
+    opts.set_lenient_stream_length(matches.is_present("lenient_length"));
+    opts.set_lazy(matches.is_present("lazy"));
+    opts.set_parallel(matches.is_present("parallel"));
+    if let Some(pw) = matches.value_of("password") {
+        opts.set_password(pw.as_bytes());
+    }
+    let mut limits = *opts.decode_limits();
+    for (arg, limit) in &mut [
+        ("max_stream_size", &mut limits.max_output),
+        ("max_expansion_ratio", &mut limits.max_ratio),
+        ("max_decoded_total", &mut limits.max_total),
+    ] {
+        if let Some(v) = matches.value_of(*arg) {
+            match v.parse::<usize>() {
+                Ok(n) => **limit = n,
+                Err(_) => exit_log!(0, "Invalid value {} for {}", v, arg),
+            }
+        }
+    }
+    opts.set_decode_limits(limits);
+    let (fi, mut ctxt, root_id) = match parse_file_with_options(test_file, &opts) {
+        Ok(v) => v,
+        Err(e) => exit_log!(e.offset(), "{}", e.msg()),
+    };
+    if let Some(rec) = fi.recovery() {
+        ta3_log!(
+            Level::Warn,
+            rec.cause().offset(),
+            "parsed using a reconstructed xref after {}",
+            rec.cause()
+        );
+        for m in rec.mismatches() {
+            ta3_log!(
+                Level::Warn,
+                0,
+                "xref mismatch for object ({},{}): xref has {:?}, file has {:?}",
+                m.id().0,
+                m.id().1,
+                m.xref(),
+                m.found()
+            );
+        }
+    }
     process_file(&fi, &mut ctxt, root_id, &mut output_text_file);
+    if matches.is_present("check_signatures") {
+        check_file_signatures(&fi, &ctxt, root_id)
+    }
+    if matches.is_present("check_linearization") {
+        check_file_linearization(&fi, &opts)
+    }
+    if matches.is_present("check_xref") {
+        check_file_xref(&fi)
+    }
+    if let Some(out_file) = matches.value_of("sanitize") {
+        let log_file = matches.value_of("sanitize_log");
+        sanitize_file(test_file, &ctxt, root_id, out_file, log_file)
+    }
 }
 
 #[cfg(feature = "kuduafl")]
@@ -470,7 +730,12 @@ fn main() {
     let path = std::env::current_dir();
     let path = path.unwrap();
     afl::fuzz!(|data: &[u8]| {
-        let (fi, mut ctxt, root_id) = parse_data(&path, data);
+        // Structurally invalid inputs are not interesting to the
+        // fuzzer beyond not crashing the parser.
+        let (fi, mut ctxt, root_id) = match parse_data(&path, data) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
         process_file(&fi, &mut ctxt, root_id, &mut None);
     });
 }
@@ -0,0 +1,651 @@
+// Copyright (c) 2019-2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Owned combinators.
+//
+// Unlike those in prim_combinators, these combinators own their
+// sub-parsers, so that combined parsers can be nested, stored and
+// returned like any other parser.  A borrowed parser can still be
+// used as a sub-parser, since '&mut P' is also a parser.
+//
+// All combinators return located values, and restore the cursor
+// when they fail.
+
+use std::convert::TryInto;
+
+use super::parsebuffer::{
+    locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParseResult, ParsleyParser,
+};
+pub use super::prim_combinators::Alt;
+
+pub struct Sequence<P1: ParsleyParser, P2: ParsleyParser> {
+    p1: P1,
+    p2: P2,
+}
+
+impl<P1: ParsleyParser, P2: ParsleyParser> Sequence<P1, P2> {
+    pub fn new(p1: P1, p2: P2) -> Sequence<P1, P2> { Sequence { p1, p2 } }
+}
+
+impl<P1: ParsleyParser, P2: ParsleyParser> ParsleyParser for Sequence<P1, P2>
+where
+    P1::T: PartialEq,
+    P2::T: PartialEq,
+{
+    type T = LocatedVal<(P1::T, P2::T)>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let o1 = self.p1.parse(buf)?;
+        match self.p2.parse(buf) {
+            Ok(o2) => Ok(LocatedVal::new((o1, o2), start, buf.get_cursor())),
+            Err(err) => {
+                buf.set_cursor_unsafe(start);
+                Err(err)
+            },
+        }
+    }
+}
+
+pub struct Alternate<P1: ParsleyParser, P2: ParsleyParser> {
+    p1: P1,
+    p2: P2,
+}
+
+impl<P1: ParsleyParser, P2: ParsleyParser> Alternate<P1, P2> {
+    pub fn new(p1: P1, p2: P2) -> Alternate<P1, P2> { Alternate { p1, p2 } }
+}
+
+impl<P1: ParsleyParser, P2: ParsleyParser> ParsleyParser for Alternate<P1, P2>
+where
+    P1::T: PartialEq,
+    P2::T: PartialEq,
+{
+    type T = LocatedVal<Alt<P1::T, P2::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        if let Ok(o1) = self.p1.parse(buf) {
+            return Ok(LocatedVal::new(Alt::Left(o1), start, buf.get_cursor()))
+        }
+        buf.set_cursor_unsafe(start);
+        match self.p2.parse(buf) {
+            Ok(o2) => Ok(LocatedVal::new(Alt::Right(o2), start, buf.get_cursor())),
+            Err(err) => {
+                buf.set_cursor_unsafe(start);
+                Err(err)
+            },
+        }
+    }
+}
+
+// Succeeds without consuming input if the sub-parser fails.
+pub struct Not<P: ParsleyParser> {
+    p: P,
+}
+
+impl<P: ParsleyParser> Not<P> {
+    pub fn new(p: P) -> Not<P> { Not { p } }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Not<P> {
+    type T = LocatedVal<()>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let r = self.p.parse(buf);
+        let end = buf.get_cursor();
+        buf.set_cursor_unsafe(start);
+        match r {
+            Ok(_) => {
+                let err = ErrorKind::GuardError("not".to_string());
+                Err(locate_value(err, start, end))
+            },
+            Err(_) => Ok(LocatedVal::new((), start, start)),
+        }
+    }
+}
+
+// Succeeds if the sub-parser succeeds, but without consuming input.
+// The value keeps the locations of the input it was parsed from.
+pub struct Peek<P: ParsleyParser> {
+    p: P,
+}
+
+impl<P: ParsleyParser> Peek<P> {
+    pub fn new(p: P) -> Peek<P> { Peek { p } }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Peek<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<P::T>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let r = self.p.parse(buf);
+        buf.set_cursor_unsafe(start);
+        Ok(LocatedVal::new(r?, start, start))
+    }
+}
+
+// Converts the value of the sub-parser.
+pub struct Map<P: ParsleyParser, F> {
+    p: P,
+    f: F,
+}
+
+impl<P: ParsleyParser, F> Map<P, F> {
+    // The bound on 'f' lets the types of closures be inferred.
+    pub fn new<U>(p: P, f: F) -> Map<P, F>
+    where
+        F: FnMut(P::T) -> U,
+    {
+        Map { p, f }
+    }
+}
+
+impl<P, F, U> ParsleyParser for Map<P, F>
+where
+    P: ParsleyParser,
+    F: FnMut(P::T) -> U,
+    U: PartialEq,
+{
+    type T = LocatedVal<U>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let o = self.p.parse(buf)?;
+        Ok(LocatedVal::new((self.f)(o), start, buf.get_cursor()))
+    }
+}
+
+pub struct Opt<P: ParsleyParser> {
+    p: P,
+}
+
+impl<P: ParsleyParser> Opt<P> {
+    pub fn new(p: P) -> Opt<P> { Opt { p } }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Opt<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Option<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.p.parse(buf) {
+            Ok(o) => Ok(LocatedVal::new(Some(o), start, buf.get_cursor())),
+            Err(_) => {
+                buf.set_cursor_unsafe(start);
+                Ok(LocatedVal::new(None, start, start))
+            },
+        }
+    }
+}
+
+// Matches the empty input; it is the separator of repetitions
+// without separators.
+struct Empty;
+
+impl ParsleyParser for Empty {
+    type T = LocatedVal<()>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        Ok(LocatedVal::new((), start, start))
+    }
+}
+
+// Parses from 'min' to 'max' (if any) values with 'p', separated by
+// values of 'sep'.  The repetition stops at the first value that
+// cannot be parsed along with its separator; this is an error only
+// if fewer than 'min' values were parsed.  It also stops at a value
+// that, with its separator, consumes no input once there are 'min'
+// values, since such a value would otherwise be repeated forever.
+fn repeat<P: ParsleyParser, S: ParsleyParser>(
+    p: &mut P, sep: &mut S, min: usize, max: Option<usize>, buf: &mut dyn ParseBufferT,
+) -> ParseResult<LocatedVal<Vec<P::T>>>
+where
+    P::T: PartialEq,
+{
+    let start = buf.get_cursor();
+    let mut end = start;
+    let mut v = Vec::new();
+    while max != Some(v.len()) {
+        let r = if v.is_empty() {
+            p.parse(buf)
+        } else {
+            sep.parse(buf).and_then(|_| p.parse(buf))
+        };
+        match r {
+            Ok(_) if buf.get_cursor() == end && v.len() >= min => break,
+            Ok(o) => {
+                v.push(o);
+                end = buf.get_cursor()
+            },
+            Err(err) if v.len() < min => {
+                buf.set_cursor_unsafe(start);
+                return Err(err)
+            },
+            Err(_) => break,
+        }
+    }
+    buf.set_cursor_unsafe(end);
+    Ok(LocatedVal::new(v, start, end))
+}
+
+// Parses at least 'min' and at most 'max' (if any) values.
+pub struct Repeat<P: ParsleyParser> {
+    p:   P,
+    min: usize,
+    max: Option<usize>,
+}
+
+impl<P: ParsleyParser> Repeat<P> {
+    pub fn new(p: P, min: usize, max: Option<usize>) -> Repeat<P> {
+        if let Some(max) = max {
+            assert!(min <= max)
+        }
+        Repeat { p, min, max }
+    }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Repeat<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Vec<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        repeat(&mut self.p, &mut Empty, self.min, self.max, buf)
+    }
+}
+
+// Parses zero or more values.
+pub struct Star<P: ParsleyParser> {
+    r: Repeat<P>,
+}
+
+impl<P: ParsleyParser> Star<P> {
+    pub fn new(p: P) -> Star<P> {
+        Star {
+            r: Repeat::new(p, 0, None),
+        }
+    }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Star<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Vec<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> { self.r.parse(buf) }
+}
+
+// Parses one or more values.
+pub struct Plus<P: ParsleyParser> {
+    r: Repeat<P>,
+}
+
+impl<P: ParsleyParser> Plus<P> {
+    pub fn new(p: P) -> Plus<P> {
+        Plus {
+            r: Repeat::new(p, 1, None),
+        }
+    }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Plus<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Vec<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> { self.r.parse(buf) }
+}
+
+// Parses exactly 'n' values.
+pub struct Count<P: ParsleyParser> {
+    r: Repeat<P>,
+}
+
+impl<P: ParsleyParser> Count<P> {
+    pub fn new(p: P, n: usize) -> Count<P> {
+        Count {
+            r: Repeat::new(p, n, Some(n)),
+        }
+    }
+}
+
+impl<P: ParsleyParser> ParsleyParser for Count<P>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Vec<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> { self.r.parse(buf) }
+}
+
+// Parses zero or more values separated by 'sep'; the separators are
+// dropped from the result.
+pub struct SepBy<P: ParsleyParser, S: ParsleyParser> {
+    p:   P,
+    sep: S,
+}
+
+impl<P: ParsleyParser, S: ParsleyParser> SepBy<P, S> {
+    pub fn new(p: P, sep: S) -> SepBy<P, S> { SepBy { p, sep } }
+}
+
+impl<P: ParsleyParser, S: ParsleyParser> ParsleyParser for SepBy<P, S>
+where
+    P::T: PartialEq,
+{
+    type T = LocatedVal<Vec<P::T>>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        repeat(&mut self.p, &mut self.sep, 0, None, buf)
+    }
+}
+
+// Parses a length with 'len', followed by a value of that many bytes
+// with 'p'.  The value has to use all its bytes.
+pub struct LengthPrefixed<L: ParsleyParser, P: ParsleyParser> {
+    len: L,
+    p:   P,
+}
+
+impl<L: ParsleyParser, P: ParsleyParser> LengthPrefixed<L, P> {
+    pub fn new(len: L, p: P) -> LengthPrefixed<L, P> { LengthPrefixed { len, p } }
+}
+
+impl<L, N, P> ParsleyParser for LengthPrefixed<L, P>
+where
+    L: ParsleyParser<T = LocatedVal<N>>,
+    N: Copy + PartialEq + TryInto<usize>,
+    P: ParsleyParser,
+    P::T: PartialEq,
+{
+    type T = LocatedVal<P::T>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let len = self.len.parse(buf)?;
+        let size = match (*len.val()).try_into() {
+            Ok(size) => size,
+            Err(_) => {
+                buf.set_cursor_unsafe(start);
+                return Err(len.place(ErrorKind::BoundsError))
+            },
+        };
+        let ofs = buf.get_cursor();
+        if buf.remaining() < size {
+            buf.set_cursor_unsafe(start);
+            return Err(locate_value(ErrorKind::EndOfBuffer, ofs, ofs))
+        }
+        let end = ofs + size;
+
+        // The view ends with the value but starts with the buffer, so
+        // that the locations in the value are those in the buffer.
+        let mut view = ParseBuffer::new_view(buf, 0, end);
+        view.set_cursor_unsafe(ofs);
+        buf.set_cursor_unsafe(start);
+        match self.p.parse(&mut view) {
+            Ok(v) if view.get_cursor() == end => {
+                buf.set_cursor_unsafe(end);
+                Ok(LocatedVal::new(v, start, end))
+            },
+            Ok(_) => {
+                let msg = "length-prefixed value has trailing data".to_string();
+                Err(locate_value(
+                    ErrorKind::GuardError(msg),
+                    view.get_cursor(),
+                    end,
+                ))
+            },
+            // The value was cut short by its length, which is not an
+            // end of input that more input could fix.
+            Err(err) if *err.val() == ErrorKind::EndOfBuffer => {
+                Err(err.place(ErrorKind::BoundsError))
+            },
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_combinators {
+    use super::super::parsebuffer::{
+        locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParsleyParser,
+    };
+    use super::super::prim_ascii::AsciiChar;
+    use super::{Alt, Alternate, Map, Not, Opt, Peek, Sequence};
+
+    fn ch(c: char) -> AsciiChar { AsciiChar::new_guarded(Box::new(move |x: &char| *x == c)) }
+
+    #[test]
+    fn test_owned() {
+        // the parsers are built and returned without borrows
+        fn ab_or_c() -> Alternate<Sequence<AsciiChar, AsciiChar>, AsciiChar> {
+            Alternate::new(Sequence::new(ch('A'), ch('B')), ch('C'))
+        }
+        let mut p = ab_or_c();
+
+        let mut pb = ParseBuffer::new(b"ABC".to_vec());
+        let r = p.parse(&mut pb).unwrap();
+        let ab = (LocatedVal::new('A', 0, 1), LocatedVal::new('B', 1, 2));
+        assert_eq!(*r.val(), Alt::Left(LocatedVal::new(ab, 0, 2)));
+        let r = p.parse(&mut pb).unwrap();
+        assert_eq!(*r.val(), Alt::Right(LocatedVal::new('C', 2, 3)));
+        assert_eq!(pb.get_cursor(), 3);
+
+        // a partial match does not advance the cursor
+        let mut pb = ParseBuffer::new(b"AC".to_vec());
+        let mut p = Sequence::new(ch('A'), ch('B'));
+        assert!(p.parse(&mut pb).is_err());
+        assert_eq!(pb.get_cursor(), 0);
+
+        // borrowed parsers can be used as well
+        let mut a = ch('A');
+        let mut p = Sequence::new(&mut a, Not::new(ch('A')));
+        let mut pb = ParseBuffer::new(b"AA".to_vec());
+        let e = ErrorKind::GuardError("not".to_string());
+        assert_eq!(p.parse(&mut pb), Err(locate_value(e, 1, 2)));
+        assert_eq!(pb.get_cursor(), 0);
+        let mut pb = ParseBuffer::new(b"AB".to_vec());
+        assert!(p.parse(&mut pb).is_ok());
+        assert_eq!(pb.get_cursor(), 1);
+        assert!(a.parse(&mut pb).is_err());
+    }
+
+    #[test]
+    fn test_map_opt_peek() {
+        let mut p = Map::new(ch('7'), |c| c.val().to_digit(10));
+        let mut pb = ParseBuffer::new(b"7".to_vec());
+        assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(Some(7), 0, 1)));
+
+        let mut p = Opt::new(Sequence::new(ch('A'), ch('B')));
+        let mut pb = ParseBuffer::new(b"AC".to_vec());
+        assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(None, 0, 0)));
+        assert_eq!(pb.get_cursor(), 0);
+        let mut pb = ParseBuffer::new(b"AB".to_vec());
+        let r = p.parse(&mut pb).unwrap();
+        assert_eq!(r.val().as_ref().map(|v| (v.start(), v.end())), Some((0, 2)));
+        assert_eq!(pb.get_cursor(), 2);
+
+        let mut p = Peek::new(ch('A'));
+        let mut pb = ParseBuffer::new(b"A".to_vec());
+        let r = LocatedVal::new(LocatedVal::new('A', 0, 1), 0, 0);
+        assert_eq!(p.parse(&mut pb), Ok(r));
+        assert_eq!(pb.get_cursor(), 0);
+        let mut pb = ParseBuffer::new(b"B".to_vec());
+        assert!(p.parse(&mut pb).is_err());
+        assert_eq!(pb.get_cursor(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test_repeat {
+    use super::super::parsebuffer::{
+        locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParsleyParser,
+    };
+    use super::super::prim_ascii::AsciiChar;
+    use super::{Count, Map, Opt, Plus, Repeat, SepBy, Sequence, Star};
+
+    fn ch(c: char) -> AsciiChar { AsciiChar::new_guarded(Box::new(move |x: &char| *x == c)) }
+
+    fn vals<T: Clone + PartialEq>(v: &LocatedVal<Vec<LocatedVal<T>>>) -> Vec<T> {
+        v.val().iter().map(|c| c.val().clone()).collect()
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut p = Repeat::new(ch('A'), 2, Some(3));
+        let mut pb = ParseBuffer::new(b"AB".to_vec());
+        let e = ErrorKind::GuardError("ascii-prim".to_string());
+        assert_eq!(p.parse(&mut pb), Err(locate_value(e, 1, 1)));
+        assert_eq!(pb.get_cursor(), 0);
+        let mut pb = ParseBuffer::new(b"AAAA".to_vec());
+        let r = p.parse(&mut pb).unwrap();
+        assert_eq!((vals(&r), r.start(), r.end()), (vec!['A'; 3], 0, 3));
+        let r = p.parse(&mut pb);
+        assert_eq!(r, Err(locate_value(ErrorKind::EndOfBuffer, 4, 4)));
+        assert_eq!(pb.get_cursor(), 3);
+
+        let mut pb = ParseBuffer::new(b"AAB".to_vec());
+        let r = Count::new(ch('A'), 2).parse(&mut pb).unwrap();
+        assert_eq!(vals(&r), vec!['A'; 2]);
+        assert!(Count::new(ch('A'), 2).parse(&mut pb).is_err());
+        let r = Star::new(ch('A')).parse(&mut pb).unwrap();
+        assert_eq!(r, LocatedVal::new(vec![], 2, 2));
+        assert!(Plus::new(ch('A')).parse(&mut pb).is_err());
+        let r = Plus::new(ch('B')).parse(&mut pb).unwrap();
+        assert_eq!(r, LocatedVal::new(vec![LocatedVal::new('B', 2, 3)], 2, 3));
+    }
+
+    #[test]
+    fn test_sep_by() {
+        let mut p = SepBy::new(ch('A'), ch(','));
+        let mut pb = ParseBuffer::new(b"A,A,AB".to_vec());
+        let r = p.parse(&mut pb).unwrap();
+        assert_eq!((vals(&r), r.start(), r.end()), (vec!['A'; 3], 0, 5));
+
+        // a trailing separator is not consumed
+        let mut pb = ParseBuffer::new(b"A,A,".to_vec());
+        let r = p.parse(&mut pb).unwrap();
+        assert_eq!(vals(&r), vec!['A'; 2]);
+        assert_eq!(pb.get_cursor(), 3);
+
+        let mut pb = ParseBuffer::new(b",A".to_vec());
+        assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(vec![], 0, 0)));
+    }
+
+    // Values that consume no input end the repetition.
+    #[test]
+    fn test_empty_values() {
+        let mut pb = ParseBuffer::new(b"AAB".to_vec());
+        let r = Star::new(Opt::new(ch('A'))).parse(&mut pb).unwrap();
+        assert_eq!((r.val().len(), r.start(), r.end()), (2, 0, 2));
+        assert_eq!(pb.get_cursor(), 2);
+        let r = Star::new(Opt::new(ch('A'))).parse(&mut pb).unwrap();
+        assert_eq!(r, LocatedVal::new(vec![], 2, 2));
+
+        // up to 'min' empty values are still parsed
+        let r = Repeat::new(Opt::new(ch('A')), 2, None)
+            .parse(&mut pb)
+            .unwrap();
+        let empty = LocatedVal::new(None, 2, 2);
+        assert_eq!(r, LocatedVal::new(vec![empty, empty], 2, 2));
+        let r = SepBy::new(Opt::new(ch('A')), Opt::new(ch(','))).parse(&mut pb);
+        assert_eq!(r, Ok(LocatedVal::new(vec![], 2, 2)));
+    }
+
+    // An array of numbers such as "[1, 23,4]", written without
+    // explicit loops.
+    #[test]
+    fn test_array() {
+        let digit = AsciiChar::new_guarded(Box::new(|c: &char| c.is_ascii_digit()));
+        let num = Map::new(Plus::new(digit), |v| {
+            v.val()
+                .iter()
+                .fold(0, |n, d| n * 10 + d.val().to_digit(10).unwrap())
+        });
+        let sep = Sequence::new(ch(','), Star::new(ch(' ')));
+        let elems = SepBy::new(num, sep);
+        let array = Sequence::new(Sequence::new(ch('['), elems), ch(']'));
+        let mut p = Map::new(array, |a| {
+            let (open_elems, _) = a.unwrap();
+            let (_, elems) = open_elems.unwrap();
+            vals(&elems)
+        });
+
+        let mut pb = ParseBuffer::new(b"[1, 23,4]".to_vec());
+        assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(vec![1, 23, 4], 0, 9)));
+        let mut pb = ParseBuffer::new(b"[]".to_vec());
+        assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(vec![], 0, 2)));
+        let mut pb = ParseBuffer::new(b"[1,]".to_vec());
+        assert!(p.parse(&mut pb).is_err());
+        assert_eq!(pb.get_cursor(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test_length_prefixed {
+    use super::super::parsebuffer::{
+        locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParsleyParser,
+    };
+    use super::super::prim_binary::{ByteVecP, Endian, UInt16P, UInt8P};
+    use super::{LengthPrefixed, Star};
+
+    #[test]
+    fn test() {
+        let mut p = LengthPrefixed::new(UInt16P::new(Endian::Big), Star::new(UInt8P));
+        let mut pb = ParseBuffer::new(vec![0, 2, 7, 8, 9]);
+        let v = vec![LocatedVal::new(7, 2, 3), LocatedVal::new(8, 3, 4)];
+        let r = LocatedVal::new(LocatedVal::new(v, 2, 4), 0, 4);
+        assert_eq!(p.parse(&mut pb), Ok(r));
+        assert_eq!(pb.get_cursor(), 4);
+
+        // truncated input
+        let mut pb = ParseBuffer::new(vec![0, 3, 7, 8]);
+        let r = p.parse(&mut pb);
+        assert_eq!(r, Err(locate_value(ErrorKind::EndOfBuffer, 2, 2)));
+        assert_eq!(pb.get_cursor(), 0);
+
+        // the value does not use all its bytes
+        let mut p = LengthPrefixed::new(UInt8P, ByteVecP::new(1));
+        let mut pb = ParseBuffer::new(vec![2, 7, 8]);
+        let e = ErrorKind::GuardError("length-prefixed value has trailing data".to_string());
+        assert_eq!(p.parse(&mut pb), Err(locate_value(e, 2, 3)));
+        assert_eq!(pb.get_cursor(), 0);
+
+        // the value needs more bytes than its length
+        let mut p = LengthPrefixed::new(UInt8P, ByteVecP::new(2));
+        let mut pb = ParseBuffer::new(vec![1, 7, 8]);
+        assert_eq!(
+            p.parse(&mut pb),
+            Err(locate_value(ErrorKind::BoundsError, 1, 1))
+        );
+        assert_eq!(pb.get_cursor(), 0);
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright (c) 2019-2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Resumable parsing of input that arrives in pieces, such as messages
+// framed over TCP or reassembled from UDP fragments.
+//
+// A parse that runs out of input, i.e. that fails with
+// ErrorKind::EndOfBuffer, returns a checkpoint instead of an error,
+// and is resumed from the checkpoint when more input is available.
+// The parsers themselves keep no state across calls, so resuming
+// re-runs the parser from the start of the value at the checkpoint.
+// For this to work, parsers need to report partial input as
+// EndOfBuffer, and not treat the end of the buffer as the end of a
+// value.
+
+use super::parsebuffer::{
+    locate_value, ErrorKind, ParseBuffer, ParseBufferT, ParseResult, ParsleyParser, StreamBufferT,
+};
+
+// The position in the buffer from which a parse is resumed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Checkpoint {
+    // the cursor at the start of the value
+    ofs:   usize,
+    // the bytes available from the cursor when the input ran out
+    avail: usize,
+}
+
+impl Checkpoint {
+    pub fn offset(&self) -> usize { self.ofs }
+    pub fn available(&self) -> usize { self.avail }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Resumable<T> {
+    Done(T),
+    // more input is needed to complete the value
+    NeedMore(Checkpoint),
+}
+
+// Parses a value at the cursor.  If the input ends before the value,
+// the cursor is restored and a checkpoint is returned.
+pub fn parse_resumable<P: ParsleyParser>(
+    p: &mut P, buf: &mut dyn ParseBufferT,
+) -> ParseResult<Resumable<P::T>> {
+    let start = buf.get_cursor();
+    let avail = buf.remaining();
+    match p.parse(buf) {
+        Ok(v) => Ok(Resumable::Done(v)),
+        Err(e) if *e.val() == ErrorKind::EndOfBuffer => {
+            buf.set_cursor_unsafe(start);
+            Ok(Resumable::NeedMore(Checkpoint { ofs: start, avail }))
+        },
+        Err(e) => Err(e),
+    }
+}
+
+// Resumes a parse from a checkpoint.  The parser is only re-run if
+// input was added to the buffer since the checkpoint.
+pub fn resume<P: ParsleyParser>(
+    p: &mut P, buf: &mut dyn ParseBufferT, cp: Checkpoint,
+) -> ParseResult<Resumable<P::T>> {
+    if buf.size() <= cp.ofs + cp.avail {
+        return Ok(Resumable::NeedMore(cp))
+    }
+    buf.set_cursor_unsafe(cp.ofs);
+    parse_resumable(p, buf)
+}
+
+// Parses a sequence of values from input that is fed as it arrives.
+// The bytes of each parsed value are dropped from the buffer, so the
+// locations in a value are relative to its start.  The buffer cannot
+// be modified while it is shared, so the values must not keep views
+// of it (e.g. from RestrictView); they should copy the bytes they
+// need instead.
+pub struct IncrementalParser<P: ParsleyParser> {
+    parser:  P,
+    buf:     ParseBuffer,
+    pending: Option<Checkpoint>,
+}
+
+impl<P: ParsleyParser> IncrementalParser<P> {
+    pub fn new(parser: P) -> IncrementalParser<P> {
+        IncrementalParser {
+            parser,
+            buf: ParseBuffer::new(Vec::new()),
+            pending: None,
+        }
+    }
+
+    // Adds input to the end of the buffer.  Returns false if the
+    // buffer is shared and cannot be extended.
+    pub fn feed(&mut self, data: &[u8]) -> bool { self.buf.append(data) }
+
+    // The number of bytes that have not been parsed.
+    pub fn buffered(&self) -> usize { self.buf.remaining() }
+
+    // Parses the next value if all its input is available.  On an
+    // error, the input is not consumed; the caller can skip over it
+    // to resynchronize.  A value that shares the buffer is an error,
+    // since its input cannot be dropped.
+    pub fn parse_next(&mut self) -> ParseResult<Resumable<P::T>> {
+        let r = match self.pending.take() {
+            Some(cp) => resume(&mut self.parser, &mut self.buf, cp),
+            None => parse_resumable(&mut self.parser, &mut self.buf),
+        };
+        match &r {
+            Ok(Resumable::Done(_)) => {
+                let len = self.buf.get_cursor();
+                if !self.buf.drop(len) {
+                    self.buf.set_cursor_unsafe(0);
+                    let err = ErrorKind::GuardError("value shares the input buffer".to_string());
+                    return Err(locate_value(err, 0, len))
+                }
+            },
+            Ok(Resumable::NeedMore(cp)) => self.pending = Some(*cp),
+            Err(_) => self.buf.set_cursor_unsafe(0),
+        }
+        r
+    }
+
+    // Drops up to 'len' bytes of unparsed input, e.g. to skip over a
+    // value that could not be parsed.
+    pub fn skip(&mut self, len: usize) {
+        let len = len.min(self.buf.remaining());
+        self.buf.set_cursor_unsafe(len);
+        let _ = self.buf.drop(len);
+        self.pending = None
+    }
+}
+
+#[cfg(test)]
+mod test_incremental {
+    use super::super::parsebuffer::{
+        locate_value, ErrorKind, LocatedVal, Location, ParseBuffer, ParseBufferT, ParseResult,
+        ParsleyParser,
+    };
+    use super::super::prim_binary::{ByteVecP, UInt8P};
+    use super::super::transforms::{BufferTransformT, RestrictView};
+    use super::{parse_resumable, resume, Checkpoint, IncrementalParser, Resumable};
+
+    // A frame with a one-byte length that cannot be zero, followed by
+    // that many bytes.
+    struct FrameP {
+        runs: usize,
+    }
+
+    impl ParsleyParser for FrameP {
+        type T = LocatedVal<Vec<u8>>;
+
+        fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+            self.runs += 1;
+            let start = buf.get_cursor();
+            let len = UInt8P.parse(buf)?;
+            if *len.val() == 0 {
+                buf.set_cursor_unsafe(start);
+                let err = ErrorKind::GuardError("empty frame".to_string());
+                return Err(locate_value(err, start, start + 1))
+            }
+            match ByteVecP::new(*len.val() as usize).parse(buf) {
+                Ok(v) => Ok(LocatedVal::new(v.unwrap(), start, buf.get_cursor())),
+                Err(e) => {
+                    buf.set_cursor_unsafe(start);
+                    Err(e)
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut p = FrameP { runs: 0 };
+        let mut pb = ParseBuffer::new(vec![9, 3, b'a']);
+        pb.set_cursor_unsafe(1);
+        let cp = Checkpoint { ofs: 1, avail: 2 };
+        assert_eq!(
+            parse_resumable(&mut p, &mut pb),
+            Ok(Resumable::NeedMore(cp))
+        );
+        assert_eq!(pb.get_cursor(), 1);
+
+        // without new input, the parser is not re-run
+        assert_eq!(resume(&mut p, &mut pb, cp), Ok(Resumable::NeedMore(cp)));
+        assert_eq!(p.runs, 1);
+
+        let mut pb = ParseBuffer::new(vec![9, 3, b'a', b'b', b'c', 1]);
+        let v = LocatedVal::new(b"abc".to_vec(), 1, 5);
+        assert_eq!(resume(&mut p, &mut pb, cp), Ok(Resumable::Done(v)));
+        assert_eq!(pb.get_cursor(), 5);
+    }
+
+    #[test]
+    fn test_incremental_parser() {
+        let mut ip = IncrementalParser::new(FrameP { runs: 0 });
+        assert!(matches!(ip.parse_next(), Ok(Resumable::NeedMore(_))));
+
+        // the frames arrive a byte at a time
+        let mut frames = Vec::new();
+        for b in &[2, b'a', b'b', 1, b'c', 3, b'd'] {
+            assert!(ip.feed(&[*b]));
+            while let Ok(Resumable::Done(v)) = ip.parse_next() {
+                frames.push(v)
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                LocatedVal::new(b"ab".to_vec(), 0, 3),
+                LocatedVal::new(b"c".to_vec(), 0, 2)
+            ]
+        );
+        assert_eq!(ip.buffered(), 2);
+        let runs = ip.parser.runs;
+        assert!(matches!(ip.parse_next(), Ok(Resumable::NeedMore(_))));
+        assert_eq!(ip.parser.runs, runs);
+
+        // several frames in one piece, with an invalid frame that is
+        // skipped
+        assert!(ip.feed(&[b'e', b'f', 0, 1, b'g']));
+        let v = LocatedVal::new(b"def".to_vec(), 0, 4);
+        assert_eq!(ip.parse_next(), Ok(Resumable::Done(v)));
+        let err = locate_value(ErrorKind::GuardError("empty frame".to_string()), 0, 1);
+        assert_eq!(ip.parse_next(), Err(err));
+        assert_eq!(ip.buffered(), 3);
+        ip.skip(1);
+        let v = LocatedVal::new(b"g".to_vec(), 0, 2);
+        assert_eq!(ip.parse_next(), Ok(Resumable::Done(v)));
+        assert_eq!(ip.buffered(), 0);
+    }
+
+    // A value that keeps a view of the buffer.
+    struct View(ParseBuffer);
+
+    impl Location for View {
+        fn loc_start(&self) -> usize { self.0.start() }
+        fn loc_end(&self) -> usize { self.0.start() + self.0.size() }
+    }
+
+    struct ViewP;
+
+    impl ParsleyParser for ViewP {
+        type T = View;
+
+        fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+            let start = buf.get_cursor();
+            let view = RestrictView::new(start, 2).transform(buf)?;
+            buf.set_cursor_unsafe(start + 2);
+            Ok(View(view))
+        }
+    }
+
+    #[test]
+    fn test_shared_buffer() {
+        let mut ip = IncrementalParser::new(ViewP);
+        assert!(ip.feed(b"abc"));
+        let err = locate_value(
+            ErrorKind::GuardError("value shares the input buffer".to_string()),
+            0,
+            2,
+        );
+        assert_eq!(ip.parse_next().err(), Some(err));
+        // the input is not consumed, and the buffer is no longer
+        // shared once the value is dropped
+        assert_eq!(ip.buffered(), 3);
+        assert!(ip.feed(b"d"));
+        assert_eq!(ip.buffered(), 4);
+    }
+}
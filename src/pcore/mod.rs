@@ -16,6 +16,8 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod combinators;
+pub mod incremental;
 pub mod parsebuffer;
 pub mod prim_ascii;
 pub mod prim_binary;
@@ -21,8 +21,13 @@
 use std::borrow::Borrow;
 use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::fmt;
+use std::fs::File;
 use std::hash::{Hash, Hasher};
-use std::rc::Rc;
+use std::io;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use memmap2::Mmap;
 
 // Location information for objects returned by parsers.
 pub trait Location {
@@ -174,6 +179,14 @@ pub trait ParsleyParser {
     fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T>;
 }
 
+// A borrowed parser is also a parser, so that it can be used where
+// the combinators expect to own their sub-parsers.
+impl<P: ParsleyParser + ?Sized> ParsleyParser for &mut P {
+    type T = P::T;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> { (**self).parse(buf) }
+}
+
 // Errors generated by this module.
 #[derive(Debug, PartialEq, Clone)]
 pub enum ErrorKind {
@@ -242,7 +255,7 @@ pub trait ParseBufferT {
     // internal api, used for view restrictions.  ideally, this would have
     // visibility   pub(in self::ParseBuffer)
     // but this is not supported (surprisingly).
-    fn rc_buf(&self) -> Rc<Vec<u8>>;
+    fn rc_buf(&self) -> Arc<BufferData>;
     fn start(&self) -> usize;
     fn shared_count(&self) -> usize; // Returns the number of sharers of the buffer
 
@@ -318,6 +331,24 @@ pub trait StreamBufferT {
     fn append(&mut self, buf: &[u8]) -> bool;
 }
 
+// The bytes underlying a parse buffer and its views: either owned
+// bytes, or a file that is mapped into memory.
+#[derive(Debug)]
+pub enum BufferData {
+    Bytes(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for BufferData {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self {
+            BufferData::Bytes(b) => b,
+            BufferData::Mapped(m) => m,
+        }
+    }
+}
+
 // The basic parsing buffer.  This implements a (possibly restricted)
 // view of length 'size' into the underlying buffer 'buf'.  The first
 // (0'th byte) of the view corresponds to byte 'start', and the last
@@ -325,7 +356,7 @@ pub trait StreamBufferT {
 
 #[derive(Debug)]
 pub struct ParseBuffer {
-    buf:   Rc<Vec<u8>>,
+    buf:   Arc<BufferData>,
     start: usize,
     end:   usize,
     // NOTE: the offset is maintained as an *absolute* index into the
@@ -340,13 +371,32 @@ impl ParseBuffer {
     pub fn new(buf: Vec<u8>) -> ParseBuffer {
         let end = buf.len();
         ParseBuffer {
-            buf: Rc::new(buf),
+            buf: Arc::new(BufferData::Bytes(buf)),
             start: 0,
             ofs: 0,
             end,
         }
     }
 
+    // Creates a default view into a file that is mapped into memory
+    // instead of being read, so that its views share the pages of the
+    // file.  The buffer cannot be modified via StreamBufferT.
+    pub fn map_file(file: &File) -> io::Result<ParseBuffer> {
+        // The mapping is read-only; as with any mapping, the file
+        // should not be modified while it is mapped.  If the file is
+        // truncated, accessing the pages past its new end raises
+        // SIGBUS and kills the process, so files that can change
+        // under the parser should be read into a buffer with `new`.
+        let map = unsafe { Mmap::map(file)? };
+        let end = map.len();
+        Ok(ParseBuffer {
+            buf: Arc::new(BufferData::Mapped(map)),
+            start: 0,
+            ofs: 0,
+            end,
+        })
+    }
+
     // Creates a subset view of the specified size from the specified
     // offset.  The cursor is set to point to the beginning of the
     // view.
@@ -407,9 +457,9 @@ impl ParseBufferT for ParseBuffer {
 
     fn buf(&self) -> &[u8] { &self.buf[self.ofs .. self.end] }
 
-    fn rc_buf(&self) -> Rc<Vec<u8>> { Rc::clone(&self.buf) }
+    fn rc_buf(&self) -> Arc<BufferData> { Arc::clone(&self.buf) }
 
-    fn shared_count(&self) -> usize { Rc::strong_count(&self.buf) }
+    fn shared_count(&self) -> usize { Arc::strong_count(&self.buf) }
 
     fn start(&self) -> usize { self.start }
 
@@ -547,8 +597,8 @@ impl StreamBufferT for ParseBuffer {
     fn drop(&mut self, len: usize) -> bool {
         // FIXME: Returning a bool doesn't allow the caller to
         // distinguish between the various causes of failure.
-        match Rc::get_mut(&mut self.buf) {
-            Some(b) => {
+        match Arc::get_mut(&mut self.buf) {
+            Some(BufferData::Bytes(b)) => {
                 // We cannot drop a segment that contains the current
                 // offset.
                 if self.ofs < self.start + len {
@@ -563,18 +613,19 @@ impl StreamBufferT for ParseBuffer {
                     true
                 }
             },
-            None => false, // buffer is being shared
+            // buffer is being shared, or is a mapped file
+            _ => false,
         }
     }
 
     fn append(&mut self, buf: &[u8]) -> bool {
-        match Rc::get_mut(&mut self.buf) {
-            Some(b) => {
+        match Arc::get_mut(&mut self.buf) {
+            Some(BufferData::Bytes(b)) => {
                 self.end += buf.len();
                 b.extend_from_slice(buf);
                 true
             },
-            None => false,
+            _ => false,
         }
     }
 }
@@ -583,6 +634,7 @@ impl StreamBufferT for ParseBuffer {
 mod test_parsebuffer {
     use super::{locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, StreamBufferT};
     use std::collections::HashMap;
+    use std::fs::{self, File};
 
     #[test]
     fn test_empty() {
@@ -725,4 +777,25 @@ mod test_parsebuffer {
         pb.append("0123456789".as_bytes());
         assert_eq!(pb.size(), 2 * orig_sz);
     }
+
+    #[test]
+    fn test_map_file() {
+        let path = "tests/test_files/minimal.pdf";
+        let mut pb = ParseBuffer::map_file(&File::open(path).unwrap()).unwrap();
+        let v = fs::read(path).unwrap();
+        assert_eq!(pb.size(), v.len());
+        assert_eq!(pb.buf(), &v[..]);
+        assert_eq!(pb.scan(b"%%EOF"), Ok(v.len() - 6));
+
+        // views share the mapping
+        let view = ParseBuffer::new_view(&pb, 9, 20);
+        assert_eq!(view.buf(), &v[9 .. 29]);
+        assert_eq!(pb.shared_count(), 2);
+        drop(view);
+
+        // a mapped file cannot be modified
+        assert!(!pb.append(b"0123456789"));
+        assert!(!pb.drop(1));
+        assert_eq!(pb.size(), v.len());
+    }
 }
@@ -28,9 +28,9 @@ use crate::pdf_lib::pdf_prim::NameT;
 use crate::pdf_lib::pdf_type_check::{
     ChoicePred, DictEntry, DictKeySpec, PDFPrimType, PDFType, TypeCheck, TypeCheckContext,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
-fn mk_pagemode_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_pagemode_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pred = ChoicePred(
         String::from("Invalid PageMode"),
         vec![
@@ -45,11 +45,11 @@ fn mk_pagemode_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "",
-        Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-        Rc::new(pred),
+        Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+        Arc::new(pred),
     )
 }
-fn mk_pagelayout_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_pagelayout_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pred = ChoicePred(
         String::from("Invalid PageLayout"),
         vec![
@@ -64,15 +64,15 @@ fn mk_pagelayout_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "",
-        Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-        Rc::new(pred),
+        Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+        Arc::new(pred),
     )
 }
 // Errata: extensions, af, dpartroot, dss
 
-pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn catalog_type(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     // Row 1
-    //TypeCheck::new(Rc::new(PDFType::Dict(vec![typ, version, extensions, pages,
+    //TypeCheck::new(Arc::new(PDFType::Dict(vec![typ, version, extensions, pages,
     // pagelabels, names, dests, viewerpreferences, pagelayout,
     let typ = DictEntry {
         key: Vec::from("Type"),
@@ -81,7 +81,7 @@ pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     };
     let version = DictEntry {
         key: Vec::from("Version"),
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Name))), /* TODO: Maybe make a whitelist of version numbers? */
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Name))), /* TODO: Maybe make a whitelist of version numbers? */
         opt: DictKeySpec::Optional,
     };
     let extensions = DictEntry {
@@ -140,7 +140,7 @@ pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     let gdict = mk_generic_dict_typchk(tctx);
     let openaction = DictEntry {
         key: Vec::from("OpenAction"),
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::Disjunct(vec![garray, gdict]))),
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::Disjunct(vec![garray, gdict]))),
         opt: DictKeySpec::Optional,
     };
     let aa = DictEntry {
@@ -179,7 +179,7 @@ pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     };
     let lang = DictEntry {
         key: Vec::from("Lang"),
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::String))),
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::String))),
         opt: DictKeySpec::Optional,
     };
     let spiderinfo = DictEntry {
@@ -228,7 +228,7 @@ pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     };
     let needsrendering = DictEntry {
         key: Vec::from("NeedsRendering"),
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Bool))),
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Bool))),
         opt: DictKeySpec::Optional,
     };
     let dss = DictEntry {
@@ -249,7 +249,7 @@ pub fn catalog_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "catalog",
-        Rc::new(PDFType::Dict(
+        Arc::new(PDFType::Dict(
             vec![
                 typ,
                 version,
@@ -294,7 +294,7 @@ mod test_name_tree {
     use crate::pcore::parsebuffer::ParseBuffer;
     use crate::pdf_lib::pdf_obj::{parse_pdf_indirect_obj, parse_pdf_obj, PDFObjContext};
     use crate::pdf_lib::pdf_type_check::{check_type, TypeCheckContext};
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
@@ -391,6 +391,6 @@ mod test_name_tree {
         // check
         let mut tctx = TypeCheckContext::new();
         let typ = catalog_type(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 }
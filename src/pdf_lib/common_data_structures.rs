@@ -24,9 +24,9 @@ use super::pdf_type_check::{
 };
 use crate::pcore::parsebuffer::LocatedVal;
 use crate::pdf_lib::pdf_prim::NameT;
-use std::rc::Rc;
+use std::sync::Arc;
 
-pub fn resources(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn resources(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let extgstate = DictEntry {
         key: Vec::from("ExtGState"),
         chk: mk_generic_dict_typchk(tctx),
@@ -70,7 +70,7 @@ pub fn resources(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "resources",
-        Rc::new(PDFType::Dict(
+        Arc::new(PDFType::Dict(
             vec![
                 extgstate, colorspace, pattern, shading, xobject, font, procset, properties,
             ],
@@ -81,63 +81,63 @@ pub fn resources(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
 
 // A generic dictionary, typically used for out-of-scope
 // dictionary values.
-pub fn mk_generic_dict_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Dict(vec![], None)))
+pub fn mk_generic_dict_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Dict(vec![], None)))
 }
 
 // A generic dictionary that is required to be an indirect reference,
 // typically used for out-of-scope dictionary values.
-pub fn mk_generic_indirect_dict_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_generic_indirect_dict_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_indirect(
         tctx,
         "",
-        Rc::new(PDFType::Dict(vec![], None)),
+        Arc::new(PDFType::Dict(vec![], None)),
         IndirectSpec::Required,
     )
 }
 
 // A generic stream, typically used for out-of-scope
 // stream values.
-pub fn mk_generic_stream_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Stream(vec![])))
+pub fn mk_generic_stream_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Stream(vec![])))
 }
 
 // A generic stream that is required to be an indirect reference,
 // typically used for out-of-scope dictionary values.
-pub fn mk_generic_indirect_stream_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_generic_indirect_stream_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_indirect(
         tctx,
         "",
-        Rc::new(PDFType::Stream(vec![])),
+        Arc::new(PDFType::Stream(vec![])),
         IndirectSpec::Required,
     )
 }
 
 // A generic array, typically used for out-of-scope
 // array values.
-pub fn mk_generic_array_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    let elem = TypeCheck::new(tctx, "", Rc::new(PDFType::Any));
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Array { elem, size: None }))
+pub fn mk_generic_array_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    let elem = TypeCheck::new(tctx, "", Arc::new(PDFType::Any));
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Array { elem, size: None }))
 }
 
 // A generic array that is required to be an indirect reference,
 // typically used for out-of-scope array values.
-pub fn mk_generic_indirect_array_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    let elem = TypeCheck::new(tctx, "", Rc::new(PDFType::Any));
+pub fn mk_generic_indirect_array_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    let elem = TypeCheck::new(tctx, "", Arc::new(PDFType::Any));
     TypeCheck::new_indirect(
         tctx,
         "",
-        Rc::new(PDFType::Array { elem, size: None }),
+        Arc::new(PDFType::Array { elem, size: None }),
         IndirectSpec::Required,
     )
 }
 
-pub fn mk_array_of_dict_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_array_of_dict_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let elem = mk_generic_dict_typchk(tctx);
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Array { elem, size: None }))
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Array { elem, size: None }))
 }
 
-pub fn mk_name_check(name: &str, msg: &str, tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_name_check(name: &str, msg: &str, tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pred = ChoicePred(
         String::from(msg),
         vec![PDFObjT::Name(NameT::new(Vec::from(name)))],
@@ -145,30 +145,30 @@ pub fn mk_name_check(name: &str, msg: &str, tctx: &mut TypeCheckContext) -> Rc<T
     TypeCheck::new_refined(
         tctx,
         "",
-        Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-        Rc::new(pred),
+        Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+        Arc::new(pred),
     )
 }
 
-pub fn mk_number_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    let int = TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Integer)));
-    let real = TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Real)));
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Disjunct(vec![int, real])))
+pub fn mk_number_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    let int = TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Integer)));
+    let real = TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Real)));
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Disjunct(vec![int, real])))
 }
 
-pub fn mk_rectangle_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_rectangle_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let elem = mk_number_typchk(tctx);
     TypeCheck::new(
         tctx,
         "rectangle",
-        Rc::new(PDFType::Array {
+        Arc::new(PDFType::Array {
             elem,
             size: Some(4),
         }),
     )
 }
 
-pub fn name_dictionary(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn name_dictionary(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let dests = DictEntry {
         key: Vec::from("Dests"),
         chk: name_tree(tctx), // this must be a NameT
@@ -222,7 +222,7 @@ pub fn name_dictionary(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "namedictionary",
-        Rc::new(PDFType::Dict(
+        Arc::new(PDFType::Dict(
             vec![
                 dests,
                 ap,
@@ -242,7 +242,7 @@ pub fn name_dictionary(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
 struct SingleReferencePredicate;
 
 impl Predicate for SingleReferencePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Reference(ref _s2) = obj.val() {
         } else {
             return Some(obj.place(TypeCheckError::PredicateError(
@@ -255,7 +255,7 @@ impl Predicate for SingleReferencePredicate {
 struct ReferencePredicate;
 
 impl Predicate for ReferencePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Array(ref s) = obj.val() {
             for c in s.objs() {
                 if let PDFObjT::Reference(ref _s2) = c.val() {
@@ -273,40 +273,40 @@ impl Predicate for ReferencePredicate {
         }
     }
 }
-pub fn mk_reference_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-    let elem = TypeCheck::new(tctx, "", Rc::new(PDFType::Any));
+pub fn mk_reference_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+    let elem = TypeCheck::new(tctx, "", Arc::new(PDFType::Any));
     TypeCheck::new_refined(
         tctx,
         "reference",
-        Rc::new(PDFType::Array { elem, size: None }),
-        Rc::new(ReferencePredicate),
+        Arc::new(PDFType::Array { elem, size: None }),
+        Arc::new(ReferencePredicate),
     )
 }
-pub fn mk_single_reference_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_single_reference_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "single",
-        Rc::new(PDFType::Any),
-        Rc::new(ReferencePredicate),
+        Arc::new(PDFType::Any),
+        Arc::new(ReferencePredicate),
     )
 }
 
-pub fn mk_date_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_date_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "date",
-        Rc::new(PDFType::PrimType(PDFPrimType::String)),
-        Rc::new(DateStringPredicate),
+        Arc::new(PDFType::PrimType(PDFPrimType::String)),
+        Arc::new(DateStringPredicate),
     )
 }
 struct DateStringPredicate;
 impl Predicate for DateStringPredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         /*
          * PDF spec 7.9.4 defines the date format like:
          *  (D:YYYYMMDDHHmmSSOHH'mm)
          */
-        if let PDFObjT::String(ref s) = obj.val() {
+        if let PDFObjT::String(ref s, _) = obj.val() {
             // official regex for Date
             let _re = regex::Regex::new(r"^D:\d{4}(([0][1-9]|[1][0-2])(([0][1-9]|[1-2][0-9]|[3][0-1])(([0-1][0-9]|[2][0-3])(([0-5][0-9])(([0-5][0-9])([+\-Z](([0-1][0-9]'|[2][0-3]')([0-5][0-9])?)?)?)?)?)?)?)?$").unwrap();
             // relaxed regex that allows a trailing ' at the end.
@@ -328,11 +328,11 @@ impl Predicate for DateStringPredicate {
 
 // used to prevent checks going upwards in a tree by allowing a
 // generic reference.
-pub fn mk_parent_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn mk_parent_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_indirect(
         tctx,
         "parent",
-        Rc::new(PDFType::Any),
+        Arc::new(PDFType::Any),
         IndirectSpec::Required,
     )
 }
@@ -343,7 +343,7 @@ mod test {
     use crate::pcore::parsebuffer::{LocatedVal, ParseBuffer};
     use crate::pdf_lib::pdf_obj::{parse_pdf_obj, PDFObjContext};
     use crate::pdf_lib::pdf_type_check::{check_type, TypeCheckContext, TypeCheckError};
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext {
         PDFObjContext::new(10)
@@ -358,7 +358,7 @@ mod test {
             let mut pb = ParseBuffer::new(v);
             let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
             let typ_chk = mk_date_typchk(&mut tctx);
-            check_type(&ctxt, &tctx, Rc::new(obj), typ_chk)
+            check_type(&ctxt, &tctx, Arc::new(obj), typ_chk)
         }
 
         let correct_test_cases = [
@@ -404,7 +404,7 @@ mod test {
 
         let mut tctx = TypeCheckContext::new();
         let typ = mk_rectangle_typchk(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         let v = Vec::from("[1 2.0 3 4.5]".as_bytes());
         let mut pb = ParseBuffer::new(v);
@@ -412,6 +412,6 @@ mod test {
 
         let mut tctx = TypeCheckContext::new();
         let typ = mk_rectangle_typchk(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 }
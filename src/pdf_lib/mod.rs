@@ -22,13 +22,25 @@ pub mod name_tree;
 pub mod number_tree;
 pub mod page;
 pub mod page_tree;
+pub mod pdf_ccitt;
 pub mod pdf_content_streams;
+pub mod pdf_encoders;
 pub mod pdf_file;
 pub mod pdf_filters;
+pub mod pdf_jbig2;
+pub mod pdf_jpeg;
+pub mod pdf_jpx;
+pub mod pdf_linearization;
 pub mod pdf_obj;
 pub mod pdf_operator_types;
 pub mod pdf_page_dom;
 pub mod pdf_prim;
+pub mod pdf_sanitize;
+pub mod pdf_security;
+pub mod pdf_signatures;
 pub mod pdf_streams;
 pub mod pdf_traverse_xref;
 pub mod pdf_type_check;
+pub mod pdf_writer;
+pub mod pdf_xref_check;
+pub mod pdf_xref_recovery;
@@ -21,18 +21,18 @@ use super::pdf_obj::{DictKey, PDFObjT};
 use crate::pdf_lib::pdf_type_check::{
     PDFType, Predicate, TypeCheck, TypeCheckContext, TypeCheckError,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
 struct NameTreePredicate;
 impl Predicate for NameTreePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Dict(ref s) = obj.val() {
             let mappings = s.map();
             if let Some(a) = mappings.get(&DictKey::new(Vec::from("Names"))) {
                 if let PDFObjT::Array(ref s) = a.val() {
                     if s.objs().len() % 2 == 0 {
                         for c in (0 .. s.objs().len()).step_by(2) {
-                            if let PDFObjT::String(ref _s1) = s.objs()[c].val() {
+                            if let PDFObjT::String(ref _s1, _) = s.objs()[c].val() {
                                 if let PDFObjT::Reference(ref _s2) = s.objs()[c + 1].val() {
                                 } else {
                                     return Some(obj.place(TypeCheckError::PredicateError(
@@ -59,7 +59,7 @@ impl Predicate for NameTreePredicate {
             if let Some(a) = mappings.get(&DictKey::new(Vec::from("Limits"))) {
                 if let PDFObjT::Array(ref s) = a.val() {
                     for c in s.objs() {
-                        if let PDFObjT::String(ref _s1) = c.val() {
+                        if let PDFObjT::String(ref _s1, _) = c.val() {
                         } else {
                             return Some(obj.place(TypeCheckError::PredicateError(
                                 "TypeMismatch: String expected".to_string(),
@@ -118,11 +118,11 @@ impl Predicate for NameTreePredicate {
 }
 struct NamesPredicate;
 impl Predicate for NamesPredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Array(ref s) = obj.val() {
             if s.objs().len() % 2 == 0 {
                 for c in (0 .. s.objs().len()).step_by(2) {
-                    if let PDFObjT::String(ref _s1) = s.objs()[c].val() {
+                    if let PDFObjT::String(ref _s1, _) = s.objs()[c].val() {
                         if let PDFObjT::Reference(ref _s2) = s.objs()[c + 1].val() {
                         } else {
                             return Some(obj.place(TypeCheckError::PredicateError(
@@ -155,12 +155,12 @@ impl Predicate for NamesPredicate {
 // Root with names
 // Root with kids
 
-pub fn name_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn name_tree(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "nametree",
-        Rc::new(PDFType::Any),
-        Rc::new(NameTreePredicate),
+        Arc::new(PDFType::Any),
+        Arc::new(NameTreePredicate),
     )
 }
 
@@ -170,7 +170,7 @@ mod test_name_tree {
     use super::super::pdf_obj::{parse_pdf_obj, PDFObjContext};
     use super::super::pdf_type_check::{check_type, TypeCheckContext, TypeCheckError};
     use super::name_tree;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
@@ -184,7 +184,7 @@ mod test_name_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     #[test]
     fn test_root_names_true_name_tree() {
@@ -207,7 +207,7 @@ mod test_name_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -223,7 +223,7 @@ mod test_name_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "Missing field or Forbidden field".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
     #[test]
     fn test_root_kids_forbidden_name_tree() {
@@ -246,7 +246,7 @@ mod test_name_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -266,7 +266,7 @@ mod test_name_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -291,7 +291,7 @@ mod test_name_tree {
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -319,7 +319,7 @@ mod test_name_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "TypeMismatch: String expected".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -355,7 +355,7 @@ mod test_name_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "Missing field or Forbidden field".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -379,7 +379,7 @@ mod test_name_tree {
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let typ = name_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -404,7 +404,7 @@ mod test_name_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "TypeMismatch: String expected".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -440,6 +440,6 @@ mod test_name_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "Missing field or Forbidden field".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 }
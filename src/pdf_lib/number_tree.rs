@@ -21,11 +21,11 @@ use super::pdf_obj::{DictKey, PDFObjT};
 use crate::pdf_lib::pdf_type_check::{
     PDFType, Predicate, TypeCheck, TypeCheckContext, TypeCheckError,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
 struct NumberTreePredicate;
 impl Predicate for NumberTreePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Dict(ref s) = obj.val() {
             let mappings = s.map();
             if let Some(a) = mappings.get(&DictKey::new(Vec::from("Names"))) {
@@ -116,18 +116,18 @@ impl Predicate for NumberTreePredicate {
         }
     }
 }
-pub fn number_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn number_tree(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "numbertree",
-        Rc::new(PDFType::Any),
-        Rc::new(NumberTreePredicate),
+        Arc::new(PDFType::Any),
+        Arc::new(NumberTreePredicate),
     )
 }
 struct ReferencePredicate;
 
 impl Predicate for ReferencePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Array(ref s) = obj.val() {
             for c in s.objs() {
                 if let PDFObjT::Reference(ref _s2) = c.val() {
@@ -148,7 +148,7 @@ impl Predicate for ReferencePredicate {
 
 struct NumsPredicate;
 impl Predicate for NumsPredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Array(ref s) = obj.val() {
             if s.objs().len() % 2 == 0 {
                 for c in (0 .. s.objs().len()).step_by(2) {
@@ -191,7 +191,7 @@ mod test_number_tree {
     use super::super::pdf_obj::{parse_pdf_obj, PDFObjContext};
     use super::super::pdf_type_check::{check_type, TypeCheckContext, TypeCheckError};
     use super::number_tree;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
     #[test]
@@ -204,7 +204,7 @@ mod test_number_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     #[test]
     fn test_root_names_true_num_tree() {
@@ -227,7 +227,7 @@ mod test_number_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     #[test]
     fn test_root_kids_false_num_tree() {
@@ -242,7 +242,7 @@ mod test_number_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "Missing field or Forbidden field".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
     #[test]
     fn test_root_kids_forbidden_num_tree() {
@@ -265,7 +265,7 @@ mod test_number_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -285,7 +285,7 @@ mod test_number_tree {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         //let v = Vec::from("<< /Count 3 >>".as_bytes());
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -310,7 +310,7 @@ mod test_number_tree {
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -338,7 +338,7 @@ mod test_number_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "TypeMismatch: Integer expected".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -374,7 +374,7 @@ mod test_number_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "TypeMismatch: Integer expected".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -398,7 +398,7 @@ mod test_number_tree {
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let typ = number_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -425,7 +425,7 @@ mod test_number_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "TypeMismatch: Integer expected".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -461,6 +461,6 @@ mod test_number_tree {
         let err = obj.place(TypeCheckError::PredicateError(
             "Missing field or Forbidden field".to_string(),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 }
@@ -26,9 +26,9 @@ use crate::pdf_lib::pdf_prim::NameT;
 use crate::pdf_lib::pdf_type_check::{
     ChoicePred, DictEntry, DictKeySpec, PDFPrimType, PDFType, TypeCheck, TypeCheckContext,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
-fn mk_tabs_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_tabs_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pred = ChoicePred(
         String::from("Invalid PageLayout"),
         vec![
@@ -42,23 +42,23 @@ fn mk_tabs_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "",
-        Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-        Rc::new(pred),
+        Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+        Arc::new(pred),
     )
 }
 
-fn mk_contents_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_contents_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     // This is either a stream or an array of streams.
-    let stm = TypeCheck::new(tctx, "", Rc::new(PDFType::Stream(vec![])));
+    let stm = TypeCheck::new(tctx, "", Arc::new(PDFType::Stream(vec![])));
     let arr = TypeCheck::new(
         tctx,
         "",
-        Rc::new(PDFType::Array {
-            elem: Rc::clone(&stm),
+        Arc::new(PDFType::Array {
+            elem: Arc::clone(&stm),
             size: None,
         }),
     );
-    TypeCheck::new(tctx, "", Rc::new(PDFType::Disjunct(vec![stm, arr])))
+    TypeCheck::new(tctx, "", Arc::new(PDFType::Disjunct(vec![stm, arr])))
 }
 
 fn mk_generic_page_entries(tctx: &mut TypeCheckContext) -> Vec<DictEntry> {
@@ -112,7 +112,7 @@ fn mk_generic_page_entries(tctx: &mut TypeCheckContext) -> Vec<DictEntry> {
         chk: TypeCheck::new(
             tctx,
             "rotate",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         ),
         opt: DictKeySpec::Optional,
     };
@@ -161,14 +161,14 @@ fn mk_generic_page_entries(tctx: &mut TypeCheckContext) -> Vec<DictEntry> {
         chk: TypeCheck::new(
             tctx,
             "structparents",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         ),
         opt: DictKeySpec::Optional,
     };
     let id = DictEntry {
         key: Vec::from("ID"),
         // This needs to be a byte stream, indirect reference preferred.
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::String))),
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::String))),
         opt: DictKeySpec::Optional,
     };
     let pz = DictEntry {
@@ -188,7 +188,7 @@ fn mk_generic_page_entries(tctx: &mut TypeCheckContext) -> Vec<DictEntry> {
     };
     let templateinstantiated = DictEntry {
         key: Vec::from("TemplateInstantiated"),
-        chk: TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Name))),
+        chk: TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Name))),
         opt: DictKeySpec::Optional,
     };
     let pressteps = DictEntry {
@@ -255,7 +255,7 @@ fn mk_generic_page_entries(tctx: &mut TypeCheckContext) -> Vec<DictEntry> {
     ]
 }
 
-pub fn template_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn template_type(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let typ = DictEntry {
         key: Vec::from("Type"),
         chk: mk_name_check("Template", "Not a Template", tctx),
@@ -274,9 +274,9 @@ pub fn template_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     let mut generic_ents = mk_generic_page_entries(tctx);
     ents.append(&mut generic_ents);
 
-    TypeCheck::new(tctx, "template", Rc::new(PDFType::Dict(ents, None)))
+    TypeCheck::new(tctx, "template", Arc::new(PDFType::Dict(ents, None)))
 }
-pub fn page_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn page_type(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let typ = DictEntry {
         key: Vec::from("Type"),
         chk: mk_name_check("Page", "Not a Page", tctx),
@@ -301,7 +301,7 @@ pub fn page_type(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     let mut generic_ents = mk_generic_page_entries(tctx);
     ents.append(&mut generic_ents);
 
-    TypeCheck::new(tctx, "page", Rc::new(PDFType::Dict(ents, None)))
+    TypeCheck::new(tctx, "page", Arc::new(PDFType::Dict(ents, None)))
 }
 #[cfg(test)]
 
@@ -310,7 +310,7 @@ mod test_page {
     use crate::pcore::parsebuffer::ParseBuffer;
     use crate::pdf_lib::pdf_obj::{parse_pdf_indirect_obj, parse_pdf_obj, PDFObjContext};
     use crate::pdf_lib::pdf_type_check::{check_type, TypeCheckContext};
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
@@ -338,7 +338,7 @@ mod test_page {
         // check
         let mut tctx = TypeCheckContext::new();
         let typ = page_type(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     #[test]
     fn test_another_page() {
@@ -469,6 +469,6 @@ mod test_page {
 
         let mut tctx = TypeCheckContext::new();
         let typ = page_type(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 }
@@ -25,9 +25,9 @@ use crate::pdf_lib::pdf_type_check::{
     ChoicePred, DictEntry, DictKeySpec, IndirectSpec, PDFPrimType, PDFType, Predicate, TypeCheck,
     TypeCheckContext, TypeCheckError,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 
-fn mk_pages_check(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_pages_check(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pred = ChoicePred(
         String::from("Pages not present."),
         vec![PDFObjT::Name(NameT::new(Vec::from("Pages")))],
@@ -35,20 +35,20 @@ fn mk_pages_check(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new_refined(
         tctx,
         "pages",
-        Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-        Rc::new(pred),
+        Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+        Arc::new(pred),
     )
 }
 
-fn mk_count_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+fn mk_count_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "count",
-        Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+        Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
     )
 }
 
-pub fn root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn root_page_tree(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pages = DictEntry {
         key: Vec::from("Type"),
         chk: mk_pages_check(tctx), // this must be a NameT
@@ -59,13 +59,13 @@ pub fn root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
         chk: mk_count_typchk(tctx),
         opt: DictKeySpec::Required,
     };
-    let opts = Rc::new(PDFType::Disjunct(vec![
+    let opts = Arc::new(PDFType::Disjunct(vec![
         non_root_page_tree(tctx),
         page_type(tctx),
         template_type(tctx),
     ]));
     let elem = TypeCheck::new_indirect(tctx, "kid", opts, IndirectSpec::Required);
-    let kids = Rc::new(PDFType::Array { elem, size: None });
+    let kids = Arc::new(PDFType::Array { elem, size: None });
     let kids = DictEntry {
         key: Vec::from("Kids"),
         chk: TypeCheck::new(tctx, "kids", kids),
@@ -79,11 +79,11 @@ pub fn root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "root-page-tree",
-        Rc::new(PDFType::Dict(vec![pages, count, kids, parent], None)),
+        Arc::new(PDFType::Dict(vec![pages, count, kids, parent], None)),
     )
 }
 
-pub fn non_root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+pub fn non_root_page_tree(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
     let pages = DictEntry {
         key: Vec::from("Type"),
         chk: mk_pages_check(tctx), // this must be a NameT
@@ -94,13 +94,13 @@ pub fn non_root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
         chk: mk_count_typchk(tctx),
         opt: DictKeySpec::Required,
     };
-    let opts = Rc::new(PDFType::Disjunct(vec![
+    let opts = Arc::new(PDFType::Disjunct(vec![
         page_type(tctx),
         TypeCheck::new_named("root-non-page-tree"),
         template_type(tctx),
     ]));
     let elem = TypeCheck::new_indirect(tctx, "kid", opts, IndirectSpec::Required);
-    let kids = Rc::new(PDFType::Array { elem, size: None });
+    let kids = Arc::new(PDFType::Array { elem, size: None });
     let kids = DictEntry {
         key: Vec::from("Kids"),
         chk: TypeCheck::new(tctx, "kids", kids),
@@ -116,14 +116,14 @@ pub fn non_root_page_tree(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
     TypeCheck::new(
         tctx,
         "root-non-page-tree",
-        Rc::new(PDFType::Dict(vec![pages, count, kids, parent], None)),
+        Arc::new(PDFType::Dict(vec![pages, count, kids, parent], None)),
     )
 }
 
 struct ReferencePredicate;
 
 impl Predicate for ReferencePredicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         if let PDFObjT::Array(ref s) = obj.val() {
             for c in s.objs() {
                 if let PDFObjT::Reference(ref _s2) = c.val() {
@@ -149,7 +149,7 @@ mod test_page_tree {
     use super::super::pdf_obj::{parse_pdf_indirect_obj, parse_pdf_obj, PDFObjContext};
     use super::super::pdf_type_check::{check_type, TypeCheckContext};
     use super::{non_root_page_tree, root_page_tree};
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
@@ -215,7 +215,7 @@ mod test_page_tree {
         // check
         let mut tctx = TypeCheckContext::new();
         let typ = root_page_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -279,7 +279,7 @@ mod test_page_tree {
         // check
         let mut tctx = TypeCheckContext::new();
         let typ = non_root_page_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     // Page Tree Non Root Node Tests End
 
@@ -363,7 +363,7 @@ mod test_page_tree {
         // check
         let mut tctx = TypeCheckContext::new();
         let typ = root_page_tree(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
     // Page Tree Root Node Tests End
 }
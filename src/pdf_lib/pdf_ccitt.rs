@@ -0,0 +1,755 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// A decoder for CCITT Group 3 (ITU-T T.4) and Group 4 (ITU-T T.6)
+// facsimile data, as used by the CCITTFaxDecode filter (Section
+// 7.4.6).  Rows are represented by their changing elements, i.e. the
+// columns at which the color changes, starting from white.
+
+use super::super::pcore::parsebuffer::{
+    locate_value, ErrorKind, Location, ParseBuffer, ParseBufferT,
+};
+use super::super::pcore::transforms::TransformResult;
+use super::super::pdf_lib::pdf_filters::DecodeBudget;
+
+// The decode parameters of the filter (Table 11).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaxParams {
+    // < 0: pure two-dimensional (Group 4)
+    // = 0: pure one-dimensional (Group 3, 1-D)
+    // > 0: mixed one- and two-dimensional (Group 3, 2-D)
+    pub k: i64,
+    pub end_of_line: bool,
+    pub encoded_byte_align: bool,
+    pub columns: usize,
+    // 0 if the number of rows is not known
+    pub rows: usize,
+    pub end_of_block: bool,
+    pub black_is_1: bool,
+    pub damaged_rows_before_error: usize,
+}
+
+impl Default for FaxParams {
+    fn default() -> Self {
+        FaxParams {
+            k: 0,
+            end_of_line: false,
+            encoded_byte_align: false,
+            columns: 1728,
+            rows: 0,
+            end_of_block: true,
+            black_is_1: false,
+            damaged_rows_before_error: 0,
+        }
+    }
+}
+
+// Codes are given as (length in bits, code, value).
+type Code = (u8, u16, u16);
+
+const WHITE_CODES: &[Code] = &[
+    // terminating codes
+    (8, 0b00110101, 0),
+    (6, 0b000111, 1),
+    (4, 0b0111, 2),
+    (4, 0b1000, 3),
+    (4, 0b1011, 4),
+    (4, 0b1100, 5),
+    (4, 0b1110, 6),
+    (4, 0b1111, 7),
+    (5, 0b10011, 8),
+    (5, 0b10100, 9),
+    (5, 0b00111, 10),
+    (5, 0b01000, 11),
+    (6, 0b001000, 12),
+    (6, 0b000011, 13),
+    (6, 0b110100, 14),
+    (6, 0b110101, 15),
+    (6, 0b101010, 16),
+    (6, 0b101011, 17),
+    (7, 0b0100111, 18),
+    (7, 0b0001100, 19),
+    (7, 0b0001000, 20),
+    (7, 0b0010111, 21),
+    (7, 0b0000011, 22),
+    (7, 0b0000100, 23),
+    (7, 0b0101000, 24),
+    (7, 0b0101011, 25),
+    (7, 0b0010011, 26),
+    (7, 0b0100100, 27),
+    (7, 0b0011000, 28),
+    (8, 0b00000010, 29),
+    (8, 0b00000011, 30),
+    (8, 0b00011010, 31),
+    (8, 0b00011011, 32),
+    (8, 0b00010010, 33),
+    (8, 0b00010011, 34),
+    (8, 0b00010100, 35),
+    (8, 0b00010101, 36),
+    (8, 0b00010110, 37),
+    (8, 0b00010111, 38),
+    (8, 0b00101000, 39),
+    (8, 0b00101001, 40),
+    (8, 0b00101010, 41),
+    (8, 0b00101011, 42),
+    (8, 0b00101100, 43),
+    (8, 0b00101101, 44),
+    (8, 0b00000100, 45),
+    (8, 0b00000101, 46),
+    (8, 0b00001010, 47),
+    (8, 0b00001011, 48),
+    (8, 0b01010010, 49),
+    (8, 0b01010011, 50),
+    (8, 0b01010100, 51),
+    (8, 0b01010101, 52),
+    (8, 0b00100100, 53),
+    (8, 0b00100101, 54),
+    (8, 0b01011000, 55),
+    (8, 0b01011001, 56),
+    (8, 0b01011010, 57),
+    (8, 0b01011011, 58),
+    (8, 0b01001010, 59),
+    (8, 0b01001011, 60),
+    (8, 0b00110010, 61),
+    (8, 0b00110011, 62),
+    (8, 0b00110100, 63),
+    // make-up codes
+    (5, 0b11011, 64),
+    (5, 0b10010, 128),
+    (6, 0b010111, 192),
+    (7, 0b0110111, 256),
+    (8, 0b00110110, 320),
+    (8, 0b00110111, 384),
+    (8, 0b01100100, 448),
+    (8, 0b01100101, 512),
+    (8, 0b01101000, 576),
+    (8, 0b01100111, 640),
+    (9, 0b011001100, 704),
+    (9, 0b011001101, 768),
+    (9, 0b011010010, 832),
+    (9, 0b011010011, 896),
+    (9, 0b011010100, 960),
+    (9, 0b011010101, 1024),
+    (9, 0b011010110, 1088),
+    (9, 0b011010111, 1152),
+    (9, 0b011011000, 1216),
+    (9, 0b011011001, 1280),
+    (9, 0b011011010, 1344),
+    (9, 0b011011011, 1408),
+    (9, 0b010011000, 1472),
+    (9, 0b010011001, 1536),
+    (9, 0b010011010, 1600),
+    (6, 0b011000, 1664),
+    (9, 0b010011011, 1728),
+];
+
+const BLACK_CODES: &[Code] = &[
+    // terminating codes
+    (10, 0b0000110111, 0),
+    (3, 0b010, 1),
+    (2, 0b11, 2),
+    (2, 0b10, 3),
+    (3, 0b011, 4),
+    (4, 0b0011, 5),
+    (4, 0b0010, 6),
+    (5, 0b00011, 7),
+    (6, 0b000101, 8),
+    (6, 0b000100, 9),
+    (7, 0b0000100, 10),
+    (7, 0b0000101, 11),
+    (7, 0b0000111, 12),
+    (8, 0b00000100, 13),
+    (8, 0b00000111, 14),
+    (9, 0b000011000, 15),
+    (10, 0b0000010111, 16),
+    (10, 0b0000011000, 17),
+    (10, 0b0000001000, 18),
+    (11, 0b00001100111, 19),
+    (11, 0b00001101000, 20),
+    (11, 0b00001101100, 21),
+    (11, 0b00000110111, 22),
+    (11, 0b00000101000, 23),
+    (11, 0b00000010111, 24),
+    (11, 0b00000011000, 25),
+    (12, 0b000011001010, 26),
+    (12, 0b000011001011, 27),
+    (12, 0b000011001100, 28),
+    (12, 0b000011001101, 29),
+    (12, 0b000001101000, 30),
+    (12, 0b000001101001, 31),
+    (12, 0b000001101010, 32),
+    (12, 0b000001101011, 33),
+    (12, 0b000011010010, 34),
+    (12, 0b000011010011, 35),
+    (12, 0b000011010100, 36),
+    (12, 0b000011010101, 37),
+    (12, 0b000011010110, 38),
+    (12, 0b000011010111, 39),
+    (12, 0b000001101100, 40),
+    (12, 0b000001101101, 41),
+    (12, 0b000011011010, 42),
+    (12, 0b000011011011, 43),
+    (12, 0b000001010100, 44),
+    (12, 0b000001010101, 45),
+    (12, 0b000001010110, 46),
+    (12, 0b000001010111, 47),
+    (12, 0b000001100100, 48),
+    (12, 0b000001100101, 49),
+    (12, 0b000001010010, 50),
+    (12, 0b000001010011, 51),
+    (12, 0b000000100100, 52),
+    (12, 0b000000110111, 53),
+    (12, 0b000000111000, 54),
+    (12, 0b000000100111, 55),
+    (12, 0b000000101000, 56),
+    (12, 0b000001011000, 57),
+    (12, 0b000001011001, 58),
+    (12, 0b000000101011, 59),
+    (12, 0b000000101100, 60),
+    (12, 0b000001011010, 61),
+    (12, 0b000001100110, 62),
+    (12, 0b000001100111, 63),
+    // make-up codes
+    (10, 0b0000001111, 64),
+    (12, 0b000011001000, 128),
+    (12, 0b000011001001, 192),
+    (12, 0b000001011011, 256),
+    (12, 0b000000110011, 320),
+    (12, 0b000000110100, 384),
+    (12, 0b000000110101, 448),
+    (13, 0b0000001101100, 512),
+    (13, 0b0000001101101, 576),
+    (13, 0b0000001001010, 640),
+    (13, 0b0000001001011, 704),
+    (13, 0b0000001001100, 768),
+    (13, 0b0000001001101, 832),
+    (13, 0b0000001110010, 896),
+    (13, 0b0000001110011, 960),
+    (13, 0b0000001110100, 1024),
+    (13, 0b0000001110101, 1088),
+    (13, 0b0000001110110, 1152),
+    (13, 0b0000001110111, 1216),
+    (13, 0b0000001010010, 1280),
+    (13, 0b0000001010011, 1344),
+    (13, 0b0000001010100, 1408),
+    (13, 0b0000001010101, 1472),
+    (13, 0b0000001011010, 1536),
+    (13, 0b0000001011011, 1600),
+    (13, 0b0000001100100, 1664),
+    (13, 0b0000001100101, 1728),
+];
+
+// make-up codes shared by both colors
+const EXTENDED_CODES: &[Code] = &[
+    (11, 0b00000001000, 1792),
+    (11, 0b00000001100, 1856),
+    (11, 0b00000001101, 1920),
+    (12, 0b000000010010, 1984),
+    (12, 0b000000010011, 2048),
+    (12, 0b000000010100, 2112),
+    (12, 0b000000010101, 2176),
+    (12, 0b000000010110, 2240),
+    (12, 0b000000010111, 2304),
+    (12, 0b000000011100, 2368),
+    (12, 0b000000011101, 2432),
+    (12, 0b000000011110, 2496),
+    (12, 0b000000011111, 2560),
+];
+
+// The end-of-line code, which is 11 zeros followed by a 1.
+const EOL: u32 = 1;
+const EOL_LEN: u8 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Pass,
+    Horizontal,
+    // the offset of a1 from b1
+    Vertical(isize),
+}
+
+const MODE_CODES: &[(u8, u16, Mode)] = &[
+    (1, 0b1, Mode::Vertical(0)),
+    (3, 0b001, Mode::Horizontal),
+    (3, 0b011, Mode::Vertical(1)),
+    (3, 0b010, Mode::Vertical(-1)),
+    (4, 0b0001, Mode::Pass),
+    (6, 0b000011, Mode::Vertical(2)),
+    (6, 0b000010, Mode::Vertical(-2)),
+    (7, 0b0000011, Mode::Vertical(3)),
+    (7, 0b0000010, Mode::Vertical(-3)),
+];
+
+// Reads the bits of the encoded data starting from the high-order
+// bit of each byte.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos:  usize, // in bits
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize { self.data.len() * 8 - self.pos }
+
+    // Returns the next n <= 16 bits without consuming them, padding
+    // with zeros past the end of the data.
+    fn peek(&self, n: u8) -> u32 {
+        let mut v = 0u32;
+        for i in 0 .. n as usize {
+            let p = self.pos + i;
+            let bit = match self.data.get(p / 8) {
+                Some(b) => (b >> (7 - p % 8)) & 1,
+                None => 0,
+            };
+            v = (v << 1) | bit as u32;
+        }
+        v
+    }
+
+    fn consume(&mut self, n: u8) { self.pos = (self.pos + n as usize).min(self.data.len() * 8) }
+
+    fn align(&mut self) { self.pos = self.pos.div_ceil(8).min(self.data.len()) * 8 }
+
+    // Whether only zero bits remain.
+    fn at_end(&self) -> bool {
+        let bits = (8 - self.pos % 8) % 8;
+        self.peek(bits as u8) == 0 && self.data[self.pos.div_ceil(8) ..].iter().all(|b| *b == 0)
+    }
+
+    // Skips fill bits, i.e. zero bits that precede an EOL.
+    fn skip_fill(&mut self) {
+        while self.remaining() > EOL_LEN as usize && self.peek(EOL_LEN) == 0 {
+            self.consume(1)
+        }
+    }
+
+    fn at_eol(&self) -> bool { self.remaining() >= EOL_LEN as usize && self.peek(EOL_LEN) == EOL }
+
+    // Finds the code matching the next bits in a code table.
+    fn lookup<T: Copy>(&mut self, codes: &[(u8, u16, T)]) -> Option<T> {
+        for len in 1 ..= 13 {
+            if len as usize > self.remaining() {
+                return None
+            }
+            let bits = self.peek(len) as u16;
+            if let Some((_, _, v)) = codes.iter().find(|(l, c, _)| *l == len && *c == bits) {
+                self.consume(len);
+                return Some(*v)
+            }
+        }
+        None
+    }
+}
+
+struct FaxDecoder<'a> {
+    params: &'a FaxParams,
+    bits:   BitReader<'a>,
+    budget: &'a DecodeBudget,
+}
+
+impl FaxDecoder<'_> {
+    // Reads a run length for the given color: any make-up codes
+    // followed by a terminating code.
+    fn run(&mut self, black: bool) -> Result<usize, String> {
+        let mut run = 0;
+        loop {
+            let codes = if black { BLACK_CODES } else { WHITE_CODES };
+            let v = match self.bits.lookup(codes) {
+                Some(v) => v,
+                None => match self.bits.lookup(EXTENDED_CODES) {
+                    Some(v) => v,
+                    None => {
+                        let color = if black { "black" } else { "white" };
+                        return Err(format!("invalid {} run-length code", color))
+                    },
+                },
+            };
+            run += v as usize;
+            if v < 64 {
+                return Ok(run)
+            }
+        }
+    }
+
+    // Adds a changing element at column a1, checking that the row
+    // does not run backwards or past its end.  Changes at the same
+    // column, from zero-length runs, cancel out.
+    fn change(&self, row: &mut Vec<usize>, a0: usize, a1: usize) -> Result<(), String> {
+        if a1 < a0 || a1 > self.params.columns {
+            return Err(format!(
+                "changing element {} is outside columns {} ..= {}",
+                a1, a0, self.params.columns
+            ))
+        }
+        if row.last() == Some(&a1) {
+            row.pop();
+        } else {
+            row.push(a1)
+        }
+        Ok(())
+    }
+
+    // Decodes a one-dimensionally coded row.
+    fn row_1d(&mut self) -> Result<Vec<usize>, String> {
+        let mut row = Vec::new();
+        let mut a0 = 0;
+        let mut black = false;
+        while a0 < self.params.columns {
+            let a1 = a0 + self.run(black)?;
+            self.change(&mut row, a0, a1)?;
+            a0 = a1;
+            black = !black;
+        }
+        Ok(row)
+    }
+
+    // Decodes a two-dimensionally coded row, using the changing
+    // elements of the reference row.
+    fn row_2d(&mut self, refrow: &[usize]) -> Result<Vec<usize>, String> {
+        let columns = self.params.columns;
+        let mut row = Vec::new();
+        // a0 starts at an imaginary white element before the row.
+        let mut a0: Option<usize> = None;
+        let mut black = false;
+        while a0.is_none_or(|a0| a0 < columns) {
+            // b1 is the first changing element on the reference row
+            // to the right of a0 that changes to the opposite color
+            // of a0, and b2 is the next one.  Even indices change to
+            // black.
+            let parity = if black { 1 } else { 0 };
+            let b1_idx = (0 .. refrow.len())
+                .find(|&i| i % 2 == parity && a0.is_none_or(|a0| refrow[i] > a0))
+                .unwrap_or(refrow.len());
+            let b1 = refrow.get(b1_idx).copied().unwrap_or(columns);
+            let b2 = refrow.get(b1_idx + 1).copied().unwrap_or(columns);
+            let start = a0.unwrap_or(0);
+            if self.bits.at_eol() {
+                return Err("unexpected EOL in row".to_string())
+            }
+            match self.bits.lookup(MODE_CODES) {
+                // a0 moves below b2, without a color change
+                Some(Mode::Pass) => a0 = Some(b2),
+                Some(Mode::Horizontal) => {
+                    let a1 = start + self.run(black)?;
+                    self.change(&mut row, start, a1)?;
+                    let a2 = a1 + self.run(!black)?;
+                    self.change(&mut row, a1, a2)?;
+                    a0 = Some(a2);
+                },
+                Some(Mode::Vertical(d)) => {
+                    let a1 = b1 as isize + d;
+                    if a1 < 0 || a0.is_some_and(|a0| a1 as usize <= a0) {
+                        return Err(format!(
+                            "vertical mode moves to column {} before column {}",
+                            a1, start
+                        ))
+                    }
+                    self.change(&mut row, start, a1 as usize)?;
+                    a0 = Some(a1 as usize);
+                    black = !black;
+                },
+                None => {
+                    if self.bits.peek(7) == 0b0000001 {
+                        return Err("uncompressed mode is not supported".to_string())
+                    }
+                    return Err("invalid two-dimensional mode code".to_string())
+                },
+            }
+        }
+        Ok(row)
+    }
+
+    // Skips past the next EOL, to resynchronize after a damaged row.
+    fn skip_to_eol(&mut self) -> bool {
+        while self.bits.remaining() >= EOL_LEN as usize {
+            if self.bits.peek(EOL_LEN) == EOL {
+                return true
+            }
+            self.bits.consume(1)
+        }
+        false
+    }
+
+    fn decode(&mut self) -> Result<Vec<Vec<usize>>, String> {
+        let params = self.params;
+        let mut rows: Vec<Vec<usize>> = Vec::new();
+        let mut damaged = 0;
+        // The reference row for the first row is all white.
+        let mut refrow = Vec::new();
+        loop {
+            if params.rows > 0 && rows.len() == params.rows {
+                break
+            }
+            self.bits.skip_fill();
+            // Consume any EOLs, each followed by a tag bit in mixed
+            // mode.  Consecutive EOLs mark the end of the data (RTC
+            // in Group 3, EOFB in Group 4).
+            let mut eols = 0;
+            let mut tag = None;
+            while self.bits.at_eol() {
+                self.bits.consume(EOL_LEN);
+                eols += 1;
+                if params.k > 0 {
+                    tag = Some(self.bits.peek(1));
+                    self.bits.consume(1)
+                }
+                if eols == 2 {
+                    break
+                }
+            }
+            // Without an EOL, the row data begins at the next byte
+            // boundary; otherwise the fill bits before the EOL align it.
+            if params.encoded_byte_align && eols == 0 {
+                self.bits.align()
+            }
+            if eols == 2 || self.bits.at_end() {
+                if params.rows > 0 && rows.len() < params.rows {
+                    return Err(format!(
+                        "data ends after {} of {} rows",
+                        rows.len(),
+                        params.rows
+                    ))
+                }
+                if eols < 2 && params.end_of_block && params.rows == 0 {
+                    return Err("no end-of-block pattern".to_string())
+                }
+                break
+            }
+            if params.end_of_line && eols == 0 {
+                return Err(format!("missing EOL before row {}", rows.len()))
+            }
+            // Check the size of the rendered output before each row.
+            let size = (rows.len() + 1).saturating_mul(params.columns.div_ceil(8));
+            self.budget.check(self.bits.data.len(), size)?;
+            let two_d = match params.k {
+                k if k < 0 => true,
+                0 => false,
+                _ => {
+                    let tag = match tag {
+                        Some(t) => t,
+                        None => {
+                            let t = self.bits.peek(1);
+                            self.bits.consume(1);
+                            t
+                        },
+                    };
+                    tag == 0
+                },
+            };
+            let row = if two_d {
+                self.row_2d(&refrow)
+            } else {
+                self.row_1d()
+            };
+            let row = match row {
+                Ok(row) => row,
+                // Rows can only be resynchronized at EOLs.
+                Err(msg) => {
+                    if params.end_of_line
+                        && damaged < params.damaged_rows_before_error
+                        && self.skip_to_eol()
+                    {
+                        damaged += 1;
+                        refrow.clone()
+                    } else {
+                        return Err(msg)
+                    }
+                },
+            };
+            refrow = row.clone();
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
+
+// Renders rows of changing elements into a 1 bit per pixel bitmap,
+// with each row starting on a byte boundary.
+fn render(rows: &[Vec<usize>], columns: usize, black_is_1: bool) -> Vec<u8> {
+    let row_bytes = columns.div_ceil(8);
+    // Pixels are white (1 unless /BlackIs1) until the first change.
+    let white: u8 = if black_is_1 { 0 } else { 1 };
+    let mut out = Vec::with_capacity(row_bytes * rows.len());
+    for row in rows {
+        let mut line = vec![0u8; row_bytes];
+        let mut color = white;
+        let mut changes = row.iter().peekable();
+        for col in 0 .. columns {
+            while changes.peek().is_some_and(|&&c| c == col) {
+                color ^= 1;
+                changes.next();
+            }
+            if color == 1 {
+                line[col / 8] |= 0x80 >> (col % 8)
+            }
+        }
+        out.extend_from_slice(&line);
+    }
+    out
+}
+
+pub fn decode_fax(
+    buf: &dyn ParseBufferT, params: &FaxParams, budget: &DecodeBudget,
+) -> TransformResult {
+    let loc = buf.get_location();
+    if params.columns == 0 {
+        let err = ErrorKind::TransformError("CCITTFaxDecode: /Columns is 0".to_string());
+        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+    }
+    let mut decoder = FaxDecoder {
+        params,
+        bits: BitReader {
+            data: buf.buf(),
+            pos:  0,
+        },
+        budget,
+    };
+    match decoder.decode() {
+        Ok(rows) => Ok(ParseBuffer::new(render(
+            &rows,
+            params.columns,
+            params.black_is_1,
+        ))),
+        Err(msg) => {
+            // Locate the error at the byte containing the bad code.
+            let ofs = (loc.loc_start() + decoder.bits.pos / 8).min(loc.loc_end());
+            let err = ErrorKind::TransformError(format!("CCITTFaxDecode: {}", msg));
+            Err(locate_value(err, ofs, ofs))
+        },
+    }
+}
+
+#[cfg(test)]
+mod test_ccitt {
+    use super::{decode_fax, FaxParams, BLACK_CODES, EXTENDED_CODES, WHITE_CODES};
+    use crate::pcore::parsebuffer::{ErrorKind, ParseBuffer, ParseBufferT};
+    use crate::pdf_lib::pdf_filters::DecodeBudget;
+
+    // Packs a string of '0' and '1' characters into bytes.
+    fn bits(s: &str) -> Vec<u8> {
+        let s: Vec<u8> = s.bytes().filter(|b| *b == b'0' || *b == b'1').collect();
+        s.chunks(8)
+            .map(|c| {
+                let mut b = 0u8;
+                for (i, bit) in c.iter().enumerate() {
+                    b |= (bit - b'0') << (7 - i)
+                }
+                b
+            })
+            .collect()
+    }
+
+    fn decode(data: &str, params: &FaxParams) -> Result<Vec<u8>, ErrorKind> {
+        let pb = ParseBuffer::new(bits(data));
+        match decode_fax(&pb, params, &DecodeBudget::default()) {
+            Ok(b) => Ok(b.buf().to_vec()),
+            Err(e) => Err(e.val().clone()),
+        }
+    }
+
+    #[test]
+    fn test_codes_prefix_free() {
+        for codes in &[WHITE_CODES, BLACK_CODES] {
+            let all: Vec<_> = codes.iter().chain(EXTENDED_CODES.iter()).collect();
+            for (i, (l1, c1, _)) in all.iter().enumerate() {
+                for (l2, c2, _) in all.iter().skip(i + 1) {
+                    let l = l1.min(l2);
+                    assert_ne!(c1 >> (l1 - l), c2 >> (l2 - l));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_1d() {
+        let params = FaxParams {
+            columns: 8,
+            rows: 2,
+            ..FaxParams::default()
+        };
+        // 2 white, 3 black, 3 white; 0 white, 8 black
+        let data = "0111 10 1000  00110101 000101";
+        assert_eq!(decode(data, &params).unwrap(), vec![0b11000111, 0b00000000]);
+        let params = FaxParams {
+            black_is_1: true,
+            ..params
+        };
+        assert_eq!(decode(data, &params).unwrap(), vec![0b00111000, 0b11111111]);
+        // invalid code
+        assert!(decode("0111 0000000011", &params).is_err());
+        // runs past the row
+        assert!(decode("1011 0011", &params).is_err());
+        // missing rows
+        assert!(decode("0111 10 1000", &params).is_err());
+    }
+
+    #[test]
+    fn test_2d() {
+        let params = FaxParams {
+            k: -1,
+            columns: 8,
+            ..FaxParams::default()
+        };
+        // row 1: H(2 white, 3 black), V0 -> 3 white
+        // row 2: V0, V0, VL2, V0 -> 2 white, 3 black, 1 white, 2 black
+        // EOFB
+        let data = "001 0111 10 1  1 1 000010 1  000000000001 000000000001";
+        assert_eq!(decode(data, &params).unwrap(), vec![0b11000111, 0b11000100]);
+        // pass mode: a0 moves under b2 without a change
+        let data = "001 0111 10 1  0001 1  000000000001 000000000001";
+        assert!(decode(data, &params).is_ok());
+        // missing EOFB
+        assert!(decode("001 0111 10 1", &params).is_err());
+        // with a known number of rows, EOFB is not needed
+        let params = FaxParams { rows: 1, ..params };
+        assert_eq!(decode("001 0111 10 1", &params).unwrap(), vec![0b11000111]);
+        // vertical mode beyond the row
+        assert!(decode("0000011 1", &params).is_err());
+        // uncompressed mode
+        assert!(decode("0000001111", &params).is_err());
+    }
+
+    #[test]
+    fn test_eol() {
+        let params = FaxParams {
+            columns: 8,
+            end_of_line: true,
+            ..FaxParams::default()
+        };
+        // EOL-prefixed rows, terminated by RTC
+        let eol = "000000000001";
+        let data = format!("{0} 0111 10 1000 {0} 10011 {0}{0}{0}{0}{0}{0}", eol);
+        assert_eq!(
+            decode(&data, &params).unwrap(),
+            vec![0b11000111, 0b11111111]
+        );
+        // a missing EOL
+        let data = format!("{0} 0111 10 1000 10011 {0}{0}", eol);
+        assert!(decode(&data, &params).is_err());
+        // a damaged row is replaced by the previous one
+        let data = format!("{0} 0111 10 1000 {0} 0000000011 {0} 10011 {0}{0}", eol);
+        assert!(decode(&data, &params).is_err());
+        let params = FaxParams {
+            damaged_rows_before_error: 1,
+            ..params
+        };
+        assert_eq!(
+            decode(&data, &params).unwrap(),
+            vec![0b11000111, 0b11000111, 0b11111111]
+        );
+    }
+}
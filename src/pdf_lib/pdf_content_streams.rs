@@ -379,7 +379,7 @@ impl<'a> TextExtractor<'a> {
                         CSObjT::Array(array) => {
                             for o in array.objs() {
                                 match o.val() {
-                                    PDFObjT::String(v) => {
+                                    PDFObjT::String(v, _) => {
                                         texts.push(TextToken::RawText(v.clone()))
                                     },
 
@@ -0,0 +1,578 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Encoders for the stream filters that are decoded by the transforms
+// in pdf_filters.  Each encoder takes the same /DecodeParms options
+// as its decoder, so that decoding its output with those options
+// gives back its input.
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::super::pcore::parsebuffer::{
+    locate_value, ErrorKind, LocatedVal, Location, ParseBuffer, ParseBufferT,
+};
+use super::super::pcore::transforms::{BufferTransformT, TransformResult};
+use super::super::pdf_lib::pdf_obj::{DictT, PDFObjT};
+
+// Returns the integer value of an option, or a default.
+fn int_option(options: &Option<&DictT>, key: &[u8], default: i64) -> i64 {
+    options
+        .and_then(|x| x.get(key))
+        .and_then(|x| match x.val() {
+            PDFObjT::Integer(x) => Some(x.int_val()),
+            _ => None,
+        })
+        .unwrap_or(default)
+}
+
+fn encode_error(filter: &str, loc: &dyn Location, msg: String) -> LocatedVal<ErrorKind> {
+    let err = ErrorKind::TransformError(format!("{}: {}", filter, msg));
+    locate_value(err, loc.loc_start(), loc.loc_end())
+}
+
+// The predictor parameters shared by FlateDecode and LZWDecode.
+struct Predictor {
+    predictor: i64,
+    colors:    usize,
+    bpc:       usize,
+    columns:   usize,
+}
+
+impl Predictor {
+    fn new(options: &Option<&DictT>) -> Predictor {
+        Predictor {
+            predictor: int_option(options, b"Predictor", 1),
+            colors:    int_option(options, b"Colors", 1).max(0) as usize,
+            bpc:       int_option(options, b"BitsPerComponent", 8).max(0) as usize,
+            columns:   int_option(options, b"Columns", 1).max(0) as usize,
+        }
+    }
+
+    // Applies the predictor to the data before it is compressed.
+    fn apply(
+        &self, filter: &str, buf: &dyn ParseBufferT,
+    ) -> Result<Vec<u8>, LocatedVal<ErrorKind>> {
+        let data = buf.buf();
+        let loc = &buf.get_location();
+        match self.predictor {
+            1 => Ok(data.to_vec()),
+            2 => {
+                if self.bpc != 8 {
+                    let msg = format!("TIFF predictor: unsupported BitsPerComponent {}", self.bpc);
+                    return Err(encode_error(filter, loc, msg))
+                }
+                let row_length = self.columns * self.colors;
+                if row_length == 0 || !data.len().is_multiple_of(row_length) {
+                    let msg = format!(
+                        "TIFF predictor: size {} is not a multiple of the row size {}",
+                        data.len(),
+                        row_length
+                    );
+                    return Err(encode_error(filter, loc, msg))
+                }
+                let mut out = Vec::with_capacity(data.len());
+                for row in data.chunks(row_length) {
+                    for j in 0 .. row_length {
+                        let left = if j >= self.colors {
+                            row[j - self.colors]
+                        } else {
+                            0
+                        };
+                        out.push(row[j].wrapping_sub(left))
+                    }
+                }
+                Ok(out)
+            },
+            10 ..= 15 => {
+                let bytes_per_pixel = std::cmp::max(1, (self.colors * self.bpc).div_ceil(8));
+                let row_length = (self.columns * self.colors * self.bpc).div_ceil(8);
+                if row_length == 0 || !data.len().is_multiple_of(row_length) {
+                    let msg = format!(
+                        "PNG predictor: size {} is not a multiple of the row size {}",
+                        data.len(),
+                        row_length
+                    );
+                    return Err(encode_error(filter, loc, msg))
+                }
+                let mut out = Vec::with_capacity(data.len() + data.len() / row_length);
+                let zero = vec![0; row_length];
+                let mut prev: &[u8] = &zero;
+                for row in data.chunks(row_length) {
+                    let tag = match self.predictor {
+                        // PNG Optimum selects the row filter per row.
+                        15 => (0 ..= 4)
+                            .min_by_key(|t| {
+                                png_filter_row(*t, row, prev, bytes_per_pixel)
+                                    .iter()
+                                    .map(|b| (*b as i8).unsigned_abs() as usize)
+                                    .sum::<usize>()
+                            })
+                            .unwrap_or(0),
+                        p => (p - 10) as u8,
+                    };
+                    out.push(tag);
+                    out.extend(png_filter_row(tag, row, prev, bytes_per_pixel));
+                    prev = row
+                }
+                Ok(out)
+            },
+            p => Err(encode_error(
+                filter,
+                loc,
+                format!("unknown predictor {}", p),
+            )),
+        }
+    }
+}
+
+// Filters a row with the given PNG filter type.
+fn png_filter_row(tag: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(row.len());
+    for j in 0 .. row.len() {
+        let left = if j >= bpp { row[j - bpp] } else { 0 };
+        let upleft = if j >= bpp { prev[j - bpp] } else { 0 };
+        let up = prev[j];
+        let pred = match tag {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            _ => paeth(left, up, upleft),
+        };
+        out.push(row[j].wrapping_sub(pred))
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let (ia, ib, ic) = (a as i16, b as i16, c as i16);
+    let p = ia + ib - ic;
+    let (pa, pb, pc) = ((p - ia).abs(), (p - ib).abs(), (p - ic).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+pub struct FlateEncode<'a> {
+    options: &'a Option<&'a DictT>,
+}
+
+impl FlateEncode<'_> {
+    pub fn new<'a>(options: &'a Option<&'a DictT>) -> FlateEncode<'a> { FlateEncode { options } }
+}
+
+impl BufferTransformT for FlateEncode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let data = Predictor::new(self.options).apply("FlateEncode", buf)?;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        match encoder.write_all(&data).and_then(|_| encoder.finish()) {
+            Ok(encoded) => Ok(ParseBuffer::new(encoded)),
+            Err(e) => {
+                let msg = format!("error encoding: {}", e);
+                Err(encode_error("FlateEncode", &buf.get_location(), msg))
+            },
+        }
+    }
+}
+
+pub struct LZWEncode<'a> {
+    options: &'a Option<&'a DictT>,
+}
+
+impl LZWEncode<'_> {
+    pub fn new<'a>(options: &'a Option<&'a DictT>) -> LZWEncode<'a> { LZWEncode { options } }
+}
+
+impl BufferTransformT for LZWEncode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let data = Predictor::new(self.options).apply("LZWEncode", buf)?;
+        let earlychange = int_option(self.options, b"EarlyChange", 1);
+        Ok(ParseBuffer::new(encode_bytes_lzw(&data, earlychange != 0)))
+    }
+}
+
+const LZW_CLEAR: u16 = 256;
+const LZW_EOD: u16 = 257;
+
+// Packs codes of varying widths starting from the high-order bit.
+struct MsbWriter {
+    out:   Vec<u8>,
+    acc:   u32,
+    nbits: u8,
+}
+
+impl MsbWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.acc = (self.acc << width) | code as u32;
+        self.nbits += width;
+        while self.nbits >= 8 {
+            self.nbits -= 8;
+            self.out.push((self.acc >> self.nbits) as u8);
+        }
+        self.acc &= (1 << self.nbits) - 1;
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 {
+            self.out.push((self.acc << (8 - self.nbits)) as u8)
+        }
+        self.out
+    }
+}
+
+// The LZW variant of decode_bytes_lzw().  The decoder adds a table
+// entry one code later than the encoder, so the encoder widens its
+// codes when the entry it adds is the one that makes the decoder
+// widen.  The table is cleared before the codes need to exceed 12
+// bits.
+fn encode_bytes_lzw(data: &[u8], earlychange: bool) -> Vec<u8> {
+    let early = earlychange as u16;
+    let mut w = MsbWriter {
+        out:   Vec::new(),
+        acc:   0,
+        nbits: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = LZW_EOD + 1;
+    let mut width = 9;
+    let mut cur: Option<u16> = None;
+    w.write(LZW_CLEAR, width);
+    for &c in data {
+        let prefix = match cur {
+            None => {
+                cur = Some(c as u16);
+                continue
+            },
+            Some(p) => p,
+        };
+        if let Some(code) = table.get(&(prefix, c)) {
+            cur = Some(*code);
+            continue
+        }
+        w.write(prefix, width);
+        table.insert((prefix, c), next);
+        if next + early == (1 << width) && width < 12 {
+            width += 1
+        }
+        next += 1;
+        if next + early == 4096 {
+            w.write(LZW_CLEAR, width);
+            table.clear();
+            next = LZW_EOD + 1;
+            width = 9;
+        }
+        cur = Some(c as u16)
+    }
+    if let Some(code) = cur {
+        w.write(code, width);
+        // the decoder adds an entry for this code as well
+        if next + early == (1 << width) && width < 12 {
+            width += 1
+        }
+    }
+    w.write(LZW_EOD, width);
+    w.finish()
+}
+
+pub struct ASCIIHexEncode<'a> {
+    _options: &'a Option<&'a DictT>,
+}
+
+impl ASCIIHexEncode<'_> {
+    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> ASCIIHexEncode<'a> {
+        ASCIIHexEncode { _options }
+    }
+}
+
+impl BufferTransformT for ASCIIHexEncode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let mut out = Vec::with_capacity(buf.buf().len() * 2 + 1);
+        for b in buf.buf() {
+            out.extend_from_slice(format!("{:02X}", b).as_bytes())
+        }
+        out.push(b'>');
+        Ok(ParseBuffer::new(out))
+    }
+}
+
+pub struct ASCII85Encode<'a> {
+    _options: &'a Option<&'a DictT>,
+}
+
+impl ASCII85Encode<'_> {
+    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> ASCII85Encode<'a> {
+        ASCII85Encode { _options }
+    }
+}
+
+impl BufferTransformT for ASCII85Encode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        // PDF uses the ~> EOD, but not the <~ prefix.
+        let encoded = ascii85::encode(buf.buf());
+        let encoded = encoded.trim_start_matches("<~");
+        Ok(ParseBuffer::new(Vec::from(encoded.as_bytes())))
+    }
+}
+
+pub struct RunLengthEncode<'a> {
+    _options: &'a Option<&'a DictT>,
+}
+
+impl RunLengthEncode<'_> {
+    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> RunLengthEncode<'a> {
+        RunLengthEncode { _options }
+    }
+}
+
+impl BufferTransformT for RunLengthEncode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let input = buf.buf();
+        let mut out = Vec::new();
+        let mut literal = 0;
+        let mut i = 0;
+        while i < input.len() {
+            let mut run = 1;
+            while run < 128 && i + run < input.len() && input[i + run] == input[i] {
+                run += 1
+            }
+            if run > 1 {
+                // flush the pending literal run
+                if literal < i {
+                    out.push((i - literal - 1) as u8);
+                    out.extend_from_slice(&input[literal .. i]);
+                }
+                out.push((257 - run) as u8);
+                out.push(input[i]);
+                i += run;
+                literal = i
+            } else {
+                i += 1;
+                if i - literal == 128 {
+                    out.push(127);
+                    out.extend_from_slice(&input[literal .. i]);
+                    literal = i
+                }
+            }
+        }
+        if literal < input.len() {
+            out.push((input.len() - literal - 1) as u8);
+            out.extend_from_slice(&input[literal ..]);
+        }
+        // EOD
+        out.push(128);
+        Ok(ParseBuffer::new(out))
+    }
+}
+
+#[cfg(test)]
+mod test_encoders {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use super::{ASCII85Encode, ASCIIHexEncode, FlateEncode, LZWEncode, RunLengthEncode};
+    use crate::pcore::parsebuffer::{LocatedVal, ParseBuffer, ParseBufferT};
+    use crate::pcore::transforms::BufferTransformT;
+    use crate::pdf_lib::pdf_filters::{
+        ASCII85Decode, ASCIIHexDecode, DecodeBudget, FlateDecode, LZWDecode, RunLengthDecode,
+    };
+    use crate::pdf_lib::pdf_obj::{DictKey, DictT, PDFObjT};
+    use crate::pdf_lib::pdf_prim::IntegerT;
+
+    // A simple generator of test inputs, mixing random bytes and
+    // runs of repeated bytes.
+    fn inputs() -> Vec<Vec<u8>> {
+        let mut state: u32 = 0x2545_f491;
+        let mut rand = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let mut inputs = vec![Vec::new(), vec![0], vec![0; 4096]];
+        for n in 0 .. 64 {
+            let len = (rand() % 4096) as usize;
+            let mut v = Vec::with_capacity(len);
+            while v.len() < len {
+                let b = rand() as u8;
+                if n % 2 == 0 && b.is_multiple_of(4) {
+                    let run = (rand() % 300) as usize;
+                    v.resize(v.len() + run, b)
+                } else {
+                    v.push(if n % 4 == 1 { b % 4 } else { b })
+                }
+            }
+            v.truncate(len);
+            inputs.push(v)
+        }
+        inputs
+    }
+
+    fn mk_parms(parms: &[(&str, i64)]) -> DictT {
+        let mut map = BTreeMap::new();
+        for (k, v) in parms {
+            let v = PDFObjT::Integer(IntegerT::new(*v));
+            map.insert(
+                DictKey::new(Vec::from(*k)),
+                Arc::new(LocatedVal::new(v, 0, 0)),
+            );
+        }
+        DictT::new(map)
+    }
+
+    fn round_trip(
+        encoder: &mut dyn BufferTransformT, decoder: &mut dyn BufferTransformT, data: &[u8],
+    ) {
+        let encoded = encoder.transform(&ParseBuffer::new(data.to_vec())).unwrap();
+        let decoded = decoder.transform(&encoded).unwrap();
+        assert_eq!(decoded.buf(), data);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let budget = DecodeBudget::default();
+        let opts = None;
+        for data in inputs() {
+            round_trip(
+                &mut FlateEncode::new(&opts),
+                &mut FlateDecode::new(&opts, &budget),
+                &data,
+            );
+            round_trip(
+                &mut LZWEncode::new(&opts),
+                &mut LZWDecode::new(&opts, &budget),
+                &data,
+            );
+            round_trip(
+                &mut ASCIIHexEncode::new(&opts),
+                &mut ASCIIHexDecode::new(&opts, &budget),
+                &data,
+            );
+            round_trip(
+                &mut ASCII85Encode::new(&opts),
+                &mut ASCII85Decode::new(&opts, &budget),
+                &data,
+            );
+            round_trip(
+                &mut RunLengthEncode::new(&opts),
+                &mut RunLengthDecode::new(&opts, &budget),
+                &data,
+            );
+        }
+        // the GIF variant of LZW
+        let parms = mk_parms(&[("EarlyChange", 0)]);
+        let opts = Some(&parms);
+        for data in inputs() {
+            round_trip(
+                &mut LZWEncode::new(&opts),
+                &mut LZWDecode::new(&opts, &budget),
+                &data,
+            );
+        }
+        // the bytes after EOD are not decoded
+        for data in inputs() {
+            let pb = ParseBuffer::new(data.clone());
+            let encoded = LZWEncode::new(&None).transform(&pb).unwrap();
+            let mut v = encoded.buf().to_vec();
+            v.extend_from_slice(&[0xff, 0xff, 0x00, 0x41]);
+            let pb = ParseBuffer::new(v);
+            let decoded = LZWDecode::new(&None, &budget).transform(&pb).unwrap();
+            assert_eq!(decoded.buf(), data.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_ascii85_decode() {
+        let budget = DecodeBudget::default();
+        let decode = |s: &[u8]| {
+            let pb = ParseBuffer::new(s.to_vec());
+            ASCII85Decode::new(&None, &budget)
+                .transform(&pb)
+                .map(|res| res.buf().to_vec())
+        };
+        assert_eq!(decode(b"9jqo^F*2M7/c~>").unwrap(), b"Man sure.");
+        assert_eq!(decode(b"<~z 9jqo^\n~>").unwrap(), b"\0\0\0\0Man ");
+        // out of range groups, single digit groups, misplaced 'z' and
+        // illegal characters are errors
+        assert!(decode(b"uuuuu~>").is_err());
+        assert_eq!(decode(b"s8W-!~>").unwrap(), [0xff; 4]);
+        assert!(decode(b"s8W-\"~>").is_err());
+        assert!(decode(b"9jqo^F~>").is_err());
+        assert!(decode(b"9jzqo~>").is_err());
+        assert!(decode(b"9jqo^v~>").is_err());
+        assert!(decode(b"9jqo^~").is_err());
+    }
+
+    #[test]
+    fn test_predictors() {
+        let budget = DecodeBudget::default();
+        // rows of 3 colors with 8 bits for 4 columns
+        for predictor in &[2, 10, 11, 12, 13, 14, 15] {
+            let parms = mk_parms(&[("Predictor", *predictor), ("Colors", 3), ("Columns", 4)]);
+            let opts = Some(&parms);
+            // the PNG decoder needs at least one row
+            for mut data in inputs().into_iter().filter(|d| d.len() >= 12) {
+                data.truncate(data.len() / 12 * 12);
+                round_trip(
+                    &mut FlateEncode::new(&opts),
+                    &mut FlateDecode::new(&opts, &budget),
+                    &data,
+                );
+                round_trip(
+                    &mut LZWEncode::new(&opts),
+                    &mut LZWDecode::new(&opts, &budget),
+                    &data,
+                );
+            }
+        }
+        // sub-byte components
+        let parms = mk_parms(&[("Predictor", 15), ("BitsPerComponent", 4), ("Columns", 5)]);
+        let opts = Some(&parms);
+        for mut data in inputs().into_iter().filter(|d| d.len() >= 3) {
+            data.truncate(data.len() / 3 * 3);
+            round_trip(
+                &mut FlateEncode::new(&opts),
+                &mut FlateDecode::new(&opts, &budget),
+                &data,
+            );
+        }
+        // data that does not fill the rows
+        let parms = mk_parms(&[("Predictor", 12), ("Columns", 4)]);
+        let opts = Some(&parms);
+        let pb = ParseBuffer::new(vec![0; 5]);
+        assert!(FlateEncode::new(&opts).transform(&pb).is_err());
+
+        // row sizes that are not positive or that overflow
+        let pb = ParseBuffer::new(vec![0; 8]);
+        let pb = FlateEncode::new(&None).transform(&pb).unwrap();
+        for parms in &[
+            vec![("Predictor", 12), ("Columns", -1)],
+            vec![("Predictor", 2), ("Colors", 0)],
+            vec![("Predictor", 12), ("Columns", i64::MAX), ("Colors", 3)],
+        ] {
+            let parms = mk_parms(parms);
+            let opts = Some(&parms);
+            assert!(FlateDecode::new(&opts, &budget).transform(&pb).is_err());
+        }
+    }
+}
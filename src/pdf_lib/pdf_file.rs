@@ -463,6 +463,7 @@ pub struct TrailerT {
 
 impl TrailerT {
     pub fn dict(&self) -> &DictT { &self.dict }
+    pub fn into_dict(self) -> DictT { self.dict }
 }
 
 pub struct TrailerP<'a> {
@@ -547,7 +548,7 @@ impl ParsleyParser for StartXrefP {
 #[cfg(test)]
 mod test_pdf_file {
     use std::collections::BTreeMap;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use super::super::super::pcore::parsebuffer::{
         locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParsleyParser,
@@ -928,12 +929,12 @@ endobj"
         let key = NameT::new(Vec::from("Size".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 12, 17).val().normalize()),
-            Rc::new(LocatedVal::new(PDFObjT::Integer(IntegerT::new(8)), 18, 19)),
+            Arc::new(LocatedVal::new(PDFObjT::Integer(IntegerT::new(8)), 18, 19)),
         );
         let key = NameT::new(Vec::from("Root".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 21, 26).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Reference(ReferenceT::new(1, 0)),
                 27,
                 32,
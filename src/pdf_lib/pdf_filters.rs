@@ -18,24 +18,165 @@
 
 use binascii::hex2bin;
 use flate2::write::ZlibDecoder;
-use lzw::{Decoder, DecoderEarlyChange, LsbReader};
+use std::convert::TryFrom;
 use std::io::Write;
 use std::num::Wrapping;
-use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use super::super::pcore::parsebuffer::{
-    locate_value, ErrorKind, Location, ParseBuffer, ParseBufferT,
+    locate_value, ErrorKind, LocatedVal, Location, ParseBuffer, ParseBufferT, ParseResult,
+    ParsleyParser,
 };
 use super::super::pcore::transforms::{BufferTransformT, TransformResult};
+use super::super::pdf_lib::pdf_ccitt::{decode_fax, FaxParams};
+use super::super::pdf_lib::pdf_jbig2::{Jbig2Stream, Jbig2StreamP};
+use super::super::pdf_lib::pdf_jpeg::{JpegImageP, SegmentKind};
+use super::super::pdf_lib::pdf_jpx::JpxImageP;
 use super::super::pdf_lib::pdf_obj::DictT;
 use super::super::pdf_lib::pdf_obj::PDFObjT;
 
+// Limits on the output of the stream filters, to protect against
+// decompression bombs.  Since small inputs can legitimately expand
+// by large factors, the expansion ratio is only enforced on outputs
+// larger than `ratio_threshold` bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeLimits {
+    // the maximum decoded size of a stream
+    pub max_output:      usize,
+    // the maximum ratio of decoded to encoded size
+    pub max_ratio:       usize,
+    pub ratio_threshold: usize,
+    // the maximum total of decoded bytes in a document
+    pub max_total:       usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_output:      256 << 20,
+            max_ratio:       4096,
+            ratio_threshold: 1 << 20,
+            max_total:       1 << 30,
+        }
+    }
+}
+
+// Tracks the decoded bytes of a document against its limits.  Each
+// filter charges its output to the budget, which can be shared
+// between threads.
+#[derive(Debug, Default)]
+pub struct DecodeBudget {
+    limits: DecodeLimits,
+    total:  AtomicUsize,
+}
+
+impl DecodeBudget {
+    pub fn new(limits: DecodeLimits) -> DecodeBudget {
+        DecodeBudget {
+            limits,
+            total: AtomicUsize::new(0),
+        }
+    }
+    pub fn limits(&self) -> &DecodeLimits { &self.limits }
+    // the total bytes decoded so far
+    pub fn total(&self) -> usize { self.total.load(Ordering::Relaxed) }
+
+    // Checks the per-stream limits for `output_len` bytes decoded
+    // from `input_len` bytes, and returns the limit that is exceeded.
+    pub fn check_stream(&self, input_len: usize, output_len: usize) -> Result<(), String> {
+        let l = &self.limits;
+        if output_len > l.max_output {
+            return Err(format!(
+                "decoded size exceeds the maximum stream output of {} bytes",
+                l.max_output
+            ))
+        }
+        if output_len > l.ratio_threshold
+            && output_len > input_len.max(1).saturating_mul(l.max_ratio)
+        {
+            return Err(format!(
+                "decoded size exceeds the maximum expansion ratio of {} for {} input bytes",
+                l.max_ratio, input_len
+            ))
+        }
+        Ok(())
+    }
+
+    // Checks the per-stream limits as well as the document total.
+    pub fn check(&self, input_len: usize, output_len: usize) -> Result<(), String> {
+        self.check_stream(input_len, output_len)?;
+        if self.total().saturating_add(output_len) > self.limits.max_total {
+            return Err(format!(
+                "total decoded bytes exceed the maximum of {} bytes per document",
+                self.limits.max_total
+            ))
+        }
+        Ok(())
+    }
+
+    // The largest output within the limits for `input_len` bytes of
+    // input.
+    pub fn output_limit(&self, input_len: usize) -> usize {
+        let l = &self.limits;
+        let ratio = input_len.max(1).saturating_mul(l.max_ratio);
+        l.max_output
+            .min(ratio.max(l.ratio_threshold))
+            .min(l.max_total.saturating_sub(self.total()))
+    }
+
+    pub fn charge(&self, output_len: usize) {
+        let _ = self
+            .total
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |t| {
+                Some(t.saturating_add(output_len))
+            });
+    }
+}
+
+// Checks the output of a filter against the budget, and charges it
+// if it is within the limits.
+fn charge_output(
+    filter: &str, budget: &DecodeBudget, buf: &dyn ParseBufferT, output_len: usize,
+) -> Result<(), LocatedVal<ErrorKind>> {
+    if let Err(msg) = budget.check(buf.buf().len(), output_len) {
+        let err = ErrorKind::TransformError(format!("{}: {}", filter, msg));
+        let loc = buf.get_location();
+        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+    }
+    budget.charge(output_len);
+    Ok(())
+}
+
+// A writer that fails once its output exceeds a limit.
+struct LimitedWriter {
+    out:   Vec<u8>,
+    limit: usize,
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.out.len() + data.len() > self.limit {
+            // Keep one byte past the limit to identify the failure.
+            let n = self.limit + 1 - self.out.len();
+            self.out.extend_from_slice(&data[.. n]);
+            let err = std::io::Error::other("output limit exceeded");
+            return Err(err)
+        }
+        self.out.extend_from_slice(data);
+        Ok(data.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
 pub struct FlateDecode<'a> {
     options: &'a Option<&'a DictT>,
+    budget:  &'a DecodeBudget,
 }
 
 impl FlateDecode<'_> {
-    pub fn new<'a>(options: &'a Option<&'a DictT>) -> FlateDecode { FlateDecode { options } }
+    pub fn new<'a>(options: &'a Option<&'a DictT>, budget: &'a DecodeBudget) -> FlateDecode<'a> {
+        FlateDecode { options, budget }
+    }
 }
 
 impl BufferTransformT for FlateDecode<'_> {
@@ -82,7 +223,11 @@ impl BufferTransformT for FlateDecode<'_> {
             })
             .unwrap_or(1);
 
-        let mut decoder = ZlibDecoder::new(Vec::new());
+        let limit = self.budget.output_limit(buf.buf().len());
+        let mut decoder = ZlibDecoder::new(LimitedWriter {
+            out: Vec::new(),
+            limit,
+        });
 
         // PDF streams can have bytes trailing the filter content, so
         // write_all() could cause spurious errors due to the trailing
@@ -90,37 +235,46 @@ impl BufferTransformT for FlateDecode<'_> {
         // an internal consuming loop, we could rely on it to consume
         // all relevant bytes in a single call.
 
-        if let Err(e) = decoder.write(buf.buf()) {
+        let res = decoder.write(buf.buf());
+        if decoder.get_ref().out.len() > limit {
+            charge_output("FlateDecode", self.budget, buf, limit + 1)?
+        }
+        if let Err(e) = res {
             let err = ErrorKind::TransformError(format!("flatedecode write error: {}", e));
             let loc = buf.get_location();
             return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
         };
         // otherwise, all bytes were consumed.
 
-        match decoder.finish() {
-            Err(e) => {
-                let err = ErrorKind::TransformError(format!("flatedecode finish error: {}", e));
-                let loc = buf.get_location();
-                Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-            },
-            Ok(decoded) => flate_lzw_filter(
-                decoded,
-                &buf.get_location(),
-                predictor as usize,
-                colors as usize,
-                columns as usize,
-                bitspercolumn as usize,
-            ),
+        let res = decoder.try_finish();
+        if decoder.get_ref().out.len() > limit {
+            charge_output("FlateDecode", self.budget, buf, limit + 1)?
+        }
+        if let Err(e) = res {
+            let err = ErrorKind::TransformError(format!("flatedecode finish error: {}", e));
+            let loc = buf.get_location();
+            return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
         }
+        let decoded = std::mem::take(&mut decoder.get_mut().out);
+        charge_output("FlateDecode", self.budget, buf, decoded.len())?;
+        flate_lzw_filter(
+            decoded,
+            &buf.get_location(),
+            predictor as usize,
+            colors,
+            columns,
+            bitspercolumn,
+        )
     }
 }
 
 // the paeth prediction algorithm
 fn paeth(a: Wrapping<u8>, b: Wrapping<u8>, c: Wrapping<u8>) -> Wrapping<u8> {
-    let p = a + b - c;
-    let pa = if p > a { p - a } else { a - p };
-    let pb = if p > b { p - b } else { b - p };
-    let pc = if p > c { p - c } else { c - p };
+    let (ia, ib, ic) = (a.0 as i16, b.0 as i16, c.0 as i16);
+    let p = ia + ib - ic;
+    let pa = (p - ia).abs();
+    let pb = (p - ib).abs();
+    let pc = (p - ic).abs();
 
     // algorithm
     if pa <= pb && pa <= pc {
@@ -134,10 +288,13 @@ fn paeth(a: Wrapping<u8>, b: Wrapping<u8>, c: Wrapping<u8>) -> Wrapping<u8> {
 
 pub struct LZWDecode<'a> {
     options: &'a Option<&'a DictT>,
+    budget:  &'a DecodeBudget,
 }
 
 impl LZWDecode<'_> {
-    pub fn new<'a>(options: &'a Option<&'a DictT>) -> LZWDecode { LZWDecode { options } }
+    pub fn new<'a>(options: &'a Option<&'a DictT>, budget: &'a DecodeBudget) -> LZWDecode<'a> {
+        LZWDecode { options, budget }
+    }
 }
 
 impl BufferTransformT for LZWDecode<'_> {
@@ -175,82 +332,167 @@ impl BufferTransformT for LZWDecode<'_> {
                 _ => None,
             })
             .unwrap_or(1);
-        let earlyexchange = self
+        let earlychange = self
             .options
-            .and_then(|x| x.get(b"EarlyExchange"))
+            .and_then(|x| x.get(b"EarlyChange"))
             .and_then(|x| match x.val() {
                 PDFObjT::Integer(x) => Some(x.int_val()),
                 _ => None,
             })
             .unwrap_or(1);
 
-        let decoded = decode_bytes_lzw(buf, earlyexchange);
+        let limit = self.budget.output_limit(buf.buf().len());
+        let decoded = match decode_bytes_lzw(buf, earlychange, limit) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                let err = ErrorKind::TransformError(format!("LZWDecode: error decoding: {}", e));
+                let loc = buf.get_location();
+                return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+            },
+        };
+        charge_output("LZWDecode", self.budget, buf, decoded.len())?;
 
         flate_lzw_filter(
             decoded,
             &buf.get_location(),
             predictor as usize,
-            colors as usize,
-            columns as usize,
-            bitspercolumn as usize,
+            colors,
+            columns,
+            bitspercolumn,
         )
     }
 }
 
-fn decode_bytes_lzw(buf: &dyn ParseBufferT, earlyexchange: i64) -> Vec<u8> {
+// PDF uses the variant of LZW in TIFF, with codes packed starting
+// from the high-order bit.  The code width increases one code early
+// unless EarlyChange is 0.  Decoding stops at the EOD code, so that
+// any bytes after it are ignored, or once the output exceeds `limit`
+// bytes.
+fn decode_bytes_lzw(
+    buf: &dyn ParseBufferT, earlychange: i64, limit: usize,
+) -> Result<Vec<u8>, String> {
+    const CLEAR: usize = 256;
+    const EOD: usize = 257;
+    let early = (earlychange != 0) as usize;
+    let input = buf.buf();
     let mut out = Vec::<u8>::new();
-    let reader = LsbReader::new();
-    let size: u8 = 8;
-
-    if earlyexchange == 1 {
-        let mut decoder = DecoderEarlyChange::new(reader, size);
-        let mut read = 0;
-        let input = buf.buf();
-        loop {
-            if read >= input.len() {
-                break
-            }
-            let (len, bytes) = decoder.decode_bytes(&input[read ..]).unwrap();
-            read += len;
-            out.extend(bytes.iter().copied());
+    // Each entry of the table is the code of its prefix, if any, and
+    // its last byte.  The entries for CLEAR and EOD are never used.
+    let reset = |table: &mut Vec<(Option<usize>, u8)>| {
+        table.clear();
+        table.extend((0 ..= 255).map(|b| (None, b)));
+        table.extend([(None, 0), (None, 0)]);
+    };
+    let mut table = Vec::with_capacity(4096);
+    reset(&mut table);
+    let mut width = 9;
+    let mut prev: Option<usize> = None;
+    // the position in bits of the next code
+    let mut pos = 0;
+    while pos + width <= input.len() * 8 && out.len() <= limit {
+        let code = (pos .. pos + width).fold(0, |c, i| {
+            (c << 1) | ((input[i / 8] >> (7 - i % 8)) & 1) as usize
+        });
+        pos += width;
+        match code {
+            CLEAR => {
+                reset(&mut table);
+                width = 9;
+                prev = None;
+                continue
+            },
+            EOD => break,
+            _ => (),
         }
-    } else {
-        let mut decoder = Decoder::new(reader, size);
-        let mut read = 0;
-        let input = buf.buf();
-        loop {
-            if read >= input.len() {
-                break
+        let next = table.len();
+        let start = out.len();
+        match prev {
+            _ if code < next => lzw_entry(&table, code, &mut out),
+            // the entry that is being added, which starts and ends
+            // with the first byte of the previous one
+            Some(p) if code == next => {
+                lzw_entry(&table, p, &mut out);
+                out.push(out[start])
+            },
+            _ => return Err(format!("invalid code {}, expected at most {}", code, next)),
+        }
+        if let Some(p) = prev {
+            if next < 4096 {
+                table.push((Some(p), out[start]))
             }
-            let (len, bytes) = decoder.decode_bytes(&input[read ..]).unwrap();
-            read += len;
-            out.extend(bytes.iter().copied());
         }
+        if next + 1 + early == (1 << width) && width < 12 {
+            width += 1
+        }
+        prev = Some(code)
+    }
+    Ok(out)
+}
+
+// Appends the bytes of an LZW table entry.
+fn lzw_entry(table: &[(Option<usize>, u8)], code: usize, out: &mut Vec<u8>) {
+    let start = out.len();
+    let mut code = Some(code);
+    while let Some(c) = code {
+        let (prefix, b) = table[c];
+        out.push(b);
+        code = prefix
+    }
+    out[start ..].reverse()
+}
+
+// Checks a predictor parameter from the decode parameters.
+fn predictor_param(loc: &dyn Location, name: &str, val: i64) -> ParseResult<usize> {
+    match usize::try_from(val) {
+        Ok(v) if v > 0 => Ok(v),
+        _ => {
+            let err = ErrorKind::TransformError(format!("predictor: invalid /{} {}", name, val));
+            Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+        },
     }
-    out
 }
 
 fn flate_lzw_filter(
-    decoded: Vec<u8>, loc: &dyn Location, predictor: usize, colors: usize, columns: usize,
-    bitspercolumn: usize,
+    decoded: Vec<u8>, loc: &dyn Location, predictor: usize, colors: i64, columns: i64,
+    bitspercolumn: i64,
 ) -> TransformResult {
     let mut row_data = Vec::<Wrapping<u8>>::new();
     let mut out_buffer = Vec::<u8>::new();
 
     if predictor == 1 {
-        Ok(ParseBuffer::new(decoded))
-    } else if predictor == 2 {
+        return Ok(ParseBuffer::new(decoded))
+    }
+    // The row sizes are computed from these, so they need to be
+    // positive and small enough for the sizes not to overflow.
+    let colors = predictor_param(loc, "Colors", colors)?;
+    let columns = predictor_param(loc, "Columns", columns)?;
+    let bitspercolumn = predictor_param(loc, "BitsPerComponent", bitspercolumn)?;
+    let row_bits = match columns
+        .checked_mul(colors)
+        .and_then(|n| n.checked_mul(bitspercolumn))
+    {
+        Some(n) => n,
+        None => {
+            let err = ErrorKind::TransformError("predictor: row size overflows".to_string());
+            return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+        },
+    };
+
+    if predictor == 2 {
         // TIFF encoding
-        let row_length = columns * colors;
-        if row_length < 1 {
-            // No data.
-            return Ok(ParseBuffer::new([].to_vec()))
+        if bitspercolumn != 8 {
+            let err = ErrorKind::TransformError(format!(
+                "TIFF predictor: unsupported BitsPerComponent {}",
+                bitspercolumn
+            ));
+            return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
         }
+        let row_length = columns * colors;
 
         let rows = decoded.len() / row_length;
         if decoded.len() % row_length != 0 {
             let err = ErrorKind::TransformError(format!(
-                "PNG filter: decoded size {} does not match multiple of expected row size {}",
+                "TIFF predictor: decoded size {} does not match multiple of expected row size {}",
                 decoded.len(),
                 row_length
             ));
@@ -279,10 +521,11 @@ fn flate_lzw_filter(
         }
         Ok(ParseBuffer::new(out_buffer))
     } else if (10..=15).contains(&predictor) {
-        // PNG
-        let row_length = columns * colors + 1;
+        // PNG: the predictor value only selects PNG prediction; each
+        // row is tagged with the PNG filter type used for that row.
+        let bytes_per_pixel = std::cmp::max(1, (colors * bitspercolumn).div_ceil(8));
+        let row_length = row_bits.div_ceil(8) + 1;
         let rows = decoded.len() / row_length;
-        let bytes_per_pixel = bitspercolumn / 8;
 
         if row_length > decoded.len() {
             let err = ErrorKind::TransformError(
@@ -309,69 +552,36 @@ fn flate_lzw_filter(
             {
                 row_data.push(Wrapping(*d))
             }
-            match predictor {
-                10 => {
+            match row_data[0].0 {
+                0 => {
                     // PNG None
-                    if row_data[0].0 != 0 {
-                        let err = ErrorKind::TransformError(format!(
-                            "PNG filter: row filter {} is not None for None predictor",
-                            row_data[0].0
-                        ));
-                        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-                    }
                 },
-                11 => {
+                1 => {
                     // PNG Sub
-                    if row_data[0].0 != 1 {
-                        let err = ErrorKind::TransformError(format!(
-                            "PNG filter: row filter {} is not Sub for Sub predictor",
-                            row_data[0].0
-                        ));
-                        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-                    }
                     for k in 1 + bytes_per_pixel .. row_length {
                         row_data[k] = row_data[k] + row_data[k - bytes_per_pixel]
                     }
                 },
-                12 => {
+                2 => {
                     // PNG Up
-                    if row_data[0].0 != 2 {
-                        let err = ErrorKind::TransformError(format!(
-                            "PNG filter: row filter {} is not Up for Up predictor",
-                            row_data[0].0
-                        ));
-                        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-                    }
                     for j in 1 .. row_length {
                         row_data[j] += prev_row[j];
                     }
                 },
-                13 => {
-                    // PNG Avg
-                    if row_data[0].0 != 3 {
-                        let err = ErrorKind::TransformError(format!(
-                            "PNG filter: row filter {} is not Avg for Avg predictor",
-                            row_data[0].0
-                        ));
-                        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-                    }
-                    for j in 1 .. 1 + bytes_per_pixel {
-                        row_data[j] += prev_row[j] / Wrapping(2);
-                    }
-                    for j in bytes_per_pixel .. row_length {
-                        let incr = (row_data[j - bytes_per_pixel] + prev_row[j]) / Wrapping(2);
-                        row_data[j] += incr
+                3 => {
+                    // PNG Avg, computed without overflow.
+                    for j in 1 .. row_length {
+                        let left = if j > bytes_per_pixel {
+                            row_data[j - bytes_per_pixel].0 as u16
+                        } else {
+                            0
+                        };
+                        let avg = (left + prev_row[j].0 as u16) / 2;
+                        row_data[j] += Wrapping(avg as u8);
                     }
                 },
-                14 => {
-                    if row_data[0].0 != 4 {
-                        let err = ErrorKind::TransformError(format!(
-                            "PNG filter: row filter {} is not Paeth for Paeth predictor",
-                            row_data[0].0
-                        ));
-                        return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-                    }
-                    // Paeth algorithm prediction.
+                4 => {
+                    // PNG Paeth
                     let mut a = Wrapping(0);
                     let mut c = Wrapping(0);
                     for j in 1 .. row_length {
@@ -380,14 +590,12 @@ fn flate_lzw_filter(
                             a = row_data[j - bytes_per_pixel];
                             c = prev_row[j - bytes_per_pixel];
                         }
-                        row_data[j] = paeth(a, b, c);
+                        row_data[j] += paeth(a, b, c);
                     }
                 },
-                _ => {
-                    let err = ErrorKind::TransformError(format!(
-                        "PNG filter: unknown predictor {}",
-                        predictor
-                    ));
+                t => {
+                    let err =
+                        ErrorKind::TransformError(format!("PNG filter: unknown row filter {}", t));
                     return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
                 },
             }
@@ -407,11 +615,14 @@ fn flate_lzw_filter(
 
 pub struct ASCIIHexDecode<'a> {
     _options: &'a Option<&'a DictT>,
+    budget:   &'a DecodeBudget,
 }
 
 impl ASCIIHexDecode<'_> {
-    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> ASCIIHexDecode {
-        ASCIIHexDecode { _options }
+    pub fn new<'a>(
+        _options: &'a Option<&'a DictT>, budget: &'a DecodeBudget,
+    ) -> ASCIIHexDecode<'a> {
+        ASCIIHexDecode { _options, budget }
     }
 }
 
@@ -420,14 +631,15 @@ impl BufferTransformT for ASCIIHexDecode<'_> {
         let loc = &buf.get_location();
         let mut stage = Vec::new();
         let mut saw_eod = false;
-        for (i, b) in buf.buf().iter().enumerate() {
+        for b in buf.buf() {
             match b {
                 // ignore PDF whitespace
                 0x00 | 0x09 | 0x0A | 0x0C | 0x0D | 0x20 => continue,
-                // handle EOD
+                // handle EOD, which implies a final 0 for an odd
+                // number of digits
                 0x3E => {
                     saw_eod = true;
-                    if i % 2 == 1 {
+                    if stage.len() % 2 == 1 {
                         stage.push(0x30);
                     }
                     break
@@ -451,9 +663,12 @@ impl BufferTransformT for ASCIIHexDecode<'_> {
             let err = ErrorKind::TransformError("ASCIIHexDecode: no EOD in input".to_string());
             return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
         }
-        let mut out = Vec::<u8>::with_capacity(stage.len() / 2 + 1);
+        let mut out = vec![0; stage.len() / 2];
         match hex2bin(&stage, &mut out) {
-            Ok(res) => Ok(ParseBuffer::new(Vec::from(res))),
+            Ok(res) => {
+                charge_output("ASCIIHexDecode", self.budget, buf, res.len())?;
+                Ok(ParseBuffer::new(Vec::from(res)))
+            },
             Err(e) => {
                 let err =
                     ErrorKind::TransformError(format!("ASCIIHexDecode: error decoding: {:?}", e));
@@ -465,72 +680,366 @@ impl BufferTransformT for ASCIIHexDecode<'_> {
 
 pub struct ASCII85Decode<'a> {
     _options: &'a Option<&'a DictT>,
+    budget:   &'a DecodeBudget,
 }
 
 impl ASCII85Decode<'_> {
-    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> ASCII85Decode { ASCII85Decode { _options } }
+    pub fn new<'a>(_options: &'a Option<&'a DictT>, budget: &'a DecodeBudget) -> ASCII85Decode<'a> {
+        ASCII85Decode { _options, budget }
+    }
 }
 
 impl BufferTransformT for ASCII85Decode<'_> {
     fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
         let loc = &buf.get_location();
-        let mut stage = String::new();
+        match decode_bytes_ascii85(buf.buf()) {
+            Ok(res) => {
+                charge_output("ASCII85Decode", self.budget, buf, res.len())?;
+                Ok(ParseBuffer::new(res))
+            },
+            Err(e) => {
+                let err = ErrorKind::TransformError(format!("ASCII85Decode: {}", e));
+                Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+            },
+        }
+    }
+}
 
-        for b in buf.buf() {
-            match b {
-                // ignore PDF whitespace ourselves, since the ascii85
-                // crate's definition differs from PDF.
-                0x00 | 0x09 | 0x0A | 0x0C | 0x0D | 0x20 => continue,
+// Decodes the base-85 groups of the input up to the EOD marker `~>`.
+// A leading `<~`, which some producers emit, is skipped.  Groups whose
+// value does not fit in four bytes and a final group of a single
+// digit are errors.
+fn decode_bytes_ascii85(input: &[u8]) -> Result<Vec<u8>, String> {
+    let input = input.strip_prefix(b"<~").unwrap_or(input);
+    let mut out = Vec::with_capacity(input.len() / 5 * 4 + 4);
+    let mut group = [0u8; 5];
+    let mut n = 0;
+    let mut iter = input.iter();
+    while let Some(b) = iter.next() {
+        match b {
+            // PDF whitespace is ignored.
+            0x00 | 0x09 | 0x0A | 0x0C | 0x0D | 0x20 => continue,
+            b'~' => {
+                if iter.next() != Some(&b'>') {
+                    return Err("malformed EOD".to_string())
+                }
+                break
+            },
+            b'z' if n == 0 => out.extend_from_slice(&[0; 4]),
+            b'z' => return Err("'z' inside a group".to_string()),
+            b'!' ..= b'u' => {
+                group[n] = b - b'!';
+                n += 1;
+                if n == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    n = 0
+                }
+            },
+            c => return Err(format!("illegal char {:?} in input", c)),
+        }
+    }
+    match n {
+        0 => (),
+        1 => return Err("final group of a single digit".to_string()),
+        // A partial group of n digits is padded with 'u' and decodes
+        // to n - 1 bytes.
+        _ => {
+            for d in group.iter_mut().skip(n) {
+                *d = 84
+            }
+            out.extend_from_slice(&ascii85_group(&group)?[.. n - 1])
+        },
+    }
+    Ok(out)
+}
 
-                // let the crate handle EOD and illegal characters.
-                c => stage.push(*c as char),
+fn ascii85_group(group: &[u8; 5]) -> Result<[u8; 4], String> {
+    let v = group.iter().fold(0u64, |acc, d| acc * 85 + u64::from(*d));
+    match u32::try_from(v) {
+        Ok(v) => Ok(v.to_be_bytes()),
+        Err(_) => Err("group value out of range".to_string()),
+    }
+}
+
+pub struct RunLengthDecode<'a> {
+    _options: &'a Option<&'a DictT>,
+    budget:   &'a DecodeBudget,
+}
+
+impl RunLengthDecode<'_> {
+    pub fn new<'a>(
+        _options: &'a Option<&'a DictT>, budget: &'a DecodeBudget,
+    ) -> RunLengthDecode<'a> {
+        RunLengthDecode { _options, budget }
+    }
+}
+
+impl BufferTransformT for RunLengthDecode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let loc = &buf.get_location();
+        let err = |msg: String| {
+            let err = ErrorKind::TransformError(format!("RunLengthDecode: {}", msg));
+            Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+        };
+        let input = buf.buf();
+        let limit = self.budget.output_limit(input.len());
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            if out.len() > limit {
+                charge_output("RunLengthDecode", self.budget, buf, out.len())?
+            }
+            let len = input[i] as usize;
+            i += 1;
+            match len {
+                // literal run of len + 1 bytes
+                0 ..= 127 => {
+                    if i + len + 1 > input.len() {
+                        return err(format!("literal run of {} bytes overshoots data", len + 1))
+                    }
+                    out.extend_from_slice(&input[i .. i + len + 1]);
+                    i += len + 1
+                },
+                // EOD, which has to end the data
+                128 => {
+                    if i < input.len() {
+                        return err(format!(
+                            "{} bytes of trailing data after EOD",
+                            input.len() - i
+                        ))
+                    }
+                    charge_output("RunLengthDecode", self.budget, buf, out.len())?;
+                    return Ok(ParseBuffer::new(out))
+                },
+                // the next byte repeated 257 - len times
+                _ => {
+                    if i >= input.len() {
+                        return err(format!("repeat run of {} bytes overshoots data", 257 - len))
+                    }
+                    out.resize(out.len() + 257 - len, input[i]);
+                    i += 1
+                },
             }
         }
+        err("no EOD in input".to_string())
+    }
+}
 
-        let prev_hook = panic::take_hook();
+pub struct CCITTFaxDecode<'a> {
+    options: &'a Option<&'a DictT>,
+    budget:  &'a DecodeBudget,
+}
 
-        panic::set_hook(Box::new(|_info| {}));
+impl CCITTFaxDecode<'_> {
+    pub fn new<'a>(options: &'a Option<&'a DictT>, budget: &'a DecodeBudget) -> CCITTFaxDecode<'a> {
+        CCITTFaxDecode { options, budget }
+    }
+}
 
-        let result = panic::catch_unwind(|| match ascii85::decode(&stage) {
-            Ok(res) => Ok(ParseBuffer::new(res)),
-            Err(e) => {
-                let err =
-                    ErrorKind::TransformError(format!("ASCII85Decode: error decoding: {:?}", e));
-                Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-            },
-        });
-        panic::set_hook(prev_hook);
-        match result {
-            Ok(res) => res,
-            Err(e) => {
-                let err = ErrorKind::TransformError(format!(
-                    "Error in the JPEG decoder library: {:?}",
-                    e
-                ));
-                Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-            },
+impl BufferTransformT for CCITTFaxDecode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        // Extract values from options if available, else use defaults.
+        let int = |k: &[u8]| {
+            self.options
+                .and_then(|x| x.get(k))
+                .and_then(|x| match x.val() {
+                    PDFObjT::Integer(x) => Some(x.int_val()),
+                    _ => None,
+                })
+        };
+        let boolean = |k: &[u8]| {
+            self.options
+                .and_then(|x| x.get(k))
+                .and_then(|x| match x.val() {
+                    PDFObjT::Boolean(b) => Some(*b),
+                    _ => None,
+                })
+        };
+        let mut params = FaxParams::default();
+        if let Some(k) = int(b"K") {
+            params.k = k
         }
+        for (key, val) in &mut [
+            (&b"Columns"[..], &mut params.columns),
+            (b"Rows", &mut params.rows),
+            (
+                b"DamagedRowsBeforeError",
+                &mut params.damaged_rows_before_error,
+            ),
+        ] {
+            match int(key) {
+                Some(i) if i >= 0 => **val = i as usize,
+                Some(i) => {
+                    let err = ErrorKind::TransformError(format!(
+                        "CCITTFaxDecode: invalid /{} {}",
+                        String::from_utf8_lossy(key),
+                        i
+                    ));
+                    let loc = buf.get_location();
+                    return Err(locate_value(err, loc.loc_start(), loc.loc_end()))
+                },
+                None => (),
+            }
+        }
+        for (key, val) in &mut [
+            (&b"EndOfLine"[..], &mut params.end_of_line),
+            (b"EncodedByteAlign", &mut params.encoded_byte_align),
+            (b"EndOfBlock", &mut params.end_of_block),
+            (b"BlackIs1", &mut params.black_is_1),
+        ] {
+            if let Some(b) = boolean(key) {
+                **val = b
+            }
+        }
+        let output = decode_fax(buf, &params, self.budget)?;
+        charge_output("CCITTFaxDecode", self.budget, buf, output.size())?;
+        Ok(output)
     }
 }
 
+// JPEG data is not decoded, but its marker segments are validated
+// against the image dictionary of the stream, and it is passed
+// through unchanged.
 pub struct DCTDecode<'a> {
     _options: &'a Option<&'a DictT>,
+    dict:     &'a DictT,
+    budget:   &'a DecodeBudget,
 }
 
 impl DCTDecode<'_> {
-    pub fn new<'a>(_options: &'a Option<&'a DictT>) -> DCTDecode { DCTDecode { _options } }
+    pub fn new<'a>(
+        _options: &'a Option<&'a DictT>, dict: &'a DictT, budget: &'a DecodeBudget,
+    ) -> DCTDecode<'a> {
+        DCTDecode {
+            _options,
+            dict,
+            budget,
+        }
+    }
 }
 
 impl BufferTransformT for DCTDecode<'_> {
     fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
-        let loc = &buf.get_location();
-        let mut decoder = jpeg_decoder::Decoder::new(buf.buf());
-        match decoder.decode() {
-            Ok(res) => Ok(ParseBuffer::new(res)),
-            Err(e) => {
-                let err = ErrorKind::TransformError(format!("DCTDecode: error decoding: {:?}", e));
-                Err(locate_value(err, loc.loc_start(), loc.loc_end()))
-            },
+        let mut output = ParseBuffer::new(Vec::from(buf.buf()));
+        let img = match JpegImageP.parse(&mut output) {
+            Ok(img) => img,
+            Err(e) => return Err(validation_error("DCTDecode", buf, e)),
+        };
+        if let Err(msg) = img.val().check_image_dict(self.dict) {
+            let frame = img
+                .val()
+                .segments()
+                .iter()
+                .find(|s| matches!(s.val(), SegmentKind::Frame(_)))
+                .unwrap();
+            let err = frame.place(ErrorKind::GuardError(msg));
+            return Err(validation_error("DCTDecode", buf, err))
+        }
+        charge_output("DCTDecode", self.budget, buf, output.size())?;
+        output.set_cursor_unsafe(0);
+        Ok(output)
+    }
+}
+
+// JBIG2 data is not decoded, but its segment structure is validated
+// and it is passed through unchanged.  Since /JBIG2Globals is an
+// indirect reference, the parsed globals need to be supplied
+// separately; without them, references to segments outside the
+// stream are assumed to be to the globals if the stream specifies
+// any.
+pub struct JBIG2Decode<'a> {
+    options: &'a Option<&'a DictT>,
+    globals: Option<&'a Jbig2Stream>,
+    budget:  &'a DecodeBudget,
+}
+
+impl JBIG2Decode<'_> {
+    pub fn new<'a>(options: &'a Option<&'a DictT>, budget: &'a DecodeBudget) -> JBIG2Decode<'a> {
+        JBIG2Decode {
+            options,
+            globals: None,
+            budget,
         }
     }
+    pub fn new_with_globals<'a>(
+        options: &'a Option<&'a DictT>, globals: &'a Jbig2Stream, budget: &'a DecodeBudget,
+    ) -> JBIG2Decode<'a> {
+        JBIG2Decode {
+            options,
+            globals: Some(globals),
+            budget,
+        }
+    }
+}
+
+impl BufferTransformT for JBIG2Decode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let has_globals = self
+            .options
+            .is_some_and(|x| x.get(b"JBIG2Globals").is_some());
+        let mut parser = match self.globals {
+            Some(g) => Jbig2StreamP::new(Some(g)),
+            None if has_globals => Jbig2StreamP::new_unresolved_globals(),
+            None => Jbig2StreamP::new(None),
+        };
+        let mut output = ParseBuffer::new(Vec::from(buf.buf()));
+        if let Err(e) = parser.parse(&mut output) {
+            return Err(validation_error("JBIG2Decode", buf, e))
+        }
+        charge_output("JBIG2Decode", self.budget, buf, output.size())?;
+        output.set_cursor_unsafe(0);
+        Ok(output)
+    }
+}
+
+// JPEG 2000 data is not decoded, but its structure is validated
+// against the image dictionary of the stream, and it is passed
+// through unchanged.
+pub struct JPXDecode<'a> {
+    _options: &'a Option<&'a DictT>,
+    dict:     &'a DictT,
+    budget:   &'a DecodeBudget,
+}
+
+impl JPXDecode<'_> {
+    pub fn new<'a>(
+        _options: &'a Option<&'a DictT>, dict: &'a DictT, budget: &'a DecodeBudget,
+    ) -> JPXDecode<'a> {
+        JPXDecode {
+            _options,
+            dict,
+            budget,
+        }
+    }
+}
+
+impl BufferTransformT for JPXDecode<'_> {
+    fn transform(&mut self, buf: &dyn ParseBufferT) -> TransformResult {
+        let mut output = ParseBuffer::new(Vec::from(buf.buf()));
+        let img = match JpxImageP.parse(&mut output) {
+            Ok(img) => img,
+            Err(e) => return Err(validation_error("JPXDecode", buf, e)),
+        };
+        if let Err(msg) = img.val().check_image_dict(self.dict) {
+            let siz = img.val().codestream().val().siz();
+            let err = siz.place(ErrorKind::GuardError(msg));
+            return Err(validation_error("JPXDecode", buf, err))
+        }
+        charge_output("JPXDecode", self.budget, buf, output.size())?;
+        output.set_cursor_unsafe(0);
+        Ok(output)
+    }
+}
+
+// Converts an error from validating a copy of the input of a filter
+// into one located within the input.
+fn validation_error(
+    filter: &str, buf: &dyn ParseBufferT, e: LocatedVal<ErrorKind>,
+) -> LocatedVal<ErrorKind> {
+    let loc = buf.get_location();
+    let err = match e.val() {
+        ErrorKind::GuardError(m) => ErrorKind::TransformError(format!("{}: {}", filter, m)),
+        k => k.clone(),
+    };
+    locate_value(err, loc.loc_start() + e.start(), loc.loc_start() + e.end())
 }
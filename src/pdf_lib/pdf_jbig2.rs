@@ -0,0 +1,874 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Structural parsing and validation of JBIG2 data (ITU-T T.88) in the
+// embedded stream organization used by the JBIG2Decode filter
+// (Section 7.4.7), i.e. a sequence of segments without the file
+// header, and the /JBIG2Globals streams it can refer to.  Region
+// bitmaps are not decoded.
+
+use std::collections::BTreeMap;
+
+use crate::pcore::parsebuffer::{
+    locate_value, ErrorKind, LocatedVal, ParseBufferT, ParseResult, ParsleyParser,
+};
+use crate::pcore::prim_binary::{ByteVecP, Endian, UInt16P, UInt32P, UInt8P};
+
+// Implementation limits on bitmap dimensions.
+pub const MAX_DIMENSION: u32 = 1 << 18;
+pub const MAX_PIXELS: u64 = 1 << 31;
+
+// The value of an unknown page height or segment data length.
+const UNKNOWN: u32 = 0xffff_ffff;
+
+const FILE_HEADER_ID: &[u8] = b"\x97JB2\r\n\x1a\n";
+
+// Segment types (T.88 Section 7.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentType {
+    SymbolDictionary,
+    IntermediateTextRegion,
+    ImmediateTextRegion,
+    ImmediateLosslessTextRegion,
+    PatternDictionary,
+    IntermediateHalftoneRegion,
+    ImmediateHalftoneRegion,
+    ImmediateLosslessHalftoneRegion,
+    IntermediateGenericRegion,
+    ImmediateGenericRegion,
+    ImmediateLosslessGenericRegion,
+    IntermediateGenericRefinementRegion,
+    ImmediateGenericRefinementRegion,
+    ImmediateLosslessGenericRefinementRegion,
+    PageInformation,
+    EndOfPage,
+    EndOfStripe,
+    EndOfFile,
+    Profiles,
+    Tables,
+    ColourPalette,
+    Extension,
+}
+
+impl SegmentType {
+    pub fn from_u8(t: u8) -> Option<SegmentType> {
+        let t = match t {
+            0 => SegmentType::SymbolDictionary,
+            4 => SegmentType::IntermediateTextRegion,
+            6 => SegmentType::ImmediateTextRegion,
+            7 => SegmentType::ImmediateLosslessTextRegion,
+            16 => SegmentType::PatternDictionary,
+            20 => SegmentType::IntermediateHalftoneRegion,
+            22 => SegmentType::ImmediateHalftoneRegion,
+            23 => SegmentType::ImmediateLosslessHalftoneRegion,
+            36 => SegmentType::IntermediateGenericRegion,
+            38 => SegmentType::ImmediateGenericRegion,
+            39 => SegmentType::ImmediateLosslessGenericRegion,
+            40 => SegmentType::IntermediateGenericRefinementRegion,
+            42 => SegmentType::ImmediateGenericRefinementRegion,
+            43 => SegmentType::ImmediateLosslessGenericRefinementRegion,
+            48 => SegmentType::PageInformation,
+            49 => SegmentType::EndOfPage,
+            50 => SegmentType::EndOfStripe,
+            51 => SegmentType::EndOfFile,
+            52 => SegmentType::Profiles,
+            53 => SegmentType::Tables,
+            54 => SegmentType::ColourPalette,
+            62 => SegmentType::Extension,
+            _ => return None,
+        };
+        Some(t)
+    }
+
+    // Region segments begin with a region segment information field.
+    pub fn is_region(self) -> bool {
+        self.is_text() || self.is_halftone() || self.is_generic() || self.is_refinement()
+    }
+
+    pub fn is_intermediate_region(self) -> bool {
+        matches!(
+            self,
+            SegmentType::IntermediateTextRegion
+                | SegmentType::IntermediateHalftoneRegion
+                | SegmentType::IntermediateGenericRegion
+                | SegmentType::IntermediateGenericRefinementRegion
+        )
+    }
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            SegmentType::IntermediateTextRegion
+                | SegmentType::ImmediateTextRegion
+                | SegmentType::ImmediateLosslessTextRegion
+        )
+    }
+
+    fn is_halftone(self) -> bool {
+        matches!(
+            self,
+            SegmentType::IntermediateHalftoneRegion
+                | SegmentType::ImmediateHalftoneRegion
+                | SegmentType::ImmediateLosslessHalftoneRegion
+        )
+    }
+
+    fn is_generic(self) -> bool {
+        matches!(
+            self,
+            SegmentType::IntermediateGenericRegion
+                | SegmentType::ImmediateGenericRegion
+                | SegmentType::ImmediateLosslessGenericRegion
+        )
+    }
+
+    fn is_refinement(self) -> bool {
+        matches!(
+            self,
+            SegmentType::IntermediateGenericRefinementRegion
+                | SegmentType::ImmediateGenericRefinementRegion
+                | SegmentType::ImmediateLosslessGenericRefinementRegion
+        )
+    }
+
+    // Whether a segment of this type can refer to one of type `rt`.
+    fn may_refer_to(self, rt: SegmentType) -> bool {
+        if self.is_text() || self == SegmentType::SymbolDictionary {
+            rt == SegmentType::SymbolDictionary || rt == SegmentType::Tables
+        } else if self.is_halftone() {
+            rt == SegmentType::PatternDictionary
+        } else if self.is_refinement() {
+            rt.is_intermediate_region()
+        } else {
+            !(self.is_generic()
+                || self == SegmentType::PatternDictionary
+                || self == SegmentType::PageInformation
+                || self == SegmentType::EndOfPage
+                || self == SegmentType::EndOfStripe)
+        }
+    }
+
+    // Segments that are not associated with a page, and hence can
+    // appear in /JBIG2Globals.
+    fn is_global(self) -> bool {
+        matches!(
+            self,
+            SegmentType::SymbolDictionary
+                | SegmentType::PatternDictionary
+                | SegmentType::Profiles
+                | SegmentType::Tables
+                | SegmentType::ColourPalette
+                | SegmentType::Extension
+        )
+    }
+}
+
+// Segment header (T.88 Section 7.2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentHeader {
+    number:              u32,
+    seg_type:            SegmentType,
+    deferred_non_retain: bool,
+    referred:            Vec<u32>,
+    page:                u32,
+    data_length:         u32,
+}
+
+impl SegmentHeader {
+    pub fn number(&self) -> u32 { self.number }
+    pub fn seg_type(&self) -> SegmentType { self.seg_type }
+    pub fn deferred_non_retain(&self) -> bool { self.deferred_non_retain }
+    pub fn referred(&self) -> &[u32] { &self.referred }
+    pub fn page(&self) -> u32 { self.page }
+    pub fn data_length(&self) -> u32 { self.data_length }
+}
+
+pub struct SegmentHeaderP;
+
+impl ParsleyParser for SegmentHeaderP {
+    type T = LocatedVal<SegmentHeader>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.parse_header(buf, start) {
+            Ok(h) => Ok(LocatedVal::new(h, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl SegmentHeaderP {
+    fn parse_header(
+        &mut self, buf: &mut dyn ParseBufferT, start: usize,
+    ) -> ParseResult<SegmentHeader> {
+        let number = UInt32P::new(Endian::Big).parse(buf)?.unwrap();
+        let flags = UInt8P.parse(buf)?;
+        let seg_type = match SegmentType::from_u8(flags.val() & 0x3f) {
+            Some(t) => t,
+            None => {
+                let msg = format!("invalid JBIG2 segment type {}", flags.val() & 0x3f);
+                return Err(flags.place(ErrorKind::GuardError(msg)))
+            },
+        };
+        let deferred_non_retain = flags.val() & 0x80 != 0;
+        let long_page = flags.val() & 0x40 != 0;
+
+        // The referred-to segment count is in the top three bits, or
+        // in the long form, in the low 29 bits of a 32-bit field
+        // followed by the retention flags.
+        let count = UInt8P.parse(buf)?;
+        let count = match count.val() >> 5 {
+            c @ 0 ..= 4 => c as usize,
+            7 => {
+                buf.set_cursor_unsafe(count.start());
+                let long = UInt32P::new(Endian::Big).parse(buf)?;
+                let c = (long.val() & 0x1fff_ffff) as usize;
+                // Each referred-to segment takes at least one byte.
+                if c > buf.remaining() {
+                    let msg = format!("JBIG2 referred-to segment count {} is too large", c);
+                    return Err(long.place(ErrorKind::GuardError(msg)))
+                }
+                ByteVecP::new((c + 1).div_ceil(8)).parse(buf)?;
+                c
+            },
+            c => {
+                let msg = format!("invalid JBIG2 referred-to segment count {}", c);
+                return Err(count.place(ErrorKind::GuardError(msg)))
+            },
+        };
+        let mut referred = Vec::new();
+        for _ in 0 .. count {
+            let r = if number <= 256 {
+                *UInt8P.parse(buf)?.val() as u32
+            } else if number <= 65536 {
+                *UInt16P::new(Endian::Big).parse(buf)?.val() as u32
+            } else {
+                *UInt32P::new(Endian::Big).parse(buf)?.val()
+            };
+            if r >= number {
+                let msg = format!(
+                    "JBIG2 segment {} refers to segment {}, which is not earlier",
+                    number, r
+                );
+                let err = ErrorKind::GuardError(msg);
+                return Err(locate_value(err, start, buf.get_cursor()))
+            }
+            referred.push(r)
+        }
+        let page = if long_page {
+            UInt32P::new(Endian::Big).parse(buf)?.unwrap()
+        } else {
+            *UInt8P.parse(buf)?.val() as u32
+        };
+        let data_length = UInt32P::new(Endian::Big).parse(buf)?.unwrap();
+        Ok(SegmentHeader {
+            number,
+            seg_type,
+            deferred_non_retain,
+            referred,
+            page,
+            data_length,
+        })
+    }
+}
+
+// Page information segment data (T.88 Section 7.4.8).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageInfo {
+    width:        u32,
+    height:       u32,
+    x_resolution: u32,
+    y_resolution: u32,
+    flags:        u8,
+    striping:     u16,
+}
+
+impl PageInfo {
+    pub fn width(&self) -> u32 { self.width }
+    // UNKNOWN when the page is striped and its height is not known.
+    pub fn height(&self) -> u32 { self.height }
+    pub fn x_resolution(&self) -> u32 { self.x_resolution }
+    pub fn y_resolution(&self) -> u32 { self.y_resolution }
+    pub fn flags(&self) -> u8 { self.flags }
+    pub fn is_striped(&self) -> bool { self.striping & 0x8000 != 0 }
+    pub fn max_stripe_size(&self) -> u16 { self.striping & 0x7fff }
+}
+
+pub struct PageInfoP;
+
+impl ParsleyParser for PageInfoP {
+    type T = LocatedVal<PageInfo>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let mut u32p = UInt32P::new(Endian::Big);
+        let info = (|| {
+            Ok(PageInfo {
+                width:        u32p.parse(buf)?.unwrap(),
+                height:       u32p.parse(buf)?.unwrap(),
+                x_resolution: u32p.parse(buf)?.unwrap(),
+                y_resolution: u32p.parse(buf)?.unwrap(),
+                flags:        UInt8P.parse(buf)?.unwrap(),
+                striping:     UInt16P::new(Endian::Big).parse(buf)?.unwrap(),
+            })
+        })();
+        let info = match info {
+            Ok(i) => i,
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                return Err(e)
+            },
+        };
+        let end = buf.get_cursor();
+        let fail = |msg: String| locate_value(ErrorKind::GuardError(msg), start, end);
+        if info.height == UNKNOWN && !info.is_striped() {
+            return Err(fail(
+                "JBIG2 page with unknown height is not striped".to_string(),
+            ))
+        }
+        let height = if info.height == UNKNOWN {
+            0
+        } else {
+            info.height
+        };
+        check_dimensions("page", info.width, height).map_err(fail)?;
+        Ok(LocatedVal::new(info, start, end))
+    }
+}
+
+// Region segment information field (T.88 Section 7.4.1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionInfo {
+    width:  u32,
+    height: u32,
+    x:      u32,
+    y:      u32,
+    flags:  u8,
+}
+
+impl RegionInfo {
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn x(&self) -> u32 { self.x }
+    pub fn y(&self) -> u32 { self.y }
+    pub fn flags(&self) -> u8 { self.flags }
+}
+
+pub struct RegionInfoP;
+
+impl ParsleyParser for RegionInfoP {
+    type T = LocatedVal<RegionInfo>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let mut u32p = UInt32P::new(Endian::Big);
+        let info = (|| {
+            Ok(RegionInfo {
+                width:  u32p.parse(buf)?.unwrap(),
+                height: u32p.parse(buf)?.unwrap(),
+                x:      u32p.parse(buf)?.unwrap(),
+                y:      u32p.parse(buf)?.unwrap(),
+                flags:  UInt8P.parse(buf)?.unwrap(),
+            })
+        })();
+        let info = match info {
+            Ok(i) => i,
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                return Err(e)
+            },
+        };
+        let end = buf.get_cursor();
+        if let Err(msg) = check_dimensions("region", info.width, info.height) {
+            return Err(locate_value(ErrorKind::GuardError(msg), start, end))
+        }
+        Ok(LocatedVal::new(info, start, end))
+    }
+}
+
+fn check_dimensions(what: &str, width: u32, height: u32) -> Result<(), String> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || width as u64 * height as u64 > MAX_PIXELS
+    {
+        return Err(format!(
+            "JBIG2 {} size {}x{} exceeds implementation limits",
+            what, width, height
+        ))
+    }
+    Ok(())
+}
+
+// The parsed part of the segment data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentData {
+    PageInfo(PageInfo),
+    Region(RegionInfo),
+    // the row number of the end of the stripe
+    EndOfStripe(u32),
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    header: LocatedVal<SegmentHeader>,
+    data:   LocatedVal<SegmentData>,
+}
+
+impl Segment {
+    pub fn header(&self) -> &LocatedVal<SegmentHeader> { &self.header }
+    // The location of the data covers the whole segment data.
+    pub fn data(&self) -> &LocatedVal<SegmentData> { &self.data }
+}
+
+pub struct SegmentP;
+
+impl ParsleyParser for SegmentP {
+    type T = LocatedVal<Segment>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let header = SegmentHeaderP.parse(buf)?;
+        match self.parse_data(buf, &header) {
+            Ok(data) => {
+                let s = Segment { header, data };
+                Ok(LocatedVal::new(s, start, buf.get_cursor()))
+            },
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl SegmentP {
+    fn parse_data(
+        &mut self, buf: &mut dyn ParseBufferT, header: &LocatedVal<SegmentHeader>,
+    ) -> ParseResult<LocatedVal<SegmentData>> {
+        let h = header.val();
+        let fail = |msg: String| Err(header.place(ErrorKind::GuardError(msg)));
+        if h.data_length == UNKNOWN {
+            return fail(format!(
+                "JBIG2 segment {} has an unknown data length, which is not supported",
+                h.number
+            ))
+        }
+        let len = h.data_length as usize;
+        if len > buf.remaining() {
+            return fail(format!(
+                "JBIG2 segment {} data length {} exceeds the {} remaining bytes",
+                h.number,
+                len,
+                buf.remaining()
+            ))
+        }
+        let start = buf.get_cursor();
+        let data = match h.seg_type {
+            SegmentType::PageInformation => SegmentData::PageInfo(PageInfoP.parse(buf)?.unwrap()),
+            SegmentType::EndOfStripe => {
+                SegmentData::EndOfStripe(UInt32P::new(Endian::Big).parse(buf)?.unwrap())
+            },
+            t if t.is_region() => SegmentData::Region(RegionInfoP.parse(buf)?.unwrap()),
+            _ => SegmentData::Other,
+        };
+        let parsed = buf.get_cursor() - start;
+        if parsed > len {
+            let msg = format!(
+                "JBIG2 segment {} data length {} is too short for its type",
+                h.number, len
+            );
+            return Err(locate_value(ErrorKind::GuardError(msg), start, start + len))
+        }
+        let fixed = match h.seg_type {
+            SegmentType::EndOfPage => Some(0),
+            SegmentType::PageInformation => Some(19),
+            SegmentType::EndOfStripe => Some(4),
+            _ => None,
+        };
+        if fixed.is_some_and(|f| f != len) {
+            let msg = format!("JBIG2 segment {} has invalid data length {}", h.number, len);
+            return Err(locate_value(ErrorKind::GuardError(msg), start, start + len))
+        }
+        buf.set_cursor_unsafe(start + len);
+        Ok(LocatedVal::new(data, start, start + len))
+    }
+}
+
+// The segments of an embedded JBIG2 stream or of a /JBIG2Globals
+// stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jbig2Stream {
+    segments: Vec<LocatedVal<Segment>>,
+}
+
+impl Jbig2Stream {
+    pub fn segments(&self) -> &[LocatedVal<Segment>] { &self.segments }
+
+    pub fn page_info(&self) -> Option<&PageInfo> {
+        self.segments
+            .iter()
+            .find_map(|s| match s.val().data().val() {
+                SegmentData::PageInfo(p) => Some(p),
+                _ => None,
+            })
+    }
+}
+
+// Parses and validates an embedded JBIG2 stream, or a /JBIG2Globals
+// stream.  The segments of an embedded stream can refer to those in
+// its globals; when the globals are not available (i.e. they have not
+// been resolved) but the stream specifies them, references to
+// segments that are not in the stream are assumed to be to globals.
+pub struct Jbig2StreamP<'a> {
+    is_globals:     bool,
+    globals:        Option<&'a Jbig2Stream>,
+    assume_globals: bool,
+}
+
+impl Jbig2StreamP<'_> {
+    pub fn new_globals() -> Jbig2StreamP<'static> {
+        Jbig2StreamP {
+            is_globals:     true,
+            globals:        None,
+            assume_globals: false,
+        }
+    }
+    pub fn new(globals: Option<&Jbig2Stream>) -> Jbig2StreamP<'_> {
+        Jbig2StreamP {
+            is_globals: false,
+            globals,
+            assume_globals: false,
+        }
+    }
+    pub fn new_unresolved_globals() -> Jbig2StreamP<'static> {
+        Jbig2StreamP {
+            is_globals:     false,
+            globals:        None,
+            assume_globals: true,
+        }
+    }
+
+    fn validate(
+        &self, seg: &LocatedVal<Segment>, seen: &BTreeMap<u32, SegmentType>,
+        page: Option<&(u32, PageInfo)>,
+    ) -> Result<(), String> {
+        let h = seg.val().header().val();
+        let t = h.seg_type;
+        if t == SegmentType::EndOfFile {
+            return Err("JBIG2 end-of-file segment is not allowed in PDF".to_string())
+        }
+        if seen.contains_key(&h.number) {
+            return Err(format!("duplicate JBIG2 segment number {}", h.number))
+        }
+        if self.is_globals {
+            if !t.is_global() || h.page != 0 {
+                return Err(format!(
+                    "JBIG2 segment {} of type {:?} is not allowed in globals",
+                    h.number, t
+                ))
+            }
+        } else if !t.is_global() || h.page != 0 {
+            // A page segment: the first has to be the page
+            // information, and all have to be for the same page.
+            match page {
+                None if t != SegmentType::PageInformation => {
+                    return Err(format!(
+                        "JBIG2 segment {} of type {:?} precedes the page information",
+                        h.number, t
+                    ))
+                },
+                Some(_) if t == SegmentType::PageInformation => {
+                    return Err("multiple JBIG2 page information segments".to_string())
+                },
+                Some((p, _)) if *p != h.page => {
+                    return Err(format!(
+                        "JBIG2 segment {} is for page {} instead of page {}",
+                        h.number, h.page, p
+                    ))
+                },
+                _ => (),
+            }
+        }
+
+        // Check the types of the referred-to segments.
+        let mut types = Vec::new();
+        for r in &h.referred {
+            let rt = seen.get(r).copied().or_else(|| {
+                self.globals.and_then(|g| {
+                    g.segments
+                        .iter()
+                        .find(|s| s.val().header().val().number == *r)
+                        .map(|s| s.val().header().val().seg_type)
+                })
+            });
+            match rt {
+                Some(rt) => types.push(rt),
+                None if self.assume_globals => (),
+                None => {
+                    return Err(format!(
+                        "JBIG2 segment {} refers to missing segment {}",
+                        h.number, r
+                    ))
+                },
+            }
+        }
+        if let Some(rt) = types.iter().find(|rt| !t.may_refer_to(**rt)) {
+            return Err(format!(
+                "JBIG2 segment {} of type {:?} cannot refer to a segment of type {:?}",
+                h.number, t, rt
+            ))
+        }
+        if t.is_halftone() && h.referred.len() != 1 {
+            return Err(format!(
+                "JBIG2 halftone region segment {} has to refer to one pattern dictionary",
+                h.number
+            ))
+        }
+        if t.is_refinement() && h.referred.len() > 1 {
+            return Err(format!(
+                "JBIG2 refinement region segment {} refers to more than one region",
+                h.number
+            ))
+        }
+
+        // Regions have to be within the page.
+        if let (SegmentData::Region(r), Some((_, p))) = (seg.val().data().val(), page) {
+            let outside = r.x as u64 + r.width as u64 > p.width as u64
+                || (p.height != UNKNOWN && r.y as u64 + r.height as u64 > p.height as u64);
+            if outside {
+                return Err(format!(
+                    "JBIG2 region {}x{} at ({},{}) of segment {} is outside the {}x{} page",
+                    r.width, r.height, r.x, r.y, h.number, p.width, p.height
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ParsleyParser for Jbig2StreamP<'_> {
+    type T = LocatedVal<Jbig2Stream>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        if buf.buf().starts_with(FILE_HEADER_ID) {
+            let msg = "JBIG2 file header is not allowed in PDF".to_string();
+            let err = ErrorKind::GuardError(msg);
+            return Err(locate_value(err, start, start + FILE_HEADER_ID.len()))
+        }
+        let mut segments = Vec::new();
+        let mut seen = BTreeMap::new();
+        let mut page: Option<(u32, PageInfo)> = None;
+        let mut ended = false;
+        while buf.remaining() > 0 {
+            let seg = match SegmentP.parse(buf) {
+                Ok(s) => s,
+                Err(e) => {
+                    buf.set_cursor_unsafe(start);
+                    return Err(e)
+                },
+            };
+            let h = seg.val().header().val();
+            let err = if ended {
+                Err(format!(
+                    "JBIG2 segment {} follows the end of the page",
+                    h.number
+                ))
+            } else {
+                self.validate(&seg, &seen, page.as_ref())
+            };
+            if let Err(msg) = err {
+                buf.set_cursor_unsafe(start);
+                return Err(seg.place(ErrorKind::GuardError(msg)))
+            }
+            if let SegmentData::PageInfo(p) = seg.val().data().val() {
+                page = Some((h.page, p.clone()))
+            }
+            ended = h.seg_type == SegmentType::EndOfPage;
+            seen.insert(h.number, h.seg_type);
+            segments.push(seg)
+        }
+        if !self.is_globals && page.is_none() {
+            let msg = "JBIG2 stream has no page information segment".to_string();
+            return Err(locate_value(
+                ErrorKind::GuardError(msg),
+                start,
+                buf.get_cursor(),
+            ))
+        }
+        Ok(LocatedVal::new(
+            Jbig2Stream { segments },
+            start,
+            buf.get_cursor(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test_jbig2 {
+    use super::{Jbig2StreamP, SegmentData, SegmentHeaderP, SegmentType};
+    use crate::pcore::parsebuffer::{ErrorKind, ParseBuffer, ParsleyParser};
+
+    // Builds a segment with a short-form header and a one-byte page
+    // association.
+    fn seg(number: u32, t: u8, referred: &[u8], page: u8, data: &[u8]) -> Vec<u8> {
+        let mut v = number.to_be_bytes().to_vec();
+        v.push(t);
+        v.push((referred.len() as u8) << 5);
+        v.extend_from_slice(referred);
+        v.push(page);
+        v.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        v.extend_from_slice(data);
+        v
+    }
+
+    fn page_info(width: u32, height: u32) -> Vec<u8> {
+        let mut v = width.to_be_bytes().to_vec();
+        v.extend_from_slice(&height.to_be_bytes());
+        v.extend_from_slice(&[0; 8]);
+        v.extend_from_slice(&[0, 0, 0]);
+        v
+    }
+
+    fn region(width: u32, height: u32, x: u32, y: u32, rest: &[u8]) -> Vec<u8> {
+        let mut v = Vec::new();
+        for i in &[width, height, x, y] {
+            v.extend_from_slice(&i.to_be_bytes())
+        }
+        v.push(0);
+        v.extend_from_slice(rest);
+        v
+    }
+
+    fn check(data: Vec<u8>) -> Result<usize, (String, usize)> {
+        let mut pb = ParseBuffer::new(data);
+        match Jbig2StreamP::new(None).parse(&mut pb) {
+            Ok(s) => Ok(s.val().segments().len()),
+            Err(e) => match e.val() {
+                ErrorKind::GuardError(m) => Err((m.clone(), e.start())),
+                e => Err((format!("{:?}", e), 0)),
+            },
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        // long-form referred-to count, 2-byte segment numbers and a
+        // 4-byte page association
+        let mut v = 300u32.to_be_bytes().to_vec();
+        v.push(0x40 | 38);
+        v.extend_from_slice(&[0xe0, 0, 0, 9, 0, 0]);
+        for r in 1u16 .. 10 {
+            v.extend_from_slice(&r.to_be_bytes())
+        }
+        v.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 5]);
+        let mut pb = ParseBuffer::new(v);
+        let h = SegmentHeaderP.parse(&mut pb).unwrap();
+        let h = h.val();
+        assert_eq!(h.number(), 300);
+        assert_eq!(h.seg_type(), SegmentType::ImmediateGenericRegion);
+        assert_eq!(h.referred(), &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(h.page(), 2);
+        assert_eq!(h.data_length(), 5);
+
+        // invalid type and count
+        let mut pb = ParseBuffer::new(vec![0, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0]);
+        assert!(SegmentHeaderP.parse(&mut pb).is_err());
+        let mut pb = ParseBuffer::new(vec![0, 0, 0, 1, 48, 0xa0, 1, 0, 0, 0, 0]);
+        assert!(SegmentHeaderP.parse(&mut pb).is_err());
+        // references to later segments
+        let mut pb = ParseBuffer::new(vec![0, 0, 0, 1, 48, 0x20, 2, 1, 0, 0, 0, 0]);
+        assert!(SegmentHeaderP.parse(&mut pb).is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(1, 0, &[], 1, &[0; 10]));
+        v.extend(seg(2, 6, &[1], 1, &region(64, 32, 0, 0, &[0; 4])));
+        v.extend(seg(3, 38, &[], 1, &region(8, 8, 56, 24, &[0; 4])));
+        v.extend(seg(4, 49, &[], 1, &[]));
+        assert_eq!(check(v.clone()), Ok(5));
+        let mut pb = ParseBuffer::new(v);
+        let s = Jbig2StreamP::new(None).parse(&mut pb).unwrap();
+        assert_eq!(s.val().page_info().unwrap().width(), 64);
+        match s.val().segments()[3].val().data().val() {
+            SegmentData::Region(r) => assert_eq!((r.x(), r.y()), (56, 24)),
+            _ => panic!("no region info"),
+        }
+
+        // a region outside the page
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        let bad = seg(1, 38, &[], 1, &region(8, 8, 60, 0, &[]));
+        v.extend(bad);
+        assert_eq!(check(v).unwrap_err().1, 11 + 19);
+        // no page information
+        assert!(check(seg(1, 38, &[], 1, &region(8, 8, 0, 0, &[]))).is_err());
+        // a region that is too large
+        let mut v = seg(0, 48, &[], 1, &page_info(1 << 20, 32));
+        v.extend(seg(1, 38, &[], 1, &region(1 << 20, 8, 0, 0, &[])));
+        assert!(check(v).is_err());
+        // a reference to a missing segment and of the wrong type
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(2, 6, &[1], 1, &region(8, 8, 0, 0, &[])));
+        assert!(check(v).is_err());
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(1, 6, &[0], 1, &region(8, 8, 0, 0, &[])));
+        assert!(check(v).is_err());
+        // data beyond the end of the stream
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.truncate(v.len() - 1);
+        assert!(check(v).is_err());
+        // segments after the end of the page, and end of file
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(1, 49, &[], 1, &[]));
+        v.extend(seg(2, 38, &[], 1, &region(8, 8, 0, 0, &[])));
+        assert!(check(v).is_err());
+        let mut v = seg(0, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(1, 51, &[], 0, &[]));
+        assert!(check(v).is_err());
+        // the file header
+        let mut v = b"\x97JB2\r\n\x1a\n\x01\x00\x00\x00\x01".to_vec();
+        v.extend(seg(0, 48, &[], 1, &page_info(64, 32)));
+        assert!(check(v).is_err());
+    }
+
+    #[test]
+    fn test_globals() {
+        let g = seg(0, 0, &[], 0, &[0; 10]);
+        let mut pb = ParseBuffer::new(g);
+        let globals = Jbig2StreamP::new_globals().parse(&mut pb).unwrap();
+        let globals = globals.val();
+
+        let mut v = seg(1, 48, &[], 1, &page_info(64, 32));
+        v.extend(seg(2, 6, &[0], 1, &region(64, 32, 0, 0, &[])));
+        assert!(check(v.clone()).is_err());
+        let mut pb = ParseBuffer::new(v.clone());
+        assert!(Jbig2StreamP::new(Some(globals)).parse(&mut pb).is_ok());
+        let mut pb = ParseBuffer::new(v);
+        assert!(Jbig2StreamP::new_unresolved_globals()
+            .parse(&mut pb)
+            .is_ok());
+
+        // page segments are not allowed in globals
+        let mut pb = ParseBuffer::new(seg(0, 48, &[], 1, &page_info(64, 32)));
+        assert!(Jbig2StreamP::new_globals().parse(&mut pb).is_err());
+    }
+}
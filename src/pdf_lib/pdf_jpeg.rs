@@ -0,0 +1,682 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Structural parsing and validation of JPEG data (ITU-T T.81) used
+// by the DCTDecode filter.  The marker segments are parsed and
+// checked, and the entropy-coded data of each scan is delimited, but
+// not decoded.
+
+use crate::pcore::parsebuffer::{
+    locate_value, ErrorKind, LocatedVal, ParseBufferT, ParseResult, ParsleyParser,
+};
+use crate::pcore::prim_binary::{Endian, UInt16P, UInt8P};
+use crate::pdf_lib::pdf_obj::{DictT, PDFObjT};
+
+// Markers, without the 0xFF prefix.
+const SOI: u8 = 0xd8;
+const EOI: u8 = 0xd9;
+const SOS: u8 = 0xda;
+const DQT: u8 = 0xdb;
+const DNL: u8 = 0xdc;
+const DRI: u8 = 0xdd;
+const DHT: u8 = 0xc4;
+const DAC: u8 = 0xcc;
+const COM: u8 = 0xfe;
+
+// Signatures of formats that can be appended to JPEG data to create
+// polyglot files.
+const PAYLOAD_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF", "PDF"),
+    (b"PK\x03\x04", "ZIP"),
+    (b"Rar!", "RAR"),
+    (b"7z\xbc\xaf\x27\x1c", "7z"),
+    (b"\x1f\x8b", "gzip"),
+    (b"MZ", "PE executable"),
+    (b"\x7fELF", "ELF executable"),
+    (b"#!", "script"),
+    (b"<?php", "PHP"),
+    (b"<html", "HTML"),
+    (b"<script", "script"),
+    (b"\x89PNG", "PNG"),
+    (b"GIF8", "GIF"),
+    (b"\xff\xd8\xff", "JPEG"),
+];
+
+fn guard_err<T>(msg: String, start: usize, end: usize) -> ParseResult<T> {
+    Err(locate_value(ErrorKind::GuardError(msg), start, end))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameComponent {
+    id:          u8,
+    h:           u8,
+    v:           u8,
+    quant_table: u8,
+}
+
+impl FrameComponent {
+    pub fn id(&self) -> u8 { self.id }
+    pub fn h(&self) -> u8 { self.h }
+    pub fn v(&self) -> u8 { self.v }
+    pub fn quant_table(&self) -> u8 { self.quant_table }
+}
+
+// Frame header (SOFn marker segment).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    marker:     u8,
+    precision:  u8,
+    // 0 if defined by a DNL segment after the first scan
+    height:     u16,
+    width:      u16,
+    components: Vec<FrameComponent>,
+}
+
+impl Frame {
+    pub fn marker(&self) -> u8 { self.marker }
+    pub fn precision(&self) -> u8 { self.precision }
+    pub fn height(&self) -> u16 { self.height }
+    pub fn width(&self) -> u16 { self.width }
+    pub fn components(&self) -> &[FrameComponent] { &self.components }
+    pub fn is_progressive(&self) -> bool { self.marker & 3 == 2 }
+    pub fn is_arithmetic(&self) -> bool { self.marker & 8 != 0 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanComponent {
+    id:       u8,
+    dc_table: u8,
+    ac_table: u8,
+}
+
+impl ScanComponent {
+    pub fn id(&self) -> u8 { self.id }
+    pub fn dc_table(&self) -> u8 { self.dc_table }
+    pub fn ac_table(&self) -> u8 { self.ac_table }
+}
+
+// Scan header (SOS marker segment).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    components:     Vec<ScanComponent>,
+    spectral_start: u8,
+    spectral_end:   u8,
+    approx:         u8,
+}
+
+impl Scan {
+    pub fn components(&self) -> &[ScanComponent] { &self.components }
+    pub fn spectral_start(&self) -> u8 { self.spectral_start }
+    pub fn spectral_end(&self) -> u8 { self.spectral_end }
+    pub fn approx(&self) -> u8 { self.approx }
+}
+
+// The interpreted content of a marker segment.  The location of a
+// segment covers its marker and, for a scan, its entropy-coded data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentKind {
+    StartOfImage,
+    EndOfImage,
+    // APPn
+    App(u8),
+    // the ids of the defined tables
+    QuantTables(Vec<u8>),
+    // the (class, id) of the defined tables
+    HuffmanTables(Vec<(u8, u8)>),
+    Frame(Frame),
+    // the scan header, and the size of the entropy-coded data
+    Scan(Scan, usize),
+    RestartInterval(u16),
+    NumberOfLines(u16),
+    Comment,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JpegImage {
+    segments: Vec<LocatedVal<SegmentKind>>,
+}
+
+impl JpegImage {
+    pub fn segments(&self) -> &[LocatedVal<SegmentKind>] { &self.segments }
+
+    pub fn frame(&self) -> &Frame {
+        self.segments
+            .iter()
+            .find_map(|s| match s.val() {
+                SegmentKind::Frame(f) => Some(f),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    // The image height, taking into account a DNL segment.
+    pub fn height(&self) -> u16 {
+        let f = self.frame();
+        if f.height != 0 {
+            return f.height
+        }
+        self.segments
+            .iter()
+            .find_map(|s| match s.val() {
+                SegmentKind::NumberOfLines(n) => Some(*n),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    // Checks the frame against the /Width, /Height, /ColorSpace and
+    // /BitsPerComponent entries of the image dictionary.  Color
+    // spaces whose component counts cannot be determined from the
+    // dictionary alone (e.g. /ICCBased) are not checked.
+    pub fn check_image_dict(&self, dict: &DictT) -> Result<(), String> {
+        let f = self.frame();
+        let dims = [
+            (&b"Width"[..], f.width as usize),
+            (b"Height", self.height() as usize),
+            (b"BitsPerComponent", f.precision as usize),
+        ];
+        for (key, val) in &dims {
+            if let Some(v) = dict.get_usize(key) {
+                if v != *val {
+                    return Err(format!(
+                        "/{} {} does not match the frame value {}",
+                        String::from_utf8_lossy(key),
+                        v,
+                        val
+                    ))
+                }
+            }
+        }
+        let cs = match dict.get(b"ColorSpace").map(|o| o.val()) {
+            Some(PDFObjT::Name(n)) => Some(n.val().to_vec()),
+            Some(PDFObjT::Array(a)) => a.objs().first().and_then(|o| match o.val() {
+                PDFObjT::Name(n) => Some(n.val().to_vec()),
+                _ => None,
+            }),
+            _ => None,
+        };
+        let ncomps = match cs.as_deref() {
+            Some(b"DeviceGray") | Some(b"CalGray") => Some(1),
+            Some(b"DeviceRGB") | Some(b"CalRGB") | Some(b"Lab") => Some(3),
+            Some(b"DeviceCMYK") => Some(4),
+            _ => None,
+        };
+        if let Some(n) = ncomps {
+            if n != f.components.len() {
+                return Err(format!(
+                    "/ColorSpace {} has {} components, but the frame has {}",
+                    String::from_utf8_lossy(&cs.unwrap()),
+                    n,
+                    f.components.len()
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct JpegImageP;
+
+impl ParsleyParser for JpegImageP {
+    type T = LocatedVal<JpegImage>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.parse_image(buf, start) {
+            Ok(img) => Ok(LocatedVal::new(img, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl JpegImageP {
+    // Parses a marker, skipping any fill bytes.
+    fn marker(&self, buf: &mut dyn ParseBufferT) -> ParseResult<LocatedVal<u8>> {
+        let start = buf.get_cursor();
+        if UInt8P.parse(buf)?.unwrap() != 0xff {
+            return guard_err("expected a JPEG marker".to_string(), start, start + 1)
+        }
+        loop {
+            let m = UInt8P.parse(buf)?;
+            match m.val() {
+                0xff => continue,
+                0 => return guard_err("invalid JPEG marker".to_string(), start, m.end()),
+                _ => return Ok(LocatedVal::new(m.unwrap(), start, buf.get_cursor())),
+            }
+        }
+    }
+
+    // Parses the length of a marker segment, and returns the offset
+    // of its end.
+    fn length(&self, buf: &mut dyn ParseBufferT, marker: u8) -> ParseResult<usize> {
+        let len = UInt16P::new(Endian::Big).parse(buf)?;
+        let l = *len.val() as usize;
+        if l < 2 || l - 2 > buf.remaining() {
+            let msg = format!("JPEG marker {:02X} has invalid length {}", marker, l);
+            return Err(len.place(ErrorKind::GuardError(msg)))
+        }
+        Ok(len.start() + l)
+    }
+
+    fn parse_image(&mut self, buf: &mut dyn ParseBufferT, start: usize) -> ParseResult<JpegImage> {
+        let soi = self.marker(buf)?;
+        if *soi.val() != SOI {
+            return guard_err(
+                "JPEG data does not start with SOI".to_string(),
+                start,
+                soi.end(),
+            )
+        }
+        let mut segments = vec![soi.place(SegmentKind::StartOfImage)];
+        let mut frame: Option<Frame> = None;
+        let mut quant = [false; 4];
+        // DC and AC Huffman tables
+        let mut huffman = [[false; 4]; 2];
+        let mut scans = 0;
+        loop {
+            let m = self.marker(buf)?;
+            let seg_start = m.start();
+            let marker = *m.val();
+            let fail = |msg: String| guard_err(msg, seg_start, m.end());
+            let kind = match marker {
+                SOI => return fail("unexpected JPEG SOI".to_string()),
+                EOI => {
+                    if scans == 0 {
+                        return fail("JPEG data has no scans".to_string())
+                    }
+                    segments.push(m.place(SegmentKind::EndOfImage));
+                    break
+                },
+                0xd0 ..= 0xd7 | 0x01 => SegmentKind::Other(marker),
+                SOS => {
+                    let f = match &frame {
+                        Some(f) => f,
+                        None => return fail("JPEG scan precedes the frame header".to_string()),
+                    };
+                    let end = self.length(buf, marker)?;
+                    let scan = self.parse_scan(buf, f, &quant, &huffman, seg_start, end)?;
+                    buf.set_cursor_unsafe(end);
+                    let data = self.entropy_data(buf)?;
+                    scans += 1;
+                    SegmentKind::Scan(scan, data)
+                },
+                _ => {
+                    let end = self.length(buf, marker)?;
+                    let kind = match marker {
+                        0xe0 ..= 0xef => SegmentKind::App(marker - 0xe0),
+                        COM => SegmentKind::Comment,
+                        DQT => {
+                            let ids = self.parse_dqt(buf, seg_start, end)?;
+                            for id in &ids {
+                                quant[*id as usize] = true
+                            }
+                            SegmentKind::QuantTables(ids)
+                        },
+                        DHT => {
+                            let ids = self.parse_dht(buf, seg_start, end)?;
+                            for (c, id) in &ids {
+                                huffman[*c as usize][*id as usize] = true
+                            }
+                            SegmentKind::HuffmanTables(ids)
+                        },
+                        DRI | DNL => {
+                            if end != seg_start + 6 {
+                                return fail(format!(
+                                    "JPEG marker {:02X} has invalid length",
+                                    marker
+                                ))
+                            }
+                            let v = UInt16P::new(Endian::Big).parse(buf)?.unwrap();
+                            if marker == DRI {
+                                SegmentKind::RestartInterval(v)
+                            } else if scans != 1
+                                || frame.as_ref().is_none_or(|f| f.height != 0)
+                                || v == 0
+                            {
+                                return fail("unexpected JPEG DNL".to_string())
+                            } else {
+                                SegmentKind::NumberOfLines(v)
+                            }
+                        },
+                        DAC => SegmentKind::Other(marker),
+                        0xc0 ..= 0xcf => {
+                            if frame.is_some() {
+                                return fail("multiple JPEG frame headers".to_string())
+                            }
+                            let f = self.parse_frame(buf, marker, seg_start, end)?;
+                            frame = Some(f.clone());
+                            SegmentKind::Frame(f)
+                        },
+                        // DHP, EXP and JPGn are not used in sequential,
+                        // progressive or lossless JPEG data.
+                        _ => return fail(format!("unexpected JPEG marker {:02X}", marker)),
+                    };
+                    buf.set_cursor_unsafe(end);
+                    kind
+                },
+            };
+            let end = buf.get_cursor();
+            segments.push(LocatedVal::new(kind, seg_start, end))
+        }
+
+        // Only whitespace padding can follow EOI.
+        let trailing = buf.buf();
+        if let Some(p) = trailing
+            .iter()
+            .position(|b| !b" \t\r\n\x0c\x00".contains(b))
+        {
+            let pos = buf.get_cursor() + p;
+            let end = buf.get_cursor() + trailing.len();
+            let payload = PAYLOAD_SIGNATURES
+                .iter()
+                .find(|(sig, _)| trailing[p ..].starts_with(sig));
+            let msg = match payload {
+                Some((_, name)) => format!("{} payload appended after JPEG EOI", name),
+                None => format!("{} bytes of data after JPEG EOI", end - pos),
+            };
+            return guard_err(msg, pos, end)
+        }
+        buf.set_cursor_unsafe(buf.get_cursor() + trailing.len());
+        Ok(JpegImage { segments })
+    }
+
+    fn parse_frame(
+        &mut self, buf: &mut dyn ParseBufferT, marker: u8, start: usize, end: usize,
+    ) -> ParseResult<Frame> {
+        let fail = |msg: String| guard_err(format!("JPEG frame header: {}", msg), start, end);
+        let precision = UInt8P.parse(buf)?.unwrap();
+        let height = UInt16P::new(Endian::Big).parse(buf)?.unwrap();
+        let width = UInt16P::new(Endian::Big).parse(buf)?.unwrap();
+        let n = UInt8P.parse(buf)?.unwrap() as usize;
+        if n == 0 || n > 4 || end != start + 10 + 3 * n {
+            return fail(format!("invalid component count {}", n))
+        }
+        let lossless = marker & 3 == 3;
+        if (!lossless && precision != 8 && precision != 12)
+            || (lossless && !(2 ..= 16).contains(&precision))
+        {
+            return fail(format!("invalid precision {}", precision))
+        }
+        if width == 0 {
+            return fail("zero width".to_string())
+        }
+        let mut components: Vec<FrameComponent> = Vec::new();
+        for _ in 0 .. n {
+            let id = UInt8P.parse(buf)?.unwrap();
+            let hv = UInt8P.parse(buf)?.unwrap();
+            let quant_table = UInt8P.parse(buf)?.unwrap();
+            let (h, v) = (hv >> 4, hv & 0xf);
+            if components.iter().any(|c| c.id == id) {
+                return fail(format!("duplicate component {}", id))
+            }
+            if !(1 ..= 4).contains(&h) || !(1 ..= 4).contains(&v) || quant_table > 3 {
+                return fail(format!("invalid parameters for component {}", id))
+            }
+            components.push(FrameComponent {
+                id,
+                h,
+                v,
+                quant_table,
+            })
+        }
+        Ok(Frame {
+            marker,
+            precision,
+            height,
+            width,
+            components,
+        })
+    }
+
+    fn parse_scan(
+        &mut self, buf: &mut dyn ParseBufferT, frame: &Frame, quant: &[bool; 4],
+        huffman: &[[bool; 4]; 2], start: usize, end: usize,
+    ) -> ParseResult<Scan> {
+        let fail = |msg: String| guard_err(format!("JPEG scan header: {}", msg), start, end);
+        let n = UInt8P.parse(buf)?.unwrap() as usize;
+        if n == 0 || n > 4 || end != start + 8 + 2 * n {
+            return fail(format!("invalid component count {}", n))
+        }
+        let mut components = Vec::new();
+        for _ in 0 .. n {
+            let id = UInt8P.parse(buf)?.unwrap();
+            let t = UInt8P.parse(buf)?.unwrap();
+            components.push(ScanComponent {
+                id,
+                dc_table: t >> 4,
+                ac_table: t & 0xf,
+            })
+        }
+        let scan = Scan {
+            components,
+            spectral_start: UInt8P.parse(buf)?.unwrap(),
+            spectral_end: UInt8P.parse(buf)?.unwrap(),
+            approx: UInt8P.parse(buf)?.unwrap(),
+        };
+        let lossless = frame.marker & 3 == 3;
+        if !lossless && (scan.spectral_start > scan.spectral_end || scan.spectral_end > 63) {
+            return fail("invalid spectral selection".to_string())
+        }
+        for c in &scan.components {
+            let fc = match frame.components.iter().find(|fc| fc.id == c.id) {
+                Some(fc) => fc,
+                None => return fail(format!("component {} is not in the frame", c.id)),
+            };
+            if !lossless && !quant[fc.quant_table as usize] {
+                return fail(format!(
+                    "quantization table {} is not defined",
+                    fc.quant_table
+                ))
+            }
+            // Arithmetic coding conditioning tables have defaults, and
+            // DC refinement scans do not use Huffman tables.  Lossless
+            // scans only use DC tables.
+            let huffman_coded = !frame.is_arithmetic();
+            let dc =
+                huffman_coded && (lossless || (scan.spectral_start == 0 && scan.approx >> 4 == 0));
+            let ac = huffman_coded && !lossless && scan.spectral_end > 0;
+            if c.dc_table > 3
+                || c.ac_table > 3
+                || (dc && !huffman[0][c.dc_table as usize])
+                || (ac && !huffman[1][c.ac_table as usize])
+            {
+                return fail(format!(
+                    "entropy coding tables for component {} are not defined",
+                    c.id
+                ))
+            }
+        }
+        Ok(scan)
+    }
+
+    fn parse_dqt(
+        &mut self, buf: &mut dyn ParseBufferT, start: usize, end: usize,
+    ) -> ParseResult<Vec<u8>> {
+        let mut ids = Vec::new();
+        while buf.get_cursor() < end {
+            let pq = UInt8P.parse(buf)?.unwrap();
+            let (precision, id) = (pq >> 4, pq & 0xf);
+            let size = if precision == 0 { 64 } else { 128 };
+            if precision > 1 || id > 3 || buf.get_cursor() + size > end {
+                let msg = format!("invalid JPEG quantization table {}", id);
+                return guard_err(msg, start, end)
+            }
+            buf.set_cursor_unsafe(buf.get_cursor() + size);
+            ids.push(id)
+        }
+        Ok(ids)
+    }
+
+    fn parse_dht(
+        &mut self, buf: &mut dyn ParseBufferT, start: usize, end: usize,
+    ) -> ParseResult<Vec<(u8, u8)>> {
+        let mut ids = Vec::new();
+        while buf.get_cursor() < end {
+            let tc = UInt8P.parse(buf)?.unwrap();
+            let (class, id) = (tc >> 4, tc & 0xf);
+            let total: usize = if buf.get_cursor() + 16 <= end {
+                buf.extract(16)?.iter().map(|c| *c as usize).sum()
+            } else {
+                usize::MAX
+            };
+            if class > 1 || id > 3 || total > 256 || buf.get_cursor() + total > end {
+                let msg = format!("invalid JPEG Huffman table {}", id);
+                return guard_err(msg, start, end)
+            }
+            buf.set_cursor_unsafe(buf.get_cursor() + total);
+            ids.push((class, id))
+        }
+        Ok(ids)
+    }
+
+    // Skips the entropy-coded data of a scan up to the next marker
+    // other than RSTn, and returns its size.
+    fn entropy_data(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<usize> {
+        let start = buf.get_cursor();
+        let data = buf.buf();
+        let mut i = 0;
+        while i + 1 < data.len() {
+            if data[i] == 0xff {
+                match data[i + 1] {
+                    0x00 | 0xd0 ..= 0xd7 => i += 1,
+                    // fill bytes before a marker
+                    0xff => (),
+                    _ => {
+                        buf.set_cursor_unsafe(start + i);
+                        return Ok(i)
+                    },
+                }
+            }
+            i += 1
+        }
+        let msg = "JPEG scan data is not terminated by a marker".to_string();
+        guard_err(msg, start, start + data.len())
+    }
+}
+
+#[cfg(test)]
+mod test_jpeg {
+    use super::{JpegImageP, SegmentKind};
+    use crate::pcore::parsebuffer::{ErrorKind, ParseBuffer, ParsleyParser};
+    use crate::pdf_lib::pdf_obj::{DictP, PDFObjContext};
+
+    // Builds a baseline JPEG with one component, with the given scan
+    // data.
+    fn jpeg(width: u16, height: u16, data: &[u8]) -> Vec<u8> {
+        let mut v = vec![0xff, 0xd8];
+        v.extend_from_slice(b"\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00");
+        v.extend_from_slice(&[0xff, 0xdb, 0, 67, 0]);
+        v.extend_from_slice(&[1; 64]);
+        v.extend_from_slice(&[0xff, 0xc0, 0, 11, 8]);
+        v.extend_from_slice(&height.to_be_bytes());
+        v.extend_from_slice(&width.to_be_bytes());
+        v.extend_from_slice(&[1, 1, 0x11, 0]);
+        for class in &[0x00, 0x10] {
+            v.extend_from_slice(&[0xff, 0xc4, 0, 20, *class, 1]);
+            v.extend_from_slice(&[0; 15]);
+            v.push(0);
+        }
+        v.extend_from_slice(&[0xff, 0xda, 0, 8, 1, 1, 0, 0, 63, 0]);
+        v.extend_from_slice(data);
+        v.extend_from_slice(&[0xff, 0xd9]);
+        v
+    }
+
+    fn check(v: Vec<u8>) -> Result<usize, (String, usize)> {
+        let mut pb = ParseBuffer::new(v);
+        match JpegImageP.parse(&mut pb) {
+            Ok(img) => Ok(img.val().segments().len()),
+            Err(e) => match e.val() {
+                ErrorKind::GuardError(m) => Err((m.clone(), e.start())),
+                k => Err((format!("{:?}", k), e.start())),
+            },
+        }
+    }
+
+    #[test]
+    fn test_segments() {
+        let data = b"\x12\xff\x00\x34\xff\xd0\x56";
+        let mut pb = ParseBuffer::new(jpeg(16, 8, data));
+        let img = JpegImageP.parse(&mut pb).unwrap();
+        let img = img.val();
+        let offsets: Vec<usize> = img.segments().iter().map(|s| s.start()).collect();
+        assert_eq!(offsets, vec![0, 2, 20, 89, 102, 124, 146, 163]);
+        match img.segments()[6].val() {
+            SegmentKind::Scan(s, len) => {
+                assert_eq!(s.components().len(), 1);
+                assert_eq!(*len, data.len())
+            },
+            _ => panic!("no scan"),
+        }
+        assert_eq!(img.frame().width(), 16);
+        assert_eq!(img.height(), 8);
+
+        // trailing data and payloads
+        assert!(check([jpeg(16, 8, &[1]), b"\r\n\0".to_vec()].concat()).is_ok());
+        let (msg, ofs) = check([jpeg(16, 8, &[1]), b"\n%PDF-1.4".to_vec()].concat()).unwrap_err();
+        assert_eq!(ofs, 160);
+        assert!(msg.contains("PDF payload"));
+        let (msg, _) = check([jpeg(16, 8, &[1]), b"abc".to_vec()].concat()).unwrap_err();
+        assert!(msg.contains("3 bytes"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        // missing SOI and EOI
+        assert!(check(jpeg(16, 8, &[1])[2 ..].to_vec()).is_err());
+        let mut v = jpeg(16, 8, &[1]);
+        v.truncate(v.len() - 2);
+        assert!(check(v).is_err());
+        // zero width
+        assert!(check(jpeg(0, 8, &[1])).is_err());
+        // a scan using an undefined Huffman table
+        let mut v = jpeg(16, 8, &[1]);
+        v[152] = 0x11;
+        assert_eq!(check(v).unwrap_err().1, 146);
+        // a frame component using an undefined quantization table
+        let mut v = jpeg(16, 8, &[1]);
+        v[101] = 1;
+        assert!(check(v).is_err());
+        // a truncated segment
+        let mut v = jpeg(16, 8, &[1]);
+        v[22] = 0xff;
+        assert_eq!(check(v).unwrap_err().1, 22);
+    }
+
+    #[test]
+    fn test_image_dict() {
+        let mut pb = ParseBuffer::new(jpeg(16, 8, &[1]));
+        let img = JpegImageP.parse(&mut pb).unwrap();
+        let dict = |s: &str| {
+            let mut ctxt = PDFObjContext::new(10);
+            let mut pb = ParseBuffer::new(Vec::from(s.as_bytes()));
+            DictP::new(&mut ctxt).parse(&mut pb).unwrap()
+        };
+        let check = |s: &str| img.val().check_image_dict(&dict(s));
+        assert!(check("<</Width 16 /Height 8 /ColorSpace /DeviceGray>>").is_ok());
+        assert!(check("<</Width 16 /Height 8 /ColorSpace [/ICCBased 5 0 R]>>").is_ok());
+        assert!(check("<</Width 16 /Height 8 /ColorSpace [/CalRGB <<>>]>>").is_err());
+        assert!(check("<</Width 16 /Height 8 /ColorSpace /DeviceRGB>>").is_err());
+        assert!(check("<</Width 16 /Height 9>>").is_err());
+        assert!(check("<</Width 16 /Height 8 /BitsPerComponent 12>>").is_err());
+    }
+}
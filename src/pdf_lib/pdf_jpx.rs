@@ -0,0 +1,980 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Structural parsing and validation of JPEG 2000 data (ISO/IEC
+// 15444-1) used by the JPXDecode filter: either a JP2 file (a
+// sequence of boxes), or a bare codestream.  The marker segments of
+// the codestream are checked, but the tile data is not decoded.
+
+use crate::pcore::parsebuffer::{
+    locate_value, ErrorKind, LocatedVal, ParseBufferT, ParseResult, ParsleyParser,
+};
+use crate::pcore::prim_binary::{Endian, UInt16P, UInt32P, UInt64P, UInt8P};
+use crate::pdf_lib::pdf_obj::DictT;
+
+// Implementation limits.
+pub const MAX_DIMENSION: u32 = 1 << 18;
+pub const MAX_PIXELS: u64 = 1 << 31;
+pub const MAX_COMPONENTS: usize = 16384;
+pub const MAX_TILES: u64 = 65535;
+
+// Codestream markers.
+const SOC: u16 = 0xff4f;
+const CAP: u16 = 0xff50;
+const SIZ: u16 = 0xff51;
+const COD: u16 = 0xff52;
+const COC: u16 = 0xff53;
+const TLM: u16 = 0xff55;
+const PLM: u16 = 0xff57;
+const PLT: u16 = 0xff58;
+const QCD: u16 = 0xff5c;
+const QCC: u16 = 0xff5d;
+const RGN: u16 = 0xff5e;
+const POC: u16 = 0xff5f;
+const PPM: u16 = 0xff60;
+const PPT: u16 = 0xff61;
+const CRG: u16 = 0xff63;
+const COM: u16 = 0xff64;
+const SOT: u16 = 0xff90;
+const SOD: u16 = 0xff93;
+const EOC: u16 = 0xffd9;
+
+const JP2_SIGNATURE: &[u8] = b"\x00\x00\x00\x0cjP  \x0d\x0a\x87\x0a";
+
+fn guard_err<T>(msg: String, start: usize, end: usize) -> ParseResult<T> {
+    Err(locate_value(ErrorKind::GuardError(msg), start, end))
+}
+
+// Image header box of a JP2 file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageHeader {
+    height:     u32,
+    width:      u32,
+    components: u16,
+    // 255 if the components have different depths
+    bpc:        u8,
+}
+
+impl ImageHeader {
+    pub fn height(&self) -> u32 { self.height }
+    pub fn width(&self) -> u32 { self.width }
+    pub fn components(&self) -> u16 { self.components }
+    pub fn bits_per_component(&self) -> Option<u8> {
+        if self.bpc == 255 {
+            None
+        } else {
+            Some((self.bpc & 0x7f) + 1)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentSiz {
+    precision: u8,
+    signed:    bool,
+    dx:        u8,
+    dy:        u8,
+}
+
+impl ComponentSiz {
+    pub fn precision(&self) -> u8 { self.precision }
+    pub fn signed(&self) -> bool { self.signed }
+    pub fn dx(&self) -> u8 { self.dx }
+    pub fn dy(&self) -> u8 { self.dy }
+}
+
+// Image and tile size (SIZ) marker segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Siz {
+    capabilities:  u16,
+    width:         u32,
+    height:        u32,
+    x_offset:      u32,
+    y_offset:      u32,
+    tile_width:    u32,
+    tile_height:   u32,
+    tile_x_offset: u32,
+    tile_y_offset: u32,
+    components:    Vec<ComponentSiz>,
+}
+
+impl Siz {
+    pub fn capabilities(&self) -> u16 { self.capabilities }
+    pub fn image_width(&self) -> u32 { self.width - self.x_offset }
+    pub fn image_height(&self) -> u32 { self.height - self.y_offset }
+    pub fn tile_width(&self) -> u32 { self.tile_width }
+    pub fn tile_height(&self) -> u32 { self.tile_height }
+    pub fn components(&self) -> &[ComponentSiz] { &self.components }
+    pub fn tiles_x(&self) -> u64 {
+        ((self.width - self.tile_x_offset) as u64).div_ceil(self.tile_width as u64)
+    }
+    pub fn tiles_y(&self) -> u64 {
+        ((self.height - self.tile_y_offset) as u64).div_ceil(self.tile_height as u64)
+    }
+    pub fn num_tiles(&self) -> u64 { self.tiles_x() * self.tiles_y() }
+
+    // The precision of the components, if they all have the same.
+    pub fn bits_per_component(&self) -> Option<u8> {
+        let p = self.components[0].precision;
+        if self.components.iter().all(|c| c.precision == p) {
+            Some(p)
+        } else {
+            None
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.width <= self.x_offset || self.height <= self.y_offset {
+            return Err(format!(
+                "empty image area {}x{} at offset ({},{})",
+                self.width, self.height, self.x_offset, self.y_offset
+            ))
+        }
+        let (w, h) = (self.image_width(), self.image_height());
+        if w > MAX_DIMENSION || h > MAX_DIMENSION || w as u64 * h as u64 > MAX_PIXELS {
+            return Err(format!(
+                "image size {}x{} exceeds implementation limits",
+                w, h
+            ))
+        }
+        if self.tile_width == 0
+            || self.tile_height == 0
+            || self.tile_x_offset > self.x_offset
+            || self.tile_y_offset > self.y_offset
+            || self.tile_x_offset as u64 + self.tile_width as u64 <= self.x_offset as u64
+            || self.tile_y_offset as u64 + self.tile_height as u64 <= self.y_offset as u64
+        {
+            return Err(format!(
+                "invalid tiling {}x{} at offset ({},{})",
+                self.tile_width, self.tile_height, self.tile_x_offset, self.tile_y_offset
+            ))
+        }
+        if self.num_tiles() > MAX_TILES {
+            return Err(format!("{} tiles exceed the maximum", self.num_tiles()))
+        }
+        for (i, c) in self.components.iter().enumerate() {
+            if c.precision > 38 || c.dx == 0 || c.dy == 0 {
+                return Err(format!(
+                    "invalid precision {} or subsampling {}x{} for component {}",
+                    c.precision, c.dx, c.dy, i
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct SizP;
+
+impl ParsleyParser for SizP {
+    type T = LocatedVal<Siz>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.parse_siz(buf, start) {
+            Ok(siz) => Ok(LocatedVal::new(siz, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl SizP {
+    fn parse_siz(&mut self, buf: &mut dyn ParseBufferT, start: usize) -> ParseResult<Siz> {
+        let mut u16p = UInt16P::new(Endian::Big);
+        let mut u32p = UInt32P::new(Endian::Big);
+        if u16p.parse(buf)?.unwrap() != SIZ {
+            return guard_err(
+                "JPX codestream is missing SIZ".to_string(),
+                start,
+                start + 2,
+            )
+        }
+        let len = u16p.parse(buf)?.unwrap() as usize;
+        let capabilities = u16p.parse(buf)?.unwrap();
+        let width = u32p.parse(buf)?.unwrap();
+        let height = u32p.parse(buf)?.unwrap();
+        let x_offset = u32p.parse(buf)?.unwrap();
+        let y_offset = u32p.parse(buf)?.unwrap();
+        let tile_width = u32p.parse(buf)?.unwrap();
+        let tile_height = u32p.parse(buf)?.unwrap();
+        let tile_x_offset = u32p.parse(buf)?.unwrap();
+        let tile_y_offset = u32p.parse(buf)?.unwrap();
+        let count = u16p.parse(buf)?;
+        let n = *count.val() as usize;
+        if n == 0 || n > MAX_COMPONENTS {
+            let msg = format!("JPX SIZ has invalid component count {}", n);
+            return Err(count.place(ErrorKind::GuardError(msg)))
+        }
+        if len != 38 + 3 * n {
+            let msg = format!("JPX SIZ length {} does not match {} components", len, n);
+            return guard_err(msg, start, buf.get_cursor())
+        }
+        let mut components = Vec::new();
+        for _ in 0 .. n {
+            let ssiz = UInt8P.parse(buf)?.unwrap();
+            components.push(ComponentSiz {
+                precision: (ssiz & 0x7f) + 1,
+                signed:    ssiz & 0x80 != 0,
+                dx:        UInt8P.parse(buf)?.unwrap(),
+                dy:        UInt8P.parse(buf)?.unwrap(),
+            })
+        }
+        let siz = Siz {
+            capabilities,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            tile_width,
+            tile_height,
+            tile_x_offset,
+            tile_y_offset,
+            components,
+        };
+        if let Err(msg) = siz.validate() {
+            return guard_err(format!("JPX SIZ: {}", msg), start, buf.get_cursor())
+        }
+        Ok(siz)
+    }
+}
+
+// Coding style default (COD) marker segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cod {
+    style:       u8,
+    progression: u8,
+    layers:      u16,
+    mct:         u8,
+    levels:      u8,
+    cb_width:    u8,
+    cb_height:   u8,
+    cb_style:    u8,
+    transform:   u8,
+}
+
+impl Cod {
+    pub fn style(&self) -> u8 { self.style }
+    pub fn progression(&self) -> u8 { self.progression }
+    pub fn layers(&self) -> u16 { self.layers }
+    pub fn mct(&self) -> u8 { self.mct }
+    pub fn levels(&self) -> u8 { self.levels }
+    // code-block sizes as exponents of 2
+    pub fn cb_width(&self) -> u8 { self.cb_width }
+    pub fn cb_height(&self) -> u8 { self.cb_height }
+    pub fn cb_style(&self) -> u8 { self.cb_style }
+    pub fn transform(&self) -> u8 { self.transform }
+}
+
+pub struct CodP;
+
+impl ParsleyParser for CodP {
+    type T = LocatedVal<Cod>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.parse_cod(buf, start) {
+            Ok(cod) => Ok(LocatedVal::new(cod, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl CodP {
+    fn parse_cod(&mut self, buf: &mut dyn ParseBufferT, start: usize) -> ParseResult<Cod> {
+        let mut u16p = UInt16P::new(Endian::Big);
+        if u16p.parse(buf)?.unwrap() != COD {
+            return guard_err("expected JPX COD".to_string(), start, start + 2)
+        }
+        let len = u16p.parse(buf)?.unwrap() as usize;
+        let mut cod = Cod {
+            style:       UInt8P.parse(buf)?.unwrap(),
+            progression: UInt8P.parse(buf)?.unwrap(),
+            layers:      u16p.parse(buf)?.unwrap(),
+            mct:         UInt8P.parse(buf)?.unwrap(),
+            levels:      UInt8P.parse(buf)?.unwrap(),
+            cb_width:    UInt8P.parse(buf)?.unwrap(),
+            cb_height:   UInt8P.parse(buf)?.unwrap(),
+            cb_style:    UInt8P.parse(buf)?.unwrap(),
+            transform:   UInt8P.parse(buf)?.unwrap(),
+        };
+        let fail = |msg: String| guard_err(format!("JPX COD: {}", msg), start, start + 2 + len);
+        // The precinct sizes are present for user-defined precincts.
+        let precincts = if cod.style & 1 != 0 {
+            cod.levels as usize + 1
+        } else {
+            0
+        };
+        if len != 12 + precincts {
+            return fail(format!("invalid length {}", len))
+        }
+        if cod.style & !7 != 0
+            || cod.progression > 4
+            || cod.layers == 0
+            || cod.mct > 1
+            || cod.levels > 32
+            || cod.transform > 1
+        {
+            return fail("invalid coding parameters".to_string())
+        }
+        // The code-block size exponents are stored offset by 2, and
+        // are checked before the offset is added.
+        if cod.cb_width > 8 || cod.cb_height > 8 || cod.cb_width + cod.cb_height > 8 {
+            return fail(format!(
+                "invalid code-block size exponents {}, {}",
+                cod.cb_width, cod.cb_height
+            ))
+        }
+        cod.cb_width += 2;
+        cod.cb_height += 2;
+        skip(buf, precincts, start)?;
+        Ok(cod)
+    }
+}
+
+// Skips bytes within a marker segment starting at `start`.
+fn skip(buf: &mut dyn ParseBufferT, len: usize, start: usize) -> ParseResult<()> {
+    if len > buf.remaining() {
+        let msg = "JPX marker segment extends beyond the end of the data".to_string();
+        return guard_err(msg, start, buf.get_cursor() + buf.remaining())
+    }
+    buf.set_cursor_unsafe(buf.get_cursor() + len);
+    Ok(())
+}
+
+// Start of tile-part (SOT) marker segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TilePart {
+    tile:      u16,
+    // the length of the tile-part from the SOT marker, or 0 if it
+    // extends to the EOC marker
+    length:    u32,
+    part:      u8,
+    num_parts: u8,
+}
+
+impl TilePart {
+    pub fn tile(&self) -> u16 { self.tile }
+    pub fn length(&self) -> u32 { self.length }
+    pub fn part(&self) -> u8 { self.part }
+    pub fn num_parts(&self) -> u8 { self.num_parts }
+}
+
+pub struct SotP;
+
+impl ParsleyParser for SotP {
+    type T = LocatedVal<TilePart>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let mut u16p = UInt16P::new(Endian::Big);
+        let tp = (|| {
+            if u16p.parse(buf)?.unwrap() != SOT {
+                return guard_err("expected JPX SOT".to_string(), start, start + 2)
+            }
+            let len = u16p.parse(buf)?.unwrap();
+            if len != 10 {
+                let msg = format!("JPX SOT has invalid length {}", len);
+                return guard_err(msg, start, start + 4)
+            }
+            Ok(TilePart {
+                tile:      u16p.parse(buf)?.unwrap(),
+                length:    UInt32P::new(Endian::Big).parse(buf)?.unwrap(),
+                part:      UInt8P.parse(buf)?.unwrap(),
+                num_parts: UInt8P.parse(buf)?.unwrap(),
+            })
+        })();
+        match tp {
+            Ok(tp) => Ok(LocatedVal::new(tp, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Codestream {
+    siz:        LocatedVal<Siz>,
+    cod:        LocatedVal<Cod>,
+    tile_parts: Vec<LocatedVal<TilePart>>,
+}
+
+impl Codestream {
+    pub fn siz(&self) -> &LocatedVal<Siz> { &self.siz }
+    pub fn cod(&self) -> &LocatedVal<Cod> { &self.cod }
+    pub fn tile_parts(&self) -> &[LocatedVal<TilePart>] { &self.tile_parts }
+}
+
+// Parses a codestream that has to end at the `end` offset, or at the
+// end of the buffer.
+pub struct CodestreamP {
+    end: Option<usize>,
+}
+
+impl CodestreamP {
+    pub fn new() -> CodestreamP { CodestreamP { end: None } }
+    fn new_bounded(end: usize) -> CodestreamP { CodestreamP { end: Some(end) } }
+
+    // Parses a marker segment that is not interpreted, and returns
+    // its marker.
+    fn skip_segment(&self, buf: &mut dyn ParseBufferT) -> ParseResult<u16> {
+        let start = buf.get_cursor();
+        let mut u16p = UInt16P::new(Endian::Big);
+        let marker = u16p.parse(buf)?.unwrap();
+        let len = u16p.parse(buf)?;
+        if *len.val() < 2 {
+            let msg = format!("JPX marker {:04X} has invalid length {}", marker, len.val());
+            return Err(len.place(ErrorKind::GuardError(msg)))
+        }
+        skip(buf, *len.val() as usize - 2, start)?;
+        Ok(marker)
+    }
+
+    fn peek_marker(&self, buf: &mut dyn ParseBufferT) -> ParseResult<LocatedVal<u16>> {
+        let m = UInt16P::new(Endian::Big).parse(buf)?;
+        buf.set_cursor_unsafe(m.start());
+        Ok(m)
+    }
+
+    fn parse_codestream(
+        &mut self, buf: &mut dyn ParseBufferT, start: usize,
+    ) -> ParseResult<Codestream> {
+        let end = self.end.unwrap_or(buf.get_cursor() + buf.remaining());
+        if UInt16P::new(Endian::Big).parse(buf)?.unwrap() != SOC {
+            return guard_err(
+                "JPX codestream is missing SOC".to_string(),
+                start,
+                start + 2,
+            )
+        }
+        let siz = SizP.parse(buf)?;
+
+        // The main header.
+        let mut cod = None;
+        let mut qcd = false;
+        loop {
+            let m = self.peek_marker(buf)?;
+            match *m.val() {
+                COD if cod.is_some() => {
+                    return Err(m.place(ErrorKind::GuardError("duplicate JPX COD".to_string())))
+                },
+                COD => cod = Some(CodP.parse(buf)?),
+                QCD if qcd => {
+                    return Err(m.place(ErrorKind::GuardError("duplicate JPX QCD".to_string())))
+                },
+                QCD => {
+                    self.skip_segment(buf)?;
+                    qcd = true
+                },
+                CAP | COC | QCC | RGN | POC | PPM | TLM | PLM | CRG | COM => {
+                    self.skip_segment(buf)?;
+                },
+                SOT => break,
+                mk => {
+                    let msg = format!("unexpected JPX marker {:04X} in main header", mk);
+                    return Err(m.place(ErrorKind::GuardError(msg)))
+                },
+            }
+        }
+        let cod = match (cod, qcd) {
+            (Some(cod), true) => cod,
+            _ => {
+                let msg = "JPX main header is missing COD or QCD".to_string();
+                return guard_err(msg, start, buf.get_cursor())
+            },
+        };
+
+        // The tile-parts.
+        let num_tiles = siz.val().num_tiles() as usize;
+        let mut parts = vec![(0u8, 0u8); num_tiles];
+        let mut tile_parts = Vec::new();
+        loop {
+            let m = self.peek_marker(buf)?;
+            if *m.val() == EOC {
+                break
+            }
+            let sot = SotP.parse(buf)?;
+            let tp = sot.val();
+            let fail = |msg: String| guard_err(format!("JPX SOT: {}", msg), sot.start(), sot.end());
+            let tile = tp.tile as usize;
+            if tile >= num_tiles {
+                return fail(format!(
+                    "tile {} is not among the {} tiles",
+                    tile, num_tiles
+                ))
+            }
+            let (count, total) = parts[tile];
+            if tp.part != count || (tp.num_parts != 0 && tp.num_parts <= tp.part) {
+                return fail(format!("unexpected tile-part {} of tile {}", tp.part, tile))
+            }
+            if tp.num_parts != 0 && total != 0 && tp.num_parts != total {
+                return fail(format!("inconsistent tile-part count for tile {}", tile))
+            }
+            parts[tile] = (count + 1, tp.num_parts.max(total));
+            let part_end = if tp.length == 0 {
+                // This has to be the last tile-part.
+                if end < sot.end() + 4 {
+                    return fail("tile-part extends beyond the end of the data".to_string())
+                }
+                end - 2
+            } else {
+                let e = sot.start() + tp.length as usize;
+                if tp.length < 14 || e > end {
+                    return fail(format!("invalid tile-part length {}", tp.length))
+                }
+                e
+            };
+
+            // The tile-part header.
+            loop {
+                let m = self.peek_marker(buf)?;
+                match *m.val() {
+                    SOD => break,
+                    COD => {
+                        CodP.parse(buf)?;
+                    },
+                    COC | QCD | QCC | RGN | POC | PPT | PLT | COM => {
+                        self.skip_segment(buf)?;
+                    },
+                    mk => {
+                        let msg = format!("unexpected JPX marker {:04X} in tile-part header", mk);
+                        return Err(m.place(ErrorKind::GuardError(msg)))
+                    },
+                }
+                if buf.get_cursor() > part_end {
+                    return fail("tile-part header extends beyond the tile-part".to_string())
+                }
+            }
+            if buf.get_cursor() + 2 > part_end {
+                return fail("tile-part header extends beyond the tile-part".to_string())
+            }
+            buf.set_cursor_unsafe(part_end);
+            tile_parts.push(sot)
+        }
+        for (tile, (count, total)) in parts.iter().enumerate() {
+            if *count == 0 || (*total != 0 && count != total) {
+                let msg = format!("JPX tile {} has missing tile-parts", tile);
+                return guard_err(msg, start, buf.get_cursor())
+            }
+        }
+        // EOC
+        let eoc = UInt16P::new(Endian::Big).parse(buf)?;
+        if buf.get_cursor() != end {
+            let msg = "data follows the end of the JPX codestream".to_string();
+            return guard_err(msg, eoc.end(), end)
+        }
+        Ok(Codestream {
+            siz,
+            cod,
+            tile_parts,
+        })
+    }
+}
+
+impl Default for CodestreamP {
+    fn default() -> Self { Self::new() }
+}
+
+impl ParsleyParser for CodestreamP {
+    type T = LocatedVal<Codestream>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        match self.parse_codestream(buf, start) {
+            Ok(cs) => Ok(LocatedVal::new(cs, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+// A box of a JP2 file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JP2Box {
+    box_type:      [u8; 4],
+    content_start: usize,
+    content_end:   usize,
+}
+
+impl JP2Box {
+    pub fn box_type(&self) -> &[u8; 4] { &self.box_type }
+    pub fn content_start(&self) -> usize { self.content_start }
+    pub fn content_end(&self) -> usize { self.content_end }
+}
+
+// Parses a box header within data ending at `end`, leaving the cursor
+// at the start of the content.
+pub struct JP2BoxP {
+    end: usize,
+}
+
+impl JP2BoxP {
+    pub fn new(end: usize) -> JP2BoxP { JP2BoxP { end } }
+}
+
+impl ParsleyParser for JP2BoxP {
+    type T = LocatedVal<JP2Box>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let b = (|| {
+            let len = UInt32P::new(Endian::Big).parse(buf)?.unwrap() as u64;
+            let mut box_type = [0; 4];
+            box_type.copy_from_slice(&UInt32P::new(Endian::Big).parse(buf)?.unwrap().to_be_bytes());
+            let len = match len {
+                0 => (self.end - start) as u64,
+                1 => UInt64P::new(Endian::Big).parse(buf)?.unwrap(),
+                l => l,
+            };
+            let content_start = buf.get_cursor();
+            if len < (content_start - start) as u64 || len > (self.end - start) as u64 {
+                let msg = format!(
+                    "JPX box {} has invalid length {}",
+                    String::from_utf8_lossy(&box_type),
+                    len
+                );
+                return guard_err(msg, start, content_start)
+            }
+            Ok(JP2Box {
+                box_type,
+                content_start,
+                content_end: start + len as usize,
+            })
+        })();
+        match b {
+            Ok(b) => Ok(LocatedVal::new(b, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+// JPEG 2000 data: a codestream, with the image header if it is
+// within a JP2 file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JpxImage {
+    header:     Option<LocatedVal<ImageHeader>>,
+    codestream: LocatedVal<Codestream>,
+}
+
+impl JpxImage {
+    pub fn header(&self) -> Option<&LocatedVal<ImageHeader>> { self.header.as_ref() }
+    pub fn codestream(&self) -> &LocatedVal<Codestream> { &self.codestream }
+    pub fn width(&self) -> u32 { self.codestream.val().siz().val().image_width() }
+    pub fn height(&self) -> u32 { self.codestream.val().siz().val().image_height() }
+    pub fn num_components(&self) -> usize { self.codestream.val().siz().val().components().len() }
+
+    // Checks the image against the /Width, /Height and
+    // /BitsPerComponent entries of its image dictionary.
+    pub fn check_image_dict(&self, dict: &DictT) -> Result<(), String> {
+        let siz = self.codestream.val().siz().val();
+        for (key, val) in &[(&b"Width"[..], self.width()), (b"Height", self.height())] {
+            if let Some(v) = dict.get_usize(key) {
+                if v != *val as usize {
+                    return Err(format!(
+                        "/{} {} does not match the image {} {}",
+                        String::from_utf8_lossy(key),
+                        v,
+                        String::from_utf8_lossy(key).to_lowercase(),
+                        val
+                    ))
+                }
+            }
+        }
+        if let Some(bpc) = dict.get_usize(b"BitsPerComponent") {
+            if siz.bits_per_component().is_none_or(|p| p as usize != bpc) {
+                return Err(format!(
+                    "/BitsPerComponent {} does not match the component precisions",
+                    bpc
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct JpxImageP;
+
+impl ParsleyParser for JpxImageP {
+    type T = LocatedVal<JpxImage>;
+
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        let img = if buf.buf().starts_with(JP2_SIGNATURE) {
+            self.parse_jp2(buf, start)
+        } else {
+            CodestreamP::new().parse(buf).map(|codestream| JpxImage {
+                header: None,
+                codestream,
+            })
+        };
+        match img {
+            Ok(img) => Ok(LocatedVal::new(img, start, buf.get_cursor())),
+            Err(e) => {
+                buf.set_cursor_unsafe(start);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl JpxImageP {
+    fn parse_jp2(&mut self, buf: &mut dyn ParseBufferT, start: usize) -> ParseResult<JpxImage> {
+        let end = buf.get_cursor() + buf.remaining();
+        buf.set_cursor_unsafe(start + JP2_SIGNATURE.len());
+        let ftyp = JP2BoxP::new(end).parse(buf)?;
+        if ftyp.val().box_type() != b"ftyp"
+            || ftyp.val().content_end() - ftyp.val().content_start() < 8
+        {
+            let msg = "JP2 signature is not followed by a file type box".to_string();
+            return Err(ftyp.place(ErrorKind::GuardError(msg)))
+        }
+        buf.set_cursor_unsafe(ftyp.val().content_end());
+
+        let mut header = None;
+        let mut codestream = None;
+        while buf.get_cursor() < end {
+            let b = JP2BoxP::new(end).parse(buf)?;
+            let content_end = b.val().content_end();
+            match b.val().box_type() {
+                b"jp2h" if header.is_some() => {
+                    let msg = "duplicate JP2 header box".to_string();
+                    return Err(b.place(ErrorKind::GuardError(msg)))
+                },
+                b"jp2h" => header = Some(self.parse_jp2h(buf, content_end)?),
+                // Only the first codestream is used.
+                b"jp2c" if codestream.is_none() => {
+                    if header.is_none() {
+                        let msg = "JP2 codestream precedes the header box".to_string();
+                        return Err(b.place(ErrorKind::GuardError(msg)))
+                    }
+                    codestream = Some(CodestreamP::new_bounded(content_end).parse(buf)?)
+                },
+                _ => (),
+            }
+            buf.set_cursor_unsafe(content_end)
+        }
+        let (header, codestream) = match (header, codestream) {
+            (Some(h), Some(c)) => (h, c),
+            _ => {
+                let msg = "JP2 file is missing a header or codestream box".to_string();
+                return guard_err(msg, start, end)
+            },
+        };
+
+        // The image header has to agree with the codestream.
+        let h = header.val();
+        let siz = codestream.val().siz().val();
+        if h.width != siz.image_width()
+            || h.height != siz.image_height()
+            || h.components as usize != siz.components().len()
+            || (h.bits_per_component().is_some()
+                && h.bits_per_component() != siz.bits_per_component())
+        {
+            let msg = "JP2 image header does not match the codestream".to_string();
+            return Err(header.place(ErrorKind::GuardError(msg)))
+        }
+        Ok(JpxImage {
+            header: Some(header),
+            codestream,
+        })
+    }
+
+    // Parses the header superbox, which has to begin with the image
+    // header box.
+    fn parse_jp2h(
+        &mut self, buf: &mut dyn ParseBufferT, end: usize,
+    ) -> ParseResult<LocatedVal<ImageHeader>> {
+        let b = JP2BoxP::new(end).parse(buf)?;
+        if b.val().box_type() != b"ihdr" || b.val().content_end() - b.val().content_start() != 14 {
+            let msg = "JP2 header box does not begin with an image header box".to_string();
+            return Err(b.place(ErrorKind::GuardError(msg)))
+        }
+        let mut u32p = UInt32P::new(Endian::Big);
+        let height = u32p.parse(buf)?.unwrap();
+        let width = u32p.parse(buf)?.unwrap();
+        let components = UInt16P::new(Endian::Big).parse(buf)?.unwrap();
+        let bpc = UInt8P.parse(buf)?.unwrap();
+        let compression = UInt8P.parse(buf)?.unwrap();
+        if compression != 7 {
+            let msg = format!(
+                "JP2 image header has invalid compression type {}",
+                compression
+            );
+            return guard_err(msg, b.start(), b.val().content_end())
+        }
+        let ihdr = ImageHeader {
+            height,
+            width,
+            components,
+            bpc,
+        };
+        Ok(LocatedVal::new(ihdr, b.start(), b.val().content_end()))
+    }
+}
+
+#[cfg(test)]
+mod test_jpx {
+    use super::{CodestreamP, JpxImageP};
+    use crate::pcore::parsebuffer::{ErrorKind, ParseBuffer, ParsleyParser};
+    use crate::pdf_lib::pdf_obj::{DictP, PDFObjContext};
+
+    // Builds a codestream for a `width`x`height` image with 8-bit
+    // components and the given tiles, each with a single tile-part.
+    fn codestream(width: u32, height: u32, comps: u16, tile: u32, tiles: u16) -> Vec<u8> {
+        let mut v = vec![0xff, 0x4f, 0xff, 0x51];
+        v.extend_from_slice(&(38 + 3 * comps).to_be_bytes());
+        v.extend_from_slice(&[0, 0]);
+        for i in &[width, height, 0, 0, tile, tile, 0, 0] {
+            v.extend_from_slice(&i.to_be_bytes())
+        }
+        v.extend_from_slice(&comps.to_be_bytes());
+        for _ in 0 .. comps {
+            v.extend_from_slice(&[7, 1, 1])
+        }
+        // COD and QCD
+        v.extend_from_slice(&[0xff, 0x52, 0, 12, 0, 0, 0, 1, 0, 5, 4, 4, 0, 0]);
+        v.extend_from_slice(&[0xff, 0x5c, 0, 4, 0x40, 0x48]);
+        for t in 0 .. tiles {
+            v.extend_from_slice(&[0xff, 0x90, 0, 10]);
+            v.extend_from_slice(&t.to_be_bytes());
+            v.extend_from_slice(&18u32.to_be_bytes());
+            v.extend_from_slice(&[0, 1, 0xff, 0x93, 1, 2, 3, 4]);
+        }
+        v.extend_from_slice(&[0xff, 0xd9]);
+        v
+    }
+
+    fn jp2(cs: &[u8], width: u32, height: u32, comps: u16) -> Vec<u8> {
+        let mut v = b"\x00\x00\x00\x0cjP  \x0d\x0a\x87\x0a".to_vec();
+        v.extend_from_slice(b"\x00\x00\x00\x14ftypjp2 \x00\x00\x00\x00jp2 ");
+        v.extend_from_slice(b"\x00\x00\x00\x2djp2h\x00\x00\x00\x16ihdr");
+        v.extend_from_slice(&height.to_be_bytes());
+        v.extend_from_slice(&width.to_be_bytes());
+        v.extend_from_slice(&comps.to_be_bytes());
+        v.extend_from_slice(&[7, 7, 0, 0]);
+        v.extend_from_slice(b"\x00\x00\x00\x0fcolr\x01\x00\x00\x00\x00\x00\x10");
+        v.extend_from_slice(&(cs.len() as u32 + 8).to_be_bytes());
+        v.extend_from_slice(b"jp2c");
+        v.extend_from_slice(cs);
+        v
+    }
+
+    fn check(v: Vec<u8>) -> Result<(u32, u32, usize), (String, usize)> {
+        let mut pb = ParseBuffer::new(v);
+        match JpxImageP.parse(&mut pb) {
+            Ok(img) => {
+                let img = img.val();
+                Ok((img.width(), img.height(), img.num_components()))
+            },
+            Err(e) => match e.val() {
+                ErrorKind::GuardError(m) => Err((m.clone(), e.start())),
+                k => Err((format!("{:?}", k), e.start())),
+            },
+        }
+    }
+
+    #[test]
+    fn test_codestream() {
+        assert_eq!(check(codestream(64, 48, 3, 32, 4)), Ok((64, 48, 3)));
+        let mut pb = ParseBuffer::new(codestream(64, 48, 1, 64, 1));
+        let cs = CodestreamP::new().parse(&mut pb).unwrap();
+        let cs = cs.val();
+        assert_eq!(cs.siz().val().bits_per_component(), Some(8));
+        assert_eq!(cs.cod().val().layers(), 1);
+        assert_eq!(cs.tile_parts().len(), 1);
+        assert_eq!(cs.tile_parts()[0].start(), 65);
+
+        // a missing tile
+        assert!(check(codestream(64, 48, 3, 32, 3)).is_err());
+        // an image that is too large
+        assert!(check(codestream(1 << 20, 48, 3, 1 << 20, 1)).is_err());
+        // an invalid component count
+        assert!(check(codestream(64, 48, 0, 64, 1)).is_err());
+        // too many tiles
+        assert!(check(codestream(1 << 16, 1 << 14, 1, 1, 1)).is_err());
+        // trailing data
+        let mut v = codestream(64, 48, 1, 64, 1);
+        v.push(0);
+        assert_eq!(check(v).unwrap_err().1, 85);
+        // a tile-part that is too long
+        let mut v = codestream(64, 48, 1, 64, 1);
+        v[71] = 0xff;
+        assert_eq!(check(v).unwrap_err().1, 65);
+        // missing QCD
+        let mut v = codestream(64, 48, 1, 64, 1);
+        v.drain(59 .. 65);
+        assert!(check(v).is_err());
+        // an unexpected marker
+        let mut v = codestream(64, 48, 1, 64, 1);
+        v[60] = 0x93;
+        assert!(check(v).is_err());
+        // code-block size exponents that overflow when offset
+        let mut v = codestream(64, 48, 1, 64, 1);
+        v[55] = 0xfe;
+        let msg = "JPX COD: invalid code-block size exponents 254, 4".to_string();
+        assert_eq!(check(v), Err((msg, 45)));
+    }
+
+    #[test]
+    fn test_jp2() {
+        let cs = codestream(64, 48, 3, 64, 1);
+        assert_eq!(check(jp2(&cs, 64, 48, 3)), Ok((64, 48, 3)));
+        // mismatched header
+        assert!(check(jp2(&cs, 64, 40, 3)).is_err());
+        assert!(check(jp2(&cs, 64, 48, 1)).is_err());
+        // truncated codestream box
+        let mut v = jp2(&cs, 64, 48, 3);
+        v.truncate(v.len() - 1);
+        assert!(check(v).is_err());
+        // missing file type box
+        let mut v = jp2(&cs, 64, 48, 3);
+        v.drain(12 .. 32);
+        assert!(check(v).is_err());
+    }
+
+    #[test]
+    fn test_image_dict() {
+        let mut pb = ParseBuffer::new(codestream(64, 48, 3, 64, 1));
+        let img = JpxImageP.parse(&mut pb).unwrap();
+        let dict = |s: &str| {
+            let mut ctxt = PDFObjContext::new(10);
+            let mut pb = ParseBuffer::new(Vec::from(s.as_bytes()));
+            DictP::new(&mut ctxt).parse(&mut pb).unwrap()
+        };
+        let d = dict("<</Width 64 /Height 48 /BitsPerComponent 8>>");
+        assert!(img.val().check_image_dict(&d).is_ok());
+        let d = dict("<</Width 64 /Height 48>>");
+        assert!(img.val().check_image_dict(&d).is_ok());
+        let d = dict("<</Width 64 /Height 64>>");
+        assert!(img.val().check_image_dict(&d).is_err());
+        let d = dict("<</Width 64 /Height 48 /BitsPerComponent 16>>");
+        assert!(img.val().check_image_dict(&d).is_err());
+    }
+}
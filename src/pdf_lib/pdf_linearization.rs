@@ -0,0 +1,1107 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Validation of linearized files (Annex F).  A linearized file starts
+// with the linearization parameter dictionary, followed by the xref
+// section for the first page, whose trailer points via /Prev to the
+// main xref section at the end of the file.  The primary hint stream
+// has the page offset and shared object hint tables.  The parameters
+// and the hint tables are checked against the layout of the file
+// given by its xref, and the first-page and main xref sections are
+// checked to agree, since a reader using the linearized view could
+// otherwise see a different document from one using the full xref.
+// All offsets are relative to the PDF header.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use super::super::pcore::parsebuffer::{ParseBuffer, ParseBufferT, ParsleyParser};
+use super::pdf_file::HeaderP;
+use super::pdf_filters::DecodeBudget;
+use super::pdf_obj::{DictT, IndirectP, ObjectId, PDFObjContext, PDFObjT};
+use super::pdf_prim::WhitespaceEOL;
+use super::pdf_streams::{decode_stream_with_budget, XrefEntStatus};
+use super::pdf_traverse_xref::{parse_data_with_revisions, ParseOptions, PdfParseResult, Revision};
+
+// The linearization parameter dictionary.
+#[derive(Debug, PartialEq)]
+pub struct LinearizationParams {
+    id:             ObjectId,
+    offset:         usize,
+    // /L
+    file_length:    usize,
+    // /H: the offset and length of the primary hint stream, and of
+    // the overflow hint stream if any
+    hints:          Vec<(usize, usize)>,
+    // /O
+    first_page_obj: usize,
+    // /E
+    first_page_end: usize,
+    // /N
+    num_pages:      usize,
+    // /T
+    main_xref:      usize,
+    // /P
+    first_page:     usize,
+}
+
+impl LinearizationParams {
+    pub fn id(&self) -> ObjectId { self.id }
+    pub fn offset(&self) -> usize { self.offset }
+    pub fn file_length(&self) -> usize { self.file_length }
+    pub fn hints(&self) -> &[(usize, usize)] { &self.hints }
+    pub fn first_page_obj(&self) -> usize { self.first_page_obj }
+    pub fn first_page_end(&self) -> usize { self.first_page_end }
+    pub fn num_pages(&self) -> usize { self.num_pages }
+    pub fn main_xref(&self) -> usize { self.main_xref }
+    pub fn first_page(&self) -> usize { self.first_page }
+
+    // Converts an offset in a hint table, which does not count the
+    // primary hint stream, into a file offset.  Returns None if the
+    // file offset is too large.
+    pub fn hint_offset(&self, ofs: usize) -> Option<usize> {
+        match self.hints.first() {
+            Some((h, len)) if ofs >= *h => ofs.checked_add(*len),
+            _ => Some(ofs),
+        }
+    }
+
+    fn from_dict(id: ObjectId, offset: usize, d: &DictT) -> Result<LinearizationParams, String> {
+        let get = |k: &[u8]| match d.get_usize(k) {
+            Some(v) => Ok(v),
+            None => Err(format!(
+                "missing or invalid /{} in linearization dictionary",
+                String::from_utf8_lossy(k)
+            )),
+        };
+        let hints = match d.get_array(b"H") {
+            Some(a) if a.objs().len() == 2 || a.objs().len() == 4 => {
+                let mut v = Vec::new();
+                for o in a.objs() {
+                    match o.val() {
+                        PDFObjT::Integer(i) if i.is_usize() => v.push(i.usize_val()),
+                        _ => return Err("invalid entry in /H of linearization dictionary".into()),
+                    }
+                }
+                v.chunks(2).map(|c| (c[0], c[1])).collect()
+            },
+            _ => return Err("missing or invalid /H in linearization dictionary".to_string()),
+        };
+        Ok(LinearizationParams {
+            id,
+            offset,
+            file_length: get(b"L")?,
+            hints,
+            first_page_obj: get(b"O")?,
+            first_page_end: get(b"E")?,
+            num_pages: get(b"N")?,
+            main_xref: get(b"T")?,
+            first_page: d.get_usize(b"P").unwrap_or(0),
+        })
+    }
+}
+
+// Reads the big-endian bit-packed values of a hint table.
+struct HintReader<'a> {
+    data: &'a [u8],
+    pos:  usize, // in bits
+}
+
+impl HintReader<'_> {
+    fn read(&mut self, n: usize) -> Result<usize, String> {
+        if self.pos + n > self.data.len() * 8 {
+            return Err("hint table is truncated".to_string())
+        }
+        let mut v = 0usize;
+        for _ in 0 .. n {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            v = v.checked_mul(2).ok_or("hint table value is too large")? | bit as usize;
+            self.pos += 1
+        }
+        Ok(v)
+    }
+
+    // Reads a value of n bits for each of the given number of items,
+    // with padding to a byte boundary at the end.
+    fn read_items(&mut self, count: usize, n: usize) -> Result<Vec<usize>, String> {
+        let mut v = Vec::new();
+        for _ in 0 .. count {
+            v.push(self.read(n)?)
+        }
+        self.align();
+        Ok(v)
+    }
+
+    fn align(&mut self) { self.pos = self.pos.div_ceil(8) * 8 }
+}
+
+// Adds the delta of a hint table entry to the minimum value.
+fn add_delta(min: usize, delta: usize) -> Result<usize, String> {
+    min.checked_add(delta)
+        .ok_or_else(|| "hint table value is too large".to_string())
+}
+
+// An entry of the page offset hint table.
+#[derive(Debug, PartialEq)]
+pub struct PageHint {
+    objects:        usize,
+    length:         usize,
+    shared:         Vec<usize>,
+    numerators:     Vec<usize>,
+    content_offset: usize,
+    content_length: usize,
+}
+
+impl PageHint {
+    pub fn objects(&self) -> usize { self.objects }
+    pub fn length(&self) -> usize { self.length }
+    // The shared object groups referenced by the page.
+    pub fn shared(&self) -> &[usize] { &self.shared }
+    pub fn numerators(&self) -> &[usize] { &self.numerators }
+    pub fn content_offset(&self) -> usize { self.content_offset }
+    pub fn content_length(&self) -> usize { self.content_length }
+}
+
+// The page offset hint table (Tables F.3 and F.4), with the values of
+// the per-page entries resolved from their deltas.
+#[derive(Debug, PartialEq)]
+pub struct PageOffsetHints {
+    first_page_offset: usize,
+    denominator:       usize,
+    pages:             Vec<PageHint>,
+}
+
+impl PageOffsetHints {
+    pub fn first_page_offset(&self) -> usize { self.first_page_offset }
+    pub fn denominator(&self) -> usize { self.denominator }
+    pub fn pages(&self) -> &[PageHint] { &self.pages }
+
+    // The entries can be zero bits wide, so the caller bounds the
+    // number of pages, and the shared object groups bound the shared
+    // references of each page.
+    fn parse(data: &[u8], num_pages: usize, num_groups: usize) -> Result<PageOffsetHints, String> {
+        let mut r = HintReader { data, pos: 0 };
+        let min_objs = r.read(32)?;
+        let first_page_offset = r.read(32)?;
+        let objs_bits = r.read(16)?;
+        let min_len = r.read(32)?;
+        let len_bits = r.read(16)?;
+        let min_cs_ofs = r.read(32)?;
+        let cs_ofs_bits = r.read(16)?;
+        let min_cs_len = r.read(32)?;
+        let cs_len_bits = r.read(16)?;
+        let nshared_bits = r.read(16)?;
+        let shared_id_bits = r.read(16)?;
+        let numerator_bits = r.read(16)?;
+        let denominator = r.read(16)?;
+
+        let objs = r.read_items(num_pages, objs_bits)?;
+        let lens = r.read_items(num_pages, len_bits)?;
+        let nshared = r.read_items(num_pages, nshared_bits)?;
+        if let Some(n) = nshared.iter().find(|n| **n > num_groups) {
+            return Err(format!(
+                "a page references {} shared object groups, but there are {}",
+                n, num_groups
+            ))
+        }
+        let mut shared = Vec::new();
+        for n in &nshared {
+            shared.push(r.read_items(*n, shared_id_bits)?)
+        }
+        let mut numerators = Vec::new();
+        for n in &nshared {
+            numerators.push(r.read_items(*n, numerator_bits)?)
+        }
+        let cs_ofs = r.read_items(num_pages, cs_ofs_bits)?;
+        let cs_lens = r.read_items(num_pages, cs_len_bits)?;
+
+        let mut pages = Vec::new();
+        let entries = shared.into_iter().zip(numerators);
+        for (i, (shared, numerators)) in entries.enumerate() {
+            pages.push(PageHint {
+                objects: add_delta(min_objs, objs[i])?,
+                length: add_delta(min_len, lens[i])?,
+                shared,
+                numerators,
+                content_offset: add_delta(min_cs_ofs, cs_ofs[i])?,
+                content_length: add_delta(min_cs_len, cs_lens[i])?,
+            })
+        }
+        Ok(PageOffsetHints {
+            first_page_offset,
+            denominator,
+            pages,
+        })
+    }
+}
+
+// A group of the shared object hint table.
+#[derive(Debug, PartialEq)]
+pub struct SharedGroup {
+    length:    usize,
+    signature: Option<Vec<u8>>,
+    objects:   usize,
+}
+
+impl SharedGroup {
+    pub fn length(&self) -> usize { self.length }
+    pub fn signature(&self) -> Option<&[u8]> { self.signature.as_deref() }
+    pub fn objects(&self) -> usize { self.objects }
+}
+
+// The shared object hint table (Tables F.5 and F.6).
+#[derive(Debug, PartialEq)]
+pub struct SharedObjectHints {
+    first_obj:          usize,
+    first_offset:       usize,
+    first_page_entries: usize,
+    groups:             Vec<SharedGroup>,
+}
+
+impl SharedObjectHints {
+    pub fn first_obj(&self) -> usize { self.first_obj }
+    pub fn first_offset(&self) -> usize { self.first_offset }
+    pub fn first_page_entries(&self) -> usize { self.first_page_entries }
+    pub fn groups(&self) -> &[SharedGroup] { &self.groups }
+
+    fn parse(data: &[u8]) -> Result<SharedObjectHints, String> {
+        let mut r = HintReader { data, pos: 0 };
+        let first_obj = r.read(32)?;
+        let first_offset = r.read(32)?;
+        let first_page_entries = r.read(32)?;
+        let num_entries = r.read(32)?;
+        let objs_bits = r.read(16)?;
+        let min_len = r.read(32)?;
+        let len_bits = r.read(16)?;
+        if num_entries < first_page_entries {
+            return Err(format!(
+                "shared object hint table has {} entries, fewer than the {} for the first page",
+                num_entries, first_page_entries
+            ))
+        }
+        // Each entry takes at least a bit, which bounds the count.
+        if num_entries > data.len() * 8 {
+            return Err("hint table is truncated".to_string())
+        }
+        let lens = r.read_items(num_entries, len_bits)?;
+        let flags = r.read_items(num_entries, 1)?;
+        let mut sigs = Vec::new();
+        for f in &flags {
+            if *f == 1 {
+                let mut sig = Vec::new();
+                for _ in 0 .. 16 {
+                    sig.push(r.read(8)? as u8)
+                }
+                sigs.push(Some(sig))
+            } else {
+                sigs.push(None)
+            }
+        }
+        let objs = r.read_items(num_entries, objs_bits)?;
+        let mut groups = Vec::new();
+        for (i, signature) in sigs.into_iter().enumerate() {
+            groups.push(SharedGroup {
+                length: add_delta(min_len, lens[i])?,
+                signature,
+                objects: add_delta(1, objs[i])?,
+            })
+        }
+        Ok(SharedObjectHints {
+            first_obj,
+            first_offset,
+            first_page_entries,
+            groups,
+        })
+    }
+}
+
+// The result of checking a linearized file.
+#[derive(Debug)]
+pub struct LinearizationReport {
+    params:       Option<LinearizationParams>,
+    page_hints:   Option<PageOffsetHints>,
+    shared_hints: Option<SharedObjectHints>,
+    errors:       Vec<String>,
+}
+
+impl LinearizationReport {
+    pub fn params(&self) -> Option<&LinearizationParams> { self.params.as_ref() }
+    pub fn page_hints(&self) -> Option<&PageOffsetHints> { self.page_hints.as_ref() }
+    pub fn shared_hints(&self) -> Option<&SharedObjectHints> { self.shared_hints.as_ref() }
+    pub fn errors(&self) -> &[String] { &self.errors }
+    pub fn is_valid(&self) -> bool { self.errors.is_empty() }
+}
+
+// The page objects in order, from a depth-first traversal of the page
+// tree.
+fn page_objects(ctxt: &PDFObjContext, root: ObjectId) -> Vec<ObjectId> {
+    let mut pages = Vec::new();
+    let catalog = match ctxt.lookup_obj(root).map(|o| o.val()) {
+        Some(PDFObjT::Dict(d)) => d,
+        _ => return pages,
+    };
+    let mut visited = BTreeSet::new();
+    let mut queue = match catalog.get_ref(b"Pages") {
+        Some(r) => vec![r.id()],
+        None => return pages,
+    };
+    while let Some(id) = queue.pop() {
+        if !visited.insert(id) {
+            continue
+        }
+        let d = match ctxt.lookup_obj(id).map(|o| o.val()) {
+            Some(PDFObjT::Dict(d)) => d,
+            _ => continue,
+        };
+        match d.get_resolved_array(ctxt, b"Kids") {
+            Some(kids) if d.get_name(b"Type") != Some(b"Page") => {
+                for k in kids.objs().iter().rev() {
+                    if let PDFObjT::Reference(r) = k.val() {
+                        queue.push(r.id())
+                    }
+                }
+            },
+            _ => pages.push(id),
+        }
+    }
+    pages
+}
+
+// The file offsets of the objects in use in a revision, by object
+// number.
+fn offsets(rev: &Revision) -> BTreeMap<usize, (usize, usize)> {
+    let mut ofs = BTreeMap::new();
+    for e in rev.ents() {
+        let e = e.val();
+        if let XrefEntStatus::InUse { file_ofs } = e.status() {
+            ofs.insert(e.obj(), (e.gen(), *file_ofs));
+        }
+    }
+    ofs
+}
+
+fn is_xref_entry(b: &[u8]) -> bool {
+    b.len() >= 18
+        && b[.. 10].iter().all(|c| c.is_ascii_digit())
+        && b[10] == b' '
+        && b[11 .. 16].iter().all(|c| c.is_ascii_digit())
+        && b[16] == b' '
+        && (b[17] == b'n' || b[17] == b'f')
+}
+
+// Parses the first object of the file, which is the linearization
+// dictionary if the file is linearized.
+fn first_object(data: &[u8]) -> Option<(ObjectId, usize, DictT)> {
+    let mut pb = ParseBuffer::new(data.to_vec());
+    HeaderP.parse(&mut pb).ok()?;
+    WhitespaceEOL::new(true).parse(&mut pb).ok()?;
+    let offset = pb.get_cursor();
+    let mut ctxt = PDFObjContext::new(50);
+    let io = IndirectP::new(&mut ctxt).parse(&mut pb).ok()?.unwrap();
+    let id = (io.num(), io.gen());
+    match io.obj().val() {
+        PDFObjT::Dict(d) if d.get(b"Linearized").is_some() => {
+            Some((id, offset, DictT::new(d.map().clone())))
+        },
+        _ => None,
+    }
+}
+
+struct Checker<'a> {
+    data:   &'a [u8],
+    params: &'a LinearizationParams,
+    // the decode budget of the document, for the hint stream
+    budget: &'a DecodeBudget,
+    errors: Vec<String>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, msg: String) { self.errors.push(msg) }
+
+    // Checks /O, /E and /N against the page tree and the first-page
+    // xref section.
+    fn check_pages(&mut self, pages: &[ObjectId], first: &BTreeMap<usize, (usize, usize)>) {
+        let p = self.params;
+        if p.num_pages != pages.len() {
+            self.error(format!(
+                "/N is {} but the page tree has {} pages",
+                p.num_pages,
+                pages.len()
+            ))
+        }
+        if pages.get(p.first_page).map(|id| id.0) != Some(p.first_page_obj) {
+            self.error(format!(
+                "/O is {} but the first page is {:?}",
+                p.first_page_obj,
+                pages.get(p.first_page)
+            ))
+        }
+        if p.first_page_end > p.file_length {
+            self.error(format!(
+                "/E {} is beyond the file length {}",
+                p.first_page_end, p.file_length
+            ))
+        }
+        for (num, (_, ofs)) in first {
+            if *ofs >= p.first_page_end {
+                self.error(format!(
+                    "object {} of the first-page xref is at {}, after /E {}",
+                    num, ofs, p.first_page_end
+                ))
+            }
+        }
+    }
+
+    // Checks /T against the main xref section.
+    fn check_main_xref(&mut self, main_ofs: usize) {
+        let t = self.params.main_xref;
+        if self.data[main_ofs ..].starts_with(b"xref") {
+            // /T is the whitespace before the first entry of the table.
+            let entry = self.data.get(t + 1 ..).unwrap_or(&[]);
+            let ok = t > main_ofs
+                && t < main_ofs + 32
+                && self.data.get(t).is_some_and(|b| b" \r\n".contains(b))
+                && is_xref_entry(entry);
+            if !ok {
+                self.error(format!(
+                    "/T {} is not before the first entry of the main xref at {}",
+                    t, main_ofs
+                ))
+            }
+        } else if t != main_ofs {
+            self.error(format!(
+                "/T {} is not the main xref stream at {}",
+                t, main_ofs
+            ))
+        }
+    }
+
+    // Parses the primary hint stream, and returns its page offset and
+    // shared object hint tables.  The page offset table is read for at
+    // most the number of pages in the page tree.
+    fn hint_tables(
+        &mut self, tree_pages: usize,
+    ) -> Option<(PageOffsetHints, Option<SharedObjectHints>)> {
+        let p = self.params;
+        let (h_ofs, h_len) = p.hints[0];
+        let mut pb = ParseBuffer::new(self.data.to_vec());
+        let mut ctxt = PDFObjContext::new(50);
+        if !pb.check_cursor(h_ofs) {
+            self.error(format!("hint stream offset {} is out of bounds", h_ofs));
+            return None
+        }
+        pb.set_cursor_unsafe(h_ofs);
+        let io = match IndirectP::new(&mut ctxt).parse(&mut pb) {
+            Ok(io) => io,
+            Err(e) => {
+                self.error(format!(
+                    "cannot parse hint stream at {}: {}",
+                    h_ofs,
+                    e.val()
+                ));
+                return None
+            },
+        };
+        // The length covers the object and its end-of-line.
+        let end = io.end();
+        let eol = self.data[end ..]
+            .iter()
+            .take(2)
+            .take_while(|b| b"\r\n".contains(b))
+            .count();
+        if h_ofs + h_len < end || h_ofs + h_len > end + eol {
+            self.error(format!(
+                "hint stream at {} ends at {}, not at {}",
+                h_ofs,
+                end,
+                h_ofs + h_len
+            ))
+        }
+        let s = match io.val().obj().val() {
+            PDFObjT::Stream(s) => s,
+            _ => {
+                self.error(format!("hint stream at {} is not a stream", h_ofs));
+                return None
+            },
+        };
+        let content = match decode_stream_with_budget(s, self.budget) {
+            Ok(d) => d.content().to_vec(),
+            Err(e) => {
+                self.error(format!("cannot decode hint stream: {}", e.val()));
+                return None
+            },
+        };
+        let shared = match s.dict().val().get_usize(b"S") {
+            Some(ofs) if ofs <= content.len() => match SharedObjectHints::parse(&content[ofs ..]) {
+                Ok(h) => Some(h),
+                Err(e) => {
+                    self.error(format!("shared object hint table: {}", e));
+                    None
+                },
+            },
+            _ => {
+                self.error("missing or invalid /S in hint stream".to_string());
+                None
+            },
+        };
+        if p.num_pages > tree_pages {
+            self.error(format!(
+                "page offset hint table: /N is {}, but the page tree has {} pages",
+                p.num_pages, tree_pages
+            ));
+            return None
+        }
+        let num_groups = shared.as_ref().map_or(0, |s| s.groups.len());
+        let page_hints = match PageOffsetHints::parse(&content, p.num_pages, num_groups) {
+            Ok(h) => h,
+            Err(e) => {
+                self.error(format!("page offset hint table: {}", e));
+                return None
+            },
+        };
+        Some((page_hints, shared))
+    }
+
+    // Checks the page offset hint table against the locations of the
+    // page objects.
+    fn check_page_hints(
+        &mut self, hints: &PageOffsetHints, pages: &[ObjectId],
+        ofs: &BTreeMap<usize, (usize, usize)>,
+    ) {
+        let p = self.params;
+        let mut start = hints.first_page_offset;
+        for (i, (h, id)) in hints.pages.iter().zip(pages).enumerate() {
+            let range = start
+                .checked_add(h.length)
+                .and_then(|end| Some((end, p.hint_offset(start)?, p.hint_offset(end)?)));
+            let (page_start, page_end) = match range {
+                Some((end, page_start, page_end)) => {
+                    start = end;
+                    (page_start, page_end)
+                },
+                _ => {
+                    self.error(format!("page {} ends beyond the largest file offset", i));
+                    return
+                },
+            };
+            match ofs.get(&id.0) {
+                Some((_, o)) if *o == page_start => (),
+                o => self.error(format!(
+                    "page {} starts at {} in the hint table, but its object {} is at {:?}",
+                    i,
+                    page_start,
+                    id.0,
+                    o.map(|o| o.1)
+                )),
+            }
+            if i == 0 && page_end != p.first_page_end {
+                self.error(format!(
+                    "the first page ends at {} in the hint table, but /E is {}",
+                    page_end, p.first_page_end
+                ))
+            }
+            // The objects of the other pages are numbered consecutively
+            // from the page object.
+            if i > 0 && h.objects > ofs.len() {
+                self.error(format!(
+                    "page {} has {} objects in the hint table, but the xref has {}",
+                    i,
+                    h.objects,
+                    ofs.len()
+                ))
+            } else if i > 0 {
+                let mut outside = (id.0 ..).take(h.objects).filter(|num| {
+                    !matches!(ofs.get(num), Some((_, o)) if *o >= page_start && *o < page_end)
+                });
+                if let Some(num) = outside.next() {
+                    self.error(format!(
+                        "{} objects of page {} are not within {}..{}, the first is {}",
+                        outside.count() + 1,
+                        i,
+                        page_start,
+                        page_end,
+                        num
+                    ))
+                }
+            }
+        }
+    }
+
+    fn check_shared_hints(
+        &mut self, hints: &SharedObjectHints, ofs: &BTreeMap<usize, (usize, usize)>,
+    ) {
+        if hints.groups.len() <= hints.first_page_entries {
+            return
+        }
+        let expected = match self.params.hint_offset(hints.first_offset) {
+            Some(e) => e,
+            None => {
+                self.error("the shared objects start beyond the largest file offset".to_string());
+                return
+            },
+        };
+        match ofs.get(&hints.first_obj) {
+            Some((_, o)) if *o == expected => (),
+            o => self.error(format!(
+                "the shared objects start at {} in the hint table, but object {} is at {:?}",
+                expected,
+                hints.first_obj,
+                o.map(|o| o.1)
+            )),
+        }
+    }
+}
+
+// Checks the linearization of a file.  Returns None if the file is
+// not linearized.
+pub fn check_linearization(
+    path: &Path, data: &[u8], opts: &ParseOptions,
+) -> PdfParseResult<Option<LinearizationReport>> {
+    let (fi, ctxt, root, revs) = parse_data_with_revisions(path, data, opts)?;
+    let pdf = &data[fi.file_offset(0) ..];
+    let (id, offset, dict) = match first_object(pdf) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let params = match LinearizationParams::from_dict(id, offset, &dict) {
+        Ok(p) => p,
+        Err(e) => {
+            return Ok(Some(LinearizationReport {
+                params:       None,
+                page_hints:   None,
+                shared_hints: None,
+                errors:       vec![e],
+            }))
+        },
+    };
+    let mut c = Checker {
+        data:   pdf,
+        params: &params,
+        budget: ctxt.decode_budget(),
+        errors: Vec::new(),
+    };
+
+    if params.file_length != pdf.len() {
+        c.error(format!(
+            "/L is {} but the file length is {}",
+            params.file_length,
+            pdf.len()
+        ))
+    }
+
+    // The first-page section is the one following the linearization
+    // dictionary, and its /Prev is the main section.
+    let first = revs.iter().min_by_key(|r| r.xref_offset()).unwrap();
+    let prev = first.trailer().and_then(|t| t.val().get_usize(b"Prev"));
+    let main = revs.iter().find(|r| Some(r.xref_offset()) == prev);
+    if revs.len() > 2 {
+        c.error(format!(
+            "the file has {} xref sections; it was updated after linearization",
+            revs.len()
+        ))
+    }
+    let first_ofs = offsets(first);
+    let mut all_ofs = BTreeMap::new();
+    match main {
+        Some(main) => {
+            c.check_main_xref(main.xref_offset());
+            // A linearized reader uses the first-page section for the
+            // objects it lists.
+            for (num, e) in offsets(main) {
+                match first_ofs.get(&num) {
+                    Some(f) if *f != e => c.error(format!(
+                        "object {} is at {} in the first-page xref, but at {} in the main xref",
+                        num, f.1, e.1
+                    )),
+                    _ => (),
+                }
+                all_ofs.insert(num, e);
+            }
+        },
+        None => c.error("the first-page xref has no /Prev to a main xref".to_string()),
+    }
+    all_ofs.extend(first_ofs.iter().map(|(n, e)| (*n, *e)));
+    match all_ofs.get(&id.0) {
+        Some((gen, ofs)) if (id.1, offset) == (*gen, *ofs) => (),
+        e => c.error(format!(
+            "the linearization dictionary ({},{}) at {} has xref entry {:?}",
+            id.0, id.1, offset, e
+        )),
+    }
+
+    let pages = page_objects(&ctxt, root);
+    c.check_pages(&pages, &first_ofs);
+
+    let (page_hints, shared_hints) = match c.hint_tables(pages.len()) {
+        Some((ph, sh)) => {
+            c.check_page_hints(&ph, &pages, &all_ofs);
+            if let Some(sh) = &sh {
+                c.check_shared_hints(sh, &all_ofs)
+            }
+            (Some(ph), sh)
+        },
+        None => (None, None),
+    };
+    let errors = c.errors;
+    Ok(Some(LinearizationReport {
+        params: Some(params),
+        page_hints,
+        shared_hints,
+        errors,
+    }))
+}
+
+#[cfg(test)]
+mod test_linearization {
+    use std::path::Path;
+
+    use super::{check_linearization, PageOffsetHints, SharedObjectHints};
+    use crate::pdf_lib::pdf_traverse_xref::ParseOptions;
+
+    struct BitWriter {
+        bytes: Vec<u8>,
+        pos:   usize,
+    }
+
+    impl BitWriter {
+        fn put(&mut self, v: usize, n: usize) {
+            for i in (0 .. n).rev() {
+                if self.pos.is_multiple_of(8) {
+                    self.bytes.push(0)
+                }
+                let bit = ((v >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (7 - self.pos % 8);
+                self.pos += 1
+            }
+        }
+        fn align(&mut self) { self.pos = self.pos.div_ceil(8) * 8 }
+    }
+
+    // The page offset and shared object hint tables for two pages,
+    // where the second page uses the one shared object group.
+    fn hint_tables(page_ofs: usize, lens: [usize; 2], font_ofs: usize) -> (Vec<u8>, usize) {
+        let mut w = BitWriter {
+            bytes: Vec::new(),
+            pos:   0,
+        };
+        for (v, n) in [
+            (0, 32),
+            (page_ofs, 32),
+            (8, 16),
+            (0, 32),
+            (16, 16),
+            (0, 32),
+            (16, 16),
+            (0, 32),
+            (16, 16),
+            (8, 16),
+            (8, 16),
+            (0, 16),
+            (1, 16),
+        ] {
+            w.put(v, n)
+        }
+        let items = [
+            (vec![2, 2], 8),
+            (lens.to_vec(), 16),
+            (vec![0, 1], 8),
+            (vec![0], 8),
+            (vec![0, 0], 16),
+            (vec![50, 50], 16),
+        ];
+        for (vals, n) in items.iter() {
+            for v in vals {
+                w.put(*v, *n)
+            }
+            w.align()
+        }
+        let shared = w.bytes.len();
+        for (v, n) in [
+            (3, 32),
+            (font_ofs, 32),
+            (0, 32),
+            (1, 32),
+            (8, 16),
+            (0, 32),
+            (16, 16),
+        ] {
+            w.put(v, n)
+        }
+        for (v, n) in [(40, 16), (0, 1), (0, 8)] {
+            w.put(v, n);
+            w.align()
+        }
+        (w.bytes, shared)
+    }
+
+    fn xref(ents: &[usize]) -> String {
+        ents.iter()
+            .map(|o| format!("{:010} 00000 n\r\n", o))
+            .collect()
+    }
+
+    // Builds a linearized file with two pages, given the values that
+    // are only known after layout.
+    fn build(vals: &[usize; 8], dup: bool) -> (Vec<u8>, [usize; 8]) {
+        let [l, h_ofs, h_len, e, t, prev, page_ofs, font_ofs] = *vals;
+        let mut v = b"%PDF-1.5\n".to_vec();
+        let lin = v.len();
+        v.extend_from_slice(
+            format!(
+                "10 0 obj\n<</Linearized 1 /L {:010} /H [{:010} {:010}] /O 12 /E {:010} /N 2 /T {:010}>>\nendobj\n",
+                l, h_ofs, h_len, e, t
+            )
+            .as_bytes(),
+        );
+        let fp_xref = v.len();
+        let mut fp_ents = vec![lin];
+        let mut body = Vec::new();
+        let mut ofs = [0; 6];
+
+        let len0 = e.saturating_sub(h_len + page_ofs);
+        let len1 = font_ofs.saturating_sub(page_ofs + len0);
+        let (hints, shared) = hint_tables(page_ofs, [len0, len1], font_ofs);
+        let mut hint = format!(
+            "15 0 obj\n<</Length {} /S {}>>\nstream\n",
+            hints.len(),
+            shared
+        )
+        .into_bytes();
+        hint.extend_from_slice(&hints);
+        hint.extend_from_slice(b"\nendstream\nendobj\n");
+        let objs: [(usize, &str); 4] = [
+            (11, "<</Type /Catalog /Pages 14 0 R>>"),
+            (14, "<</Type /Pages /Kids [12 0 R 1 0 R] /Count 2>>"),
+            (
+                12,
+                "<</Type /Page /Parent 14 0 R /MediaBox [0 0 612 792] /Contents 13 0 R>>",
+            ),
+            (13, "<</Length 9>>\nstream\nBT\n  ET\n \nendstream"),
+        ];
+        let fp_trailer = format!(
+            "xref\n10 6\n{}trailer\n<</Size 16 /Root 11 0 R /Prev {:010}>>\nstartxref\n0\n%%EOF\n",
+            xref(&[0; 6]),
+            prev
+        );
+        let start = fp_xref + fp_trailer.len();
+        ofs[5] = start;
+        body.extend_from_slice(&hint);
+        for (num, o) in objs.iter() {
+            ofs[num - 10] = start + body.len();
+            body.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", num, o).as_bytes())
+        }
+        fp_ents.extend_from_slice(&ofs[1 ..]);
+        let fp_trailer = format!(
+            "xref\n10 6\n{}trailer\n<</Size 16 /Root 11 0 R /Prev {:010}>>\nstartxref\n0\n%%EOF\n",
+            xref(&fp_ents),
+            prev
+        );
+        v.extend_from_slice(fp_trailer.as_bytes());
+        v.extend_from_slice(&body);
+        let end = v.len();
+
+        let mut main = Vec::new();
+        for (num, o) in [
+            (1, "<</Type /Page /Parent 14 0 R /MediaBox [0 0 612 792] /Contents 2 0 R /Resources <</Font <</F1 3 0 R>>>>>>"),
+            (2, "<</Length 9>>\nstream\nBT\n  ET\n \nendstream"),
+            (3, "<</Type /Font /Subtype /Type1 /BaseFont /Helvetica>>"),
+        ] {
+            main.push(v.len());
+            v.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", num, o).as_bytes())
+        }
+        let main_xref = v.len();
+        let mut tbl = format!("xref\n0 4\n0000000000 65535 f\r\n{}", xref(&main));
+        if dup {
+            tbl.push_str(&format!("11 1\n{}", xref(&[ofs[4]])))
+        }
+        v.extend_from_slice(tbl.as_bytes());
+        v.extend_from_slice(
+            format!(
+                "trailer\n<</Size 16 /Root 11 0 R>>\nstartxref\n{}\n%%EOF\n",
+                fp_xref
+            )
+            .as_bytes(),
+        );
+        let page_ofs = ofs[2] - hint.len();
+        let font_ofs = main[2] - hint.len();
+        let layout = [
+            v.len(),
+            ofs[5],
+            hint.len(),
+            end,
+            main_xref + 8,
+            main_xref,
+            page_ofs,
+            font_ofs,
+        ];
+        (v, layout)
+    }
+
+    fn linearized(dup: bool) -> Vec<u8> {
+        let (_, layout) = build(&[0; 8], dup);
+        let (v, check) = build(&layout, dup);
+        assert_eq!(layout, check);
+        v
+    }
+
+    fn patch(v: &[u8], from: &str, to: &str) -> Vec<u8> {
+        let (from, to) = (from.as_bytes(), to.as_bytes());
+        let i = v.windows(from.len()).position(|w| w == from).unwrap();
+        let mut v = v.to_vec();
+        v[i .. i + from.len()].copy_from_slice(to);
+        v
+    }
+
+    fn errors(v: &[u8]) -> Vec<String> {
+        let path = Path::new("linearized.pdf");
+        let r = check_linearization(path, v, &ParseOptions::new());
+        r.unwrap().unwrap().errors().to_vec()
+    }
+
+    #[test]
+    fn test_linearization() {
+        let path = Path::new("linearized.pdf");
+        let opts = ParseOptions::new();
+        let v = linearized(false);
+        let r = check_linearization(path, &v, &opts).unwrap().unwrap();
+        assert_eq!(r.errors(), &[] as &[String]);
+        let p = r.params().unwrap();
+        assert_eq!(p.id(), (10, 0));
+        assert_eq!(p.file_length(), v.len());
+        assert_eq!(p.num_pages(), 2);
+        let ph = r.page_hints().unwrap();
+        assert_eq!(ph.pages().len(), 2);
+        let lens: usize = ph.pages().iter().map(|p| p.length()).sum();
+        assert_eq!(
+            ph.first_page_offset() + lens,
+            r.shared_hints().unwrap().first_offset()
+        );
+        assert_eq!(ph.pages()[1].shared(), &[0]);
+        assert_eq!(ph.pages()[1].content_length(), 50);
+        let sh = r.shared_hints().unwrap();
+        assert_eq!(sh.first_obj(), 3);
+        assert_eq!(sh.groups().len(), 1);
+        assert_eq!(sh.groups()[0].objects(), 1);
+        assert!(sh.groups()[0].signature().is_none());
+
+        // a file that is not linearized
+        let w = patch(&v, "/Linearized", "/Linearizex");
+        assert!(check_linearization(path, &w, &opts).unwrap().is_none());
+
+        let w = patch(&v, "/N 2", "/N 3");
+        let e = errors(&w);
+        assert!(e[0].starts_with("/N is 3"), "{:?}", e);
+        assert!(
+            e.iter()
+                .any(|e| e.ends_with("/N is 3, but the page tree has 2 pages")),
+            "{:?}",
+            e
+        );
+        let w = patch(&v, "/O 12", "/O 13");
+        assert!(errors(&w)[0].starts_with("/O is 13"));
+        let l = format!("/L {:010}", v.len());
+        let w = patch(&v, &l, "/L 0000000001");
+        assert!(errors(&w)[0].starts_with("/L is 1"));
+        let h = format!("/H [{:010}", p.hints()[0].0);
+        let w = patch(&v, &h, "/H [0000000009");
+        assert!(errors(&w).iter().any(|e| e.contains("hint stream at 9")));
+
+        // the linearized and full xref views disagree on object 11
+        let v = linearized(true);
+        let e = errors(&v);
+        assert!(
+            e.iter().any(|e| e.starts_with("object 11 is at")),
+            "{:?}",
+            e
+        );
+
+        // an incremental update after linearization
+        let mut v = linearized(false);
+        let fp = v.windows(4).position(|w| w == b"xref").unwrap();
+        let ofs = v.len();
+        v.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000000 65535 f\r\ntrailer\n<</Size 16 /Root 11 0 R /Prev {}>>\nstartxref\n{}\n%%EOF\n",
+                fp, ofs
+            )
+            .as_bytes(),
+        );
+        let e = errors(&v);
+        assert!(
+            e.iter().any(|e| e.contains("updated after linearization")),
+            "{:?}",
+            e
+        );
+        assert!(e.iter().any(|e| e.starts_with("/L is")), "{:?}", e);
+    }
+
+    #[test]
+    fn test_hint_overflow() {
+        // a page offset table whose object count delta overflows
+        let mut w = BitWriter {
+            bytes: Vec::new(),
+            pos:   0,
+        };
+        for (v, n) in [(1, 32), (0, 32), (64, 16)] {
+            w.put(v, n)
+        }
+        for (v, n) in [(0, 32), (0, 16), (0, 32), (0, 16), (0, 32), (0, 16)] {
+            w.put(v, n)
+        }
+        for (v, n) in [(0, 16), (0, 16), (0, 16), (1, 16), (usize::MAX, 64)] {
+            w.put(v, n)
+        }
+        let r = PageOffsetHints::parse(&w.bytes, 1, 0);
+        assert_eq!(r, Err("hint table value is too large".to_string()));
+
+        // a shared object table whose group length overflows
+        let mut w = BitWriter {
+            bytes: Vec::new(),
+            pos:   0,
+        };
+        for (v, n) in [(3, 32), (0, 32), (0, 32), (1, 32), (0, 16)] {
+            w.put(v, n)
+        }
+        for (v, n) in [(1, 32), (64, 16), (usize::MAX, 64)] {
+            w.put(v, n)
+        }
+        w.put(0, 8);
+        let r = SharedObjectHints::parse(&w.bytes);
+        assert_eq!(r, Err("hint table value is too large".to_string()));
+
+        // a page with more shared references than there are groups
+        let mut w = BitWriter {
+            bytes: Vec::new(),
+            pos:   0,
+        };
+        for (v, n) in [(1, 32), (0, 32), (0, 16), (0, 32), (0, 16), (0, 32)] {
+            w.put(v, n)
+        }
+        for (v, n) in [(0, 16), (0, 32), (0, 16), (8, 16), (0, 16), (0, 16)] {
+            w.put(v, n)
+        }
+        for (v, n) in [(1, 16), (200, 8)] {
+            w.put(v, n)
+        }
+        let r = PageOffsetHints::parse(&w.bytes, 1, 1);
+        assert_eq!(
+            r,
+            Err("a page references 200 shared object groups, but there are 1".to_string())
+        );
+    }
+}
@@ -18,16 +18,19 @@
 
 // Basic PDF objects.
 
-use std::collections::{BTreeMap, HashSet};
-use std::rc::Rc;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Arc;
 
 use super::super::pcore::parsebuffer::{
     locate_value, ErrorKind, LocatedVal, Location, ParseBufferT, ParseResult, ParsleyParser,
 };
+use super::pdf_filters::{DecodeBudget, DecodeLimits};
 use super::pdf_prim::{
     Boolean, Comment, HexString, IntegerP, IntegerT, NameP, NameT, Null, RawLiteralString, RealP,
-    RealT, StreamContentP, StreamContentT, WhitespaceEOL,
+    RealT, StreamContentP, StreamContentScanP, StreamContentT, WhitespaceEOL,
 };
+use super::pdf_traverse_xref::PdfParseResult;
 
 // Object locations in the PDF file.  This will need to become
 // hierarchical to handle nested object streams.
@@ -42,19 +45,67 @@ impl Location for PDFLocation {
     fn loc_end(&self) -> usize { self.end }
 }
 
+// A stream whose content was found by scanning for its end, since its
+// declared /Length was wrong, or could not be resolved.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LengthRepair {
+    id:       ObjectId,
+    // the start of the indirect object
+    offset:   usize,
+    declared: Option<usize>,
+    actual:   usize,
+}
+
+impl LengthRepair {
+    pub fn new(
+        id: ObjectId, offset: usize, declared: Option<usize>, actual: usize,
+    ) -> LengthRepair {
+        LengthRepair {
+            id,
+            offset,
+            declared,
+            actual,
+        }
+    }
+    pub fn id(&self) -> ObjectId { self.id }
+    pub fn offset(&self) -> usize { self.offset }
+    pub fn declared(&self) -> Option<usize> { self.declared }
+    pub fn actual(&self) -> usize { self.actual }
+}
+
+// Provides the objects of a context in lazy mode, parsing each one
+// when it is first looked up.  Contexts can be shared between
+// threads, so the loader has to be thread-safe.
+pub trait ObjectLoader: Send + Sync {
+    // Returns None if the object is not in use.
+    fn load(&self, oid: ObjectId) -> PdfParseResult<Option<&Arc<LocatedVal<PDFObjT>>>>;
+    // The ids of the objects that can be loaded.
+    fn obj_ids(&self) -> Vec<ObjectId>;
+}
+
 // PDF object parsing context.  This keeps track of information
 // collected during parsing.
 
 pub struct PDFObjContext {
     // Maps object identifiers to their objects.
-    defns:                    BTreeMap<ObjectId, Rc<LocatedVal<PDFObjT>>>,
+    defns:                    BTreeMap<ObjectId, Arc<LocatedVal<PDFObjT>>>,
+    // In lazy mode, provides the objects that are not in defns.
+    loader:                   Option<Arc<dyn ObjectLoader>>,
     // whether the document is encrypted
     encrypted:                bool,
+    // the /Encrypt and /ID entries of the newest trailer
+    encrypt_dict:             Option<Arc<LocatedVal<PDFObjT>>>,
+    file_id:                  Option<Arc<LocatedVal<PDFObjT>>>,
     // Tracks the recursion depth.
     max_depth:                usize,
     cur_depth:                usize,
     // customized strictness
     eol_after_stream_content: bool,
+    // whether to scan for the end of streams with a bad /Length
+    lenient_stream_length:    bool,
+    length_repairs:           Vec<LengthRepair>,
+    // the limits on decoded stream data, shared with forked contexts
+    decode_budget:            Arc<DecodeBudget>,
 }
 
 pub type ObjectId = (usize, usize);
@@ -63,21 +114,97 @@ impl PDFObjContext {
     pub fn new(max_depth: usize) -> PDFObjContext {
         PDFObjContext {
             defns: BTreeMap::new(),
+            loader: None,
             encrypted: false,
+            encrypt_dict: None,
+            file_id: None,
             max_depth,
             cur_depth: 0,
             eol_after_stream_content: false, // not strict
+            lenient_stream_length: false,
+            length_repairs: Vec::new(),
+            decode_budget: Arc::new(DecodeBudget::default()),
         }
     }
-    pub fn register_obj(&mut self, p: &LocatedVal<IndirectT>) -> Option<Rc<LocatedVal<PDFObjT>>> {
+    // Returns an empty context with the same settings, which shares
+    // the decode budget of this one, e.g. to parse objects on another
+    // thread.
+    pub fn fork(&self) -> PDFObjContext {
+        PDFObjContext {
+            defns:                    BTreeMap::new(),
+            loader:                   None,
+            encrypted:                self.encrypted,
+            encrypt_dict:             self.encrypt_dict.clone(),
+            file_id:                  self.file_id.clone(),
+            max_depth:                self.max_depth,
+            cur_depth:                0,
+            eol_after_stream_content: self.eol_after_stream_content,
+            lenient_stream_length:    self.lenient_stream_length,
+            length_repairs:           Vec::new(),
+            decode_budget:            Arc::clone(&self.decode_budget),
+        }
+    }
+    // Adds the objects and length repairs of a forked context.  The
+    // objects that are already defined are kept, and their ids are
+    // returned.
+    pub fn join(&mut self, other: PDFObjContext) -> Vec<ObjectId> {
+        let mut dups = Vec::new();
+        for (oid, o) in other.defns {
+            match self.defns.entry(oid) {
+                Entry::Occupied(_) => dups.push(oid),
+                Entry::Vacant(e) => {
+                    let _ = e.insert(o);
+                },
+            }
+        }
+        self.length_repairs.extend(other.length_repairs);
+        dups
+    }
+    pub fn register_obj(&mut self, p: &LocatedVal<IndirectT>) -> Option<Arc<LocatedVal<PDFObjT>>> {
         self.defns
-            .insert((p.val().num(), p.val().gen()), Rc::clone(p.val().obj()))
+            .insert((p.val().num(), p.val().gen()), Arc::clone(p.val().obj()))
     }
-    pub fn lookup_obj(&self, oid: ObjectId) -> Option<&Rc<LocatedVal<PDFObjT>>> {
-        self.defns.get(&oid)
+    pub fn lookup_obj(&self, oid: ObjectId) -> Option<&Arc<LocatedVal<PDFObjT>>> {
+        self.load_obj(oid).ok().flatten()
+    }
+    // Like lookup_obj, but in lazy mode, returns the error from parsing
+    // the object.
+    pub fn load_obj(&self, oid: ObjectId) -> PdfParseResult<Option<&Arc<LocatedVal<PDFObjT>>>> {
+        match (self.defns.get(&oid), &self.loader) {
+            (Some(o), _) => Ok(Some(o)),
+            (None, Some(l)) => l.load(oid),
+            (None, None) => Ok(None),
+        }
+    }
+    pub fn set_loader(&mut self, loader: Arc<dyn ObjectLoader>) { self.loader = Some(loader) }
+    pub fn is_lazy(&self) -> bool { self.loader.is_some() }
+    // Returns the registered objects, without those of the loader.
+    pub fn into_objs(self) -> BTreeMap<ObjectId, Arc<LocatedVal<PDFObjT>>> { self.defns }
+    // Removes an object definition, e.g. to allow a newer definition
+    // of the same object id to be registered.
+    pub fn remove_obj(&mut self, oid: ObjectId) -> Option<Arc<LocatedVal<PDFObjT>>> {
+        self.defns.remove(&oid)
+    }
+    // Returns the ids of all registered objects, and in lazy mode,
+    // of those that can be loaded.
+    pub fn obj_ids(&self) -> Vec<ObjectId> {
+        let mut ids: BTreeSet<ObjectId> = self.defns.keys().cloned().collect();
+        if let Some(l) = &self.loader {
+            ids.extend(l.obj_ids())
+        }
+        ids.into_iter().collect()
     }
     pub fn set_encrypted(&mut self) { self.encrypted = true; }
     pub fn is_encrypted(&self) -> bool { self.encrypted }
+    pub fn set_encryption(
+        &mut self, encrypt: Arc<LocatedVal<PDFObjT>>, file_id: Option<Arc<LocatedVal<PDFObjT>>>,
+    ) {
+        self.encrypted = true;
+        self.encrypt_dict = Some(encrypt);
+        self.file_id = file_id
+    }
+    pub fn encrypt_dict(&self) -> Option<&Arc<LocatedVal<PDFObjT>>> { self.encrypt_dict.as_ref() }
+    pub fn file_id(&self) -> Option<&Arc<LocatedVal<PDFObjT>>> { self.file_id.as_ref() }
     pub fn enter_obj(&mut self) -> bool {
         if self.cur_depth == self.max_depth {
             false
@@ -91,16 +218,27 @@ impl PDFObjContext {
         self.cur_depth -= 1;
     }
     pub fn depth(&self) -> usize { self.cur_depth }
+    // Sets the limits on decoded stream data, and resets the total.
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.decode_budget = Arc::new(DecodeBudget::new(limits))
+    }
+    pub fn decode_budget(&self) -> &DecodeBudget { &self.decode_budget }
+    pub fn set_lenient_stream_length(&mut self, lenient: bool) {
+        self.lenient_stream_length = lenient
+    }
+    pub fn lenient_stream_length(&self) -> bool { self.lenient_stream_length }
+    // The streams whose content was found by scanning in lenient mode.
+    pub fn length_repairs(&self) -> &[LengthRepair] { &self.length_repairs }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ArrayT {
-    objs: Vec<Rc<LocatedVal<PDFObjT>>>,
+    objs: Vec<Arc<LocatedVal<PDFObjT>>>,
 }
 
 impl ArrayT {
-    pub fn new(objs: Vec<Rc<LocatedVal<PDFObjT>>>) -> ArrayT { ArrayT { objs } }
-    pub fn objs(&self) -> &[Rc<LocatedVal<PDFObjT>>] { self.objs.as_slice() }
+    pub fn new(objs: Vec<Arc<LocatedVal<PDFObjT>>>) -> ArrayT { ArrayT { objs } }
+    pub fn objs(&self) -> &[Arc<LocatedVal<PDFObjT>>] { self.objs.as_slice() }
 }
 
 pub struct ArrayP<'a> {
@@ -128,7 +266,7 @@ impl ArrayP<'_> {
             ws.parse(buf)?;
             if buf.exact(b"]").is_err() {
                 let o = parse_pdf_obj(self.ctxt, buf)?;
-                objs.push(Rc::new(o));
+                objs.push(Arc::new(o));
             } else {
                 end = true;
             }
@@ -159,14 +297,14 @@ impl std::fmt::Debug for DictKey {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DictT {
-    map: BTreeMap<DictKey, Rc<LocatedVal<PDFObjT>>>,
+    map: BTreeMap<DictKey, Arc<LocatedVal<PDFObjT>>>,
 }
 
 impl DictT {
-    pub fn new(map: BTreeMap<DictKey, Rc<LocatedVal<PDFObjT>>>) -> DictT { DictT { map } }
-    pub fn map(&self) -> &BTreeMap<DictKey, Rc<LocatedVal<PDFObjT>>> { &self.map }
-    pub fn map_mut(&mut self) -> &mut BTreeMap<DictKey, Rc<LocatedVal<PDFObjT>>> { &mut self.map }
-    pub fn get(&self, k: &[u8]) -> Option<&Rc<LocatedVal<PDFObjT>>> {
+    pub fn new(map: BTreeMap<DictKey, Arc<LocatedVal<PDFObjT>>>) -> DictT { DictT { map } }
+    pub fn map(&self) -> &BTreeMap<DictKey, Arc<LocatedVal<PDFObjT>>> { &self.map }
+    pub fn map_mut(&mut self) -> &mut BTreeMap<DictKey, Arc<LocatedVal<PDFObjT>>> { &mut self.map }
+    pub fn get(&self, k: &[u8]) -> Option<&Arc<LocatedVal<PDFObjT>>> {
         self.map.get(&DictKey::new(Vec::from(k)))
     }
     pub fn get_keys(&self) -> Vec<DictKey> { self.map.keys().cloned().collect() }
@@ -295,7 +433,7 @@ impl DictP<'_> {
                     // Drop the entry.
                 } else {
                     names.insert(key.clone());
-                    map.insert(DictKey(key), Rc::new(o));
+                    map.insert(DictKey(key), Arc::new(o));
                 }
             } else {
                 end = true;
@@ -307,7 +445,7 @@ impl DictP<'_> {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct StreamT {
-    dict:   Rc<LocatedVal<DictT>>,
+    dict:   Arc<LocatedVal<DictT>>,
     stream: LocatedVal<StreamContentT>,
 }
 impl StreamT {
@@ -326,10 +464,10 @@ impl Filter<'_> {
 }
 
 impl StreamT {
-    pub fn new(dict: Rc<LocatedVal<DictT>>, stream: LocatedVal<StreamContentT>) -> StreamT {
+    pub fn new(dict: Arc<LocatedVal<DictT>>, stream: LocatedVal<StreamContentT>) -> StreamT {
         StreamT { dict, stream }
     }
-    pub fn dict(&self) -> &Rc<LocatedVal<DictT>> { &self.dict }
+    pub fn dict(&self) -> &Arc<LocatedVal<DictT>> { &self.dict }
     pub fn stream(&self) -> &LocatedVal<StreamContentT> { &self.stream }
 
     pub fn filters(&self) -> ParseResult<Vec<Filter>> {
@@ -463,6 +601,14 @@ impl ReferenceP {
     }
 }
 
+// The form of a string object: a literal string, which the parser
+// keeps in its raw escaped form, or a hex string, which it decodes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum StringForm {
+    Literal,
+    Hex,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PDFObjT {
     Array(ArrayT),
@@ -470,7 +616,7 @@ pub enum PDFObjT {
     Stream(StreamT),
     Reference(ReferenceT),
     Boolean(bool),
-    String(Vec<u8>),
+    String(Vec<u8>, StringForm),
     Name(NameT),
     Null(()),
     Comment(Vec<u8>),
@@ -520,7 +666,7 @@ impl PDFObjP<'_> {
                 // '('
                 let mut rp = RawLiteralString;
                 let r = rp.parse(buf)?;
-                Ok(PDFObjT::String(r.unwrap()))
+                Ok(PDFObjT::String(r.unwrap(), StringForm::Literal))
             },
             Some(37) => {
                 // '%'
@@ -558,7 +704,7 @@ impl PDFObjP<'_> {
                     Some(_) | None => {
                         let mut hp = HexString;
                         let s = hp.parse(buf)?;
-                        Ok(PDFObjT::String(s.unwrap()))
+                        Ok(PDFObjT::String(s.unwrap(), StringForm::Hex))
                     },
                 }
             },
@@ -681,20 +827,23 @@ pub fn parse_pdf_obj(
 pub struct IndirectT {
     num: usize,
     gen: usize,
-    obj: Rc<LocatedVal<PDFObjT>>,
+    obj: Arc<LocatedVal<PDFObjT>>,
 }
 
 impl IndirectT {
-    pub fn new(num: usize, gen: usize, obj: Rc<LocatedVal<PDFObjT>>) -> IndirectT {
+    pub fn new(num: usize, gen: usize, obj: Arc<LocatedVal<PDFObjT>>) -> IndirectT {
         IndirectT { num, gen, obj }
     }
     pub fn num(&self) -> usize { self.num }
     pub fn gen(&self) -> usize { self.gen }
-    pub fn obj(&self) -> &Rc<LocatedVal<PDFObjT>> { &self.obj }
+    pub fn obj(&self) -> &Arc<LocatedVal<PDFObjT>> { &self.obj }
 }
 
 pub struct IndirectP<'a> {
-    ctxt: &'a mut PDFObjContext,
+    ctxt:      &'a mut PDFObjContext,
+    // whether this is the last attempt to parse the object, so that
+    // an unresolved indirect /Length will not become available later
+    last_pass: bool,
 }
 
 fn convert_stream_length(obj: &LocatedVal<PDFObjT>) -> ParseResult<usize> {
@@ -716,7 +865,18 @@ fn convert_stream_length(obj: &LocatedVal<PDFObjT>) -> ParseResult<usize> {
 }
 
 impl IndirectP<'_> {
-    pub fn new(ctxt: &mut PDFObjContext) -> IndirectP { IndirectP { ctxt } }
+    pub fn new(ctxt: &mut PDFObjContext) -> IndirectP {
+        IndirectP {
+            ctxt,
+            last_pass: false,
+        }
+    }
+    pub fn new_last_pass(ctxt: &mut PDFObjContext) -> IndirectP<'_> {
+        IndirectP {
+            ctxt,
+            last_pass: true,
+        }
+    }
     fn parse_internal(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<LocatedVal<IndirectT>> {
         let mut int = IntegerP;
         let mut ws = WhitespaceEOL::new(true);
@@ -750,6 +910,7 @@ impl IndirectP<'_> {
         ws.parse(buf)?;
 
         let o = parse_pdf_obj(self.ctxt, buf)?;
+        let mut repair = None;
 
         // If we parsed a dictionary, check whether this could be a
         // stream object.
@@ -768,18 +929,24 @@ impl IndirectP<'_> {
                         // If we find a reference, the reference could
                         // aleady be in the context, or it might not
                         // yet have been parsed.
+                        let lenient = self.ctxt.lenient_stream_length;
                         let length = match dict.get(b"Length") {
                             None => {
                                 let err = ErrorKind::GuardError("no Length specified for stream"
                                                                 .to_string());
-                                return Err(LocatedVal::new(err, dict_start, dict_end))
+                                Err(LocatedVal::new(err, dict_start, dict_end))
                             },
                             Some(l) => match l.val() {
                                 PDFObjT::Integer(_) =>
-                                    convert_stream_length(l)?,
+                                    convert_stream_length(l),
                                 PDFObjT::Reference(r) =>
                                     match self.ctxt.lookup_obj(r.id()) {
-                                        Some(o) => convert_stream_length(o)?,
+                                        Some(o) => convert_stream_length(o),
+                                        // In lenient mode, scan for the
+                                        // end on the last attempt.
+                                        None if lenient && self.last_pass => {
+                                            Err(l.place(ErrorKind::InsufficientContext))
+                                        },
                                         None => {
                                             let err = ErrorKind::InsufficientContext;
                                             return Err(l.place(err))
@@ -788,16 +955,35 @@ impl IndirectP<'_> {
                                 _ =>  {
                                     let err = ErrorKind::GuardError("invalid Length specified for stream"
                                                                     .to_string());
-                                    return Err(LocatedVal::new(err, dict_start, dict_end))
+                                    Err(LocatedVal::new(err, dict_start, dict_end))
                                 },
                             }
                         };
                         let dict = LocatedVal::new(dict, dict_start, dict_end);
-                        let mut s = StreamContentP::new(length, self.ctxt.eol_after_stream_content);
-                        let stream = s.parse(buf)?;
+                        let stream_start = buf.get_cursor();
+                        let declared = length.as_ref().ok().copied();
+                        let stream = match length {
+                            Ok(length) => {
+                                let mut s = StreamContentP::new(length, self.ctxt.eol_after_stream_content);
+                                s.parse(buf)
+                            },
+                            Err(e) => Err(e),
+                        };
+                        let stream = match stream {
+                            Ok(stream) => stream,
+                            Err(_) if lenient => {
+                                buf.set_cursor_unsafe(stream_start);
+                                let stream = StreamContentScanP.parse(buf)?;
+                                let id = (num.val().usize_val(), gen.val().usize_val());
+                                let actual = stream.val().size();
+                                repair = Some(LengthRepair::new(id, start, declared, actual));
+                                stream
+                            },
+                            Err(e) => return Err(e),
+                        };
                         let start = dict_start;
                         let end = stream.loc_end();
-                        let obj = PDFObjT::Stream(StreamT { dict: Rc::new(dict), stream });
+                        let obj = PDFObjT::Stream(StreamT { dict: Arc::new(dict), stream });
                         LocatedVal::new(obj, start, end)
                     } else {
                         panic!("can never happen")
@@ -817,15 +1003,20 @@ impl IndirectP<'_> {
         }
 
         let end = buf.get_cursor();
-        let obj = Rc::new(obj);
+        let obj = Arc::new(obj);
         let ind = IndirectT::new(
             num.val().usize_val(),
             gen.val().usize_val(),
-            Rc::clone(&obj),
+            Arc::clone(&obj),
         );
         let ind = LocatedVal::new(ind, start, end);
         match self.ctxt.register_obj(&ind) {
-            None => Ok(ind),
+            None => {
+                if let Some(r) = repair {
+                    self.ctxt.length_repairs.push(r)
+                }
+                Ok(ind)
+            },
             Some(old) => {
                 // Note that this location is inside any 'n g obj' prefix for the indirect
                 // object.
@@ -874,11 +1065,11 @@ mod test_pdf_obj {
     use super::super::pdf_prim::{IntegerT, NameT, RealT, StreamContentT};
     use super::{
         parse_pdf_obj, ArrayT, DictKey, DictT, IndirectP, IndirectT, PDFObjContext, PDFObjT,
-        ReferenceT, StreamT,
+        ReferenceT, StreamT, StringForm,
     };
     use std::borrow::Borrow;
     use std::collections::BTreeMap;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
@@ -1008,7 +1199,7 @@ mod test_pdf_obj {
         let v = Vec::from("[ 1 0 R ] \r\n".as_bytes());
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             2,
             7,
@@ -1021,7 +1212,7 @@ mod test_pdf_obj {
         let v = Vec::from("[ 1 \r 0 \n R ] \r\n".as_bytes());
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             2,
             11,
@@ -1051,7 +1242,7 @@ mod test_pdf_obj {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb);
         let inner = PDFObjT::Array(ArrayT::new(Vec::new()));
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(inner, 1, 3)));
+        objs.push(Arc::new(LocatedVal::new(inner, 1, 3)));
         let outer = PDFObjT::Array(ArrayT::new(objs));
         assert_eq!(obj, Ok(LocatedVal::new(outer, 0, 4)));
 
@@ -1077,14 +1268,14 @@ mod test_pdf_obj {
         let vlen = v.len();
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             12,
             17,
         )));
         let entval = LocatedVal::new(PDFObjT::Array(ArrayT::new(objs)), 10, 19);
         let mut map = BTreeMap::new();
-        map.insert(DictKey::new(Vec::from("Entry".as_bytes())), Rc::new(entval));
+        map.insert(DictKey::new(Vec::from("Entry".as_bytes())), Arc::new(entval));
         let val = PDFObjT::Dict(DictT::new(map));
         assert_eq!(parse_pdf_obj(&mut ctxt, &mut pb), Ok(LocatedVal::new(val, 0, 25)));
         assert_eq!(pb.get_cursor(), vlen);
@@ -1096,7 +1287,7 @@ mod test_pdf_obj {
         let vlen = v.len();
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             10,
             15,
@@ -1106,7 +1297,7 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Entry".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let val = PDFObjT::Dict(DictT::new(map));
         assert_eq!(parse_pdf_obj(&mut ctxt, &mut pb), Ok(LocatedVal::new(val, 0, 18)));
@@ -1138,7 +1329,7 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Inner".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(LocatedVal::new(inner, 9, 14)),
+            Arc::new(LocatedVal::new(inner, 9, 14)),
         );
         let outer = PDFObjT::Dict(DictT::new(map));
         assert_eq!(obj, Ok(LocatedVal::new(outer, 0, 16)));
@@ -1162,7 +1353,7 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Entry".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let val = PDFObjT::Dict(DictT::new(map));
         if let PDFObjT::Dict(d) = val {
@@ -1186,13 +1377,13 @@ mod test_pdf_obj {
         let entval = LocatedVal::new(PDFObjT::Null(()), 9, 16);
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let key = NameT::new(Vec::from("Entry2".as_bytes()));
         let entval = LocatedVal::new(PDFObjT::Boolean(true), 9, 16);
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let l = PDFObjT::Dict(DictT::new(map));
 
@@ -1201,13 +1392,13 @@ mod test_pdf_obj {
         let entval = LocatedVal::new(PDFObjT::Boolean(true), 9, 16);
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let key = NameT::new(Vec::from("Entry1".as_bytes()));
         let entval = LocatedVal::new(PDFObjT::Null(()), 9, 16);
         map.insert(
             DictKey::new(LocatedVal::new(key, 2, 8).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let r = PDFObjT::Dict(DictT::new(map));
 
@@ -1225,7 +1416,7 @@ mod test_pdf_obj {
         let vlen = v.len();
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             20,
             25,
@@ -1235,10 +1426,10 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Entry".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 11, 17).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
-        let dict = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 33));
-        let obj = IndirectT::new(1, 0, Rc::clone(&dict));
+        let dict = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 33));
+        let obj = IndirectT::new(1, 0, Arc::clone(&dict));
         assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(obj, 0, 40)));
         assert_eq!(pb.get_cursor(), vlen);
         assert_eq!(ctxt.lookup_obj((1, 0)), Some(dict.borrow()));
@@ -1257,7 +1448,7 @@ mod test_pdf_obj {
         let vlen = v.len();
         let mut pb = ParseBuffer::new(v);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Reference(ReferenceT::new(1, 0)),
             20,
             25,
@@ -1267,22 +1458,22 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Entry".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 11, 17).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
         let key = NameT::new(Vec::from("Length".as_bytes()));
         let entval = LocatedVal::new(PDFObjT::Integer(IntegerT::new(6)), 36, 37);
         map.insert(
             DictKey::new(LocatedVal::new(key, 11, 17).val().normalize()),
-            Rc::new(entval),
+            Arc::new(entval),
         );
-        let dict = Rc::new(LocatedVal::new(DictT::new(map), 8, 39));
+        let dict = Arc::new(LocatedVal::new(DictT::new(map), 8, 39));
         let content = LocatedVal::new(StreamContentT::new(47, 6, Vec::from(" junk ".as_bytes())), 40, 63);
-        let stream = Rc::new(LocatedVal::new(
+        let stream = Arc::new(LocatedVal::new(
             PDFObjT::Stream(StreamT::new(dict, content)),
             8,
             63,
         ));
-        let obj = IndirectT::new(1, 0, Rc::clone(&stream));
+        let obj = IndirectT::new(1, 0, Arc::clone(&stream));
         assert_eq!(p.parse(&mut pb), Ok(LocatedVal::new(obj, 0, 70)));
         assert_eq!(pb.get_cursor(), vlen);
         assert_eq!(ctxt.lookup_obj((1, 0)), Some(stream.borrow()));
@@ -1302,7 +1493,7 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Type".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 14, 19).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Name(NameT::new(Vec::from("Catalog".as_bytes()))),
                 20,
                 28,
@@ -1311,14 +1502,14 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Pages".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 31, 37).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Reference(ReferenceT::new(2, 0)),
                 38,
                 43,
             )),
         );
-        let d = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 10, 46));
-        let o = IndirectT::new(1, 0, Rc::clone(&d));
+        let d = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 10, 46));
+        let o = IndirectT::new(1, 0, Arc::clone(&d));
         assert_eq!(val, Ok(LocatedVal::new(o, 0, 53)));
         assert_eq!(ctxt.lookup_obj((1, 0)), Some(d.borrow()));
     }
@@ -1337,14 +1528,14 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Type".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 14, 19).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Name(NameT::new(Vec::from("Catalog".as_bytes()))),
                 20,
                 28,
             )),
         );
-        let d = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 10, 45));
-        let o = IndirectT::new(1, 0, Rc::clone(&d));
+        let d = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 10, 45));
+        let o = IndirectT::new(1, 0, Arc::clone(&d));
         assert_eq!(val, Ok(LocatedVal::new(o, 0, 52)));
         assert_eq!(ctxt.lookup_obj((1, 0)), Some(d.borrow()));
     }
@@ -1365,7 +1556,7 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Type".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 27, 32).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Name(NameT::new(Vec::from("Catalog".as_bytes()))),
                 33,
                 41,
@@ -1374,14 +1565,14 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("Pages".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 44, 50).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Reference(ReferenceT::new(2, 0)),
                 51,
                 56,
             )),
         );
-        let d = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 23, 59));
-        let o = IndirectT::new(1, 0, Rc::clone(&d));
+        let d = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 23, 59));
+        let o = IndirectT::new(1, 0, Arc::clone(&d));
         assert_eq!(val, Ok(LocatedVal::new(o, 0, 66)));
         assert_eq!(ctxt.lookup_obj((1, 0)), Some(d.borrow()));
     }
@@ -1417,13 +1608,13 @@ mod test_pdf_obj {
         let mut pb = ParseBuffer::new(v);
         let val = p.parse(&mut pb);
         let mut objs = Vec::new();
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Name(NameT::new(Vec::from(""))),
             10,
             11,
         )));
-        objs.push(Rc::new(LocatedVal::new(
-            PDFObjT::String(Vec::from("")),
+        objs.push(Arc::new(LocatedVal::new(
+            PDFObjT::String(Vec::from(""), StringForm::Hex),
             11,
             13,
         )));
@@ -1431,44 +1622,44 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from(""));
         map.insert(
             DictKey::new(LocatedVal::new(key, 15, 16).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Array(ArrayT::new(Vec::new())),
                 16,
                 18,
             )),
         );
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Dict(DictT::new(map)),
             13,
             20,
         )));
-        objs.push(Rc::new(LocatedVal::new(
-            PDFObjT::String(Vec::from("")),
+        objs.push(Arc::new(LocatedVal::new(
+            PDFObjT::String(Vec::from(""), StringForm::Literal),
             20,
             22,
         )));
-        let ea = Rc::new(LocatedVal::new(
+        let ea = Arc::new(LocatedVal::new(
             PDFObjT::Array(ArrayT::new(Vec::new())),
             23,
             25,
         ));
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Array(ArrayT::new(vec![ea])),
             22,
             26,
         )));
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Real(RealT::new(-1, 10)),
             26,
             29,
         )));
-        objs.push(Rc::new(LocatedVal::new(
+        objs.push(Arc::new(LocatedVal::new(
             PDFObjT::Name(NameT::new(Vec::from("+0"))),
             29,
             32,
         )));
-        let a = Rc::new(LocatedVal::new(PDFObjT::Array(ArrayT::new(objs)), 9, 33));
-        let o = IndirectT::new(10, 0, Rc::clone(&a));
+        let a = Arc::new(LocatedVal::new(PDFObjT::Array(ArrayT::new(objs)), 9, 33));
+        let o = IndirectT::new(10, 0, Arc::clone(&a));
         assert_eq!(pb.get_cursor(), vlen);
         assert_eq!(val, Ok(LocatedVal::new(o, 0, vlen)));
         assert_eq!(ctxt.lookup_obj((10, 0)), Some(a.borrow()));
@@ -1484,14 +1675,14 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 10, 11).val().normalize()),
-            Rc::new(LocatedVal::new(
+            Arc::new(LocatedVal::new(
                 PDFObjT::Name(NameT::new(Vec::from("".as_bytes()))),
                 11,
                 12,
             )),
         );
-        let d = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 14));
-        let o = IndirectT::new(10, 0, Rc::clone(&d));
+        let d = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 14));
+        let o = IndirectT::new(10, 0, Arc::clone(&d));
         assert_eq!(pb.get_cursor(), vlen);
         assert_eq!(val, Ok(LocatedVal::new(o, 0, vlen)));
         assert_eq!(ctxt.lookup_obj((10, 0)), Some(d.borrow()));
@@ -1507,14 +1698,14 @@ mod test_pdf_obj {
         let key = NameT::new(Vec::from("".as_bytes()));
         map.insert(
             DictKey::new(LocatedVal::new(key, 10, 11).val().normalize()),
-            Rc::new(LocatedVal::new(
-                PDFObjT::String(Vec::from("".as_bytes())),
+            Arc::new(LocatedVal::new(
+                PDFObjT::String(Vec::from("".as_bytes()), StringForm::Hex),
                 11,
                 13,
             )),
         );
-        let d = Rc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 15));
-        let o = IndirectT::new(11, 0, Rc::clone(&d));
+        let d = Arc::new(LocatedVal::new(PDFObjT::Dict(DictT::new(map)), 8, 15));
+        let o = IndirectT::new(11, 0, Arc::clone(&d));
         assert_eq!(pb.get_cursor(), vlen);
         assert_eq!(val, Ok(LocatedVal::new(o, 0, vlen)));
         assert_eq!(ctxt.lookup_obj((11, 0)), Some(d.borrow()));
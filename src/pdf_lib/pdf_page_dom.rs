@@ -20,7 +20,7 @@
 // relevant to text-extraction from pages.
 
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::super::pcore::parsebuffer::LocatedVal;
 use super::pdf_obj::{DictKey, DictT, ObjectId, PDFObjContext, PDFObjT};
@@ -30,8 +30,8 @@ pub struct DOMContext {
     // pages does not contain the root page, since that is contained
     // within the Catalog.
     pages:       BTreeMap<ObjectId, PageKid>,
-    font_dicts:  BTreeMap<ObjectId, Rc<FontDictionary>>,
-    font_descrs: BTreeMap<ObjectId, Rc<FontDescriptor>>,
+    font_dicts:  BTreeMap<ObjectId, Arc<FontDictionary>>,
+    font_descrs: BTreeMap<ObjectId, Arc<FontDescriptor>>,
 }
 impl DOMContext {
     pub fn new() -> Self {
@@ -42,8 +42,8 @@ impl DOMContext {
         }
     }
     pub fn pages(&self) -> &BTreeMap<ObjectId, PageKid> { &self.pages }
-    pub fn font_dicts(&self) -> &BTreeMap<ObjectId, Rc<FontDictionary>> { &self.font_dicts }
-    pub fn font_descrs(&self) -> &BTreeMap<ObjectId, Rc<FontDescriptor>> { &self.font_descrs }
+    pub fn font_dicts(&self) -> &BTreeMap<ObjectId, Arc<FontDictionary>> { &self.font_dicts }
+    pub fn font_descrs(&self) -> &BTreeMap<ObjectId, Arc<FontDescriptor>> { &self.font_descrs }
 }
 impl Default for DOMContext {
     fn default() -> Self { DOMContext::new() }
@@ -51,48 +51,48 @@ impl Default for DOMContext {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum PageKid {
-    Node(Rc<PageTreeNode>),
-    Leaf(Rc<Page>),
+    Node(Arc<PageTreeNode>),
+    Leaf(Arc<Page>),
 }
 
 // DOM types
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Catalog {
-    root_page: Rc<RootPageTreeNode>,
+    root_page: Arc<RootPageTreeNode>,
 }
 impl Catalog {
-    pub fn new(root_page: Rc<RootPageTreeNode>) -> Self { Self { root_page } }
+    pub fn new(root_page: Arc<RootPageTreeNode>) -> Self { Self { root_page } }
     pub fn root_page(&self) -> &RootPageTreeNode { &self.root_page }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct RootPageTreeNode {
     count:     usize,
-    resources: Option<Rc<Resources>>,
+    resources: Option<Arc<Resources>>,
     kids:      Vec<ObjectId>,
 }
 impl RootPageTreeNode {
-    pub fn new(resources: Option<Rc<Resources>>, count: usize, kids: Vec<ObjectId>) -> Self {
+    pub fn new(resources: Option<Arc<Resources>>, count: usize, kids: Vec<ObjectId>) -> Self {
         Self {
             resources,
             count,
             kids,
         }
     }
-    pub fn resources(&self) -> &Option<Rc<Resources>> { &self.resources }
+    pub fn resources(&self) -> &Option<Arc<Resources>> { &self.resources }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct PageTreeNode {
     parent:    ObjectId,
-    resources: Option<Rc<Resources>>,
+    resources: Option<Arc<Resources>>,
     count:     usize,
     kids:      Vec<ObjectId>,
 }
 impl PageTreeNode {
     pub fn new(
-        parent: ObjectId, resources: Option<Rc<Resources>>, count: usize, kids: Vec<ObjectId>,
+        parent: ObjectId, resources: Option<Arc<Resources>>, count: usize, kids: Vec<ObjectId>,
     ) -> Self {
         Self {
             parent,
@@ -102,19 +102,19 @@ impl PageTreeNode {
         }
     }
     pub fn kids(&self) -> &[ObjectId] { self.kids.as_slice() }
-    pub fn resources(&self) -> &Option<Rc<Resources>> { &self.resources }
+    pub fn resources(&self) -> &Option<Arc<Resources>> { &self.resources }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Page {
     parent:    ObjectId,
-    resources: Rc<Resources>, // after inheritance processing
-    contents:  Vec<Rc<LocatedVal<PDFObjT>>>,
+    resources: Arc<Resources>, // after inheritance processing
+    contents:  Vec<Arc<LocatedVal<PDFObjT>>>,
 }
 
 impl Page {
     pub fn new(
-        parent: ObjectId, resources: Rc<Resources>, contents: Vec<Rc<LocatedVal<PDFObjT>>>,
+        parent: ObjectId, resources: Arc<Resources>, contents: Vec<Arc<LocatedVal<PDFObjT>>>,
     ) -> Self {
         Self {
             parent,
@@ -122,17 +122,17 @@ impl Page {
             resources,
         }
     }
-    pub fn contents(&self) -> &[Rc<LocatedVal<PDFObjT>>] { self.contents.as_slice() }
+    pub fn contents(&self) -> &[Arc<LocatedVal<PDFObjT>>] { self.contents.as_slice() }
     pub fn resources(&self) -> &Resources { &self.resources }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
 pub struct Resources {
-    fonts: BTreeMap<DictKey, Rc<FontDictionary>>,
+    fonts: BTreeMap<DictKey, Arc<FontDictionary>>,
 }
 impl Resources {
-    pub fn new(fonts: BTreeMap<DictKey, Rc<FontDictionary>>) -> Self { Self { fonts } }
-    pub fn fonts(&self) -> &BTreeMap<DictKey, Rc<FontDictionary>> { &self.fonts }
+    pub fn new(fonts: BTreeMap<DictKey, Arc<FontDictionary>>) -> Self { Self { fonts } }
+    pub fn fonts(&self) -> &BTreeMap<DictKey, Arc<FontDictionary>> { &self.fonts }
     /*
     pub fn has_nonembedded_fonts(&self, ctxt: &PDFObjContext) -> bool {
         let mut has = false;
@@ -187,7 +187,7 @@ pub enum FontEncoding {
     MacExpert,
     WinAnsi,
     Unknown(String),
-    Dict(Rc<LocatedVal<PDFObjT>>),
+    Dict(Arc<LocatedVal<PDFObjT>>),
 }
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum FontFlag {
@@ -240,7 +240,7 @@ pub const STANDARD_FONTS: &[&str] = &[
 pub struct FontDictionary {
     subtype:        FontType,
     basefont:       Vec<u8>,
-    fontdescriptor: Option<Rc<FontDescriptor>>,
+    fontdescriptor: Option<Arc<FontDescriptor>>,
     encoding:       Option<FontEncoding>,
     // ToUnicode
 }
@@ -298,7 +298,7 @@ impl FontDictionary {
 // queue.  These in-scope attributes are provided to the converters.
 // The leaf objects retain a copy of these attributes if they need to.
 
-type QEntry = (ObjectId, Option<Rc<Resources>>, Rc<LocatedVal<PDFObjT>>);
+type QEntry = (ObjectId, Option<Arc<Resources>>, Arc<LocatedVal<PDFObjT>>);
 pub struct ConversionQ {
     page_nodes: VecDeque<QEntry>,
     examined:   BTreeSet<ObjectId>,
@@ -311,7 +311,7 @@ impl ConversionQ {
         }
     }
 
-    fn add(&mut self, id: ObjectId, r: Option<Rc<Resources>>, o: Rc<LocatedVal<PDFObjT>>) {
+    fn add(&mut self, id: ObjectId, r: Option<Arc<Resources>>, o: Arc<LocatedVal<PDFObjT>>) {
         // There might be cycles in the page tree.  Our type-checker
         // cannot currently catch them, so we just handle them by
         // ensuring we don't keep adding the pages in the cycle to the
@@ -338,7 +338,7 @@ pub fn to_catalog(
             Some(r) => match ctxt.lookup_obj(r.id()) {
                 Some(o) => {
                     let root = to_root_page_tree_node(ctxt, q, dom, o)?;
-                    Ok(Catalog::new(Rc::new(root)))
+                    Ok(Catalog::new(Arc::new(root)))
                 },
                 None => Err(o.place(PageDOMError::CatalogConversionPagesIdNotFound)),
             },
@@ -350,7 +350,7 @@ pub fn to_catalog(
 
 // Table 30, page 103 (2020 edn)
 fn to_page_kids(
-    ctxt: &PDFObjContext, q: &mut ConversionQ, r: &Option<Rc<Resources>>, o: &LocatedVal<PDFObjT>,
+    ctxt: &PDFObjContext, q: &mut ConversionQ, r: &Option<Arc<Resources>>, o: &LocatedVal<PDFObjT>,
 ) -> Option<Vec<ObjectId>> {
     match o.val() {
         PDFObjT::Reference(rf) => {
@@ -365,8 +365,8 @@ fn to_page_kids(
                     PDFObjT::Reference(rf) => {
                         if let Some(o) = ctxt.lookup_obj(rf.id()) {
                             match r {
-                                None => q.add(rf.id(), None, Rc::clone(o)),
-                                Some(r) => q.add(rf.id(), Some(Rc::clone(r)), Rc::clone(o)),
+                                None => q.add(rf.id(), None, Arc::clone(o)),
+                                Some(r) => q.add(rf.id(), Some(Arc::clone(r)), Arc::clone(o)),
                             }
                         };
                         // kids is updated for completeness, even
@@ -404,7 +404,7 @@ fn to_root_page_tree_node(
             let res = match d.get_resolved_dict(ctxt, b"Resources") {
                 None => None,
                 Some(d) => match to_resources(ctxt, dom, d, o) {
-                    Ok(res) => Some(Rc::new(res)),
+                    Ok(res) => Some(Arc::new(res)),
                     Err(e) => return Err(e),
                 },
             };
@@ -427,7 +427,7 @@ fn to_root_page_tree_node(
 
 // Table 30, page 103 (2020 edn)
 fn to_page_tree_node(
-    ctxt: &PDFObjContext, q: &mut ConversionQ, dom: &mut DOMContext, r: &Option<Rc<Resources>>,
+    ctxt: &PDFObjContext, q: &mut ConversionQ, dom: &mut DOMContext, r: &Option<Arc<Resources>>,
     o: &LocatedVal<PDFObjT>,
 ) -> Result<PageTreeNode, LocatedVal<PageDOMError>> {
     match o.val() {
@@ -438,10 +438,10 @@ fn to_page_tree_node(
             };
             let res = match (d.get_resolved_dict(ctxt, b"Resources"), r) {
                 (Some(d), _) => match to_resources(ctxt, dom, d, o) {
-                    Ok(res) => Some(Rc::new(res)),
+                    Ok(res) => Some(Arc::new(res)),
                     Err(e) => return Err(e),
                 },
-                (_, Some(r)) => Some(Rc::clone(r)),
+                (_, Some(r)) => Some(Arc::clone(r)),
                 (_, _) => None,
             };
             let count = match d.get_usize(b"Count") {
@@ -462,31 +462,31 @@ fn to_page_tree_node(
 }
 
 fn to_page_content(
-    ctxt: &PDFObjContext, o: &Rc<LocatedVal<PDFObjT>>,
-) -> Option<Rc<LocatedVal<PDFObjT>>> {
+    ctxt: &PDFObjContext, o: &Arc<LocatedVal<PDFObjT>>,
+) -> Option<Arc<LocatedVal<PDFObjT>>> {
     match o.val() {
         PDFObjT::Reference(r) => ctxt
             .lookup_obj(r.id())
             .and_then(|o| to_page_content(ctxt, o)),
-        PDFObjT::Stream(_) => Some(Rc::clone(o)),
+        PDFObjT::Stream(_) => Some(Arc::clone(o)),
         _ => None,
     }
 }
 
 fn to_page_contents(
-    ctxt: &PDFObjContext, o: &Rc<LocatedVal<PDFObjT>>,
-) -> Option<Vec<Rc<LocatedVal<PDFObjT>>>> {
+    ctxt: &PDFObjContext, o: &Arc<LocatedVal<PDFObjT>>,
+) -> Option<Vec<Arc<LocatedVal<PDFObjT>>>> {
     match o.val() {
         PDFObjT::Reference(r) => ctxt
             .lookup_obj(r.id())
             .and_then(|o| to_page_contents(ctxt, o)),
-        PDFObjT::Stream(_) => Some(vec![Rc::clone(o)]),
+        PDFObjT::Stream(_) => Some(vec![Arc::clone(o)]),
         PDFObjT::Array(a) => {
             let mut v = Vec::new();
             for o in a.objs() {
                 match to_page_content(ctxt, o) {
                     None => return None,
-                    Some(cs) => v.push(Rc::clone(&cs)),
+                    Some(cs) => v.push(Arc::clone(&cs)),
                 }
             }
             Some(v)
@@ -497,7 +497,7 @@ fn to_page_contents(
 
 fn to_resource_font_value(
     ctxt: &PDFObjContext, dom: &mut DOMContext, o: &LocatedVal<PDFObjT>,
-) -> Result<BTreeMap<DictKey, Rc<FontDictionary>>, LocatedVal<PageDOMError>> {
+) -> Result<BTreeMap<DictKey, Arc<FontDictionary>>, LocatedVal<PageDOMError>> {
     let mut fonts = BTreeMap::new();
     match o.val() {
         // The value should be a dictionary mapping font resource
@@ -510,9 +510,9 @@ fn to_resource_font_value(
                         Some(o) => {
                             let fd = match obj_to_font_dict(ctxt, dom, o) {
                                 Err(e) => return Err(e),
-                                Ok(fd) => Rc::new(fd),
+                                Ok(fd) => Arc::new(fd),
                             };
-                            dom.font_dicts.insert(r.id(), Rc::clone(&fd));
+                            dom.font_dicts.insert(r.id(), Arc::clone(&fd));
                             fonts.insert(frn.clone(), fd);
                         },
                     },
@@ -521,7 +521,7 @@ fn to_resource_font_value(
                             Err(e) => return Err(o.place(e)),
                             Ok(fd) => fd,
                         };
-                        fonts.insert(frn.clone(), Rc::new(fd));
+                        fonts.insert(frn.clone(), Arc::new(fd));
                     },
                     _ => return Err(o.place(PageDOMError::FontResourceNotDict)),
                 }
@@ -557,7 +557,7 @@ fn to_resources(
 
 // Table 31, page 104 (2020 edn)
 fn to_page(
-    ctxt: &PDFObjContext, dom: &mut DOMContext, r: &Option<Rc<Resources>>, o: &LocatedVal<PDFObjT>,
+    ctxt: &PDFObjContext, dom: &mut DOMContext, r: &Option<Arc<Resources>>, o: &LocatedVal<PDFObjT>,
 ) -> Result<Page, LocatedVal<PageDOMError>> {
     match o.val() {
         PDFObjT::Dict(d) => {
@@ -567,11 +567,11 @@ fn to_page(
             };
             let res = match (d.get_resolved_dict(ctxt, b"Resources"), r) {
                 (Some(d), _) => match to_resources(ctxt, dom, d, o) {
-                    Ok(r) => Rc::new(r),
+                    Ok(r) => Arc::new(r),
                     Err(e) => return Err(e),
                 },
-                (_, Some(r)) => Rc::clone(r),
-                (_, _) => Rc::new(Resources::default()),
+                (_, Some(r)) => Arc::clone(r),
+                (_, _) => Arc::new(Resources::default()),
             };
             let contents = match d.get(b"Contents") {
                 None => return Err(o.place(PageDOMError::PageNodeConversionNoContents)),
@@ -640,7 +640,7 @@ fn to_font_descriptor(d: &DictT) -> Result<FontDescriptor, PageDOMError> {
 
 // Encoding: Section 7.6.5, page 322 (2020 edn)
 fn to_encoding(
-    ctxt: &PDFObjContext, o: &Rc<LocatedVal<PDFObjT>>,
+    ctxt: &PDFObjContext, o: &Arc<LocatedVal<PDFObjT>>,
 ) -> Result<FontEncoding, LocatedVal<PageDOMError>> {
     match o.val() {
         PDFObjT::Name(n) => match std::str::from_utf8(n.val()) {
@@ -650,7 +650,7 @@ fn to_encoding(
             Ok(s) => Ok(FontEncoding::Unknown(s.to_string())),
             Err(_) => Err(o.place(PageDOMError::FontDictConversionUnknownEncoding)),
         },
-        PDFObjT::Dict(_) => Ok(FontEncoding::Dict(Rc::clone(o))),
+        PDFObjT::Dict(_) => Ok(FontEncoding::Dict(Arc::clone(o))),
         PDFObjT::Reference(r) => match ctxt.lookup_obj(r.id()) {
             Some(o) => to_encoding(ctxt, o),
             None => Err(o.place(PageDOMError::FontDictConversionBadEncoding)),
@@ -662,7 +662,7 @@ fn to_encoding(
 // Type 1 fonts: Table 109, page 313 (2020 edn)
 // Type 3 fonts: Table 110, page 317 (2020 edn)
 fn obj_to_font_dict(
-    ctxt: &PDFObjContext, dom: &mut DOMContext, o: &Rc<LocatedVal<PDFObjT>>,
+    ctxt: &PDFObjContext, dom: &mut DOMContext, o: &Arc<LocatedVal<PDFObjT>>,
 ) -> Result<FontDictionary, LocatedVal<PageDOMError>> {
     match o.val() {
         PDFObjT::Dict(d) => match to_font_dict(ctxt, dom, d) {
@@ -698,13 +698,13 @@ fn to_font_dict(
         Some(o) => match o.val() {
             PDFObjT::Dict(d) => {
                 let fd = to_font_descriptor(d)?;
-                Some(Rc::new(fd))
+                Some(Arc::new(fd))
             },
             PDFObjT::Reference(r) =>
             // Resolve this if we haven't already.
             {
                 match dom.font_descrs().get(&r.id()) {
-                    Some(fd) => Some(Rc::clone(fd)),
+                    Some(fd) => Some(Arc::clone(fd)),
                     None => match ctxt.lookup_obj(r.id()) {
                         None => {
                             return Err(PageDOMError::FontDescrConversionUnknownObjectId(r.id()))
@@ -712,8 +712,8 @@ fn to_font_dict(
                         Some(o) => match o.val() {
                             PDFObjT::Dict(d) => {
                                 let fd = to_font_descriptor(d)?;
-                                let fd = Rc::new(fd);
-                                dom.font_descrs.insert(r.id(), Rc::clone(&fd));
+                                let fd = Arc::new(fd);
+                                dom.font_descrs.insert(r.id(), Arc::clone(&fd));
                                 Some(fd)
                             },
                             _ => return Err(PageDOMError::FontDescrConversionBadFontDescr),
@@ -802,7 +802,7 @@ pub fn to_page_dom(
                         match ptn {
                             Ok(n) => {
                                 // ignore return value
-                                dom.pages.insert(id, PageKid::Node(Rc::new(n)));
+                                dom.pages.insert(id, PageKid::Node(Arc::new(n)));
                             },
                             Err(e) => return Err(e),
                         }
@@ -811,7 +811,7 @@ pub fn to_page_dom(
                         let pn = to_page(ctxt, &mut dom, &r, &o);
                         match pn {
                             Ok(p) => {
-                                dom.pages.insert(id, PageKid::Leaf(Rc::new(p)));
+                                dom.pages.insert(id, PageKid::Leaf(Arc::new(p)));
                             },
                             Err(e) => return Err(e),
                         }
@@ -277,6 +277,9 @@ impl RealT {
         let conv = <i64 as TryFrom<i128>>::try_from(self.0);
         conv.unwrap()
     }
+
+    // the (numerator, denominator) pair
+    pub fn fraction(&self) -> (i128, i128) { (self.0, self.1) }
 }
 
 pub struct RealP;
@@ -542,6 +545,78 @@ impl ParsleyParser for RawLiteralString {
     }
 }
 
+// Converts the raw representation of a literal string into the bytes
+// it denotes, by processing the backslash escapes and normalizing
+// end-of-line markers to '\n' (Section 7.3.4.2).  Unknown escapes
+// denote the escaped character itself.
+pub fn unescape_literal_string(raw: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let b = raw[i];
+        i += 1;
+        match b {
+            b'\\' if i < raw.len() => {
+                let e = raw[i];
+                i += 1;
+                match e {
+                    b'n' => v.push(b'\n'),
+                    b'r' => v.push(b'\r'),
+                    b't' => v.push(b'\t'),
+                    b'b' => v.push(b'\x08'),
+                    b'f' => v.push(b'\x0c'),
+                    b'0' ..= b'7' => {
+                        // up to three octal digits; overflow is ignored
+                        let mut o = u32::from(e - b'0');
+                        let mut n = 1;
+                        while n < 3 && i < raw.len() && (b'0' ..= b'7').contains(&raw[i]) {
+                            o = 8 * o + u32::from(raw[i] - b'0');
+                            i += 1;
+                            n += 1
+                        }
+                        v.push((o & 0xff) as u8)
+                    },
+                    b'\r' => {
+                        // line continuation
+                        if raw.get(i) == Some(&b'\n') {
+                            i += 1
+                        }
+                    },
+                    b'\n' => (), // line continuation
+                    e => v.push(e),
+                }
+            },
+            b'\\' => (), // a trailing backslash is ignored
+            b'\r' => {
+                if raw.get(i) == Some(&b'\n') {
+                    i += 1
+                }
+                v.push(b'\n')
+            },
+            b => v.push(b),
+        }
+    }
+    v
+}
+
+// The inverse of unescape_literal_string: converts bytes into a raw
+// literal string representation that denotes them.
+pub fn escape_literal_string(bytes: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(bytes.len());
+    for b in bytes {
+        match b {
+            b'\\' | b'(' | b')' => {
+                v.push(b'\\');
+                v.push(*b)
+            },
+            // escaped to avoid end-of-line normalization
+            b'\r' => v.extend_from_slice(b"\\r"),
+            b => v.push(*b),
+        }
+    }
+    v
+}
+
 // Raw names: does not perform UTF decoding, and the representation
 // does not include the leading '/'.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -783,6 +858,30 @@ impl StreamContentT {
     pub fn content(&self) -> &[u8] { &self.content }
 }
 
+// Parses the 'stream' keyword and the EOL that follows it.
+fn parse_stream_marker(buf: &mut dyn ParseBufferT) -> ParseResult<()> {
+    let start = buf.get_cursor();
+    let is_stream = buf.exact(b"stream");
+    if is_stream.is_err() {
+        let err = ErrorKind::GuardError("not at stream content".to_string());
+        return Err(locate_value(err, start, start))
+    }
+    if buf.peek() == Some(13) {
+        // '\r'
+        buf.incr_cursor_unsafe();
+    }
+    if buf.peek() == Some(10) {
+        // '\n'
+        buf.incr_cursor_unsafe();
+        Ok(())
+    } else {
+        let end = buf.get_cursor();
+        let err = ErrorKind::GuardError("not a valid stream marker".to_string());
+        buf.set_cursor_unsafe(start);
+        Err(locate_value(err, start, end))
+    }
+}
+
 pub struct StreamContentP {
     length:                   usize,
     eol_after_stream_content: bool,
@@ -804,24 +903,7 @@ impl ParsleyParser for StreamContentP {
     // has been consumed.
     fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
         let start = buf.get_cursor();
-        let is_stream = buf.exact(b"stream");
-        if is_stream.is_err() {
-            let err = ErrorKind::GuardError("not at stream content".to_string());
-            return Err(locate_value(err, start, start))
-        }
-        if buf.peek() == Some(13) {
-            // '\r'
-            buf.incr_cursor_unsafe();
-        }
-        if buf.peek() == Some(10) {
-            // '\n'
-            buf.incr_cursor_unsafe();
-        } else {
-            let end = buf.get_cursor();
-            let err = ErrorKind::GuardError("not a valid stream marker".to_string());
-            buf.set_cursor_unsafe(start);
-            return Err(locate_value(err, start, end))
-        }
+        parse_stream_marker(buf)?;
         let stream_start_cursor = buf.get_cursor();
 
         // extract the specified length of content
@@ -871,13 +953,61 @@ impl ParsleyParser for StreamContentP {
     }
 }
 
+// Stream content whose extent is found by scanning, for use when the
+// declared length of the stream is wrong or unknown.  The content
+// ends before the first 'endstream' keyword, or before the first
+// 'endobj' keyword if that comes earlier, and excludes the EOL
+// preceding the keyword.  The cursor is left after 'endstream', or at
+// 'endobj'.
+pub struct StreamContentScanP;
+
+impl ParsleyParser for StreamContentScanP {
+    type T = LocatedVal<StreamContentT>;
+
+    // This assumes that the whitespace before the 'stream' keyword
+    // has been consumed.
+    fn parse(&mut self, buf: &mut dyn ParseBufferT) -> ParseResult<Self::T> {
+        let start = buf.get_cursor();
+        parse_stream_marker(buf)?;
+        let stream_start_cursor = buf.get_cursor();
+
+        let rest = buf.buf();
+        let find = |tag: &[u8]| rest.windows(tag.len()).position(|w| w == tag);
+        let (len, tag_len) = match (find(b"endstream"), find(b"endobj")) {
+            (Some(s), Some(o)) if o < s => (o, 0),
+            (Some(s), _) => (s, 9),
+            (None, Some(o)) => (o, 0),
+            (None, None) => {
+                let end = buf.get_cursor();
+                let err = ErrorKind::GuardError("no end found for stream content".to_string());
+                buf.set_cursor_unsafe(start);
+                return Err(locate_value(err, start, end))
+            },
+        };
+        let mut size = len;
+        if rest[.. size].ends_with(b"\n") {
+            size -= 1
+        }
+        if rest[.. size].ends_with(b"\r") {
+            size -= 1
+        }
+        let v = Vec::from(&rest[.. size]);
+        buf.set_cursor_unsafe(stream_start_cursor + len + tag_len);
+
+        let stream = StreamContentT::new(stream_start_cursor, size, v);
+        let end = buf.get_cursor();
+        Ok(LocatedVal::new(stream, start, end))
+    }
+}
+
 #[cfg(test)]
 mod test_pdf_prim {
     use super::super::super::pcore::parsebuffer::{
         locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParsleyParser,
     };
+    use super::{escape_literal_string, unescape_literal_string};
     use super::{Boolean, Comment, Null, WhitespaceEOL, WhitespaceNoEOL};
-    use super::{HexString, RawLiteralString, StreamContentP, StreamContentT};
+    use super::{HexString, RawLiteralString, StreamContentP, StreamContentScanP, StreamContentT};
     use super::{IntegerP, IntegerT, NameP, NameT, RealP, RealT};
 
     #[test]
@@ -1395,6 +1525,37 @@ mod test_pdf_prim {
         assert_eq!(pb.get_cursor(), 10);
     }
 
+    #[test]
+    fn unescape_lit_string() {
+        assert_eq!(unescape_literal_string(b"abc"), b"abc".to_vec());
+        assert_eq!(
+            unescape_literal_string(b"a\\n\\r\\t\\b\\f\\(\\)\\\\"),
+            b"a\n\r\t\x08\x0c()\\".to_vec()
+        );
+        assert_eq!(
+            unescape_literal_string(b"\\0531\\5\\1234\\777"),
+            b"+1\x05S4\xff".to_vec()
+        );
+        assert_eq!(
+            unescape_literal_string(b"a\\\r\nb\\\nc\\q"),
+            b"abcq".to_vec()
+        );
+        assert_eq!(
+            unescape_literal_string(b"a\r\nb\rc\n"),
+            b"a\nb\nc\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn escape_lit_string() {
+        assert_eq!(
+            escape_literal_string(b"a(b)\\c\r\n"),
+            b"a\\(b\\)\\\\c\\r\n".to_vec()
+        );
+        let all: Vec<u8> = (0 ..= 255).collect();
+        assert_eq!(unescape_literal_string(&escape_literal_string(&all)), all);
+    }
+
     #[test]
     fn raw_lit_string_escaped_escapes() {
         let mut lit = RawLiteralString;
@@ -1672,4 +1833,46 @@ mod test_pdf_prim {
         );
         assert_eq!(pb.get_cursor(), 21);
     }
+
+    #[test]
+    fn stream_content_scan() {
+        let mut sc = StreamContentScanP;
+
+        //                 012345 6789 0123456789
+        let v = Vec::from("stream\nabc\nendstream\nendobj".as_bytes());
+        let mut pb = ParseBuffer::new(v);
+        assert_eq!(
+            sc.parse(&mut pb),
+            Ok(LocatedVal::new(
+                StreamContentT::new(7, 3, Vec::from("abc")),
+                0,
+                20
+            ))
+        );
+        assert_eq!(pb.get_cursor(), 20);
+
+        // missing endstream
+        //                 012345 6 7890 1 2
+        let v = Vec::from("stream\r\nabc\r\nendobj endstream".as_bytes());
+        let mut pb = ParseBuffer::new(v);
+        assert_eq!(
+            sc.parse(&mut pb),
+            Ok(LocatedVal::new(
+                StreamContentT::new(8, 3, Vec::from("abc")),
+                0,
+                13
+            ))
+        );
+        assert_eq!(pb.get_cursor(), 13);
+
+        let v = Vec::from("stream\nabc".as_bytes());
+        let mut pb = ParseBuffer::new(v);
+        let e = locate_value(
+            ErrorKind::GuardError("no end found for stream content".to_string()),
+            0,
+            7,
+        );
+        assert_eq!(sc.parse(&mut pb), Err(e));
+        assert_eq!(pb.get_cursor(), 0);
+    }
 }
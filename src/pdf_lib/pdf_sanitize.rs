@@ -0,0 +1,456 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Content disarm and reconstruction: a document is rebuilt from its
+// parsed objects without active content (JavaScript, actions that
+// run on open or on events, launch actions, embedded files, rich
+// media and XFA forms), and written out only if the result passes
+// the type check of the document catalog.  Objects that are no
+// longer reachable from the catalog are dropped, and since the file
+// is written afresh from the parsed objects, so are comments and any
+// data around the file proper.  Each removal is logged with its
+// reason.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use super::super::pcore::parsebuffer::LocatedVal;
+use super::catalog::catalog_type;
+use super::pdf_obj::{
+    ArrayT, DictKey, DictT, IndirectT, ObjectId, PDFObjContext, PDFObjT, StreamT,
+};
+use super::pdf_prim::StreamContentT;
+use super::pdf_type_check::{check_type, TypeCheckContext};
+use super::pdf_writer::PDFWriter;
+
+// Dictionary keys whose values are removed wherever they appear.
+const UNSAFE_KEYS: &[(&[u8], &str)] = &[
+    (b"OpenAction", "document open action"),
+    (b"AA", "additional actions"),
+    (b"JavaScript", "JavaScript"),
+    (b"JS", "JavaScript"),
+    (b"EmbeddedFiles", "embedded files"),
+    (b"EF", "embedded file"),
+    (b"XFA", "XFA form"),
+];
+
+// Action types that are removed wherever they appear.
+const UNSAFE_ACTIONS: &[(&[u8], &str)] = &[
+    (b"JavaScript", "JavaScript action"),
+    (b"Launch", "launch action"),
+    (b"RichMediaExecute", "rich media action"),
+];
+
+// A removal from the original document.  The object is that of the
+// indirect object containing the removed value, and the path locates
+// the value within it.
+#[derive(Debug, PartialEq)]
+pub struct Removal {
+    object: Option<ObjectId>,
+    path:   String,
+    reason: String,
+}
+
+impl Removal {
+    pub fn new(object: Option<ObjectId>, path: &str, reason: &str) -> Removal {
+        Removal {
+            object,
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+    pub fn object(&self) -> Option<ObjectId> { self.object }
+    pub fn path(&self) -> &str { &self.path }
+    pub fn reason(&self) -> &str { &self.reason }
+
+    pub fn to_json(&self) -> Value {
+        let object = self.object.map(|(n, g)| format!("{} {}", n, g));
+        json!({"object": object, "path": self.path, "reason": self.reason})
+    }
+}
+
+// The log of all removals as a JSON array.
+pub fn removals_to_json(log: &[Removal]) -> Value {
+    Value::Array(log.iter().map(|r| r.to_json()).collect())
+}
+
+struct Sanitizer<'a> {
+    ctxt: &'a PDFObjContext,
+    id:   ObjectId,
+    log:  Vec<Removal>,
+}
+
+impl Sanitizer<'_> {
+    fn remove(&mut self, path: &str, reason: &str) {
+        self.log.push(Removal::new(Some(self.id), path, reason))
+    }
+
+    // The reason for removing a value, if it (or the object it
+    // refers to) is active content.
+    fn unsafe_reason(&self, o: &PDFObjT) -> Option<&'static str> {
+        let o = match o {
+            PDFObjT::Reference(r) => self.ctxt.lookup_obj(r.id())?.val(),
+            _ => o,
+        };
+        let d = match o {
+            PDFObjT::Dict(d) => d,
+            PDFObjT::Stream(s) => s.dict().val(),
+            _ => return None,
+        };
+        if let Some(s) = d.get_name(b"S") {
+            if let Some((_, r)) = UNSAFE_ACTIONS.iter().find(|(n, _)| *n == s) {
+                return Some(r)
+            }
+        }
+        match d.get_name(b"Subtype") {
+            Some(b"RichMedia") => Some("rich media annotation"),
+            _ => None,
+        }
+    }
+
+    // Removes the unsafe entries of a dictionary, returning None if
+    // nothing was removed.
+    fn clean_dict(&mut self, d: &DictT, path: &str) -> Option<DictT> {
+        let mut map = BTreeMap::new();
+        let mut changed = false;
+        for (k, v) in d.map() {
+            let p = format!("{}/{}", path, String::from_utf8_lossy(k.as_slice()));
+            if let Some((_, r)) = UNSAFE_KEYS.iter().find(|(n, _)| *n == k.as_slice()) {
+                self.remove(&p, r);
+                changed = true;
+                continue
+            }
+            if let Some(r) = self.unsafe_reason(v.val()) {
+                self.remove(&p, r);
+                changed = true;
+                continue
+            }
+            let nv = self.clean(v, &p);
+            changed |= !Arc::ptr_eq(&nv, v);
+            map.insert(DictKey::new(k.as_slice().to_vec()), nv);
+        }
+        if changed {
+            Some(DictT::new(map))
+        } else {
+            None
+        }
+    }
+
+    // Returns the object with its unsafe content removed, which is
+    // the object itself if there was none.
+    fn clean(&mut self, o: &Arc<LocatedVal<PDFObjT>>, path: &str) -> Arc<LocatedVal<PDFObjT>> {
+        let n = match o.val() {
+            PDFObjT::Dict(d) => match self.clean_dict(d, path) {
+                Some(d) => PDFObjT::Dict(d),
+                None => return Arc::clone(o),
+            },
+            PDFObjT::Stream(s) => {
+                let dict = s.dict();
+                match self.clean_dict(dict.val(), path) {
+                    Some(d) => {
+                        let c = s.stream();
+                        let content = StreamContentT::new(
+                            c.val().start(),
+                            c.val().size(),
+                            s.content().to_vec(),
+                        );
+                        PDFObjT::Stream(StreamT::new(Arc::new(dict.place(d)), c.place(content)))
+                    },
+                    None => return Arc::clone(o),
+                }
+            },
+            PDFObjT::Array(a) => {
+                let mut objs = Vec::new();
+                let mut changed = false;
+                for (i, e) in a.objs().iter().enumerate() {
+                    let p = format!("{}[{}]", path, i);
+                    if let PDFObjT::Comment(_) = e.val() {
+                        self.remove(&p, "comment");
+                        changed = true;
+                        continue
+                    }
+                    if let Some(r) = self.unsafe_reason(e.val()) {
+                        self.remove(&p, r);
+                        changed = true;
+                        continue
+                    }
+                    let ne = self.clean(e, &p);
+                    changed |= !Arc::ptr_eq(&ne, e);
+                    objs.push(ne)
+                }
+                if !changed {
+                    return Arc::clone(o)
+                }
+                PDFObjT::Array(ArrayT::new(objs))
+            },
+            _ => return Arc::clone(o),
+        };
+        Arc::new(o.place(n))
+    }
+}
+
+// The removals of the data before the header and after the final
+// end-of-file marker.
+fn file_removals(data: &[u8]) -> Vec<Removal> {
+    let mut log = Vec::new();
+    let find = |pat: &[u8]| data.windows(pat.len()).position(|w| w == pat);
+    if let Some(ofs) = find(b"%PDF-") {
+        if ofs > 0 {
+            let path = format!("bytes 0..{}", ofs);
+            log.push(Removal::new(None, &path, "data before the PDF header"))
+        }
+    }
+    if let Some(ofs) = data.windows(5).rposition(|w| w == b"%%EOF") {
+        let end = ofs + 5;
+        let rest = &data[end ..];
+        if rest.iter().any(|b| !b" \t\r\n\x0c\0".contains(b)) {
+            let path = format!("bytes {}..{}", end, data.len());
+            log.push(Removal::new(None, &path, "data after the final %%EOF"))
+        }
+    }
+    log
+}
+
+// Rebuilds the document with the given root (catalog) from the
+// objects in the context, given the data of the original file.  On
+// success, the rebuilt file is returned along with the log of the
+// removals.  Objects that cannot be loaded, e.g. those of a lazy
+// context that fail to parse, are dropped.
+pub fn sanitize(
+    ctxt: &PDFObjContext, root: ObjectId, data: &[u8],
+) -> Result<(Vec<u8>, Vec<Removal>), String> {
+    let mut clean = PDFObjContext::new(50);
+    let mut logs = BTreeMap::new();
+    let mut dropped = Vec::new();
+    for id in ctxt.obj_ids() {
+        let o = match ctxt.load_obj(id) {
+            Ok(Some(o)) => o,
+            Ok(None) => {
+                dropped.push(Removal::new(Some(id), "", "object not found"));
+                continue
+            },
+            Err(e) => {
+                let reason = format!("object cannot be parsed: {}", e.msg());
+                dropped.push(Removal::new(Some(id), "", &reason));
+                continue
+            },
+        };
+        let mut s = Sanitizer {
+            ctxt,
+            id,
+            log: Vec::new(),
+        };
+        let o = s.clean(o, "");
+        clean.register_obj(&LocatedVal::new(IndirectT::new(id.0, id.1, o), 0, 0));
+        logs.insert(id, s.log);
+    }
+
+    let mut log = file_removals(data);
+    log.extend(dropped);
+    let reachable = PDFWriter::new(&clean).reachable(&[root]);
+    for (id, l) in logs {
+        if reachable.contains(&id) {
+            log.extend(l)
+        } else {
+            clean.remove_obj(id);
+            log.push(Removal::new(
+                Some(id),
+                "",
+                "not reachable from the document catalog",
+            ))
+        }
+    }
+
+    let root_obj = match clean.lookup_obj(root) {
+        Some(o) => Arc::clone(o),
+        None => {
+            return Err(format!(
+                "root object ({},{}) is not defined",
+                root.0, root.1
+            ))
+        },
+    };
+    let mut tctx = TypeCheckContext::new();
+    let typ = catalog_type(&mut tctx);
+    if let Some(err) = check_type(&clean, &tctx, root_obj, typ) {
+        return Err(format!(
+            "sanitized document fails the catalog type check at ({}, {}): {:?}",
+            err.start(),
+            err.end(),
+            err.val()
+        ))
+    }
+    let pdf = PDFWriter::new(&clean).write_file(root)?;
+    Ok((pdf, log))
+}
+
+#[cfg(test)]
+mod test_sanitize {
+    use std::path::Path;
+
+    use super::{removals_to_json, sanitize, Removal};
+    use crate::pdf_lib::pdf_obj::PDFObjT;
+    use crate::pdf_lib::pdf_traverse_xref::{parse_data, parse_data_with_options, ParseOptions};
+
+    const DOC: &[u8] = b"junk before the header
+%PDF-1.7
+1 0 obj
+<< /Type /Catalog /Pages 2 0 R /OpenAction 5 0 R
+   /AA << /WC << /S /JavaScript /JS (app.alert(1)) >> >>
+   /Names << /JavaScript 6 0 R /EmbeddedFiles 7 0 R >>
+   /AcroForm << /Fields [] /XFA 8 0 R >> >>
+endobj
+2 0 obj
+<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 300 144] >>
+endobj
+3 0 obj
+<< /Type /Page /Parent 2 0 R /Resources << >> /Contents 4 0 R
+   /Annots [9 0 R 10 0 R] >>
+endobj
+4 0 obj
+<< /Length 6 >>
+stream
+BT
+ET
+endstream
+endobj
+5 0 obj
+<< /S /JavaScript /JS (app.alert(2)) >>
+endobj
+6 0 obj
+<< /Names [(a) 5 0 R] >>
+endobj
+7 0 obj
+<< /Names [] >>
+endobj
+8 0 obj
+<< /Length 0 >>
+stream
+
+endstream
+endobj
+9 0 obj
+<< /Type /Annot /Subtype /Link /Rect [0 0 10 10]
+   /A << /S /Launch /F (calc.exe) >> >>
+endobj
+10 0 obj
+<< /Type /Annot /Subtype /RichMedia /Rect [0 0 10 10] >>
+endobj
+11 0 obj
+(unreferenced)
+endobj
+trailer
+<< /Root 1 0 R /Size 12 >>
+%%EOF
+junk after the end
+";
+
+    #[test]
+    fn test_sanitize() {
+        let mut opts = ParseOptions::new();
+        opts.set_recover_xref(true);
+        let (_, ctxt, root) = parse_data_with_options(Path::new("doc.pdf"), DOC, &opts).unwrap();
+        let (pdf, log) = sanitize(&ctxt, root, DOC).unwrap();
+        let expected = [
+            (None, "bytes 0..23", "data before the PDF header"),
+            (None, "bytes 916..936", "data after the final %%EOF"),
+            (Some((1, 0)), "/AA", "additional actions"),
+            (Some((1, 0)), "/AcroForm/XFA", "XFA form"),
+            (Some((1, 0)), "/Names/EmbeddedFiles", "embedded files"),
+            (Some((1, 0)), "/Names/JavaScript", "JavaScript"),
+            (Some((1, 0)), "/OpenAction", "document open action"),
+            (Some((3, 0)), "/Annots[1]", "rich media annotation"),
+            (Some((5, 0)), "", "not reachable from the document catalog"),
+            (Some((6, 0)), "", "not reachable from the document catalog"),
+            (Some((7, 0)), "", "not reachable from the document catalog"),
+            (Some((8, 0)), "", "not reachable from the document catalog"),
+            (Some((9, 0)), "/A", "launch action"),
+            (Some((10, 0)), "", "not reachable from the document catalog"),
+            (Some((11, 0)), "", "not reachable from the document catalog"),
+        ];
+        let expected: Vec<Removal> = expected
+            .iter()
+            .map(|(o, p, r)| Removal::new(*o, p, r))
+            .collect();
+        assert_eq!(log, expected);
+        let json = removals_to_json(&log);
+        assert_eq!(json.as_array().unwrap().len(), expected.len());
+        assert_eq!(json[2]["object"], "1 0");
+        assert_eq!(json[2]["path"], "/AA");
+
+        let (_, ctxt2, root2) = parse_data(Path::new("clean.pdf"), &pdf).unwrap();
+        assert_eq!(root2, root);
+        assert_eq!(
+            ctxt2.obj_ids(),
+            vec![(1, 0), (2, 0), (3, 0), (4, 0), (9, 0)]
+        );
+        let annot = ctxt2.lookup_obj((9, 0)).unwrap();
+        match annot.val() {
+            PDFObjT::Dict(d) => assert!(d.get(b"A").is_none()),
+            _ => panic!("annotation is not a dictionary"),
+        }
+
+        // a document without active content is only rebuilt
+        let (pdf2, log) = sanitize(&ctxt2, root2, &pdf).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(pdf2, pdf);
+    }
+
+    #[test]
+    fn test_broken_object() {
+        // the xref entry of object 5 points into the middle of
+        // object 4, which only fails when the object is loaded lazily
+        let mut v = b"%PDF-1.7\n".to_vec();
+        let mut ofs = Vec::new();
+        for o in &[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 300 144] >>",
+            "<< /Type /Page /Parent 2 0 R /Resources << >> /Contents 4 0 R >>",
+            "<< /Length 6 >>\nstream\nBT\nET\nendstream",
+        ] {
+            ofs.push(v.len());
+            v.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", ofs.len(), o).as_bytes());
+        }
+        ofs.push(ofs[3] + 20);
+        let xref = v.len();
+        v.extend_from_slice(b"xref\n0 6\n0000000000 65535 f \n");
+        for o in &ofs {
+            v.extend_from_slice(format!("{:010} 00000 n \n", o).as_bytes())
+        }
+        v.extend_from_slice(
+            format!(
+                "trailer\n<< /Root 1 0 R /Size 6 >>\nstartxref\n{}\n%%EOF\n",
+                xref
+            )
+            .as_bytes(),
+        );
+
+        let mut opts = ParseOptions::new();
+        opts.set_lazy(true);
+        let path = Path::new("broken.pdf");
+        let (_, ctxt, root) = parse_data_with_options(path, &v, &opts).unwrap();
+        let (pdf, log) = sanitize(&ctxt, root, &v).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].object(), Some((5, 0)));
+        assert!(log[0].reason().starts_with("object cannot be parsed"));
+        let (_, ctxt2, _) = parse_data(Path::new("clean.pdf"), &pdf).unwrap();
+        assert_eq!(ctxt2.obj_ids(), vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+}
@@ -0,0 +1,654 @@
+// Copyright (c) 2019-2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// The Standard security handler (Section 7.6.4), for revisions 2-6.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::pcore::parsebuffer::{locate_value, ErrorKind, LocatedVal, ParseResult};
+use crate::pdf_lib::pdf_obj::{ArrayT, DictKey, DictT, ObjectId, PDFObjT, StreamT, StringForm};
+use crate::pdf_lib::pdf_prim::{escape_literal_string, unescape_literal_string, StreamContentT};
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+// The padding string used for password processing (Algorithm 2).
+const PASSWORD_PAD: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+// The encryption algorithm applied to strings and streams.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CryptMethod {
+    Identity, // no encryption
+    RC4,
+    AESV2, // AES-128
+    AESV3, // AES-256
+}
+
+// Returns the bytes denoted by a string object.  Hex strings are
+// decoded by the parser, but literal strings are kept in their raw
+// escaped form.
+pub fn string_bytes(obj: &LocatedVal<PDFObjT>) -> Option<Vec<u8>> {
+    match obj.val() {
+        PDFObjT::String(s, StringForm::Literal) => Some(unescape_literal_string(s)),
+        PDFObjT::String(s, StringForm::Hex) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s: Vec<u8> = (0 ..= 255).collect();
+    let mut j: u8 = 0;
+    for i in 0 .. 256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|b| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            let k = s[s[i as usize].wrapping_add(s[j as usize]) as usize];
+            b ^ k
+        })
+        .collect()
+}
+
+fn md5(data: &[&[u8]]) -> Vec<u8> {
+    let mut h = Md5::new();
+    for d in data {
+        h.update(d)
+    }
+    h.finalize().to_vec()
+}
+
+fn pad_password(pw: &[u8]) -> Vec<u8> {
+    let mut v: Vec<u8> = pw.iter().take(32).cloned().collect();
+    let n = 32 - v.len();
+    v.extend_from_slice(&PASSWORD_PAD[.. n]);
+    v
+}
+
+fn xor_key(key: &[u8], i: u8) -> Vec<u8> { key.iter().map(|b| b ^ i).collect() }
+
+// Decrypts AES-CBC data that is prefixed by its initialization
+// vector, and removes the padding.
+fn aes_decrypt(key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 16 || !data.len().is_multiple_of(16) {
+        return Err(format!(
+            "invalid length {} for AES-encrypted data",
+            data.len()
+        ))
+    }
+    let (iv, data) = data.split_at(16);
+    let mut buf = data.to_vec();
+    let res = match key.len() {
+        16 => Aes128CbcDec::new_from_slices(key, iv)
+            .map_err(|e| e.to_string())?
+            .decrypt_padded_mut::<Pkcs7>(&mut buf)
+            .map(|d| d.len()),
+        _ => Aes256CbcDec::new_from_slices(key, iv)
+            .map_err(|e| e.to_string())?
+            .decrypt_padded_mut::<Pkcs7>(&mut buf)
+            .map(|d| d.len()),
+    };
+    match res {
+        Ok(n) => {
+            buf.truncate(n);
+            Ok(buf)
+        },
+        Err(_) => Err("invalid padding in AES-encrypted data".to_string()),
+    }
+}
+
+// Algorithm 2.B: the hash used for revision 6 (and the plain SHA-256
+// for revision 5).
+fn hash_r6(revision: usize, pw: &[u8], salt: &[u8], udata: &[u8]) -> Vec<u8> {
+    let mut k = Sha256::new()
+        .chain_update(pw)
+        .chain_update(salt)
+        .chain_update(udata)
+        .finalize()
+        .to_vec();
+    if revision == 5 {
+        return k
+    }
+    let mut round = 0;
+    loop {
+        let mut k1 = Vec::with_capacity(64 * (pw.len() + k.len() + udata.len()));
+        for _ in 0 .. 64 {
+            k1.extend_from_slice(pw);
+            k1.extend_from_slice(&k);
+            k1.extend_from_slice(udata);
+        }
+        let len = k1.len();
+        // k1 is a multiple of 64 bytes, so no padding is needed.
+        let e = Aes128CbcEnc::new_from_slices(&k[.. 16], &k[16 .. 32])
+            .unwrap()
+            .encrypt_padded_mut::<NoPadding>(&mut k1, len)
+            .unwrap()
+            .to_vec();
+        let sum: usize = e[.. 16].iter().map(|b| *b as usize).sum();
+        k = match sum % 3 {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && (*e.last().unwrap() as usize) <= round - 32 {
+            break
+        }
+    }
+    k.truncate(32);
+    k
+}
+
+fn aes256_decrypt_key(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut buf = data.to_vec();
+    Aes256CbcDec::new_from_slices(key, &[0u8; 16])
+        .unwrap()
+        .decrypt_padded_mut::<NoPadding>(&mut buf)
+        .unwrap()
+        .to_vec()
+}
+
+// The parameters of the /Encrypt dictionary that are used for key
+// derivation and password checks.
+struct EncryptParams {
+    revision:         usize,
+    key_len:          usize, // in bytes
+    o:                Vec<u8>,
+    u:                Vec<u8>,
+    oe:               Vec<u8>,
+    ue:               Vec<u8>,
+    p:                i32,
+    id:               Vec<u8>,
+    encrypt_metadata: bool,
+}
+
+impl EncryptParams {
+    // Algorithm 2: computes the file key from a user password.
+    fn file_key(&self, pw: &[u8]) -> Vec<u8> {
+        let p = self.p.to_le_bytes();
+        let mut parts: Vec<&[u8]> = Vec::new();
+        let padded = pad_password(pw);
+        parts.push(&padded);
+        parts.push(&self.o[.. self.o.len().min(32)]);
+        parts.push(&p);
+        parts.push(&self.id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            parts.push(&[0xff, 0xff, 0xff, 0xff]);
+        }
+        let mut h = md5(&parts);
+        if self.revision >= 3 {
+            for _ in 0 .. 50 {
+                h = md5(&[&h[.. self.key_len]]);
+            }
+        }
+        h.truncate(self.key_len);
+        h
+    }
+
+    // Algorithms 4 and 5: computes the /U value for a file key.
+    fn u_value(&self, key: &[u8]) -> Vec<u8> {
+        if self.revision == 2 {
+            return rc4(key, &PASSWORD_PAD)
+        }
+        let h = md5(&[&PASSWORD_PAD, &self.id]);
+        let mut u = rc4(key, &h);
+        for i in 1 .. 20 {
+            u = rc4(&xor_key(key, i), &u)
+        }
+        u
+    }
+
+    // Algorithm 6: returns the file key if the user password is correct.
+    fn auth_user(&self, pw: &[u8]) -> Option<Vec<u8>> {
+        if self.revision >= 5 {
+            if self.u.len() < 48 || self.ue.len() < 32 {
+                return None
+            }
+            let pw = &pw[.. pw.len().min(127)];
+            if hash_r6(self.revision, pw, &self.u[32 .. 40], &[]) != self.u[.. 32] {
+                return None
+            }
+            let k = hash_r6(self.revision, pw, &self.u[40 .. 48], &[]);
+            return Some(aes256_decrypt_key(&k, &self.ue[.. 32]))
+        }
+        let key = self.file_key(pw);
+        let u = self.u_value(&key);
+        let n = if self.revision == 2 { 32 } else { 16 };
+        if self.u.len() >= n && u[.. n] == self.u[.. n] {
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    // Algorithm 7: returns the file key if the owner password is correct.
+    fn auth_owner(&self, pw: &[u8]) -> Option<Vec<u8>> {
+        if self.revision >= 5 {
+            if self.o.len() < 48 || self.oe.len() < 32 || self.u.len() < 48 {
+                return None
+            }
+            let pw = &pw[.. pw.len().min(127)];
+            let u = &self.u[.. 48];
+            if hash_r6(self.revision, pw, &self.o[32 .. 40], u) != self.o[.. 32] {
+                return None
+            }
+            let k = hash_r6(self.revision, pw, &self.o[40 .. 48], u);
+            return Some(aes256_decrypt_key(&k, &self.oe[.. 32]))
+        }
+        // Recover the user password from /O.
+        let mut h = md5(&[&pad_password(pw)]);
+        if self.revision >= 3 {
+            for _ in 0 .. 50 {
+                h = md5(&[&h]);
+            }
+        }
+        let key = &h[.. self.key_len];
+        let mut upw = self.o.clone();
+        if self.revision == 2 {
+            upw = rc4(key, &upw)
+        } else {
+            for i in (0 .. 20).rev() {
+                upw = rc4(&xor_key(key, i), &upw)
+            }
+        }
+        self.auth_user(&upw)
+    }
+}
+
+pub struct StandardSecurityHandler {
+    revision:         usize,
+    key:              Vec<u8>,
+    // the methods of the crypt filters named by /StmF, /StrF and /EFF
+    stm_method:       CryptMethod,
+    str_method:       CryptMethod,
+    eff_method:       CryptMethod,
+    // the crypt filters in /CF, which can be used by /Crypt filters
+    // in streams; this always includes /Identity.
+    crypt_filters:    BTreeMap<Vec<u8>, CryptMethod>,
+    encrypt_metadata: bool,
+    owner:            bool,
+}
+
+impl StandardSecurityHandler {
+    // Creates a handler from the /Encrypt dictionary and the first
+    // element of the /ID array, authenticating with the given
+    // password as either the owner or the user.  The empty user
+    // password is the default.
+    pub fn new(
+        encrypt: &LocatedVal<PDFObjT>, id: &[u8], password: &[u8],
+    ) -> ParseResult<StandardSecurityHandler> {
+        let fail = |msg: String| {
+            let err = ErrorKind::GuardError(msg);
+            Err(locate_value(err, encrypt.start(), encrypt.end()))
+        };
+        let dict = match encrypt.val() {
+            PDFObjT::Dict(d) => d,
+            _ => return fail("/Encrypt is not a dictionary".to_string()),
+        };
+        match dict.get_name(b"Filter") {
+            Some(b"Standard") => (),
+            Some(f) => {
+                let f = String::from_utf8_lossy(f);
+                return fail(format!("unsupported security handler /{}", f))
+            },
+            None => return fail("no /Filter in /Encrypt".to_string()),
+        }
+        let v = dict.get_usize(b"V").unwrap_or(0);
+        let revision = match dict.get_usize(b"R") {
+            Some(r) if (2 ..= 6).contains(&r) => r,
+            Some(r) => return fail(format!("unsupported revision {}", r)),
+            None => return fail("no /R in /Encrypt".to_string()),
+        };
+        let length = dict.get_usize(b"Length");
+        let mut crypt_filters = BTreeMap::new();
+        crypt_filters.insert(b"Identity".to_vec(), CryptMethod::Identity);
+        let (stm_method, str_method, eff_method, key_len) = match v {
+            1 => (CryptMethod::RC4, CryptMethod::RC4, CryptMethod::RC4, 5),
+            2 | 3 => {
+                let length = length.unwrap_or(40);
+                if length % 8 != 0 || !(40 ..= 128).contains(&length) {
+                    return fail(format!("invalid key /Length {}", length))
+                }
+                let m = CryptMethod::RC4;
+                (m, m, m, length / 8)
+            },
+            4 | 5 => {
+                if let Err(msg) = Self::get_crypt_filters(dict, &mut crypt_filters) {
+                    return fail(msg)
+                }
+                let get = |k: &[u8], default: &[u8]| {
+                    let name = dict.get_name(k).unwrap_or(default);
+                    match crypt_filters.get(name) {
+                        Some(m) => Ok(*m),
+                        None => Err(format!(
+                            "crypt filter /{} not found in /CF",
+                            String::from_utf8_lossy(name)
+                        )),
+                    }
+                };
+                let stmf = dict.get_name(b"StmF").unwrap_or(b"Identity");
+                let methods = (get(b"StmF", b"Identity"), get(b"StrF", b"Identity"));
+                let (stm, str) = match methods {
+                    (Ok(stm), Ok(str)) => (stm, str),
+                    (Err(msg), _) | (_, Err(msg)) => return fail(msg),
+                };
+                let eff = match get(b"EFF", stmf) {
+                    Ok(m) => m,
+                    Err(msg) => return fail(msg),
+                };
+                let key_len = if v == 5 {
+                    32
+                } else {
+                    match length {
+                        Some(l) if l % 8 == 0 && (40 ..= 128).contains(&l) => l / 8,
+                        _ => 16,
+                    }
+                };
+                (stm, str, eff, key_len)
+            },
+            v => return fail(format!("unsupported algorithm /V {}", v)),
+        };
+        // The 32-byte keys of /V 5 are only derived by the SHA-based
+        // revisions, and the MD5-based ones derive at most 16 bytes.
+        if (v == 5) != (revision >= 5) {
+            return fail(format!("revision {} does not match /V {}", revision, v))
+        }
+        let get_str = |k: &[u8]| match dict.get(k) {
+            Some(o) => string_bytes(o).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let p = match dict.get(b"P") {
+            Some(o) => match o.val() {
+                // /P is a 32-bit quantity, but is sometimes written unsigned.
+                PDFObjT::Integer(i) => i.int_val() as i32,
+                _ => return fail("invalid /P in /Encrypt".to_string()),
+            },
+            None => return fail("no /P in /Encrypt".to_string()),
+        };
+        let encrypt_metadata = match dict.get(b"EncryptMetadata") {
+            Some(o) => !matches!(o.val(), PDFObjT::Boolean(false)),
+            None => true,
+        };
+        let params = EncryptParams {
+            revision,
+            key_len,
+            o: get_str(b"O"),
+            u: get_str(b"U"),
+            oe: get_str(b"OE"),
+            ue: get_str(b"UE"),
+            p,
+            id: id.to_vec(),
+            encrypt_metadata,
+        };
+        let (key, owner) = if let Some(k) = params.auth_user(password) {
+            (k, false)
+        } else if let Some(k) = params.auth_owner(password) {
+            (k, true)
+        } else {
+            return fail("incorrect password".to_string())
+        };
+        Ok(StandardSecurityHandler {
+            revision,
+            key,
+            stm_method,
+            str_method,
+            eff_method,
+            crypt_filters,
+            encrypt_metadata,
+            owner,
+        })
+    }
+
+    // Collects the crypt filters defined in /CF for /V 4 and 5.  The
+    // /Identity filter cannot be redefined.
+    fn get_crypt_filters(
+        dict: &DictT, filters: &mut BTreeMap<Vec<u8>, CryptMethod>,
+    ) -> Result<(), String> {
+        let cf = match dict.get_dict(b"CF") {
+            Some(cf) => cf,
+            None => return Ok(()),
+        };
+        for (name, f) in cf.map() {
+            let name = name.as_slice();
+            if name == b"Identity" {
+                continue
+            }
+            let f = match f.val() {
+                PDFObjT::Dict(d) => d,
+                _ => {
+                    let name = String::from_utf8_lossy(name);
+                    return Err(format!("crypt filter /{} is not a dictionary", name))
+                },
+            };
+            let m = match f.get_name(b"CFM") {
+                None | Some(b"None") => CryptMethod::Identity,
+                Some(b"V2") => CryptMethod::RC4,
+                Some(b"AESV2") => CryptMethod::AESV2,
+                Some(b"AESV3") => CryptMethod::AESV3,
+                Some(m) => {
+                    let m = String::from_utf8_lossy(m);
+                    return Err(format!("unsupported crypt filter method /{}", m))
+                },
+            };
+            filters.insert(name.to_vec(), m);
+        }
+        Ok(())
+    }
+
+    pub fn revision(&self) -> usize { self.revision }
+    pub fn stream_method(&self) -> CryptMethod { self.stm_method }
+    pub fn string_method(&self) -> CryptMethod { self.str_method }
+    pub fn embedded_file_method(&self) -> CryptMethod { self.eff_method }
+    pub fn encrypt_metadata(&self) -> bool { self.encrypt_metadata }
+    // Whether the owner password was used for authentication.
+    pub fn is_owner(&self) -> bool { self.owner }
+
+    // Gets the method used to encrypt the content of a stream: a
+    // /Crypt filter, which has to be the first filter, overrides the
+    // defaults for the stream type.
+    pub fn stream_content_method(&self, s: &StreamT) -> Result<CryptMethod, String> {
+        let filters = s.filters().map_err(|e| format!("{}", e.val()))?;
+        if let Some(f) = filters.first() {
+            if f.name().val() == b"Crypt" {
+                let name = f
+                    .options()
+                    .and_then(|o| o.get_name(b"Name"))
+                    .unwrap_or(b"Identity");
+                return match self.crypt_filters.get(name) {
+                    Some(m) => Ok(*m),
+                    None => Err(format!(
+                        "crypt filter /{} not found in /CF",
+                        String::from_utf8_lossy(name)
+                    )),
+                }
+            }
+        }
+        let dict = s.dict().val();
+        match dict.get_name(b"Type") {
+            Some(b"Metadata") if !self.encrypt_metadata => Ok(CryptMethod::Identity),
+            Some(b"EmbeddedFile") => Ok(self.eff_method),
+            _ => Ok(self.stm_method),
+        }
+    }
+
+    // Algorithm 1: the key for the strings and streams of an object.
+    fn object_key(&self, method: CryptMethod, id: ObjectId) -> Vec<u8> {
+        if method == CryptMethod::AESV3 {
+            return self.key.clone()
+        }
+        let num = (id.0 as u32).to_le_bytes();
+        let gen = (id.1 as u32).to_le_bytes();
+        let salt: &[u8] = if method == CryptMethod::AESV2 {
+            b"sAlT"
+        } else {
+            b""
+        };
+        let mut h = md5(&[&self.key, &num[.. 3], &gen[.. 2], salt]);
+        h.truncate((self.key.len() + 5).min(16));
+        h
+    }
+
+    // Decrypts a string or stream content belonging to the object
+    // with the given id.
+    pub fn decrypt_bytes(
+        &self, method: CryptMethod, id: ObjectId, data: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        match method {
+            CryptMethod::Identity => Ok(data.to_vec()),
+            CryptMethod::RC4 => Ok(rc4(&self.object_key(method, id), data)),
+            // Some writers leave empty strings unencrypted.
+            CryptMethod::AESV2 | CryptMethod::AESV3 if data.is_empty() => Ok(Vec::new()),
+            CryptMethod::AESV2 | CryptMethod::AESV3 => {
+                aes_decrypt(&self.object_key(method, id), data)
+            },
+        }
+    }
+
+    // Constructs a decrypted version of an indirect object.  The
+    // decrypted strings and stream contents retain the locations of
+    // their encrypted versions.
+    pub fn decrypt_obj(
+        &self, id: ObjectId, obj: &Arc<LocatedVal<PDFObjT>>,
+    ) -> ParseResult<Arc<LocatedVal<PDFObjT>>> {
+        let fail = |msg: String| {
+            let msg = format!("cannot decrypt object ({},{}): {}", id.0, id.1, msg);
+            let err = ErrorKind::GuardError(msg);
+            locate_value(err, obj.start(), obj.end())
+        };
+        let decrypt = |method, data: &[u8]| self.decrypt_bytes(method, id, data).map_err(fail);
+        let o = match obj.val() {
+            PDFObjT::String(_, form) => {
+                let s = string_bytes(obj).unwrap_or_default();
+                let s = decrypt(self.str_method, &s)?;
+                // Keep literal strings in their raw representation.
+                match form {
+                    StringForm::Literal => PDFObjT::String(escape_literal_string(&s), *form),
+                    StringForm::Hex => PDFObjT::String(s, *form),
+                }
+            },
+            PDFObjT::Array(a) => {
+                let mut objs = Vec::new();
+                for o in a.objs() {
+                    objs.push(self.decrypt_obj(id, o)?)
+                }
+                PDFObjT::Array(ArrayT::new(objs))
+            },
+            PDFObjT::Dict(d) => PDFObjT::Dict(self.decrypt_dict(id, d)?),
+            PDFObjT::Stream(s) => {
+                let dict = self.decrypt_dict(id, s.dict().val())?;
+                let dict = Arc::new(s.dict().place(dict));
+                let method = self.stream_content_method(s).map_err(fail)?;
+                let content = s.stream().val();
+                let decrypted = decrypt(method, content.content())?;
+                let content = StreamContentT::new(content.start(), content.size(), decrypted);
+                PDFObjT::Stream(StreamT::new(dict, s.stream().place(content)))
+            },
+            _ => return Ok(Arc::clone(obj)),
+        };
+        Ok(Arc::new(obj.place(o)))
+    }
+
+    fn decrypt_dict(&self, id: ObjectId, d: &DictT) -> ParseResult<DictT> {
+        // The /Contents of signature dictionaries are not encrypted.
+        let is_sig = matches!(d.get_name(b"Type"), Some(b"Sig") | Some(b"DocTimeStamp"));
+        let mut map = BTreeMap::new();
+        for (k, v) in d.map() {
+            let v = if is_sig && k == &DictKey::new(b"Contents".to_vec()) {
+                Arc::clone(v)
+            } else {
+                self.decrypt_obj(id, v)?
+            };
+            map.insert(k.clone(), v);
+        }
+        Ok(DictT::new(map))
+    }
+}
+
+#[cfg(test)]
+mod test_security {
+    use super::{pad_password, rc4, string_bytes, StandardSecurityHandler};
+    use crate::pcore::parsebuffer::{ErrorKind, LocatedVal, ParseBuffer};
+    use crate::pdf_lib::pdf_obj::{parse_pdf_obj, PDFObjContext};
+
+    #[test]
+    fn test_rc4() {
+        // Test vectors from RFC 6229 are for longer keys; these are
+        // the widely used ones from the original RC4 description.
+        assert_eq!(
+            rc4(b"Key", b"Plaintext"),
+            vec![0xBB, 0xF3, 0x16, 0xE8, 0xD9, 0x40, 0xAF, 0x0A, 0xD3]
+        );
+        assert_eq!(rc4(b"Wiki", b"pedia"), vec![0x10, 0x21, 0xBF, 0x04, 0x20]);
+    }
+
+    #[test]
+    fn test_pad_password() {
+        let p = pad_password(b"");
+        assert_eq!(p.len(), 32);
+        assert_eq!(p[0], 0x28);
+        let p = pad_password(b"user");
+        assert_eq!(&p[.. 5], b"user\x28");
+        let p = pad_password(&[b'x'; 40]);
+        assert_eq!(p, vec![b'x'; 32]);
+    }
+
+    #[test]
+    fn test_string_bytes() {
+        let mut ctxt = PDFObjContext::new(10);
+        let v = b"(a\\(b\\)\\101)".to_vec();
+        let mut pb = ParseBuffer::new(v);
+        let o: LocatedVal<_> = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        assert_eq!(string_bytes(&o), Some(b"a(b)A".to_vec()));
+        let v = b"<61 2862>".to_vec();
+        let mut pb = ParseBuffer::new(v);
+        let o = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        assert_eq!(string_bytes(&o), Some(b"a(b".to_vec()));
+    }
+
+    #[test]
+    fn test_revision_mismatch() {
+        let mut ctxt = PDFObjContext::new(10);
+        let v = b"<< /Filter /Standard /V 5 /R 3 /P -4 /O <00> /U <00> >>".to_vec();
+        let mut pb = ParseBuffer::new(v);
+        let o = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        let r = StandardSecurityHandler::new(&o, b"", b"");
+        let msg = "revision 3 does not match /V 5".to_string();
+        assert_eq!(
+            r.err().map(|e| e.unwrap()),
+            Some(ErrorKind::GuardError(msg))
+        );
+        let v = b"<< /Filter /Standard /V 2 /R 6 /P -4 /O <00> /U <00> >>".to_vec();
+        let mut pb = ParseBuffer::new(v);
+        let o = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        assert!(StandardSecurityHandler::new(&o, b"", b"").is_err());
+    }
+}
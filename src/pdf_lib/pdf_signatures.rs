@@ -0,0 +1,455 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Structural validation of digital signatures.  The signature fields
+// are found through the /AcroForm of the catalog, and the /ByteRange
+// of each signature value is checked to cover the file, as of some
+// revision, except for the hex string of its /Contents.  The objects
+// modified by incremental updates made after the signed revision are
+// reported, since these can change what is displayed without
+// invalidating the signature ("shadow" and "incremental saving"
+// attacks).  The signature itself (the CMS data in /Contents) is not
+// checked.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::pdf_obj::{DictT, ObjectId, PDFObjContext, PDFObjT};
+use super::pdf_traverse_xref::{
+    parse_data_with_revisions, FileInfo, ParseOptions, PdfParseResult, Revision,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Modification {
+    Added,
+    Changed,
+    Freed,
+}
+
+// A modification by an incremental update after the signed revision.
+#[derive(Debug, PartialEq)]
+pub struct LaterChange {
+    revision: usize,
+    object:   ObjectId,
+    kind:     Modification,
+}
+
+impl LaterChange {
+    pub fn revision(&self) -> usize { self.revision }
+    pub fn object(&self) -> ObjectId { self.object }
+    pub fn kind(&self) -> Modification { self.kind }
+}
+
+// The result of checking a signature field.  The byte range is given
+// as (offset, length) pairs of file offsets.
+#[derive(Debug)]
+pub struct SignatureReport {
+    field:      ObjectId,
+    name:       Option<Vec<u8>>,
+    byte_range: Vec<(usize, usize)>,
+    // the revision covered by the byte range
+    revision:   Option<usize>,
+    errors:     Vec<String>,
+    changes:    Vec<LaterChange>,
+}
+
+impl SignatureReport {
+    pub fn field(&self) -> ObjectId { self.field }
+    pub fn name(&self) -> Option<&[u8]> { self.name.as_deref() }
+    pub fn byte_range(&self) -> &[(usize, usize)] { &self.byte_range }
+    pub fn revision(&self) -> Option<usize> { self.revision }
+    pub fn errors(&self) -> &[String] { &self.errors }
+    pub fn is_valid(&self) -> bool { self.errors.is_empty() }
+    pub fn changes(&self) -> &[LaterChange] { &self.changes }
+    // Whether the signed revision is not the latest one.
+    pub fn is_modified(&self) -> bool { !self.changes.is_empty() }
+}
+
+// A signature field: the field object, its partial name, and its
+// signature dictionary.
+pub type SigField<'a> = (ObjectId, Option<Vec<u8>>, &'a DictT);
+
+fn resolve<'a>(ctxt: &'a PDFObjContext, o: &'a PDFObjT) -> Option<(Option<ObjectId>, &'a PDFObjT)> {
+    match o {
+        PDFObjT::Reference(r) => ctxt.lookup_obj(r.id()).map(|o| (Some(r.id()), o.val())),
+        _ => Some((None, o)),
+    }
+}
+
+// Finds the signature fields with a signature value in the field
+// hierarchy of the /AcroForm of the given catalog.  The field type
+// is inherited from ancestor fields.
+pub fn signature_fields(ctxt: &PDFObjContext, root: ObjectId) -> Vec<SigField<'_>> {
+    let mut fields = Vec::new();
+    let catalog = match ctxt.lookup_obj(root).map(|o| o.val()) {
+        Some(PDFObjT::Dict(d)) => d,
+        _ => return fields,
+    };
+    let acroform = match catalog.get_resolved_dict(ctxt, b"AcroForm") {
+        Some(d) => d,
+        None => return fields,
+    };
+    let top = match acroform.get_resolved_array(ctxt, b"Fields") {
+        Some(a) => a,
+        None => return fields,
+    };
+    let mut visited = BTreeSet::new();
+    let mut queue: Vec<_> = top.objs().iter().map(|o| (o, None)).collect();
+    while let Some((o, parent_ft)) = queue.pop() {
+        let (id, d) = match resolve(ctxt, o.val()) {
+            Some((Some(id), PDFObjT::Dict(d))) => (id, d),
+            // fields are indirect dictionaries
+            _ => continue,
+        };
+        if !visited.insert(id) {
+            continue
+        }
+        let ft = d.get_name(b"FT").or(parent_ft);
+        if let Some(kids) = d.get_resolved_array(ctxt, b"Kids") {
+            queue.extend(kids.objs().iter().rev().map(|k| (k, ft)));
+        }
+        if ft != Some(b"Sig") {
+            continue
+        }
+        if let Some(v) = d.get_resolved_dict(ctxt, b"V") {
+            let name = match d.get(b"T").map(|t| t.val()) {
+                Some(PDFObjT::String(s, _)) => Some(s.clone()),
+                _ => None,
+            };
+            fields.push((id, name, v))
+        }
+    }
+    fields.sort_by_key(|f| f.0);
+    fields
+}
+
+// Checks the /ByteRange of a signature dictionary, given the file
+// data and revisions, and returns the ranges, the signed revision,
+// and any errors.
+fn check_byte_range(
+    fi: &FileInfo, sig: &DictT, data: &[u8], revs: &[Revision],
+) -> (Vec<(usize, usize)>, Option<usize>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut ranges = Vec::new();
+    let arr = match sig.get_array(b"ByteRange") {
+        Some(a) => a,
+        None => {
+            errors.push("no /ByteRange array".to_string());
+            return (ranges, None, errors)
+        },
+    };
+    let mut ints = Vec::new();
+    for o in arr.objs() {
+        match o.val() {
+            PDFObjT::Integer(i) if i.is_usize() => ints.push(i.usize_val()),
+            _ => {
+                errors
+                    .push("/ByteRange has an entry that is not a non-negative integer".to_string());
+                return (ranges, None, errors)
+            },
+        }
+    }
+    if ints.len() != 4 {
+        errors.push(format!(
+            "/ByteRange has {} entries instead of 4",
+            ints.len()
+        ));
+        return (ranges, None, errors)
+    }
+    ranges = vec![(ints[0], ints[1]), (ints[2], ints[3])];
+    let (gap_start, gap_end, end) =
+        match (ints[0].checked_add(ints[1]), ints[2].checked_add(ints[3])) {
+            (Some(s), Some(e)) => (s, ints[2], e),
+            _ => {
+                errors.push("/ByteRange has out-of-range entries".to_string());
+                return (ranges, None, errors)
+            },
+        };
+    if ints[0] != 0 {
+        errors.push(format!("/ByteRange starts at {} instead of 0", ints[0]))
+    }
+    if gap_end < gap_start {
+        errors.push(format!("/ByteRange has overlapping ranges at {}", gap_end));
+        return (ranges, None, errors)
+    }
+    if end > data.len() {
+        errors.push(format!(
+            "/ByteRange extends to {} beyond the file size {}",
+            end,
+            data.len()
+        ));
+        return (ranges, None, errors)
+    }
+
+    // The gap should be exactly the hex string of /Contents.
+    let gap = &data[gap_start .. gap_end];
+    let hex = gap.len() >= 2
+        && gap[0] == b'<'
+        && gap[gap.len() - 1] == b'>'
+        && gap[1 .. gap.len() - 1]
+            .iter()
+            .all(|b| b.is_ascii_hexdigit() || b" \t\r\n\x0c\0".contains(b));
+    if !hex {
+        errors.push(format!(
+            "/ByteRange gap {}..{} is not a hex string",
+            gap_start, gap_end
+        ))
+    }
+    match sig.get(b"Contents") {
+        Some(c) => {
+            if (fi.file_offset(c.start()), fi.file_offset(c.end())) != (gap_start, gap_end) {
+                errors.push(format!(
+                    "/Contents at {}..{} is not the /ByteRange gap {}..{}",
+                    fi.file_offset(c.start()),
+                    fi.file_offset(c.end()),
+                    gap_start,
+                    gap_end
+                ))
+            }
+        },
+        None => errors.push("no /Contents".to_string()),
+    }
+
+    // The signature covers the file up to the end of a revision.
+    let revision = revs
+        .iter()
+        .position(|r| fi.file_offset(r.byte_range().1) == end);
+    if revision.is_none() {
+        errors.push(format!(
+            "/ByteRange ends at {}, which is not the end of a revision",
+            end
+        ))
+    }
+    (ranges, revision, errors)
+}
+
+// Checks the signatures of the document with the given root, given
+// the file data and its revisions.
+pub fn check_signatures(
+    fi: &FileInfo, ctxt: &PDFObjContext, root: ObjectId, data: &[u8], revs: &[Revision],
+) -> Vec<SignatureReport> {
+    let mut reports = Vec::new();
+    for (field, name, sig) in signature_fields(ctxt, root) {
+        let (byte_range, revision, errors) = check_byte_range(fi, sig, data, revs);
+        let mut changes = Vec::new();
+        if let Some(signed) = revision {
+            for (i, r) in revs.iter().enumerate().skip(signed + 1) {
+                for (objs, kind) in &[
+                    (r.added(), Modification::Added),
+                    (r.changed(), Modification::Changed),
+                    (r.freed(), Modification::Freed),
+                ] {
+                    for o in objs.iter() {
+                        changes.push(LaterChange {
+                            revision: i,
+                            object:   *o,
+                            kind:     *kind,
+                        })
+                    }
+                }
+            }
+        }
+        reports.push(SignatureReport {
+            field,
+            name,
+            byte_range,
+            revision,
+            errors,
+            changes,
+        })
+    }
+    reports
+}
+
+// Parses the file data and checks its signatures.
+pub fn check_data_signatures(
+    path: &Path, data: &[u8], opts: &ParseOptions,
+) -> PdfParseResult<Vec<SignatureReport>> {
+    let (fi, ctxt, root, revs) = parse_data_with_revisions(path, data, opts)?;
+    Ok(check_signatures(&fi, &ctxt, root, data, &revs))
+}
+
+#[cfg(test)]
+mod test_signatures {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::{check_data_signatures, LaterChange, Modification};
+    use crate::pdf_lib::pdf_traverse_xref::ParseOptions;
+
+    const PLACEHOLDER: &str = "[0000000000 0000000000 0000000000 0000000000]";
+
+    fn get_test_data(test_file: &str) -> Vec<u8> {
+        let mut file = match File::open(test_file) {
+            Err(why) => panic!("Cannot open {}: {}", test_file, why),
+            Ok(f) => f,
+        };
+        let mut v = Vec::new();
+        if let Err(why) = file.read_to_end(&mut v) {
+            panic!("Cannot read {}: {}", test_file, why)
+        };
+        v
+    }
+
+    fn find(v: &[u8], pat: &[u8]) -> usize { v.windows(pat.len()).position(|w| w == pat).unwrap() }
+
+    // Appends an incremental update to the data with the given
+    // objects, returning the offset of its xref.
+    fn append_update(v: &mut Vec<u8>, objs: &[(usize, &str)], size: usize, prev: usize) -> usize {
+        let mut ofs = Vec::new();
+        for (num, o) in objs {
+            ofs.push((*num, v.len()));
+            v.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", num, o).as_bytes());
+        }
+        let xref = v.len();
+        v.extend_from_slice(b"xref\n0 1\n0000000000 65535 f \n");
+        for (num, o) in ofs {
+            v.extend_from_slice(format!("{} 1\n{:010} 00000 n \n", num, o).as_bytes());
+        }
+        v.extend_from_slice(
+            format!(
+                "trailer\n<< /Root 1 0 R /Size {} /Prev {} >>\nstartxref\n{}\n%%EOF\n",
+                size, prev, xref
+            )
+            .as_bytes(),
+        );
+        xref
+    }
+
+    // Signs the minimal file in an incremental update, with the given
+    // /ByteRange computed from the offsets of the /Contents gap and
+    // of the end of the update.
+    fn signed_data(byte_range: &dyn Fn(usize, usize, usize) -> String) -> (Vec<u8>, usize) {
+        let mut v = get_test_data("tests/test_files/minimal.pdf");
+        let catalog =
+            "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [5 0 R] /SigFlags 3 >> >>";
+        let sig = format!(
+            "<< /Type /Sig /Filter /Adobe.PPKLite /SubFilter /adbe.pkcs7.detached \
+             /ByteRange {} /Contents <{}> >>",
+            PLACEHOLDER,
+            "0".repeat(64)
+        );
+        let objs = [
+            (1, catalog),
+            (
+                5,
+                "<< /FT /Sig /T (Signature1) /V 6 0 R /Type /Annot /Subtype /Widget >>",
+            ),
+            (6, &sig),
+        ];
+        let xref = append_update(&mut v, &objs, 7, 565);
+        let gap_start = find(&v, b"/Contents <") + 10;
+        let gap_end = gap_start + 66;
+        let br = byte_range(gap_start, gap_end, v.len());
+        assert_eq!(br.len(), PLACEHOLDER.len());
+        let p = find(&v, PLACEHOLDER.as_bytes());
+        v[p .. p + br.len()].copy_from_slice(br.as_bytes());
+        (v, xref)
+    }
+
+    fn valid_range(gap_start: usize, gap_end: usize, end: usize) -> String {
+        format!(
+            "[{:010} {:010} {:010} {:010}]",
+            0,
+            gap_start,
+            gap_end,
+            end - gap_end
+        )
+    }
+
+    #[test]
+    fn test_byte_range() {
+        let path = Path::new("signed.pdf");
+        let opts = ParseOptions::new();
+        let (v, _) = signed_data(&valid_range);
+        let reports = check_data_signatures(path, &v, &opts).unwrap();
+        assert_eq!(reports.len(), 1);
+        let r = &reports[0];
+        assert_eq!(r.field(), (5, 0));
+        assert_eq!(r.name(), Some(&b"Signature1"[..]));
+        assert!(r.is_valid(), "{:?}", r.errors());
+        assert_eq!(r.revision(), Some(1));
+        assert!(!r.is_modified());
+        let gap_start = find(&v, b"/Contents <") + 10;
+        assert_eq!(
+            r.byte_range(),
+            &[(0, gap_start), (gap_start + 66, v.len() - gap_start - 66)]
+        );
+
+        // the gap is not the /Contents string
+        let (v, _) = signed_data(&|s, e, end| valid_range(s + 1, e, end));
+        let r = &check_data_signatures(path, &v, &opts).unwrap()[0];
+        assert_eq!(r.errors().len(), 2);
+        assert_eq!(r.revision(), Some(1));
+
+        // the ranges overlap
+        let (v, _) =
+            signed_data(&|s, e, end| format!("[{:010} {:010} {:010} {:010}]", 0, e, s, end - s));
+        let r = &check_data_signatures(path, &v, &opts).unwrap()[0];
+        assert!(r.errors()[0].contains("overlapping"));
+        assert_eq!(r.revision(), None);
+
+        // the ranges do not end at the end of a revision
+        let (v, _) = signed_data(&|s, e, end| valid_range(s, e, end - 1));
+        let r = &check_data_signatures(path, &v, &opts).unwrap()[0];
+        assert_eq!(r.errors().len(), 1);
+        assert!(r.errors()[0].contains("not the end of a revision"));
+
+        // not four entries
+        let (v, _) = signed_data(&|s, e, _| format!("[{:010} {:010} {:021}]", 0, s, e));
+        let r = &check_data_signatures(path, &v, &opts).unwrap()[0];
+        assert_eq!(
+            r.errors(),
+            &["/ByteRange has 3 entries instead of 4".to_string()]
+        );
+        assert!(r.byte_range().is_empty());
+    }
+
+    #[test]
+    fn test_later_changes() {
+        let path = Path::new("signed.pdf");
+        let opts = ParseOptions::new();
+        let (mut v, xref) = signed_data(&valid_range);
+        let page = "<< /Type /Page /Parent 2 0 R /Contents 7 0 R >>";
+        let objs = [(3, page), (7, "<< /Length 0 >>\nstream\n\nendstream")];
+        append_update(&mut v, &objs, 8, xref);
+        let reports = check_data_signatures(path, &v, &opts).unwrap();
+        let r = &reports[0];
+        assert!(r.is_valid(), "{:?}", r.errors());
+        assert_eq!(r.revision(), Some(1));
+        assert!(r.is_modified());
+        let changes = vec![
+            LaterChange {
+                revision: 2,
+                object:   (7, 0),
+                kind:     Modification::Added,
+            },
+            LaterChange {
+                revision: 2,
+                object:   (3, 0),
+                kind:     Modification::Changed,
+            },
+        ];
+        assert_eq!(r.changes(), changes.as_slice());
+
+        // unsigned files have no reports
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        assert!(check_data_signatures(path, &v, &opts).unwrap().is_empty());
+    }
+}
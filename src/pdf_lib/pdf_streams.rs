@@ -17,18 +17,22 @@
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::collections::BTreeMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use super::super::pcore::parsebuffer::{
     locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParseResult, ParsleyParser,
 };
 use super::super::pcore::transforms::{BufferTransformT, RestrictView, RestrictViewFrom};
 
-use super::pdf_filters::{ASCII85Decode, ASCIIHexDecode, DCTDecode, FlateDecode};
+use super::pdf_encoders::{ASCII85Encode, ASCIIHexEncode, FlateEncode, LZWEncode, RunLengthEncode};
+use super::pdf_filters::{
+    ASCII85Decode, ASCIIHexDecode, CCITTFaxDecode, DCTDecode, DecodeBudget, FlateDecode,
+    JBIG2Decode, JPXDecode, LZWDecode, RunLengthDecode,
+};
 use super::pdf_obj::{
-    parse_pdf_obj, DictKey, DictT, Filter, IndirectT, PDFObjContext, PDFObjT, StreamT,
+    parse_pdf_obj, ArrayT, DictKey, DictT, Filter, IndirectT, PDFObjContext, PDFObjT, StreamT,
 };
-use super::pdf_prim::{IntegerP, StreamContentT, WhitespaceEOL};
+use super::pdf_prim::{IntegerP, IntegerT, NameT, StreamContentT, WhitespaceEOL};
 
 type ObjStreamObjInfo = (usize, usize); // (object#, offset) pairs
 type ObjStreamMetadata = Vec<ObjStreamObjInfo>;
@@ -36,17 +40,19 @@ type ObjStreamContent = Vec<LocatedVal<IndirectT>>;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ObjStreamT {
-    dict: Rc<LocatedVal<DictT>>,
+    dict: Arc<LocatedVal<DictT>>,
     objs: ObjStreamContent,
 }
 
 impl ObjStreamT {
-    pub fn new(dict: Rc<LocatedVal<DictT>>, objs: ObjStreamContent) -> ObjStreamT {
+    pub fn new(dict: Arc<LocatedVal<DictT>>, objs: ObjStreamContent) -> ObjStreamT {
         ObjStreamT { dict, objs }
     }
     pub fn objs(&self) -> &[LocatedVal<IndirectT>] { self.objs.as_slice() }
 }
 
+// The stream content to be parsed is expected to have been decrypted
+// if the document is encrypted.
 pub struct ObjStreamP<'a> {
     ctxt:   &'a mut PDFObjContext,
     stream: &'a StreamT,
@@ -182,8 +188,8 @@ impl ObjStreamP<'_> {
             let start = buf.get_cursor();
             let o = parse_pdf_obj(self.ctxt, buf)?;
             let end = buf.get_cursor();
-            let obj = Rc::new(o);
-            let ind = IndirectT::new(*onum, 0, Rc::clone(&obj));
+            let obj = Arc::new(o);
+            let ind = IndirectT::new(*onum, 0, Arc::clone(&obj));
             let ind = LocatedVal::new(ind, start, end);
             // Register the object into the context so that it can be
             // looked up by its id.
@@ -213,6 +219,7 @@ impl ParsleyParser for ObjStreamP<'_> {
         let start = buf.get_cursor();
         let (num_objs, first) = self.get_dict_info()?;
         let filters = self.stream.filters()?;
+        let budget = self.ctxt.decode_budget();
 
         // Handle filters, using the approach used in XrefStreamP.
         let input = buf;
@@ -228,18 +235,21 @@ impl ParsleyParser for ObjStreamP<'_> {
                 &mut views[last]
             }
         }
-        if self.ctxt.is_encrypted() {
-            let msg = "Encrypted streams are currently unsupported".to_string();
-            let err = ErrorKind::GuardError(msg);
-            return Err(self.stream.dict().place(err))
-        }
-        for filter in &filters {
+        for (i, filter) in filters.iter().enumerate() {
             let f = filter.name().as_string();
             let mut decoder: Box<dyn BufferTransformT> = match f.as_str() {
-                "FlateDecode" => Box::new(FlateDecode::new(filter.options())),
-                "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options())),
-                "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options())),
-                "DCTDecode" => Box::new(DCTDecode::new(filter.options())),
+                // Crypt filters are applied by the security handler
+                // when the objects of the document are decrypted.
+                "Crypt" if i == 0 => continue,
+                "Crypt" => return Err(self.stream.dict().place(crypt_position_error())),
+                "FlateDecode" => Box::new(FlateDecode::new(filter.options(), budget)),
+                "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options(), budget)),
+                "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options(), budget)),
+                "DCTDecode" => Box::new(DCTDecode::new(
+                    filter.options(),
+                    self.stream.dict().val(),
+                    budget,
+                )),
                 s => {
                     let msg = format!("Cannot handle filter {} in object stream", s);
                     let err = ErrorKind::GuardError(msg);
@@ -286,7 +296,7 @@ impl ParsleyParser for ObjStreamP<'_> {
         let objs = self.parse_stream(&mut objs_buf, &meta)?;
         let end = buf.get_cursor();
         Ok(LocatedVal::new(
-            ObjStreamT::new(Rc::clone(self.stream.dict()), objs),
+            ObjStreamT::new(Arc::clone(self.stream.dict()), objs),
             start,
             end,
         ))
@@ -326,21 +336,22 @@ impl XrefEntT {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct XrefStreamT {
-    dict: Rc<LocatedVal<DictT>>,
+    dict: Arc<LocatedVal<DictT>>,
     ents: Vec<LocatedVal<XrefEntT>>,
 }
 
 impl XrefStreamT {
-    pub fn new(dict: Rc<LocatedVal<DictT>>, ents: Vec<LocatedVal<XrefEntT>>) -> XrefStreamT {
+    pub fn new(dict: Arc<LocatedVal<DictT>>, ents: Vec<LocatedVal<XrefEntT>>) -> XrefStreamT {
         XrefStreamT { dict, ents }
     }
     pub fn dict(&self) -> &DictT { self.dict.val() }
     pub fn ents(&self) -> &[LocatedVal<XrefEntT>] { self.ents.as_slice() }
 }
 
+// Cross-reference streams are never encrypted (Section 7.6.1).
 pub struct XrefStreamP<'a> {
-    encrypted: bool,
-    stream:    &'a StreamT,
+    stream: &'a StreamT,
+    budget: &'a DecodeBudget,
 }
 
 pub struct XrefStreamDictInfo<'a> {
@@ -357,8 +368,8 @@ impl XrefStreamDictInfo<'_> {
 }
 
 impl XrefStreamP<'_> {
-    pub fn new(encrypted: bool, stream: &StreamT) -> XrefStreamP {
-        XrefStreamP { encrypted, stream }
+    pub fn new<'a>(stream: &'a StreamT, budget: &'a DecodeBudget) -> XrefStreamP<'a> {
+        XrefStreamP { stream, budget }
     }
 
     pub fn stream(&self) -> &StreamT { self.stream }
@@ -582,6 +593,7 @@ impl ParsleyParser for XrefStreamP<'_> {
         let start = buf.get_cursor();
         let meta = self.get_dict_info()?;
         let input = buf;
+        let budget = self.budget;
 
         // This vector is just being used to hoist the view generated
         // by a loop iteration to use as input in the next iteration.
@@ -600,18 +612,17 @@ impl ParsleyParser for XrefStreamP<'_> {
                 &mut views[last]
             }
         }
-        if self.encrypted {
-            let msg = "Encrypted streams are currently unsupported".to_string();
-            let err = ErrorKind::GuardError(msg);
-            return Err(self.stream.dict().place(err))
-        }
         for filter in &meta.filters {
             let f = filter.name().as_string();
             let mut decoder: Box<dyn BufferTransformT> = match f.as_str() {
-                "FlateDecode" => Box::new(FlateDecode::new(filter.options())),
-                "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options())),
-                "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options())),
-                "DCTDecode" => Box::new(DCTDecode::new(filter.options())),
+                "FlateDecode" => Box::new(FlateDecode::new(filter.options(), budget)),
+                "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options(), budget)),
+                "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options(), budget)),
+                "DCTDecode" => Box::new(DCTDecode::new(
+                    filter.options(),
+                    self.stream.dict().val(),
+                    budget,
+                )),
                 s => {
                     let msg = format!("Cannot handle filter {} in xref stream", s);
                     let err = ErrorKind::GuardError(msg);
@@ -624,12 +635,18 @@ impl ParsleyParser for XrefStreamP<'_> {
         }
         let input = get_input(input, &mut views);
         let ents = self.parse_stream(input, &meta)?;
-        let xref = XrefStreamT::new(Rc::clone(self.stream.dict()), ents);
+        let xref = XrefStreamT::new(Arc::clone(self.stream.dict()), ents);
         let end = input.get_cursor();
         Ok(LocatedVal::new(xref, start, end))
     }
 }
 
+// A /Crypt filter selects the decryption of the stream content, so
+// it has to come before any other filter.
+fn crypt_position_error() -> ErrorKind {
+    ErrorKind::GuardError("Crypt filter is not the first filter".to_string())
+}
+
 // A function to construct a decoded stream object from a possible
 // encoded one.  The new decoded stream content is given the same
 // location as the old stream content.  This has a few implications:
@@ -638,8 +655,20 @@ impl ParsleyParser for XrefStreamP<'_> {
 // the content should be used directly.  Similarly, the new stream
 // dictionary will have any filter-specific entries pruned from the
 // old dictionary, but will retain the old location.
+//
+// Some image filters (DCTDecode, JBIG2Decode, JPXDecode) only
+// validate their input and pass it through unchanged.  Such a filter
+// has to be the last in the sequence, and its /Filter and
+// /DecodeParms entries are retained in the new dictionary, since the
+// content is still encoded with it.
 
 pub fn decode_stream(strm: &StreamT) -> ParseResult<StreamT> {
+    decode_stream_with_budget(strm, &DecodeBudget::default())
+}
+
+// Decodes a stream, charging the decoded data to the budget of its
+// document.
+pub fn decode_stream_with_budget(strm: &StreamT, budget: &DecodeBudget) -> ParseResult<StreamT> {
     let dict = strm.dict().val();
     let content = strm.stream().val().content();
     let filters = strm.filters()?;
@@ -658,14 +687,44 @@ pub fn decode_stream(strm: &StreamT) -> ParseResult<StreamT> {
             &mut views[last]
         }
     }
+    // The index of a filter that is only validated.
+    let mut retained = None;
     // Work through the filter sequence.
-    for filter in &filters {
+    for (i, filter) in filters.iter().enumerate() {
         let f = filter.name().as_string();
+        if let Some(r) = retained {
+            let r: &Filter = &filters[r];
+            let msg = format!(
+                "Filter {} cannot follow image filter {}",
+                f,
+                r.name().as_string()
+            );
+            let err = ErrorKind::GuardError(msg);
+            return Err(strm.dict().place(err))
+        }
         let mut decoder: Box<dyn BufferTransformT> = match f.as_str() {
-            "FlateDecode" => Box::new(FlateDecode::new(filter.options())),
-            "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options())),
-            "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options())),
-            "DCTDecode" => Box::new(DCTDecode::new(filter.options())),
+            // Crypt filters are applied by the security handler when
+            // the objects of the document are decrypted.
+            "Crypt" if i == 0 => continue,
+            "Crypt" => return Err(strm.dict().place(crypt_position_error())),
+            "JBIG2Decode" => {
+                retained = Some(i);
+                Box::new(JBIG2Decode::new(filter.options(), budget))
+            },
+            "JPXDecode" => {
+                retained = Some(i);
+                Box::new(JPXDecode::new(filter.options(), dict, budget))
+            },
+            "FlateDecode" => Box::new(FlateDecode::new(filter.options(), budget)),
+            "ASCII85Decode" => Box::new(ASCII85Decode::new(filter.options(), budget)),
+            "ASCIIHexDecode" => Box::new(ASCIIHexDecode::new(filter.options(), budget)),
+            "DCTDecode" => {
+                retained = Some(i);
+                Box::new(DCTDecode::new(filter.options(), dict, budget))
+            },
+            "LZWDecode" => Box::new(LZWDecode::new(filter.options(), budget)),
+            "CCITTFaxDecode" => Box::new(CCITTFaxDecode::new(filter.options(), budget)),
+            "RunLengthDecode" => Box::new(RunLengthDecode::new(filter.options(), budget)),
             s => {
                 let msg = format!("Cannot handle filter {} in object stream", s);
                 let err = ErrorKind::GuardError(msg);
@@ -676,8 +735,13 @@ pub fn decode_stream(strm: &StreamT) -> ParseResult<StreamT> {
         let output = decoder.transform(input)?;
         views.push(output);
     }
-    // Get the final decoded buffer.
+    // Get the final decoded buffer, and check its expansion over the
+    // whole filter sequence.
     let buf = get_input(&mut input, &mut views);
+    if let Err(msg) = budget.check_stream(content.len(), buf.buf().len()) {
+        let err = ErrorKind::TransformError(format!("Filter sequence: {}", msg));
+        return Err(strm.stream().place(err))
+    }
     let content = Vec::from(buf.buf());
     let content = StreamContentT::new(0, content.len(), content);
     let content = strm.stream().place(content);
@@ -690,29 +754,195 @@ pub fn decode_stream(strm: &StreamT) -> ParseResult<StreamT> {
         {
             continue
         }
-        map.insert(key.clone(), Rc::clone(val));
+        map.insert(key.clone(), Arc::clone(val));
+    }
+    if let Some(r) = retained {
+        for (key, val) in retained_filter_entries(dict, r) {
+            map.insert(DictKey::new(Vec::from(key)), val);
+        }
     }
     let dict = strm.dict().place(DictT::new(map));
 
     // Construct the new stream object.
-    let s = StreamT::new(Rc::new(dict), content);
+    let s = StreamT::new(Arc::new(dict), content);
     Ok(s)
 }
 
+// A filter to encode a stream with, and its /DecodeParms dictionary
+// if any.
+pub struct EncodeFilter {
+    name:  NameT,
+    parms: Option<Arc<LocatedVal<PDFObjT>>>,
+}
+
+impl EncodeFilter {
+    pub fn new(name: &str, parms: Option<Arc<LocatedVal<PDFObjT>>>) -> EncodeFilter {
+        EncodeFilter {
+            name: NameT::new(Vec::from(name)),
+            parms,
+        }
+    }
+    pub fn name(&self) -> &NameT { &self.name }
+    pub fn parms(&self) -> &Option<Arc<LocatedVal<PDFObjT>>> { &self.parms }
+}
+
+// The counterpart of decode_stream(), which encodes the content of a
+// stream with a sequence of filters, given in the order of the
+// /Filter entry of the new stream.  If the stream is already
+// encoded, its filters follow the new ones.  The new dictionary has
+// /Filter, /DecodeParms and /Length entries for the new content,
+// and like in decode_stream(), the new objects retain the old
+// locations.
+pub fn encode_stream(strm: &StreamT, filters: &[EncodeFilter]) -> ParseResult<StreamT> {
+    let dict = strm.dict().val();
+    // Ensure that the existing filters are well-formed.
+    let old_filters = strm.filters()?;
+
+    let mut buf = ParseBuffer::new(Vec::from(strm.stream().val().content()));
+    for filter in filters.iter().rev() {
+        let f = filter.name().as_string();
+        let parms = match filter.parms().as_ref().map(|p| p.val()) {
+            None => None,
+            Some(PDFObjT::Dict(d)) => Some(d),
+            Some(_) => {
+                let msg = format!("Invalid DecodeParms for filter {}", f);
+                let err = ErrorKind::GuardError(msg);
+                return Err(strm.dict().place(err))
+            },
+        };
+        let options = &parms;
+        let mut encoder: Box<dyn BufferTransformT> = match f.as_str() {
+            "FlateDecode" => Box::new(FlateEncode::new(options)),
+            "LZWDecode" => Box::new(LZWEncode::new(options)),
+            "ASCIIHexDecode" => Box::new(ASCIIHexEncode::new(options)),
+            "ASCII85Decode" => Box::new(ASCII85Encode::new(options)),
+            "RunLengthDecode" => Box::new(RunLengthEncode::new(options)),
+            s => {
+                let msg = format!("Cannot encode with filter {}", s);
+                let err = ErrorKind::GuardError(msg);
+                return Err(strm.dict().place(err))
+            },
+        };
+        buf = encoder.transform(&buf)?;
+    }
+    let content = Vec::from(buf.buf());
+    let len = content.len();
+    let content = strm.stream().place(StreamContentT::new(0, len, content));
+
+    // Collect the new filter sequence.
+    let place = |o: PDFObjT| Arc::new(strm.dict().place(o));
+    let mut names = Vec::new();
+    let mut parms = Vec::new();
+    for f in filters {
+        names.push(place(PDFObjT::Name(f.name().clone())));
+        parms.push(f.parms().as_ref().map(Arc::clone));
+    }
+    match dict.get(b"Filter").map(|f| f.val()) {
+        Some(PDFObjT::Name(_)) => names.push(Arc::clone(dict.get(b"Filter").unwrap())),
+        Some(PDFObjT::Array(fa)) => names.extend(fa.objs().iter().map(Arc::clone)),
+        _ => (),
+    }
+    match dict.get(b"DecodeParms").map(|p| p.val()) {
+        Some(PDFObjT::Dict(_)) => parms.push(dict.get(b"DecodeParms").map(Arc::clone)),
+        Some(PDFObjT::Array(pa)) => {
+            for p in pa.objs() {
+                match p.val() {
+                    PDFObjT::Null(_) => parms.push(None),
+                    _ => parms.push(Some(Arc::clone(p))),
+                }
+            }
+        },
+        _ => (),
+    }
+    // A single parameter dictionary can accompany an array of
+    // filters, so the parameters are padded to one per filter.
+    parms.resize(filters.len() + old_filters.len(), None);
+
+    // Create the new dictionary.
+    let mut map = BTreeMap::new();
+    for (key, val) in dict.map() {
+        if key == &DictKey::new(Vec::from("Filter"))
+            || key == &DictKey::new(Vec::from("DecodeParms"))
+            || key == &DictKey::new(Vec::from("Length"))
+        {
+            continue
+        }
+        map.insert(key.clone(), Arc::clone(val));
+    }
+    if names.len() == 1 {
+        map.insert(DictKey::new(Vec::from("Filter")), names.remove(0));
+        if let Some(p) = parms.remove(0) {
+            map.insert(DictKey::new(Vec::from("DecodeParms")), p);
+        }
+    } else if !names.is_empty() {
+        let names = place(PDFObjT::Array(ArrayT::new(names)));
+        map.insert(DictKey::new(Vec::from("Filter")), names);
+        if parms.iter().any(|p| p.is_some()) {
+            let parms = parms
+                .into_iter()
+                .map(|p| p.unwrap_or_else(|| place(PDFObjT::Null(()))))
+                .collect();
+            let parms = place(PDFObjT::Array(ArrayT::new(parms)));
+            map.insert(DictKey::new(Vec::from("DecodeParms")), parms);
+        }
+    }
+    let length = place(PDFObjT::Integer(IntegerT::new(len as i64)));
+    map.insert(DictKey::new(Vec::from("Length")), length);
+    let dict = strm.dict().place(DictT::new(map));
+
+    Ok(StreamT::new(Arc::new(dict), content))
+}
+
+// The /Filter and /DecodeParms entries for the filter at index `idx`
+// in the filter sequence of a stream dictionary.
+fn retained_filter_entries(
+    dict: &DictT, idx: usize,
+) -> Vec<(&'static str, Arc<LocatedVal<PDFObjT>>)> {
+    let mut entries = Vec::new();
+    let filter = dict.get(b"Filter");
+    let parms = dict.get(b"DecodeParms");
+    match filter.map(|f| f.val()) {
+        Some(PDFObjT::Name(_)) => {
+            entries.push(("Filter", Arc::clone(filter.unwrap())));
+            if let Some(p) = parms {
+                entries.push(("DecodeParms", Arc::clone(p)))
+            }
+        },
+        Some(PDFObjT::Array(fa)) => {
+            entries.push(("Filter", Arc::clone(&fa.objs()[idx])));
+            if let Some(PDFObjT::Array(pa)) = parms.map(|p| p.val()) {
+                let p = &pa.objs()[idx];
+                if !matches!(p.val(), PDFObjT::Null(_)) {
+                    entries.push(("DecodeParms", Arc::clone(p)))
+                }
+            }
+        },
+        _ => (),
+    }
+    entries
+}
+
 #[cfg(test)]
 mod test_object_stream {
     use std::collections::BTreeMap;
     use std::fs::File;
-    use std::io::Read;
-    use std::rc::Rc;
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
 
-    use super::{decode_stream, ObjStreamP, ObjStreamT, XrefStreamP};
+    use super::{
+        decode_stream, decode_stream_with_budget, encode_stream, EncodeFilter, ObjStreamP,
+        ObjStreamT, XrefStreamP,
+    };
     use crate::pcore::parsebuffer::{
-        locate_value, ErrorKind, LocatedVal, ParseBuffer, ParsleyParser,
+        locate_value, ErrorKind, LocatedVal, ParseBuffer, ParseBufferT, ParseResult, ParsleyParser,
     };
     use crate::pcore::transforms::{BufferTransformT, RestrictView};
+    use crate::pdf_lib::pdf_filters::{DecodeBudget, DecodeLimits, RunLengthDecode};
     use crate::pdf_lib::pdf_obj::{
-        ArrayT, DictP, DictT, IndirectP, IndirectT, PDFObjContext, PDFObjT, StreamT,
+        ArrayT, DictP, DictT, IndirectP, IndirectT, PDFObjContext, PDFObjT, StreamT, StringForm,
     };
     use crate::pdf_lib::pdf_prim::StreamContentT;
 
@@ -722,13 +952,13 @@ mod test_object_stream {
         let v = format!("<</Type /ObjStm /N {} /First {}>>", n, first);
         Vec::from(v.as_bytes())
     }
-    fn mk_dict(n: usize, first: usize) -> Rc<LocatedVal<DictT>> {
+    fn mk_dict(n: usize, first: usize) -> Arc<LocatedVal<DictT>> {
         let v = mk_dict_buf(n, first);
         let len = v.len();
         let mut buf = ParseBuffer::new(v);
         let mut ctxt = mk_new_context();
         let mut dp = DictP::new(&mut ctxt);
-        Rc::new(LocatedVal::new(dp.parse(&mut buf).unwrap(), 0, len))
+        Arc::new(LocatedVal::new(dp.parse(&mut buf).unwrap(), 0, len))
     }
     fn mk_objstm(n: usize, first: usize) -> StreamT {
         let dict = mk_dict(n, first);
@@ -803,13 +1033,13 @@ mod test_object_stream {
 
         let mut exp = Vec::new();
         let o = LocatedVal::new(PDFObjT::Dict(DictT::new(BTreeMap::new())), 0, 4);
-        let o = IndirectT::new(10, 0, Rc::new(o));
+        let o = IndirectT::new(10, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 0, 4));
         let o = LocatedVal::new(PDFObjT::Array(ArrayT::new(vec![])), 5, 7);
-        let o = IndirectT::new(20, 0, Rc::new(o));
+        let o = IndirectT::new(20, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 5, 7));
-        let o = LocatedVal::new(PDFObjT::String(vec![]), 8, 10);
-        let o = IndirectT::new(30, 0, Rc::new(o));
+        let o = LocatedVal::new(PDFObjT::String(vec![], StringForm::Literal), 8, 10);
+        let o = IndirectT::new(30, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 8, 10));
         assert_eq!(objs, exp);
     }
@@ -832,17 +1062,17 @@ mod test_object_stream {
 
         let mut exp = Vec::new();
         let o = LocatedVal::new(PDFObjT::Dict(DictT::new(BTreeMap::new())), 0, 4);
-        let o = IndirectT::new(10, 0, Rc::new(o));
+        let o = IndirectT::new(10, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 0, 4));
         let o = LocatedVal::new(PDFObjT::Array(ArrayT::new(vec![])), 5, 7);
-        let o = IndirectT::new(20, 0, Rc::new(o));
+        let o = IndirectT::new(20, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 5, 7));
-        let o = LocatedVal::new(PDFObjT::String(vec![]), 8, 10);
-        let o = IndirectT::new(30, 0, Rc::new(o));
+        let o = LocatedVal::new(PDFObjT::String(vec![], StringForm::Literal), 8, 10);
+        let o = IndirectT::new(30, 0, Arc::new(o));
         exp.push(LocatedVal::new(o, 8, 10));
 
         let d = mk_dict(3, 14);
-        let os = ObjStreamT::new(Rc::clone(&d), exp);
+        let os = ObjStreamT::new(Arc::clone(&d), exp);
         let os = LocatedVal::new(os, 0, 24);
         assert_eq!(val, os);
     }
@@ -975,7 +1205,7 @@ endobj");
             let content = s.stream().val();
             let mut vxf = RestrictView::new(content.start(), content.size());
             let mut xref_buf = vxf.transform(&pb).unwrap();
-            let mut xrsp = XrefStreamP::new(false, s);
+            let mut xrsp = XrefStreamP::new(s, ctxt.decode_budget());
             let xrt = xrsp.parse(&mut xref_buf).unwrap();
             let ents = xrt.val().ents();
             let size = s.dict().val().get_usize(b"Size").unwrap();
@@ -995,7 +1225,12 @@ endobj");
         let io = io.val();
         if let PDFObjT::Stream(ref s) = io.obj().val() {
             match decode_stream(s) {
-                Ok(_) => (),
+                Ok(d) => {
+                    // The content is validated, but not decoded.
+                    let filter = d.dict().val().get_name(b"Filter");
+                    assert_eq!(filter, Some(&b"DCTDecode"[..]));
+                    assert_eq!(d.stream().val().content(), s.stream().val().content());
+                },
                 Err(_) => assert!(false),
             }
         } else {
@@ -1017,11 +1252,22 @@ endobj");
             let content = s.stream().val();
             let mut vxf = RestrictView::new(content.start(), content.size());
             let mut xref_buf = vxf.transform(&pb).unwrap();
-            let mut xrsp = XrefStreamP::new(false, s);
+            let mut xrsp = XrefStreamP::new(s, ctxt.decode_budget());
             let xrt = xrsp.parse(&mut xref_buf).unwrap();
             let ents = xrt.val().ents();
             let size = s.dict().val().get_usize(b"Size").unwrap();
-            assert_eq!(size, ents.len())
+            assert_eq!(size, ents.len());
+
+            // the decode limits of the context apply
+            let limits = DecodeLimits {
+                max_output: 16,
+                ..DecodeLimits::default()
+            };
+            let mut ctxt = mk_new_context();
+            ctxt.set_decode_limits(limits);
+            let mut xref_buf = vxf.transform(&pb).unwrap();
+            let mut xrsp = XrefStreamP::new(s, ctxt.decode_budget());
+            assert!(xrsp.parse(&mut xref_buf).is_err());
         } else {
             assert!(false);
         }
@@ -1100,5 +1346,275 @@ endobj");
             false, // ascii decompression actually expands data
             true,  // check content
         );
+        for f in &["lzw", "lzw_ec0", "lzw_tiff", "lzw_png"] {
+            do_test_decode_stream(
+                &format!("tests/test_files/filter_tests/image_stm_{}.obj", f),
+                "tests/test_files/filter_tests/image_stm.obj",
+                *f != "lzw", // decode parms
+                true,        // decompresses
+                true,        // check content
+            );
+        }
+        for f in &["g3_1d", "g3_2d", "g4"] {
+            for s in &["fax", "fax_wide"] {
+                do_test_decode_stream(
+                    &format!("tests/test_files/filter_tests/{}_stm_{}.obj", s, f),
+                    &format!("tests/test_files/filter_tests/{}_stm.obj", s),
+                    true, // has decode parms
+                    true, // decompresses
+                    true, // check content
+                );
+            }
+        }
+        do_test_decode_stream(
+            "tests/test_files/filter_tests/xobject_stm_rle.obj",
+            "tests/test_files/filter_tests/xobject_stm.obj",
+            false, // no decode parms
+            true,  // decompresses
+            true,  // check content
+        );
+    }
+
+    #[test]
+    fn test_run_length_decode() {
+        let decode = |v: &[u8]| {
+            let pb = ParseBuffer::new(v.to_vec());
+            RunLengthDecode::new(&None, &DecodeBudget::default())
+                .transform(&pb)
+                .map(|b| b.buf().to_vec())
+        };
+        assert_eq!(decode(b"\x80").unwrap(), b"");
+        assert_eq!(decode(b"\x02abc\xfdx\x00d\x80").unwrap(), b"abcxxxxd");
+        assert_eq!(decode(b"\x81y\x80").unwrap(), [b'y'; 128].to_vec());
+        // missing EOD
+        assert!(decode(b"").is_err());
+        assert!(decode(b"\x02abc").is_err());
+        // runs that overshoot the data
+        assert!(decode(b"\x03abc\x80").is_err());
+        assert!(decode(b"\xfd").is_err());
+        // trailing data after EOD
+        assert!(decode(b"\x00a\x80\x00b").is_err());
+        assert!(decode(b"\x00a\x80\n").is_err());
+    }
+
+    #[test]
+    fn test_jbig2_decode_stream() {
+        let mk_stream = |d: &str, content: Vec<u8>| {
+            let mut ctxt = mk_new_context();
+            let mut buf = ParseBuffer::new(Vec::from(d.as_bytes()));
+            let dict = DictP::new(&mut ctxt).parse(&mut buf).unwrap();
+            let dict = Arc::new(LocatedVal::new(dict, 0, d.len()));
+            let content = StreamContentT::new(0, content.len(), content);
+            StreamT::new(dict, LocatedVal::new(content, 0, 0))
+        };
+        // a page information segment, and a text region referring to
+        // a symbol dictionary in the globals
+        let mut jbig2 = vec![0, 0, 0, 1, 48, 0, 1, 0, 0, 0, 19, 0, 0, 0, 64, 0, 0, 0, 32];
+        jbig2.extend_from_slice(&[0; 11]);
+        jbig2.extend_from_slice(&[0, 0, 0, 2, 6, 0x20, 0, 1, 0, 0, 0, 17, 0, 0, 0, 64]);
+        jbig2.extend_from_slice(&[0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let d = "<</Filter /JBIG2Decode /DecodeParms <</JBIG2Globals 5 0 R>> /Width 64>>";
+        let s = mk_stream(d, jbig2.clone());
+        let decoded = decode_stream(&s).unwrap();
+        assert_eq!(decoded.stream().val().content(), jbig2.as_slice());
+        let dict = decoded.dict().val();
+        assert_eq!(dict.get_name(b"Filter"), Some(&b"JBIG2Decode"[..]));
+        assert!(dict.get_dict(b"DecodeParms").is_some());
+        assert_eq!(dict.get_usize(b"Width"), Some(64));
+
+        let d = "<</Filter [/RunLengthDecode /JBIG2Decode] /DecodeParms [null <</JBIG2Globals 5 0 R>>]>>";
+        let mut rle = vec![jbig2.len() as u8 - 1];
+        rle.extend_from_slice(&jbig2);
+        rle.push(128);
+        let decoded = decode_stream(&mk_stream(d, rle)).unwrap();
+        assert_eq!(decoded.stream().val().content(), jbig2.as_slice());
+        let dict = decoded.dict().val();
+        assert_eq!(dict.get_name(b"Filter"), Some(&b"JBIG2Decode"[..]));
+        assert!(dict.get_dict(b"DecodeParms").is_some());
+
+        // missing globals, and filters after the image filter
+        let s = mk_stream("<</Filter /JBIG2Decode>>", jbig2.clone());
+        assert!(decode_stream(&s).is_err());
+        let s = mk_stream("<</Filter [/JBIG2Decode /RunLengthDecode]>>", jbig2);
+        assert!(decode_stream(&s).is_err());
+    }
+
+    #[test]
+    fn test_decode_budget() {
+        let mk_stream = |d: &str, content: Vec<u8>| {
+            let mut ctxt = mk_new_context();
+            let mut buf = ParseBuffer::new(Vec::from(d.as_bytes()));
+            let dict = DictP::new(&mut ctxt).parse(&mut buf).unwrap();
+            let dict = Arc::new(LocatedVal::new(dict, 0, d.len()));
+            let content = StreamContentT::new(0, content.len(), content);
+            StreamT::new(dict, LocatedVal::new(content, 0, 0))
+        };
+        let limits = DecodeLimits {
+            max_output:      4096,
+            max_ratio:       128,
+            ratio_threshold: 256,
+            max_total:       8192,
+        };
+        let err_msg = |r: ParseResult<StreamT>| match r.unwrap_err().val() {
+            ErrorKind::TransformError(msg) => msg.clone(),
+            e => panic!("unexpected error {:?}", e),
+        };
+
+        // 32 runs of 128 bytes each, within the limits
+        let mut rle = [0x81, b'a'].repeat(32);
+        rle.push(128);
+        let s = mk_stream("<</Filter /RunLengthDecode>>", rle.clone());
+        let budget = DecodeBudget::new(limits);
+        let decoded = decode_stream_with_budget(&s, &budget).unwrap();
+        assert_eq!(decoded.stream().val().content().len(), 4096);
+        assert_eq!(budget.total(), 4096);
+        // the second stream exceeds the document total
+        let decoded = decode_stream_with_budget(&s, &budget).unwrap();
+        assert_eq!(decoded.stream().val().content().len(), 4096);
+        let msg = err_msg(decode_stream_with_budget(&s, &budget));
+        assert!(msg.starts_with("RunLengthDecode: total decoded bytes"));
+
+        // one run too many for the stream output
+        let mut rle = [0x81, b'a'].repeat(33);
+        rle.push(128);
+        let s = mk_stream("<</Filter /RunLengthDecode>>", rle);
+        let msg = err_msg(decode_stream_with_budget(&s, &DecodeBudget::new(limits)));
+        assert!(msg.contains("maximum stream output of 4096 bytes"));
+
+        // a flate bomb exceeding the expansion ratio
+        let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
+        enc.write_all(&[0; 4096]).unwrap();
+        let flate = enc.finish().unwrap();
+        let s = mk_stream("<</Filter /FlateDecode>>", flate);
+        let msg = err_msg(decode_stream_with_budget(&s, &DecodeBudget::new(limits)));
+        assert!(msg.starts_with("FlateDecode: "));
+        assert!(msg.contains("maximum expansion ratio of 128"));
+        // the default limits accept it
+        assert!(decode_stream(&s).is_ok());
+    }
+
+    #[test]
+    fn test_crypt_filter() {
+        let mk_stream = |d: &str, content: Vec<u8>| {
+            let mut ctxt = mk_new_context();
+            let mut buf = ParseBuffer::new(Vec::from(d.as_bytes()));
+            let dict = DictP::new(&mut ctxt).parse(&mut buf).unwrap();
+            let dict = Arc::new(LocatedVal::new(dict, 0, d.len()));
+            let content = StreamContentT::new(0, content.len(), content);
+            StreamT::new(dict, LocatedVal::new(content, 0, 0))
+        };
+        let s = mk_stream("<</Filter [/Crypt /ASCIIHexDecode]>>", b"414243>".to_vec());
+        let d = decode_stream(&s).unwrap();
+        assert_eq!(d.stream().val().content(), b"ABC");
+
+        // a /Crypt filter that is not the first filter
+        let s = mk_stream("<</Filter [/ASCIIHexDecode /Crypt]>>", b"414243>".to_vec());
+        let err = ErrorKind::GuardError("Crypt filter is not the first filter".to_string());
+        assert_eq!(decode_stream(&s).unwrap_err().val(), &err);
+    }
+
+    #[test]
+    fn test_encode_stream() {
+        let mk_obj = |d: &str| {
+            let mut ctxt = mk_new_context();
+            let mut buf = ParseBuffer::new(Vec::from(d.as_bytes()));
+            let dict = DictP::new(&mut ctxt).parse(&mut buf).unwrap();
+            Arc::new(LocatedVal::new(PDFObjT::Dict(dict), 0, d.len()))
+        };
+        let mk_stream = |d: &str, content: Vec<u8>| {
+            let mut ctxt = mk_new_context();
+            let mut buf = ParseBuffer::new(Vec::from(d.as_bytes()));
+            let dict = DictP::new(&mut ctxt).parse(&mut buf).unwrap();
+            let dict = Arc::new(LocatedVal::new(dict, 0, d.len()));
+            let content = StreamContentT::new(0, content.len(), content);
+            StreamT::new(dict, LocatedVal::new(content, 0, 0))
+        };
+        let data: Vec<u8> = (0 .. 1200).map(|i| (i % 7 * i % 251) as u8).collect();
+        let s = mk_stream("<</Type /XObject /Length 1200>>", data.clone());
+
+        // a single filter
+        let filters = [EncodeFilter::new("RunLengthDecode", None)];
+        let e = encode_stream(&s, &filters).unwrap();
+        let dict = e.dict().val();
+        assert_eq!(dict.get_name(b"Filter"), Some(&b"RunLengthDecode"[..]));
+        assert!(dict.get(b"DecodeParms").is_none());
+        assert_eq!(
+            dict.get_usize(b"Length"),
+            Some(e.stream().val().content().len())
+        );
+        assert_eq!(dict.get_name(b"Type"), Some(&b"XObject"[..]));
+        let d = decode_stream(&e).unwrap();
+        assert_eq!(d.stream().val().content(), data.as_slice());
+
+        // a filter sequence with parameters
+        let parms = mk_obj("<</Predictor 12 /Colors 3 /Columns 100>>");
+        let filters = [
+            EncodeFilter::new("ASCII85Decode", None),
+            EncodeFilter::new("FlateDecode", Some(Arc::clone(&parms))),
+        ];
+        let e = encode_stream(&s, &filters).unwrap();
+        let dict = e.dict().val();
+        let names: Vec<_> = match dict.get(b"Filter").unwrap().val() {
+            PDFObjT::Array(a) => a.objs().iter().map(|o| o.val()).collect(),
+            _ => panic!("expected a filter array"),
+        };
+        assert_eq!(names.len(), 2);
+        match dict.get(b"DecodeParms").unwrap().val() {
+            PDFObjT::Array(a) => {
+                assert!(matches!(a.objs()[0].val(), PDFObjT::Null(_)));
+                assert!(Arc::ptr_eq(&a.objs()[1], &parms));
+            },
+            _ => panic!("expected a parameter array"),
+        }
+        assert_eq!(
+            dict.get_usize(b"Length"),
+            Some(e.stream().val().content().len())
+        );
+        let d = decode_stream(&e).unwrap();
+        assert_eq!(d.stream().val().content(), data.as_slice());
+
+        // encoding an encoded stream
+        let filters = [EncodeFilter::new("ASCIIHexDecode", None)];
+        let e = encode_stream(&e, &filters).unwrap();
+        match e.dict().val().get(b"DecodeParms").unwrap().val() {
+            PDFObjT::Array(a) => assert_eq!(a.objs().len(), 3),
+            _ => panic!("expected a parameter array"),
+        }
+        let d = decode_stream(&e).unwrap();
+        assert_eq!(d.stream().val().content(), data.as_slice());
+
+        // encoding a stream with a filter array and a single
+        // parameter dictionary
+        let filters = [
+            EncodeFilter::new("ASCIIHexDecode", None),
+            EncodeFilter::new("RunLengthDecode", None),
+        ];
+        let e = encode_stream(&s, &filters).unwrap();
+        let content = e.stream().val().content().to_vec();
+        let d = "<</Filter [/ASCIIHexDecode /RunLengthDecode] /DecodeParms <</Foo 1>>>>";
+        let s2 = mk_stream(d, content);
+        let filters = [EncodeFilter::new("ASCII85Decode", None)];
+        let e = encode_stream(&s2, &filters).unwrap();
+        match e.dict().val().get(b"DecodeParms").unwrap().val() {
+            PDFObjT::Array(a) => {
+                assert_eq!(a.objs().len(), 3);
+                assert!(matches!(a.objs()[0].val(), PDFObjT::Null(_)));
+                assert!(matches!(a.objs()[1].val(), PDFObjT::Dict(_)));
+                assert!(matches!(a.objs()[2].val(), PDFObjT::Null(_)));
+            },
+            _ => panic!("expected a parameter array"),
+        }
+        let d = decode_stream(&e).unwrap();
+        assert_eq!(d.stream().val().content(), data.as_slice());
+
+        // unsupported filters and invalid parameters
+        let filters = [EncodeFilter::new("DCTDecode", None)];
+        assert!(encode_stream(&s, &filters).is_err());
+        let filters = [EncodeFilter::new("FlateDecode", Some(mk_obj("<<>>")))];
+        assert!(encode_stream(&s, &filters).is_ok());
+        let parms = Arc::new(LocatedVal::new(PDFObjT::Null(()), 0, 0));
+        let filters = [EncodeFilter::new("FlateDecode", Some(parms))];
+        assert!(encode_stream(&s, &filters).is_err());
     }
 }
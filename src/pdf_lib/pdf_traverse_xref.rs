@@ -22,23 +22,29 @@ extern crate env_logger;
 extern crate log;
 extern crate log_panics;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::prelude::*;
-use std::process;
-use std::rc::Rc;
 use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread::{self, ThreadId};
 
 use log::{log, Level};
+use rayon::prelude::*;
 
 use crate::pcore::parsebuffer::{
     ErrorKind, LocatedVal, Location, ParseBuffer, ParseBufferT, ParsleyParser,
 };
 use crate::pcore::transforms::{BufferTransformT, RestrictView};
 use crate::pdf_lib::pdf_file::{HeaderP, StartXrefP, TrailerP, XrefSectP};
-use crate::pdf_lib::pdf_obj::{IndirectP, ObjectId, PDFObjContext, PDFObjT};
+use crate::pdf_lib::pdf_filters::DecodeLimits;
+use crate::pdf_lib::pdf_obj::{
+    DictT, IndirectP, IndirectT, LengthRepair, ObjectId, ObjectLoader, PDFObjContext, PDFObjT,
+};
+use crate::pdf_lib::pdf_security::{string_bytes, StandardSecurityHandler};
 use crate::pdf_lib::pdf_streams::{ObjStreamP, XrefEntStatus, XrefEntT, XrefStreamP};
+use crate::pdf_lib::pdf_xref_recovery::{compare_xref, reconstruct_xref, RecoveryReport};
 
 /* from: https://osr.jpl.nasa.gov/wiki/pages/viewpage.action?spaceKey=SD&title=TA2+PDF+Safe+Parser+Evaluation
 
@@ -68,21 +74,136 @@ macro_rules! ta3_log {
     })
 }
 
-macro_rules! exit_log {
-    ($pos:expr, $($arg:tt)+) => ({
-        log!(Level::Error, "at {:>10} - {}", $pos, format_args!($($arg)+));
-        process::exit(1)
+// use this macro to abort parsing with an error at a file position.
+// Unlike the binaries, the library does not exit; it is up to the
+// caller to decide how to handle the error.
+macro_rules! fail {
+    ($kind:expr, $pos:expr, $($arg:tt)+) => ({
+        return Err(PdfParseError::new($kind, $pos, format!($($arg)+)))
     })
 }
 
+// The categories of structural failures that prevent a PDF file from
+// being parsed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PdfParseErrorKind {
+    // The file could not be located or read.
+    Io,
+    // No PDF magic, or an unparseable header.
+    Header,
+    // No startxref, or an unparseable or out-of-bounds startxref.
+    StartXref,
+    // Missing, cyclic, out-of-bounds or unparseable xref sections.
+    Xref,
+    // No root object, or an invalid root object.
+    Root,
+    // Objects that are out-of-bounds, unparseable, or do not match
+    // their xref entries.
+    Object,
+    // Unsupported encryption, an incorrect password, or objects that
+    // cannot be decrypted.
+    Encryption,
+}
+
+impl fmt::Display for PdfParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PdfParseErrorKind::Io => write!(f, "i/o error"),
+            PdfParseErrorKind::Header => write!(f, "header error"),
+            PdfParseErrorKind::StartXref => write!(f, "startxref error"),
+            PdfParseErrorKind::Xref => write!(f, "xref error"),
+            PdfParseErrorKind::Root => write!(f, "root error"),
+            PdfParseErrorKind::Object => write!(f, "object error"),
+            PdfParseErrorKind::Encryption => write!(f, "encryption error"),
+        }
+    }
+}
+
+// A fatal error encountered while parsing the structure of a PDF
+// file.  The offset is a file offset (i.e. it includes any leading
+// garbage before the PDF header).
+#[derive(Debug, PartialEq, Clone)]
+pub struct PdfParseError {
+    kind:   PdfParseErrorKind,
+    offset: usize,
+    msg:    String,
+}
+
+impl PdfParseError {
+    pub fn new(kind: PdfParseErrorKind, offset: usize, msg: String) -> PdfParseError {
+        PdfParseError { kind, offset, msg }
+    }
+    pub fn kind(&self) -> PdfParseErrorKind { self.kind }
+    pub fn offset(&self) -> usize { self.offset }
+    pub fn msg(&self) -> &str { &self.msg }
+}
+
+impl fmt::Display for PdfParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at file-offset {}: {}",
+            self.kind, self.offset, self.msg
+        )
+    }
+}
+
+impl std::error::Error for PdfParseError {}
+
+pub type PdfParseResult<T> = std::result::Result<T, PdfParseError>;
+
+// Options that control how a PDF file is parsed.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    // Rebuild the xref from a scan of the file body if the xref
+    // information is missing or inconsistent.
+    recover_xref:          bool,
+    // The user or owner password for encrypted files; the default is
+    // the empty user password.
+    password:              Vec<u8>,
+    // Limits on the decoded size of the streams in the file.
+    decode_limits:         DecodeLimits,
+    // Find the end of a stream by scanning if its /Length is wrong or
+    // cannot be resolved, instead of failing.
+    lenient_stream_length: bool,
+    // Parse each object when it is first looked up in the context,
+    // instead of all objects up front.  Errors in an object are then
+    // only reported when it is looked up.
+    lazy:                  bool,
+    // Parse the objects in the file body, and the object streams, on
+    // multiple threads.
+    parallel:              bool,
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions { ParseOptions::default() }
+    pub fn set_recover_xref(&mut self, recover: bool) { self.recover_xref = recover }
+    pub fn recover_xref(&self) -> bool { self.recover_xref }
+    pub fn set_password(&mut self, password: &[u8]) { self.password = password.to_vec() }
+    pub fn password(&self) -> &[u8] { &self.password }
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) { self.decode_limits = limits }
+    pub fn decode_limits(&self) -> &DecodeLimits { &self.decode_limits }
+    pub fn set_lenient_stream_length(&mut self, lenient: bool) {
+        self.lenient_stream_length = lenient
+    }
+    pub fn lenient_stream_length(&self) -> bool { self.lenient_stream_length }
+    pub fn set_lazy(&mut self, lazy: bool) { self.lazy = lazy }
+    pub fn lazy(&self) -> bool { self.lazy }
+    pub fn set_parallel(&mut self, parallel: bool) { self.parallel = parallel }
+    pub fn parallel(&self) -> bool { self.parallel }
+}
+
 pub struct FileInfo {
     path:        std::path::PathBuf,
     pdf_hdr_ofs: usize,
+    // set if the objects were located using a reconstructed xref
+    recovery:    Option<RecoveryReport>,
 }
 
 impl FileInfo {
     pub fn file_offset(&self, o: usize) -> usize { self.pdf_hdr_ofs + o }
     pub fn path(&self) -> &std::path::Path { &self.path }
+    pub fn recovery(&self) -> Option<&RecoveryReport> { self.recovery.as_ref() }
 }
 
 enum ObjInfo {
@@ -90,8 +211,44 @@ enum ObjInfo {
     Stream { id: usize, gen: usize },
 }
 
-type RootObjRef = Rc<LocatedVal<PDFObjT>>;
-type XRefSectInfo = (Vec<LocatedVal<XrefEntT>>, Option<RootObjRef>, Option<usize>);
+type RootObjRef = Arc<LocatedVal<PDFObjT>>;
+type TrailerRef = Arc<LocatedVal<DictT>>;
+// The xref entries of a section, its trailer (or xref stream
+// dictionary), and the offset of its end.
+type XRefSectInfo = (Vec<LocatedVal<XrefEntT>>, Option<TrailerRef>, usize);
+
+// A revision of the file, as written by its creation or by an
+// incremental update: an xref section (a table, a stream, or a
+// hybrid of the two) with its trailer, and the body it covers.
+// Offsets are relative to the PDF header.
+pub struct Revision {
+    xref_ofs: usize,
+    start:    usize,
+    end:      usize,
+    ents:     Vec<LocatedVal<XrefEntT>>,
+    trailer:  Option<TrailerRef>,
+    added:    Vec<ObjectId>,
+    changed:  Vec<ObjectId>,
+    freed:    Vec<ObjectId>,
+}
+
+impl Revision {
+    pub fn xref_offset(&self) -> usize { self.xref_ofs }
+    // The bytes from the end of the previous revision to the end of
+    // the %%EOF marker following this revision's xref section.
+    pub fn byte_range(&self) -> (usize, usize) { (self.start, self.end) }
+    pub fn ents(&self) -> &[LocatedVal<XrefEntT>] { &self.ents }
+    // The trailer dictionary, or the dictionary of the xref stream.
+    pub fn trailer(&self) -> Option<&TrailerRef> { self.trailer.as_ref() }
+    // The objects that were not in use in the previous revisions.
+    pub fn added(&self) -> &[ObjectId] { &self.added }
+    // The objects that replace objects in use in the previous
+    // revisions.
+    pub fn changed(&self) -> &[ObjectId] { &self.changed }
+    // The objects in use in the previous revisions that this revision
+    // frees.
+    pub fn freed(&self) -> &[ObjectId] { &self.freed }
+}
 
 // Parse a single xref stream.  It assumes that the parse cursor is
 // positioned at the stream object location, either via a startxref, a
@@ -100,8 +257,7 @@ fn parse_xref_stream(
     fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT,
 ) -> Option<XRefSectInfo> {
     let mut xrefs = Vec::new();
-    let mut root = None;
-    let mut prev = None;
+    let mut trailer = None;
 
     let mut sp = IndirectP::new(ctxt);
     let _xref_obj_loc = pb.get_cursor();
@@ -119,6 +275,7 @@ fn parse_xref_stream(
         return None
     };
     let xref_obj = xref_obj.unwrap();
+    let end = pb.get_cursor();
     if let PDFObjT::Stream(ref s) = xref_obj.val().obj().val() {
         /*
         ta3_log!(
@@ -146,7 +303,7 @@ fn parse_xref_stream(
             return None
         }
         let mut xref_buf = xref_buf.unwrap();
-        let mut xp = XrefStreamP::new(ctxt.is_encrypted(), s);
+        let mut xp = XrefStreamP::new(s, ctxt.decode_budget());
         let xref_stm = xp.parse(&mut xref_buf);
         if let Err(e) = xref_stm {
             ta3_log!(
@@ -175,11 +332,17 @@ fn parse_xref_stream(
         for e in xref_stm.val().ents() {
             xrefs.push(*e)
         }
-        // Extract trailer-like entries from the stream dict.
-        root = xref_stm.val().dict().get(b"Root").cloned();
-        prev = xref_stm.val().dict().get_usize(b"Prev");
+        // The stream dict has the trailer entries.
+        let dict = xref_stm.val().dict();
+        // check for encryption, keeping the newest /Encrypt
+        if let Some(e) = dict.get(b"Encrypt") {
+            if !ctxt.is_encrypted() {
+                ctxt.set_encryption(Arc::clone(e), dict.get(b"ID").cloned());
+            }
+        }
+        trailer = Some(Arc::clone(s.dict()));
     }
-    Some((xrefs, root, prev))
+    Some((xrefs, trailer, end))
 }
 
 // Parses a single xref section.  This section could be (a) an xref
@@ -189,7 +352,7 @@ fn parse_xref_stream(
 // handling a startxref or a /Prev.
 fn parse_xref_section(
     fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT,
-) -> Option<XRefSectInfo> {
+) -> PdfParseResult<Option<XRefSectInfo>> {
     // save the cursor
     let start = pb.get_cursor();
 
@@ -206,7 +369,7 @@ fn parse_xref_section(
         );
         // No xref section; check for xref stream.
         pb.set_cursor_unsafe(start);
-        return parse_xref_stream(fi, ctxt, pb)
+        return Ok(parse_xref_stream(fi, ctxt, pb))
     }
     let xrs = xrsect.unwrap();
     let mut xrefs = Vec::new();
@@ -230,7 +393,7 @@ fn parse_xref_section(
                 "No trailer found: {}",
                 e.val()
             );
-            return Some((xrefs, None, None))
+            return Ok(Some((xrefs, None, pb.get_cursor())))
         },
     }
     let mut p = TrailerP::new(ctxt);
@@ -243,17 +406,16 @@ fn parse_xref_section(
             "Cannot parse trailer: {}",
             e.val()
         );
-        return Some((xrefs, None, None))
+        return Ok(Some((xrefs, None, pb.get_cursor())))
     }
     let t = t.unwrap();
+    let end = pb.get_cursor();
 
-    // extract trailer-derived info: /Root and /Prev
-    let prev = t.val().dict().get_usize(b"Prev");
-    let root = t.val().dict().get(b"Root");
-    let root = root.cloned();
-    // check for encryption
-    if t.val().dict().get(b"Encrypt").is_some() {
-        ctxt.set_encrypted();
+    // check for encryption, keeping the newest /Encrypt
+    if let Some(e) = t.val().dict().get(b"Encrypt") {
+        if !ctxt.is_encrypted() {
+            ctxt.set_encryption(Arc::clone(e), t.val().dict().get(b"ID").cloned());
+        }
     }
 
     // Section 7.5.8.4: check for XRefStm in hybrid-reference
@@ -271,7 +433,7 @@ fn parse_xref_section(
         match pb.set_cursor(xrstart) {
             Ok(()) => {
                 let xref_stm = parse_xref_stream(fi, ctxt, pb);
-                if let Some((xrents, _root, _prev)) = xref_stm {
+                if let Some((xrents, _trailer, _end)) = xref_stm {
                     // Ignore the /Root and /Prev specifiers coming from
                     // the XRefStm.  (This seems to be implicit in the
                     // spec.)
@@ -279,47 +441,52 @@ fn parse_xref_section(
                         xrefs.push(e);
                     }
                 } else {
-                    exit_log!(
+                    fail!(
+                        PdfParseErrorKind::Xref,
                         fi.file_offset(xrefstm_loc),
                         "/XRefStm points to an invalid or encrypted XRefStm at {}",
                         xrstart
                     );
                 }
             },
-            Err(_) => exit_log!(
+            Err(_) => fail!(
+                PdfParseErrorKind::Xref,
                 fi.file_offset(xrefstm_loc),
                 "/XRefStm specifies out-of-bounds offset {}",
                 xrstart
             ),
         }
     }
-    Some((xrefs, root, prev))
+    let (start, end_t) = (t.start(), t.end());
+    let trailer = Arc::new(LocatedVal::new(t.unwrap().into_dict(), start, end_t));
+    Ok(Some((xrefs, Some(trailer), end)))
 }
 
-// This assumes that the parse cursor is set at the startxref
-// location.  It traverses a chain of xref tables (conventional or
-// hybrid) or xref streams to return the xref entries and a root.
-fn get_xref_info(
+// This assumes that the parse cursor is set at the location
+// specified by the startxref.  It traverses a chain of xref tables
+// (conventional or hybrid) or xref streams to return the revisions of
+// the file, oldest first.
+fn get_revisions(
     fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT,
-) -> (Vec<LocatedVal<XrefEntT>>, RootObjRef) {
+) -> PdfParseResult<Vec<Revision>> {
     // Collect all xref tables or streams, following the /Prev chain.
-    let mut xrefs = Vec::new();
-    let mut root = None;
+    let mut revs = Vec::new();
 
     let mut cursorset = BTreeSet::new(); // to prevent infinite loops
-    let mut idset = BTreeSet::new(); // to keep newest entries
 
     let mut next = pb.get_cursor();
     loop {
         if !cursorset.insert(next) {
-            exit_log!(
+            fail!(
+                PdfParseErrorKind::Xref,
                 fi.file_offset(next),
                 "Xref cycle detected at offset {}!",
                 next,
             );
         }
         if !pb.check_cursor(next) {
-            exit_log!(
+            fail!(
+                PdfParseErrorKind::Xref,
                 fi.file_offset(next),
                 "Xref offset {} is out of bounds!",
                 next,
@@ -327,48 +494,134 @@ fn get_xref_info(
         }
         pb.set_cursor_unsafe(next);
         // Check for a conventional or hybrid xref section.
-        let mut xinfo = parse_xref_section(fi, ctxt, pb);
+        let mut xinfo = parse_xref_section(fi, ctxt, pb)?;
         if xinfo.is_none() {
             xinfo = parse_xref_stream(fi, ctxt, pb);
         }
         if xinfo.is_none() {
-            exit_log!(
+            fail!(
+                PdfParseErrorKind::Xref,
                 fi.file_offset(next),
                 "No xref found at specified offset {}!",
                 next,
             );
         }
-        let (ents, rt, prev) = xinfo.unwrap();
-        // update root
-        if root.is_none() {
-            if rt.is_some() {
-                root = rt;
-            } else {
-                exit_log!(
-                    fi.file_offset(next),
-                    "No Root specified in xref at {}!",
-                    next,
-                );
-            }
-        }
-        // add entries
-        for e in ents {
-            let id = (e.val().obj(), e.val().gen());
-            if idset.insert(id) {
-                // This is the newest version of the object.
-                xrefs.push(e)
-            }
-        }
+        let (ents, trailer, sect_end) = xinfo.unwrap();
+        // The revision ends after the %%EOF following its section.
+        pb.set_cursor_unsafe(sect_end);
+        let end = match pb.scan(b"%%EOF") {
+            Ok(_) => {
+                // include the end-of-line marker
+                let rest = pb.buf();
+                let mut len = 5;
+                if rest.get(len) == Some(&b'\r') {
+                    len += 1
+                }
+                if rest.get(len) == Some(&b'\n') {
+                    len += 1
+                }
+                pb.get_cursor() + len
+            },
+            Err(_) => sect_end,
+        };
+        let prev = trailer.as_ref().and_then(|t| t.val().get_usize(b"Prev"));
+        revs.push(Revision {
+            xref_ofs: next,
+            start: 0,
+            end,
+            ents,
+            trailer,
+            added: Vec::new(),
+            changed: Vec::new(),
+            freed: Vec::new(),
+        });
         // goto prev
         match prev {
             None => break,
             Some(p) => next = p, // continue
         }
     }
-    if root.is_none() {
-        exit_log!(fi.file_offset(next), "No root object found!!",);
+    revs.reverse();
+
+    // Compute the changes made by each revision.  The objects in use
+    // are tracked by object number.
+    let mut in_use: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut last_end = 0;
+    for rev in revs.iter_mut() {
+        if rev.end > last_end {
+            rev.start = last_end;
+            last_end = rev.end
+        }
+        for (num, e) in revision_ents(&rev.ents) {
+            let e = e.val();
+            match (e.in_use(), in_use.get(&num)) {
+                (true, None) => rev.added.push((num, e.gen())),
+                (true, Some(_)) => rev.changed.push((num, e.gen())),
+                (false, Some(gen)) => rev.freed.push((num, *gen)),
+                (false, None) => (),
+            }
+            if e.in_use() {
+                in_use.insert(num, e.gen());
+            } else {
+                in_use.remove(&num);
+            }
+        }
+    }
+    Ok(revs)
+}
+
+// The entries of a revision by object number.  In a hybrid section,
+// the xref stream supplements the table, so entries for objects in
+// use take precedence over free entries.
+fn revision_ents(rev_ents: &[LocatedVal<XrefEntT>]) -> BTreeMap<usize, &LocatedVal<XrefEntT>> {
+    let mut ents: BTreeMap<usize, &LocatedVal<XrefEntT>> = BTreeMap::new();
+    for e in rev_ents {
+        match ents.get(&e.val().obj()) {
+            Some(o) if o.val().in_use() || !e.val().in_use() => (),
+            _ => {
+                ents.insert(e.val().obj(), e);
+            },
+        }
+    }
+    ents
+}
+
+// Merges the xref entries of the given revisions, keeping the newest
+// entry for each object number, and returns them along with the root from the
+// newest revision.
+fn merge_revisions(
+    fi: &FileInfo, revs: &[Revision],
+) -> PdfParseResult<(Vec<LocatedVal<XrefEntT>>, RootObjRef)> {
+    let newest = match revs.last() {
+        Some(r) => r,
+        None => fail!(PdfParseErrorKind::Root, 0, "No root object found!!",),
+    };
+    let root = match newest.trailer().and_then(|t| t.val().get(b"Root")) {
+        Some(r) => Arc::clone(r),
+        None => fail!(
+            PdfParseErrorKind::Root,
+            fi.file_offset(newest.xref_ofs),
+            "No Root specified in xref at {}!",
+            newest.xref_ofs,
+        ),
+    };
+    Ok((merged_ents(revs), root))
+}
+
+// The xref entries in effect after the given revisions, keeping the
+// newest entry for each object number.
+pub fn merged_ents(revs: &[Revision]) -> Vec<LocatedVal<XrefEntT>> {
+    let mut xrefs = Vec::new();
+    let mut numset = BTreeSet::new(); // to keep newest entries
+    for rev in revs.iter().rev() {
+        for (num, e) in revision_ents(&rev.ents) {
+            if numset.insert(num) {
+                // This is the newest version of the object.
+                xrefs.push(*e)
+            }
+        }
     }
-    (xrefs, root.unwrap())
+    xrefs
 }
 
 // Get the in-use object locations from the xref entries.
@@ -438,15 +691,14 @@ fn info_from_xref_entries(_fi: &FileInfo, xref_ents: &[LocatedVal<XrefEntT>]) ->
 // information.
 fn parse_objects(
     fi: &FileInfo, ctxt: &mut PDFObjContext, obj_infos: &[ObjInfo], pb: &mut dyn ParseBufferT,
-) {
+    opts: &ParseOptions,
+) -> PdfParseResult<()> {
     // Get the outermost objects at each offset in the xref table.
     // These have to be indirect/labelled objects.  Collect any
     // references to object streams since they will need to be parsed
     // subsequently.
     let mut obj_streams = BTreeSet::new();
-    // Some stream objects use references for their lengths, so
-    // collect them for a second pass.
-    let mut second_pass = Vec::new();
+    let mut first_pass = Vec::new();
     for obj in obj_infos.iter() {
         match obj {
             ObjInfo::Stream { id, gen } => {
@@ -469,129 +721,29 @@ fn parse_objects(
                      */
                     continue
                 }
-
-                let mut p = IndirectP::new(ctxt);
-                let ofs = *ofs;
-                /*
-                ta3_log!(
-                    Level::Info,
-                    fi.file_offset(ofs),
-                    "parsing object ({},{}) at {}file-offset {} (pdf-offset {})",
-                    id,
-                    gen,
-                    if ofs == 0 { "(possibly invalid) " } else { "" },
-                    fi.file_offset(ofs),
-                    ofs
-                );
-                 */
-                if !pb.check_cursor(ofs) {
-                    exit_log!(
-                        fi.file_offset(ofs),
-                        "object offset {} is out of bounds!",
-                        ofs,
-                    );
-                }
-                pb.set_cursor_unsafe(ofs);
-                let lobj = match p.parse(pb) {
-                    Ok(o) => o,
-                    Err(e) => {
-                        if let ErrorKind::InsufficientContext = e.val() {
-                            second_pass.push((id, gen, ofs));
-                            continue
-                        } else {
-                            exit_log!(
-                            fi.file_offset(e.start()),
-                            "Cannot parse object ({},{}) at file-offset {} (pdf-offset {}) in {}: {}",
-                            id,
-                            gen,
-                            fi.file_offset(e.start()),
-                            e.start(),
-                            fi.path().display(),
-                            e.val()
-                        )
-                        }
-                    },
-                };
-                let io = lobj.unwrap(); // unwrap LocatedVal.
-
-                // Validate that the object is what we expect.
-                // TODO: this constraint should be enforced in the library.
-                if (io.num(), io.gen()) != (*id, *gen) {
-                    exit_log!(
-                        fi.file_offset(ofs),
-                        "unexpected object ({},{}) found: expected ({},{}) from xref entry",
-                        io.num(),
-                        io.gen(),
-                        id,
-                        gen
-                    )
-                }
+                first_pass.push((id, gen, *ofs))
             },
         }
     }
 
-    // Do the second pass over objects that needed it.
-    for (id, gen, ofs) in second_pass {
-        // If we've already parsed this object, skip it.
-        if ctxt.lookup_obj((*id, *gen)).is_some() {
-            /*
-            ta3_log!(
-                Level::Info,
-                fi.file_offset(ofs),
-                "skipping already parsed object ({},{})",
-                id,
-                gen
-            );
-             */
-            continue
-        }
-        let mut p = IndirectP::new(ctxt);
-        /*
-        ta3_log!(
-            Level::Info,
-            fi.file_offset(ofs),
-            "second parse of object ({},{}) at {}file-offset {} (pdf-offset {})",
-            id,
-            gen,
-            if ofs == 0 { "(possibly invalid) " } else { "" },
-            fi.file_offset(ofs),
-            ofs
-        );
-         */
-        if !pb.check_cursor(ofs) {
-            exit_log!(
-                fi.file_offset(ofs),
-                "object offset {} is out of bounds!",
-                ofs,
-            );
-        }
-        pb.set_cursor_unsafe(ofs);
-        let lobj = match p.parse(pb) {
-            Ok(o) => o,
-            Err(e) => exit_log!(
-                fi.file_offset(e.start()),
-                "Cannot parse object ({},{}) at file-offset {} (pdf-offset {}) in {}: {}",
-                id,
-                gen,
-                fi.file_offset(e.start()),
-                e.start(),
-                fi.path().display(),
-                e.val()
-            ),
-        };
-        let io = lobj.unwrap(); // unwrap LocatedVal.
-                                // Validate that the object is what we expect.
-                                // TODO: this constraint should be enforced in the library.
-        if (io.num(), io.gen()) != (*id, *gen) {
-            exit_log!(
-                fi.file_offset(ofs),
-                "unexpected object ({},{}) found: expected ({},{}) from xref entry",
-                io.num(),
-                io.gen(),
-                id,
-                gen
-            )
-        }
+    // Some stream objects use references for their lengths, so
+    // collect them for a second pass.
+    let second_pass = if opts.parallel() {
+        parse_objects_parallel(fi, ctxt, pb, first_pass)?
+    } else {
+        parse_file_objects(fi, ctxt, pb, first_pass, false)?
+    };
+
+    // Do the second pass over objects that needed it.  Those whose
+    // /Length is in an object stream need a last pass after the object
+    // streams are parsed.
+    let last_pass = parse_file_objects(fi, ctxt, pb, second_pass, false)?;
+
+    // Decrypt the objects parsed so far, which includes the object
+    // streams.
+    if ctxt.is_encrypted() {
+        let ids = ctxt.obj_ids();
+        decrypt_objects(fi, ctxt, opts.password(), &ids)?;
     }
 
     // Now do the pass over the object streams, collecting only
@@ -611,7 +763,7 @@ fn parse_objects(
             },
             Some(obj) => {
                 if let PDFObjT::Stream(_) = obj.val() {
-                    let _ = defined_obj_streams.insert((id, Rc::clone(obj)));
+                    let _ = defined_obj_streams.insert((id, Arc::clone(obj)));
                 } else {
                     ta3_log!(
                         Level::Warn,
@@ -626,100 +778,626 @@ fn parse_objects(
     }
 
     // Now parse the object streams.
-    for (id, obj) in defined_obj_streams.iter() {
-        /*
+    if opts.parallel() {
+        parse_obj_streams_parallel(fi, ctxt, pb, &defined_obj_streams)
+    } else {
+        for (id, obj) in defined_obj_streams.iter() {
+            parse_obj_stream(fi, ctxt, pb, **id, obj)
+        }
+    }
+
+    if !last_pass.is_empty() {
+        let ids: Vec<ObjectId> = last_pass.iter().map(|(id, gen, _)| (**id, **gen)).collect();
+        parse_file_objects(fi, ctxt, pb, last_pass, true)?;
+        if ctxt.is_encrypted() {
+            decrypt_objects(fi, ctxt, opts.password(), &ids)?;
+        }
+    }
+    log_length_repairs(fi, ctxt.length_repairs());
+    Ok(())
+}
+
+fn log_length_repairs(fi: &FileInfo, repairs: &[LengthRepair]) {
+    for r in repairs {
         ta3_log!(
-            Level::Info,
-            fi.file_offset(0),
-            "parsing object stream ({},{})",
-            id.0,
-            id.1
+            Level::Warn,
+            fi.file_offset(r.offset()),
+            "stream object ({},{}) has /Length {:?} but {} bytes of content",
+            r.id().0,
+            r.id().1,
+            r.declared(),
+            r.actual()
         );
-         */
+    }
+}
 
-        if let PDFObjT::Stream(ref s) = obj.val() {
-            let content = s.stream().val();
-            let mut vxf = RestrictView::new(content.start(), content.size());
-            let obj_buf = vxf.transform(pb);
-            if let Err(e) = obj_buf {
+// Parses the objects in an object stream into the context.  Errors are
+// logged, and leave the objects in the stream undefined.
+fn parse_obj_stream(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT, id: ObjectId,
+    obj: &Arc<LocatedVal<PDFObjT>>,
+) {
+    /*
+    ta3_log!(
+        Level::Info,
+        fi.file_offset(0),
+        "parsing object stream ({},{})",
+        id.0,
+        id.1
+    );
+     */
+
+    if let PDFObjT::Stream(ref s) = obj.val() {
+        let content = s.stream().val();
+        let mut vxf = RestrictView::new(content.start(), content.size());
+        // The decrypted content is no longer in the file buffer.
+        let obj_buf = if ctxt.is_encrypted() {
+            Ok(ParseBuffer::new(content.content().to_vec()))
+        } else {
+            vxf.transform(pb)
+        };
+        if let Err(e) = obj_buf {
+            ta3_log!(
+                Level::Info,
+                fi.file_offset(pb.get_cursor()),
+                "Cannot create view for xref stream (offset:{}, size {}) in {} at file-offset {}: {:?}",
+                content.start(),
+                content.size(),
+                fi.path().display(),
+                fi.file_offset(e.start()),
+                e.val()
+            );
+            return
+        }
+        let mut obj_buf = obj_buf.unwrap();
+        let mut op = ObjStreamP::new(ctxt, s);
+        let obj_stm = op.parse(&mut obj_buf);
+        if let Err(e) = obj_stm {
+            ta3_log!(
+                Level::Error,
+                fi.file_offset(pb.get_cursor()),
+                "Cannot parse object stream ({},{}) in {} at file-offset {} (pdf-offset {}): {}",
+                id.0,
+                id.1,
+                fi.path().display(),
+                fi.file_offset(e.start()),
+                e.start(),
+                e.val()
+            );
+        // TODO: we could stop parsing here, but since this is
+        // a nested parse, we opt to continue for now.
+        } else {
+            let obj_stm = obj_stm.unwrap();
+            for _o in obj_stm.val().objs() {
+                /*
                 ta3_log!(
                     Level::Info,
-                    fi.file_offset(pb.get_cursor()),
-                    "Cannot create view for xref stream (offset:{}, size {}) in {} at file-offset {}: {:?}",
-                    content.start(),
-                    content.size(),
-                    fi.path().display(),
-                    fi.file_offset(e.start()),
-                    e.val()
+                    fi.file_offset(content.start()),
+                    "Parsed object ({},{}) of {} from stream ({},{}).",
+                    o.val().num(),
+                    o.val().gen(),
+                    obj_stm.val().objs().len(),
+                    id.0,
+                    id.1
                 );
-                continue
+                 */
             }
-            let mut obj_buf = obj_buf.unwrap();
-            let mut op = ObjStreamP::new(ctxt, s);
-            let obj_stm = op.parse(&mut obj_buf);
-            if let Err(e) = obj_stm {
-                ta3_log!(
-                    Level::Error,
-                    fi.file_offset(pb.get_cursor()),
-                    "Cannot parse object stream ({},{}) in {} at file-offset {} (pdf-offset {}): {}",
-                    id.0, id.1,
-                    fi.path().display(),
-                    fi.file_offset(e.start()),
-                    e.start(),
-                    e.val()
-                );
-            // TODO: we could stop parsing here, but since this is
-            // a nested parse, we opt to continue for now.
-            } else {
-                let obj_stm = obj_stm.unwrap();
-                for _o in obj_stm.val().objs() {
-                    /*
+        }
+    }
+}
+
+// Parses the object at the given offset, and validates that it is the
+// object expected from its xref entry.  Unless this is the last pass,
+// returns None if the object is a stream whose /Length refers to an
+// object that has not been parsed yet.
+fn parse_obj_at(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT, id: ObjectId, ofs: usize,
+    last_pass: bool,
+) -> PdfParseResult<Option<Arc<LocatedVal<PDFObjT>>>> {
+    let mut p = if last_pass {
+        IndirectP::new_last_pass(ctxt)
+    } else {
+        IndirectP::new(ctxt)
+    };
+    /*
+    ta3_log!(
+        Level::Info,
+        fi.file_offset(ofs),
+        "parsing object ({},{}) at {}file-offset {} (pdf-offset {})",
+        id.0,
+        id.1,
+        if ofs == 0 { "(possibly invalid) " } else { "" },
+        fi.file_offset(ofs),
+        ofs
+    );
+     */
+    if !pb.check_cursor(ofs) {
+        fail!(
+            PdfParseErrorKind::Object,
+            fi.file_offset(ofs),
+            "object offset {} is out of bounds!",
+            ofs,
+        );
+    }
+    pb.set_cursor_unsafe(ofs);
+    let lobj = match p.parse(pb) {
+        Ok(o) => o,
+        Err(e) if !last_pass && e.val() == &ErrorKind::InsufficientContext => return Ok(None),
+        Err(e) => fail!(
+            PdfParseErrorKind::Object,
+            fi.file_offset(e.start()),
+            "Cannot parse object ({},{}) at file-offset {} (pdf-offset {}) in {}: {}",
+            id.0,
+            id.1,
+            fi.file_offset(e.start()),
+            e.start(),
+            fi.path().display(),
+            e.val()
+        ),
+    };
+    let io = lobj.unwrap(); // unwrap LocatedVal.
+
+    // Validate that the object is what we expect.
+    // TODO: this constraint should be enforced in the library.
+    if (io.num(), io.gen()) != id {
+        fail!(
+            PdfParseErrorKind::Object,
+            fi.file_offset(ofs),
+            "unexpected object ({},{}) found: expected ({},{}) from xref entry",
+            io.num(),
+            io.gen(),
+            id.0,
+            id.1
+        )
+    }
+    Ok(Some(Arc::clone(io.obj())))
+}
+
+// Parses the objects at their offsets in the file, skipping those that
+// are already parsed.  Unless this is the last pass, the streams whose
+// /Length refers to an object that has not been parsed yet are
+// returned for another attempt.
+fn parse_file_objects<'a>(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT,
+    objs: Vec<(&'a usize, &'a usize, usize)>, last_pass: bool,
+) -> PdfParseResult<Vec<(&'a usize, &'a usize, usize)>> {
+    let mut deferred = Vec::new();
+    for (id, gen, ofs) in objs {
+        // If we've already parsed this object, skip it.
+        if ctxt.lookup_obj((*id, *gen)).is_some() {
+            /*
+            ta3_log!(
+                Level::Info,
+                fi.file_offset(ofs),
+                "skipping already parsed object ({},{})",
+                id,
+                gen
+            );
+             */
+            continue
+        }
+        if parse_obj_at(fi, ctxt, pb, (*id, *gen), ofs, last_pass)?.is_none() {
+            // The /Length may be in an object stream.
+            deferred.push((id, gen, ofs))
+        }
+    }
+    Ok(deferred)
+}
+
+// A view of the entire buffer, which can be parsed independently of
+// it.
+fn shared_view(pb: &dyn ParseBufferT) -> ParseBuffer {
+    let mut view = RestrictView::new(0, pb.size());
+    view.transform(pb).unwrap()
+}
+
+// Like parse_file_objects, but on multiple threads.  Each thread
+// parses a contiguous range of the objects into a forked context, and
+// the contexts are joined in order, so that the objects and the first
+// error are those of a sequential parse.  A stream whose /Length is in
+// another range is deferred.
+fn parse_objects_parallel<'a>(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &dyn ParseBufferT,
+    objs: Vec<(&'a usize, &'a usize, usize)>,
+) -> PdfParseResult<Vec<(&'a usize, &'a usize, usize)>> {
+    let buf = shared_view(pb);
+    let base = ctxt.fork();
+    let chunk = objs.len().div_ceil(rayon::current_num_threads()).max(1);
+    let parts: Vec<_> = objs
+        .par_chunks(chunk)
+        .map(|objs| {
+            let mut ctxt = base.fork();
+            let mut pb = shared_view(&buf);
+            parse_file_objects(fi, &mut ctxt, &mut pb, objs.to_vec(), false).map(|d| (ctxt, d))
+        })
+        .collect();
+    let mut deferred = Vec::new();
+    for part in parts {
+        let (part, d) = part?;
+        let _ = ctxt.join(part);
+        deferred.extend(d)
+    }
+    Ok(deferred)
+}
+
+// Parses the object streams on multiple threads, each into a forked
+// context.  The contexts are joined in order.  Unlike in a sequential
+// parse, an object that is already defined is only skipped, instead
+// of ending the parse of its stream.
+fn parse_obj_streams_parallel(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &dyn ParseBufferT,
+    obj_streams: &BTreeSet<(&ObjectId, Arc<LocatedVal<PDFObjT>>)>,
+) {
+    let buf = shared_view(pb);
+    let base = ctxt.fork();
+    let obj_streams: Vec<_> = obj_streams.iter().collect();
+    let parts: Vec<_> = obj_streams
+        .par_iter()
+        .map(|(id, obj)| {
+            let mut ctxt = base.fork();
+            parse_obj_stream(fi, &mut ctxt, &mut shared_view(&buf), **id, obj);
+            (**id, ctxt)
+        })
+        .collect();
+    for (id, part) in parts {
+        for oid in ctxt.join(part) {
+            ta3_log!(
+                Level::Error,
+                fi.file_offset(0),
+                "non-unique object id ({},{}) in object stream ({},{})",
+                oid.0,
+                oid.1,
+                id.0,
+                id.1
+            );
+        }
+    }
+}
+
+// The security handler of an encrypted file, and the id of its
+// /Encrypt object, if it is an indirect object.
+type SecurityInfo = (Option<ObjectId>, StandardSecurityHandler);
+
+fn security_handler(
+    fi: &FileInfo, ctxt: &PDFObjContext, password: &[u8],
+) -> PdfParseResult<SecurityInfo> {
+    let encrypt = Arc::clone(ctxt.encrypt_dict().unwrap());
+    let (encrypt_id, encrypt) = match encrypt.val() {
+        PDFObjT::Reference(r) => match ctxt.lookup_obj(r.id()) {
+            Some(o) => (Some(r.id()), Arc::clone(o)),
+            None => fail!(
+                PdfParseErrorKind::Encryption,
+                fi.file_offset(encrypt.start()),
+                "/Encrypt object ({},{}) not found",
+                r.num(),
+                r.gen()
+            ),
+        },
+        _ => (None, encrypt),
+    };
+    // The first element of the /ID array is needed for key
+    // derivation in revisions 2-4.
+    let id = match ctxt.file_id().map(|o| o.val()) {
+        Some(PDFObjT::Array(a)) if !a.objs().is_empty() => {
+            string_bytes(&a.objs()[0]).unwrap_or_default()
+        },
+        _ => {
+            ta3_log!(
+                Level::Warn,
+                fi.file_offset(encrypt.start()),
+                "no valid /ID found for encrypted file {}",
+                fi.path().display()
+            );
+            Vec::new()
+        },
+    };
+    match StandardSecurityHandler::new(&encrypt, &id, password) {
+        Ok(h) => Ok((encrypt_id, h)),
+        Err(e) => fail!(
+            PdfParseErrorKind::Encryption,
+            fi.file_offset(e.start()),
+            "Cannot decrypt {}: {}",
+            fi.path().display(),
+            e.val()
+        ),
+    }
+}
+
+// Returns the decrypted version of an object, or None if the object
+// is not encrypted: the /Encrypt dictionary, and xref streams.
+fn decrypt_obj(
+    fi: &FileInfo, sec: &SecurityInfo, id: ObjectId, obj: &Arc<LocatedVal<PDFObjT>>,
+) -> PdfParseResult<Option<Arc<LocatedVal<PDFObjT>>>> {
+    if Some(id) == sec.0 {
+        return Ok(None)
+    }
+    if let PDFObjT::Stream(s) = obj.val() {
+        if s.dict().val().get_name(b"Type") == Some(b"XRef") {
+            return Ok(None)
+        }
+    }
+    match sec.1.decrypt_obj(id, obj) {
+        Ok(o) => Ok(Some(o)),
+        Err(e) => fail!(
+            PdfParseErrorKind::Encryption,
+            fi.file_offset(e.start()),
+            "{}",
+            e.val()
+        ),
+    }
+}
+
+// Replaces the given objects in the context with their decrypted
+// versions, using the standard security handler.
+fn decrypt_objects(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, password: &[u8], ids: &[ObjectId],
+) -> PdfParseResult<()> {
+    let sec = security_handler(fi, ctxt, password)?;
+    for id in ids.iter().copied() {
+        let obj = Arc::clone(ctxt.lookup_obj(id).unwrap());
+        if let Some(dobj) = decrypt_obj(fi, &sec, id, &obj)? {
+            let io = IndirectT::new(id.0, id.1, dobj);
+            let _ = ctxt.register_obj(&obj.place(io));
+        }
+    }
+    Ok(())
+}
+
+type LoadResult = PdfParseResult<Option<Arc<LocatedVal<PDFObjT>>>>;
+type ObjMap = BTreeMap<ObjectId, Arc<LocatedVal<PDFObjT>>>;
+
+// Parses the objects of a context in lazy mode from their xref
+// entries.  Each object is parsed, and decrypted if needed, the first
+// time it is looked up, and the result is cached.  Stream /Length
+// references are resolved by loading the referenced object.
+struct LazyLoader {
+    this:     Weak<LazyLoader>,
+    fi:       FileInfo,
+    pb:       ParseBuffer,
+    opts:     ParseOptions,
+    ents:     BTreeMap<ObjectId, XrefEntStatus>,
+    objs:     BTreeMap<ObjectId, OnceLock<LoadResult>>,
+    // The objects parsed from each object stream, by stream number.
+    streams:  BTreeMap<usize, OnceLock<ObjMap>>,
+    // The objects being loaded by each thread, to detect reference
+    // cycles.
+    loading:  Mutex<HashSet<(ThreadId, ObjectId)>>,
+    // The settings of the contexts for parsing objects.
+    base:     PDFObjContext,
+    security: OnceLock<PdfParseResult<SecurityInfo>>,
+}
+
+impl LazyLoader {
+    fn new(
+        fi: &FileInfo, ctxt: &PDFObjContext, xref_ents: &[LocatedVal<XrefEntT>],
+        pb: &dyn ParseBufferT, opts: &ParseOptions,
+    ) -> Arc<LazyLoader> {
+        let mut ents = BTreeMap::new();
+        let mut streams = BTreeMap::new();
+        for e in xref_ents {
+            let e = e.val();
+            match e.status() {
+                XrefEntStatus::Free { .. } => continue,
+                XrefEntStatus::InUse { .. } => (),
+                XrefEntStatus::InStream { stream_obj, .. } => {
+                    let _ = streams.insert(*stream_obj, OnceLock::new());
+                },
+            }
+            let _ = ents.insert((e.obj(), e.gen()), *e.status());
+        }
+        let objs = ents.keys().map(|id| (*id, OnceLock::new())).collect();
+        Arc::new_cyclic(|this| LazyLoader {
+            this: Weak::clone(this),
+            fi: FileInfo {
+                path:        fi.path.clone(),
+                pdf_hdr_ofs: fi.pdf_hdr_ofs,
+                recovery:    None,
+            },
+            pb: shared_view(pb),
+            opts: opts.clone(),
+            ents,
+            objs,
+            streams,
+            loading: Mutex::new(HashSet::new()),
+            base: ctxt.fork(),
+            security: OnceLock::new(),
+        })
+    }
+
+    // A context for parsing objects, which loads the objects it
+    // refers to from this loader.
+    fn context(&self) -> PDFObjContext {
+        let mut ctxt = self.base.fork();
+        if let Some(this) = self.this.upgrade() {
+            ctxt.set_loader(this)
+        }
+        ctxt
+    }
+
+    fn view(&self) -> ParseBuffer { shared_view(&self.pb) }
+
+    fn parse_obj(&self, id: ObjectId) -> LoadResult {
+        match self.ents[&id] {
+            XrefEntStatus::InUse { file_ofs } => {
+                let mut ctxt = self.context();
+                let obj = parse_obj_at(&self.fi, &mut ctxt, &mut self.view(), id, file_ofs, true)?;
+                log_length_repairs(&self.fi, ctxt.length_repairs());
+                match obj {
+                    Some(o) => self.decrypt(id, o).map(Some),
+                    None => Ok(None),
+                }
+            },
+            XrefEntStatus::InStream { stream_obj, .. } => {
+                Ok(self.stream_objs(stream_obj)?.get(&id).cloned())
+            },
+            XrefEntStatus::Free { .. } => Ok(None),
+        }
+    }
+
+    // Returns the objects in an object stream.  As in eager mode, a
+    // missing or invalid object stream is logged, and leaves its
+    // objects undefined.
+    fn stream_objs(&self, num: usize) -> PdfParseResult<&ObjMap> {
+        let cell = &self.streams[&num];
+        if let Some(objs) = cell.get() {
+            return Ok(objs)
+        }
+        let mut ctxt = self.context();
+        match self.load((num, 0))? {
+            None => ta3_log!(
+                Level::Warn,
+                self.fi.file_offset(0),
+                "stream object ({},{}) not found",
+                num,
+                0
+            ),
+            Some(obj) => {
+                if let PDFObjT::Stream(_) = obj.val() {
+                    parse_obj_stream(&self.fi, &mut ctxt, &mut self.view(), (num, 0), obj)
+                } else {
                     ta3_log!(
-                        Level::Info,
-                        fi.file_offset(content.start()),
-                        "Parsed object ({},{}) of {} from stream ({},{}).",
-                        o.val().num(),
-                        o.val().gen(),
-                        obj_stm.val().objs().len(),
-                        id.0,
-                        id.1
-                    );
-                     */
+                        Level::Warn,
+                        self.fi.file_offset(0),
+                        "object ({},{}) is not a stream",
+                        num,
+                        0
+                    )
                 }
+            },
+        }
+        // Loading an object in the stream while parsing it, or another
+        // thread, could have already set the cell.
+        let objs = ctxt.into_objs();
+        Ok(cell.get_or_init(|| objs))
+    }
+
+    fn decrypt(
+        &self, id: ObjectId, obj: Arc<LocatedVal<PDFObjT>>,
+    ) -> PdfParseResult<Arc<LocatedVal<PDFObjT>>> {
+        let encrypt = match self.base.encrypt_dict() {
+            Some(e) => e,
+            None => return Ok(obj),
+        };
+        // The /Encrypt dictionary is needed for the security handler.
+        if let PDFObjT::Reference(r) = encrypt.val() {
+            if r.id() == id {
+                return Ok(obj)
             }
         }
+        let sec = match self.security.get() {
+            Some(sec) => sec,
+            None => {
+                let sec = security_handler(&self.fi, &self.context(), self.opts.password());
+                self.security.get_or_init(|| sec)
+            },
+        };
+        let sec = match sec {
+            Ok(sec) => sec,
+            Err(e) => return Err(e.clone()),
+        };
+        Ok(decrypt_obj(&self.fi, sec, id, &obj)?.unwrap_or(obj))
+    }
+}
+
+impl ObjectLoader for LazyLoader {
+    fn load(&self, id: ObjectId) -> PdfParseResult<Option<&Arc<LocatedVal<PDFObjT>>>> {
+        let cell = match self.objs.get(&id) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        if cell.get().is_none() {
+            let key = (thread::current().id(), id);
+            if !self.loading.lock().unwrap().insert(key) {
+                fail!(
+                    PdfParseErrorKind::Object,
+                    self.fi.file_offset(0),
+                    "object ({},{}) refers to itself while being loaded",
+                    id.0,
+                    id.1
+                )
+            }
+            let res = self.parse_obj(id);
+            let _ = self.loading.lock().unwrap().remove(&key);
+            let _ = cell.get_or_init(|| res);
+        }
+        match cell.get().unwrap() {
+            Ok(o) => Ok(o.as_ref()),
+            Err(e) => Err(e.clone()),
+        }
+    }
+    fn obj_ids(&self) -> Vec<ObjectId> { self.ents.keys().cloned().collect() }
+}
+
+// Puts the objects specified by the xref entries into the context,
+// either by parsing them, or in lazy mode, by installing a loader.
+fn load_objects(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, xref_ents: &[LocatedVal<XrefEntT>],
+    pb: &mut dyn ParseBufferT, opts: &ParseOptions,
+) -> PdfParseResult<()> {
+    if opts.lazy() {
+        ctxt.set_loader(LazyLoader::new(fi, ctxt, xref_ents, pb, opts));
+        return Ok(())
     }
+    let id_offsets = info_from_xref_entries(fi, xref_ents);
+    parse_objects(fi, ctxt, &id_offsets, pb, opts)
+}
+
+pub fn parse_file(test_file: &str) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    parse_file_with_options(test_file, &ParseOptions::new())
 }
 
-pub fn parse_file(test_file: &str) -> (FileInfo, PDFObjContext, ObjectId) {
+pub fn parse_file_with_options(
+    test_file: &str, opts: &ParseOptions,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
     // Print current path
     let path = env::current_dir();
     if path.is_err() {
-        exit_log!(0, "Cannot get current dir!");
+        fail!(PdfParseErrorKind::Io, 0, "Cannot get current dir!");
     }
     let mut path = path.unwrap();
     path.push(test_file);
     let display = path.as_path().display();
 
     // Open the path in read-only mode, returns `io::Result<File>`
-    let mut file = match File::open(path.as_path()) {
+    let file = match File::open(path.as_path()) {
         Err(why) => {
-            exit_log!(0, "Couldn't open {}: {}", display, why.to_string());
+            fail!(
+                PdfParseErrorKind::Io,
+                0,
+                "Couldn't open {}: {}",
+                display,
+                why.to_string()
+            );
         },
         Ok(file) => file,
     };
 
-    // Read the file contents into a string, returns `io::Result<usize>`
-    let mut v = Vec::new();
-    if let Err(why) = file.read_to_end(&mut v) {
-        exit_log!(0, "Couldn't read {}: {}", display, why.to_string());
+    // Map the file contents instead of reading them, so that large
+    // files are not copied into memory.
+    let pb = match ParseBuffer::map_file(&file) {
+        Err(why) => {
+            fail!(
+                PdfParseErrorKind::Io,
+                0,
+                "Couldn't read {}: {}",
+                display,
+                why.to_string()
+            );
+        },
+        Ok(pb) => pb,
     };
-    parse_data(&path, &v)
+    parse_buffer_with_options(&path, pb, opts)
 }
 
-pub fn parse_data(path: &Path, data: &[u8]) -> (FileInfo, PDFObjContext, ObjectId) {
-    let mut pb = ParseBuffer::new(data.to_vec());
+pub fn parse_data(path: &Path, data: &[u8]) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    parse_data_with_options(path, data, &ParseOptions::new())
+}
 
+// Locates and parses the header, and returns the buffer restricted to
+// the PDF content along with its length.
+fn parse_header(
+    path: &Path, mut pb: ParseBuffer,
+) -> PdfParseResult<(FileInfo, ParseBuffer, usize)> {
     // Handle leading garbage.
     let pdf_hdr_ofs = match pb.scan(b"%PDF-") {
         Ok(nbytes) => {
@@ -740,19 +1418,26 @@ pub fn parse_data(path: &Path, data: &[u8]) -> (FileInfo, PDFObjContext, ObjectI
             nbytes
         },
         Err(e) => {
-            exit_log!(0, "Cannot find PDF magic: {}", e.val());
+            fail!(
+                PdfParseErrorKind::Header,
+                0,
+                "Cannot find PDF magic: {}",
+                e.val()
+            );
         },
     };
     let fi = FileInfo {
         pdf_hdr_ofs,
         path: path.to_path_buf(),
+        recovery: None,
     };
 
     let buflen = pb.remaining();
     let mut p = HeaderP;
     let hdr = p.parse(&mut pb);
     if let Err(e) = hdr {
-        exit_log!(
+        fail!(
+            PdfParseErrorKind::Header,
             fi.file_offset(0),
             "Unable to parse header from {}: {}",
             fi.path().display(),
@@ -782,12 +1467,140 @@ pub fn parse_data(path: &Path, data: &[u8]) -> (FileInfo, PDFObjContext, ObjectI
             eof_ofs
         );
     }
+    Ok((fi, pb, buflen))
+}
+
+// Parses a copy of the file data; use parse_buffer_with_options to
+// parse a buffer without copying it.
+pub fn parse_data_with_options(
+    path: &Path, data: &[u8], opts: &ParseOptions,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    parse_buffer_with_options(path, ParseBuffer::new(data.to_vec()), opts)
+}
+
+// Parses the file data along with its revisions, sharing a single
+// copy of the data between them.
+pub fn parse_data_with_revisions(
+    path: &Path, data: &[u8], opts: &ParseOptions,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId, Vec<Revision>)> {
+    let pb = ParseBuffer::new(data.to_vec());
+    let view = RestrictView::new(0, pb.size()).transform(&pb).unwrap();
+    let (fi, ctxt, root) = parse_buffer_with_options(path, view, opts)?;
+    let (_, revs) = parse_buffer_revisions(path, pb)?;
+    Ok((fi, ctxt, root, revs))
+}
+
+// Parses the file contents in the buffer, which can be a mapped file.
+pub fn parse_buffer_with_options(
+    path: &Path, pb: ParseBuffer, opts: &ParseOptions,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    let (fi, mut pb, buflen) = parse_header(path, pb)?;
+
+    // Create the pdf object context.
+    // TODO: control max-depth via command-line option.
+    let mut ctxt = PDFObjContext::new(50);
+    ctxt.set_decode_limits(*opts.decode_limits());
+    ctxt.set_lenient_stream_length(opts.lenient_stream_length());
+    let mut xref_ents = Vec::new();
+    let res = parse_xref_and_objects(&fi, &mut ctxt, &mut pb, buflen, opts, &mut xref_ents);
+    let cause = match res {
+        Ok(root_id) => return Ok((fi, ctxt, root_id)),
+        Err(e) if !opts.recover_xref() => return Err(e),
+        Err(e) => e,
+    };
+
+    // Rebuild the xref from a scan of the body, and retry with a
+    // fresh context.
+    ta3_log!(
+        Level::Warn,
+        cause.offset(),
+        "reconstructing xref for {} after {}",
+        fi.path().display(),
+        cause
+    );
+    let rec = reconstruct_xref(&fi, &mut pb);
+    let root_ref = match rec.root() {
+        Some(r) => Arc::clone(r),
+        None => fail!(
+            PdfParseErrorKind::Root,
+            cause.offset(),
+            "No root object found during xref recovery after {}",
+            cause
+        ),
+    };
+    let root_id: ObjectId = if let PDFObjT::Reference(r) = root_ref.val() {
+        r.id()
+    } else {
+        fail!(
+            PdfParseErrorKind::Root,
+            fi.file_offset(root_ref.loc_start()),
+            "Root object is not a reference!"
+        );
+    };
+    let mut ctxt = PDFObjContext::new(50);
+    ctxt.set_decode_limits(*opts.decode_limits());
+    ctxt.set_lenient_stream_length(opts.lenient_stream_length());
+    if let Some(e) = rec.encrypt() {
+        ctxt.set_encryption(Arc::clone(e), rec.file_id().cloned())
+    }
+    load_objects(&fi, &mut ctxt, rec.ents(), &mut pb, opts)?;
+
+    let mismatches = compare_xref(&xref_ents, rec.ents());
+    let mut fi = fi;
+    fi.recovery = Some(RecoveryReport::new(cause, mismatches));
+    Ok((fi, ctxt, root_id))
+}
+
+// Parses the xref information starting from the startxref, and then
+// the objects it specifies.  The xref entries are collected into
+// 'xref_ents' as they are found, so that they are available to the
+// caller even when a subsequent step fails.
+fn parse_xref_and_objects(
+    fi: &FileInfo, ctxt: &mut PDFObjContext, pb: &mut dyn ParseBufferT, buflen: usize,
+    opts: &ParseOptions, xref_ents: &mut Vec<LocatedVal<XrefEntT>>,
+) -> PdfParseResult<ObjectId> {
+    parse_startxref(fi, pb, buflen)?;
+    let revs = get_revisions(fi, ctxt, pb)?;
+    let (ents, root_ref) = merge_revisions(fi, &revs)?;
+    xref_ents.extend(ents);
+    /*
+    ta3_log!(
+        Level::Info,
+        fi.file_offset(pb.get_cursor()),
+        "Found {} objects in xref table.",
+        xref_ents.len()
+    );
+     */
+
+    // Parse the objects using their xref entries, and put them into the context.
+    load_objects(fi, ctxt, xref_ents, pb, opts)?;
 
+    root_id(fi, &root_ref)
+}
+
+fn root_id(fi: &FileInfo, root_ref: &RootObjRef) -> PdfParseResult<ObjectId> {
+    if let PDFObjT::Reference(r) = root_ref.val() {
+        Ok(r.id())
+    } else {
+        // Is there any case where this is not the case?  Should
+        // this constraint be part of the safe subset specification?
+        fail!(
+            PdfParseErrorKind::Root,
+            fi.file_offset(root_ref.loc_start()),
+            "Root object is not a reference!"
+        );
+    }
+}
+
+// Finds and parses the last startxref in the file, and sets the parse
+// cursor to the xref location it specifies.
+fn parse_startxref(fi: &FileInfo, pb: &mut dyn ParseBufferT, buflen: usize) -> PdfParseResult<()> {
     // Scan backward for startxref.
     let sxref = pb.backward_scan(b"startxref");
     if let Err(e) = sxref {
-        exit_log!(
-            0,
+        fail!(
+            PdfParseErrorKind::StartXref,
+            fi.file_offset(0),
             "Could not find startxref in {}: {}",
             fi.path().display(),
             e.val()
@@ -804,9 +1617,10 @@ pub fn parse_data(path: &Path, data: &[u8]) -> (FileInfo, PDFObjContext, ObjectI
     );
      */
     let mut p = StartXrefP;
-    let sxref = p.parse(&mut pb);
+    let sxref = p.parse(pb);
     if let Err(e) = sxref {
-        exit_log!(
+        fail!(
+            PdfParseErrorKind::StartXref,
             fi.file_offset(sxref_ofs),
             "Could not parse startxref in {} at file-offset {} (pdf-offset {}): {}",
             fi.path().display(),
@@ -838,44 +1652,578 @@ pub fn parse_data(path: &Path, data: &[u8]) -> (FileInfo, PDFObjContext, ObjectI
     );
      */
 
-    // Create the pdf object context.
-    // TODO: control max-depth via command-line option.
-    let mut ctxt = PDFObjContext::new(50);
-
     // Parse xref table at that offset.
     if !pb.check_cursor(sxref_offset) {
-        exit_log!(
+        fail!(
+            PdfParseErrorKind::StartXref,
             fi.file_offset(sxref_loc_start),
             "startxref specifies out-of-bounds offset {}",
             sxref_offset
         );
     }
     pb.set_cursor_unsafe(sxref_offset);
-    let (xref_ents, root_ref) = get_xref_info(&fi, &mut ctxt, &mut pb);
-    /*
-    ta3_log!(
-        Level::Info,
-        fi.file_offset(pb.get_cursor()),
-        "Found {} objects in xref table.",
-        xref_ents.len()
-    );
-     */
+    Ok(())
+}
 
-    let id_offsets = info_from_xref_entries(&fi, &xref_ents);
+// Parses the revisions of a copy of the file data, without parsing
+// their objects.
+pub fn parse_revisions(path: &Path, data: &[u8]) -> PdfParseResult<(FileInfo, Vec<Revision>)> {
+    parse_buffer_revisions(path, ParseBuffer::new(data.to_vec()))
+}
 
-    // Parse the objects using their xref entries, and put them into the context.
-    parse_objects(&fi, &mut ctxt, &id_offsets, &mut pb);
+// Parses the revisions of the file contents in the buffer.
+pub fn parse_buffer_revisions(
+    path: &Path, pb: ParseBuffer,
+) -> PdfParseResult<(FileInfo, Vec<Revision>)> {
+    let (fi, mut pb, buflen) = parse_header(path, pb)?;
+    let mut ctxt = PDFObjContext::new(50);
+    parse_startxref(&fi, &mut pb, buflen)?;
+    let revs = get_revisions(&fi, &mut ctxt, &mut pb)?;
+    Ok((fi, revs))
+}
 
-    let root_id: ObjectId = if let PDFObjT::Reference(r) = root_ref.val() {
-        r.id()
-    } else {
-        // Is there any case where this is not the case?  Should
-        // this constraint be part of the safe subset specification?
-        exit_log!(
-            fi.file_offset(root_ref.loc_start()),
-            "Root object is not a reference!"
+// Parses the file as of the given revision, where revision 0 is the
+// oldest.  Objects added or changed by later revisions are ignored.
+// The xref is not recovered if it is damaged.  The data is copied;
+// parse_buffer_at_revision parses a buffer without copying it.
+pub fn parse_data_at_revision(
+    path: &Path, data: &[u8], opts: &ParseOptions, rev: usize,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    parse_buffer_at_revision(path, ParseBuffer::new(data.to_vec()), opts, rev)
+}
+
+pub fn parse_buffer_at_revision(
+    path: &Path, pb: ParseBuffer, opts: &ParseOptions, rev: usize,
+) -> PdfParseResult<(FileInfo, PDFObjContext, ObjectId)> {
+    let (fi, mut pb, buflen) = parse_header(path, pb)?;
+    parse_startxref(&fi, &mut pb, buflen)?;
+    let mut ctxt = PDFObjContext::new(50);
+    ctxt.set_decode_limits(*opts.decode_limits());
+    ctxt.set_lenient_stream_length(opts.lenient_stream_length());
+    // Parsing the xref streams registers them in the context, so use
+    // a fork that shares the decode budget for the revisions.
+    let revs = get_revisions(&fi, &mut ctxt.fork(), &mut pb)?;
+    if rev >= revs.len() {
+        fail!(
+            PdfParseErrorKind::Xref,
+            0,
+            "No revision {} in {}, which has {} revisions",
+            rev,
+            fi.path().display(),
+            revs.len()
         );
+    }
+    let revs = &revs[..= rev];
+    let (ents, root_ref) = merge_revisions(&fi, revs)?;
+
+    // use the newest /Encrypt as of the revision
+    let encrypt = revs
+        .iter()
+        .rev()
+        .filter_map(|r| r.trailer())
+        .find(|t| t.val().get(b"Encrypt").is_some());
+    if let Some(t) = encrypt {
+        let t = t.val();
+        ctxt.set_encryption(
+            Arc::clone(t.get(b"Encrypt").unwrap()),
+            t.get(b"ID").cloned(),
+        )
+    }
+    load_objects(&fi, &mut ctxt, &ents, &mut pb, opts)?;
+    let root_id = root_id(&fi, &root_ref)?;
+    Ok((fi, ctxt, root_id))
+}
+
+#[cfg(test)]
+mod test_traverse_xref {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+    use std::thread;
+
+    use super::{
+        parse_data, parse_data_at_revision, parse_data_with_options, parse_file, parse_revisions,
+        ParseOptions, PdfParseErrorKind,
     };
+    use crate::pdf_lib::pdf_obj::{LengthRepair, PDFObjContext, PDFObjT};
+    use crate::pdf_lib::pdf_security::string_bytes;
+    use crate::pdf_lib::pdf_streams::{decode_stream, XrefEntStatus};
+
+    fn get_test_data(test_file: &str) -> Vec<u8> {
+        let mut file = match File::open(test_file) {
+            Err(why) => panic!("Cannot open {}: {}", test_file, why),
+            Ok(f) => f,
+        };
+        let mut v = Vec::new();
+        if let Err(why) = file.read_to_end(&mut v) {
+            panic!("Cannot read {}: {}", test_file, why)
+        };
+        v
+    }
+
+    #[test]
+    fn test_parse_data() {
+        let path = Path::new("tests/test_files/minimal.pdf");
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let (fi, ctxt, root_id) = parse_data(path, &v).unwrap();
+        assert_eq!(fi.file_offset(0), 0);
+        assert_eq!(root_id, (1, 0));
+        assert!(ctxt.lookup_obj(root_id).is_some());
+
+        // the mapped file gives the same objects
+        let (_, mapped, mapped_root_id) = parse_file("tests/test_files/minimal.pdf").unwrap();
+        assert_eq!(mapped_root_id, root_id);
+        assert_eq!(mapped.obj_ids(), ctxt.obj_ids());
+        for id in ctxt.obj_ids() {
+            assert_eq!(mapped.lookup_obj(id), ctxt.lookup_obj(id));
+        }
+
+        let path = Path::new("tests/test_files/minimal_leading_garbage.pdf");
+        let v = get_test_data("tests/test_files/minimal_leading_garbage.pdf");
+        let (fi, _, root_id) = parse_data(path, &v).unwrap();
+        assert!(fi.file_offset(0) > 0);
+        assert_eq!(root_id, (1, 0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let path = Path::new("test.pdf");
+
+        // no header
+        let err = parse_data(path, b"garbage").err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::Header);
+        assert_eq!(err.offset(), 0);
+
+        // no startxref
+        let err = parse_data(path, b"junk%PDF-1.1\n1 0 obj\n<<>>\nendobj\n%%EOF\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::StartXref);
+        assert_eq!(err.offset(), 4);
+
+        // startxref out of bounds
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let s = String::from_utf8_lossy(&v).replace("startxref\n565", "startxref\n9999");
+        let err = parse_data(path, s.as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::StartXref);
+
+        // xref offset that does not point to an xref
+        let s = String::from_utf8_lossy(&v).replace("startxref\n565", "startxref\n2");
+        let err = parse_data(path, s.as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::Xref);
+        assert_eq!(err.offset(), 2);
+    }
+
+    #[test]
+    fn test_recover_xref() {
+        let path = Path::new("test.pdf");
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let mut opts = ParseOptions::new();
+        opts.set_recover_xref(true);
+
+        // recovery is not performed unless requested, nor when the
+        // xref is valid
+        let (fi, _, _) = parse_data_with_options(path, &v, &opts).unwrap();
+        assert!(fi.recovery().is_none());
+
+        // xref offset that does not point to an xref
+        let s = String::from_utf8_lossy(&v).replace("startxref\n565", "startxref\n2");
+        let (fi, ctxt, root_id) = parse_data_with_options(path, s.as_bytes(), &opts).unwrap();
+        assert_eq!(root_id, (1, 0));
+        for id in 1 .. 5 {
+            assert!(ctxt.lookup_obj((id, 0)).is_some());
+        }
+        let rec = fi.recovery().unwrap();
+        assert_eq!(rec.cause().kind(), PdfParseErrorKind::Xref);
+        // there was no original xref to compare against
+        assert_eq!(rec.mismatches().len(), 4);
+
+        // xref entry that points to the wrong location
+        let s = String::from_utf8_lossy(&v).replace("0000000077 00000 n", "0000000070 00000 n");
+        let err = parse_data(path, s.as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::Object);
+        let (fi, ctxt, root_id) = parse_data_with_options(path, s.as_bytes(), &opts).unwrap();
+        assert_eq!(root_id, (1, 0));
+        assert!(ctxt.lookup_obj((2, 0)).is_some());
+        let rec = fi.recovery().unwrap();
+        assert_eq!(rec.cause().kind(), PdfParseErrorKind::Object);
+        assert_eq!(rec.mismatches().len(), 1);
+        let m = &rec.mismatches()[0];
+        assert_eq!(m.id(), (2, 0));
+        assert_eq!(m.xref(), Some(XrefEntStatus::InUse { file_ofs: 70 }));
+        assert_eq!(m.found(), Some(XrefEntStatus::InUse { file_ofs: 77 }));
+    }
+
+    // Checks the decrypted info dictionary and content stream of the
+    // encryption test files.
+    fn check_decrypted(ctxt: &PDFObjContext) {
+        let info = ctxt.lookup_obj((5, 0)).unwrap();
+        let info = match info.val() {
+            PDFObjT::Dict(d) => d,
+            _ => panic!("info is not a dictionary"),
+        };
+        let title = string_bytes(info.get(b"Title").unwrap());
+        assert_eq!(title, Some(b"Encrypted (title)".to_vec()));
+        let producer = string_bytes(info.get(b"Producer").unwrap());
+        assert_eq!(producer, Some(b"parsley".to_vec()));
+        let content = ctxt.lookup_obj((4, 0)).unwrap();
+        let content = match content.val() {
+            PDFObjT::Stream(s) => decode_stream(s).unwrap(),
+            _ => panic!("contents is not a stream"),
+        };
+        assert_eq!(
+            content.content(),
+            b"BT /F1 18 Tf 0 0 Td (Hello World) Tj ET\n"
+        );
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let path = Path::new("test.pdf");
+        for f in &["rc4_40", "rc4_128", "aes_128", "aes_256"] {
+            let v = get_test_data(&format!("tests/test_files/encryption/{}.pdf", f));
+            let (_, ctxt, root_id) = parse_data(path, &v).unwrap();
+            assert!(ctxt.is_encrypted());
+            assert_eq!(root_id, (1, 0));
+            // For aes_256, the info dictionary is in object stream
+            // (7,0).
+            check_decrypted(&ctxt);
+        }
+    }
 
-    (fi, ctxt, root_id)
+    #[test]
+    fn test_crypt_filters() {
+        let path = Path::new("test.pdf");
+        let v = get_test_data("tests/test_files/encryption/crypt_filters.pdf");
+        let (_, ctxt, root_id) = parse_data(path, &v).unwrap();
+        assert!(ctxt.is_encrypted());
+        assert_eq!(root_id, (1, 0));
+        let stream = |id| match ctxt.lookup_obj(id).unwrap().val() {
+            PDFObjT::Stream(s) => s,
+            _ => panic!("object ({},{}) is not a stream", id.0, id.1),
+        };
+
+        // the default /StmF crypt filter
+        let content = decode_stream(stream((4, 0))).unwrap();
+        assert_eq!(
+            content.content(),
+            b"BT /F1 18 Tf 0 0 Td (Hello World) Tj ET\n"
+        );
+        // /StrF is /Identity
+        let info = ctxt.lookup_obj((5, 0)).unwrap();
+        let info = match info.val() {
+            PDFObjT::Dict(d) => d,
+            _ => panic!("info is not a dictionary"),
+        };
+        let title = string_bytes(info.get(b"Title").unwrap());
+        assert_eq!(title, Some(b"Clear (title)".to_vec()));
+        // /EncryptMetadata is false
+        let metadata = stream((7, 0));
+        assert_eq!(
+            metadata.stream().val().content(),
+            b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>"
+        );
+        // the /EFF crypt filter
+        let embedded = stream((9, 0));
+        assert_eq!(embedded.stream().val().content(), b"embedded data");
+        // an /Identity /Crypt filter followed by another filter
+        let flate = decode_stream(stream((10, 0))).unwrap();
+        assert_eq!(flate.content(), b"Hello");
+        // a named /Crypt filter
+        let named = decode_stream(stream((11, 0))).unwrap();
+        assert_eq!(named.content(), b"named");
+    }
+
+    #[test]
+    fn test_decrypt_password() {
+        let path = Path::new("test.pdf");
+        for f in &["rc4_128_userpw", "aes_256_userpw"] {
+            let v = get_test_data(&format!("tests/test_files/encryption/{}.pdf", f));
+
+            // the empty user password does not work
+            let err = parse_data(path, &v).err().unwrap();
+            assert_eq!(err.kind(), PdfParseErrorKind::Encryption);
+
+            let mut opts = ParseOptions::new();
+            for pw in &["user", "owner"] {
+                opts.set_password(pw.as_bytes());
+                let (_, ctxt, _) = parse_data_with_options(path, &v, &opts).unwrap();
+                check_decrypted(&ctxt);
+            }
+            opts.set_password(b"wrong");
+            let err = parse_data_with_options(path, &v, &opts).err().unwrap();
+            assert_eq!(err.kind(), PdfParseErrorKind::Encryption);
+        }
+    }
+
+    // Appends two incremental updates to the minimal file: the first
+    // (with an xref table) changes object 4 and adds object 5, and the
+    // second (with an xref stream) frees object 5.
+    fn incremental_data() -> (Vec<u8>, Vec<usize>) {
+        let mut v = get_test_data("tests/test_files/minimal.pdf");
+        let mut ends = vec![v.len()];
+        let ofs4 = v.len();
+        v.extend_from_slice(b"4 0 obj\n<< /Length 10 >>\nstream\nBT\n  ET\n \nendstream\nendobj\n");
+        let ofs5 = v.len();
+        v.extend_from_slice(b"5 0 obj\n(added)\nendobj\n");
+        let xref = v.len();
+        v.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000000 65535 f \n4 2\n{:010} 00000 n \n{:010} 00000 n \n\
+                 trailer\n<< /Root 1 0 R /Size 6 /Prev 565 >>\nstartxref\n{}\n%%EOF\n",
+                ofs4, ofs5, xref
+            )
+            .as_bytes(),
+        );
+        ends.push(v.len());
+        let xref2 = v.len();
+        let mut ents = vec![0, 0, 0, 1, 1];
+        ents.extend_from_slice(&[1, (xref2 >> 8) as u8, xref2 as u8, 0]);
+        v.extend_from_slice(
+            format!(
+                "6 0 obj\n<< /Type /XRef /Size 7 /W [1 2 1] /Index [5 2] /Root 1 0 R \
+                 /Prev {} /Length 8 >>\nstream\n",
+                xref
+            )
+            .as_bytes(),
+        );
+        v.extend_from_slice(&ents[1 ..]);
+        v.extend_from_slice(
+            format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref2).as_bytes(),
+        );
+        ends.push(v.len());
+        (v, ends)
+    }
+
+    #[test]
+    fn test_revisions() {
+        let path = Path::new("incremental.pdf");
+        let (v, ends) = incremental_data();
+        let (_, revs) = parse_revisions(path, &v).unwrap();
+        assert_eq!(revs.len(), 3);
+        assert_eq!(revs[0].xref_offset(), 565);
+        assert_eq!(revs[0].byte_range(), (0, ends[0]));
+        assert_eq!(revs[1].byte_range(), (ends[0], ends[1]));
+        assert_eq!(revs[2].byte_range(), (ends[1], ends[2]));
+        assert_eq!(revs[2].xref_offset(), ends[1]);
+
+        assert_eq!(revs[0].added(), &[(1, 0), (2, 0), (3, 0), (4, 0)]);
+        assert!(revs[0].changed().is_empty() && revs[0].freed().is_empty());
+        assert_eq!(revs[1].added(), &[(5, 0)]);
+        assert_eq!(revs[1].changed(), &[(4, 0)]);
+        assert!(revs[1].freed().is_empty());
+        assert_eq!(revs[2].added(), &[(6, 0)]);
+        assert!(revs[2].changed().is_empty());
+        assert_eq!(revs[2].freed(), &[(5, 0)]);
+
+        let t = revs[1].trailer().unwrap();
+        assert_eq!(t.val().get_usize(b"Prev"), Some(565));
+        let t = revs[2].trailer().unwrap();
+        assert_eq!(t.val().get_name(b"Type"), Some(&b"XRef"[..]));
+
+        let content = |ctxt: &PDFObjContext| match ctxt.lookup_obj((4, 0)).unwrap().val() {
+            PDFObjT::Stream(s) => s.content().to_vec(),
+            _ => panic!("object 4 is not a stream"),
+        };
+        let opts = ParseOptions::new();
+        let (_, ctxt, root_id) = parse_data_at_revision(path, &v, &opts, 0).unwrap();
+        assert_eq!(root_id, (1, 0));
+        assert_eq!(ctxt.obj_ids(), vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+        assert_eq!(content(&ctxt).len(), 55);
+        let (_, ctxt, _) = parse_data_at_revision(path, &v, &opts, 1).unwrap();
+        assert_eq!(ctxt.obj_ids(), vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
+        assert_eq!(content(&ctxt), b"BT\n  ET\n \n");
+        let (_, ctxt, _) = parse_data_at_revision(path, &v, &opts, 2).unwrap();
+        assert!(ctxt.lookup_obj((5, 0)).is_none());
+        assert_eq!(content(&ctxt), b"BT\n  ET\n \n");
+        assert!(parse_data_at_revision(path, &v, &opts, 3).is_err());
+
+        // the newest revision is the one that is normally parsed
+        let (_, ctxt2, _) = parse_data(path, &v).unwrap();
+        assert_eq!(ctxt2.obj_ids(), ctxt.obj_ids());
+    }
+
+    #[test]
+    fn test_stream_length() {
+        let path = Path::new("length.pdf");
+        let mut lenient = ParseOptions::new();
+        lenient.set_lenient_stream_length(true);
+        let content = |ctxt: &PDFObjContext| match ctxt.lookup_obj((4, 0)).unwrap().val() {
+            PDFObjT::Stream(s) => s.content().to_vec(),
+            _ => panic!("object 4 is not a stream"),
+        };
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let (_, ctxt, _) = parse_data(path, &v).unwrap();
+        let expected = content(&ctxt);
+
+        // a wrong /Length
+        let w = String::from_utf8_lossy(&v).replace("/Length 55", "/Length 50");
+        let w = w.as_bytes();
+        assert!(parse_data(path, w).is_err());
+        let (_, ctxt, _) = parse_data_with_options(path, w, &lenient).unwrap();
+        assert_eq!(content(&ctxt), expected);
+        assert_eq!(
+            ctxt.length_repairs(),
+            &[LengthRepair::new((4, 0), 457, Some(50), 55)]
+        );
+
+        // a /Length that cannot be resolved
+        let w = String::from_utf8_lossy(&v).replace("  << /Length 55 >>", "<< /Length 9 0 R>>");
+        let w = w.as_bytes();
+        assert!(parse_data(path, w).is_err());
+        let (_, ctxt, _) = parse_data_with_options(path, w, &lenient).unwrap();
+        assert_eq!(content(&ctxt), expected);
+        assert_eq!(
+            ctxt.length_repairs(),
+            &[LengthRepair::new((4, 0), 457, None, 55)]
+        );
+
+        // a /Length in an object stream, which is parsed after the
+        // stream that needs it
+        let v = obj_stream_data(v, "6");
+        let (_, ctxt, _) = parse_data(path, &v).unwrap();
+        assert_eq!(content(&ctxt), b"BT\n  ET\n \n");
+        assert!(ctxt.length_repairs().is_empty());
+    }
+
+    // Appends an update to the minimal file in which the /Length of
+    // object 4 is object 6, in object stream 5 of the given /Length.
+    fn obj_stream_data(mut v: Vec<u8>, length5: &str) -> Vec<u8> {
+        let ofs4 = v.len();
+        v.extend_from_slice(
+            b"4 0 obj\n<< /Length 6 0 R >>\nstream\nBT\n  ET\n \nendstream\nendobj\n",
+        );
+        let ofs5 = v.len();
+        v.extend_from_slice(
+            format!(
+                "5 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Length {} >>\nstream\n6 0 10\nendstream\nendobj\n",
+                length5
+            )
+            .as_bytes(),
+        );
+        let xref = v.len();
+        let mut strm = vec![1, (ofs4 >> 8) as u8, ofs4 as u8, 0];
+        strm.extend_from_slice(&[1, (ofs5 >> 8) as u8, ofs5 as u8, 0, 2, 0, 5, 0]);
+        strm.extend_from_slice(&[1, (xref >> 8) as u8, xref as u8, 0]);
+        v.extend_from_slice(
+            format!(
+                "7 0 obj\n<< /Type /XRef /Size 8 /Index [4 4] /W [1 2 1] /Root 1 0 R /Prev 565 /Length {} >>\nstream\n",
+                strm.len()
+            )
+            .as_bytes(),
+        );
+        v.extend_from_slice(&strm);
+        v.extend_from_slice(
+            format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref).as_bytes(),
+        );
+        v
+    }
+
+    #[test]
+    fn test_lazy() {
+        let path = Path::new("lazy.pdf");
+        let mut lazy = ParseOptions::new();
+        lazy.set_lazy(true);
+        let check_same = |v: &[u8]| {
+            let (_, eager, root_id) = parse_data(path, v).unwrap();
+            let (_, ctxt, lazy_root_id) = parse_data_with_options(path, v, &lazy).unwrap();
+            assert!(ctxt.is_lazy());
+            assert_eq!(lazy_root_id, root_id);
+            assert_eq!(ctxt.obj_ids(), eager.obj_ids());
+            for id in eager.obj_ids() {
+                assert_eq!(ctxt.load_obj(id), Ok(eager.lookup_obj(id)));
+            }
+            ctxt
+        };
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        check_same(&v);
+        for f in &["rc4_40", "rc4_128", "aes_128", "crypt_filters"] {
+            let v = get_test_data(&format!("tests/test_files/encryption/{}.pdf", f));
+            let ctxt = check_same(&v);
+            if *f != "crypt_filters" {
+                check_decrypted(&ctxt)
+            }
+        }
+
+        // a /Length in an object stream
+        let w = obj_stream_data(v.clone(), "6");
+        check_same(&w);
+
+        // errors are reported when the object is loaded
+        let w = String::from_utf8_lossy(&v).replace("/Length 55", "/Length 50");
+        let w = w.as_bytes();
+        let err = parse_data(path, w).err().unwrap();
+        let (_, ctxt, _) = parse_data_with_options(path, w, &lazy).unwrap();
+        assert!(ctxt.lookup_obj((1, 0)).is_some());
+        assert!(ctxt.lookup_obj((4, 0)).is_none());
+        assert_eq!(ctxt.load_obj((4, 0)), Err(err));
+
+        let v = get_test_data("tests/test_files/encryption/rc4_128_userpw.pdf");
+        let (_, ctxt, _) = parse_data_with_options(path, &v, &lazy).unwrap();
+        let err = ctxt.load_obj((5, 0)).err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::Encryption);
+
+        // an object stream whose /Length is in the stream itself
+        let w = obj_stream_data(get_test_data("tests/test_files/minimal.pdf"), "6 0 R");
+        let err = parse_data(path, &w).err().unwrap();
+        let (_, ctxt, _) = parse_data_with_options(path, &w, &lazy).unwrap();
+        assert_eq!(ctxt.load_obj((4, 0)), Err(err));
+        let err = ctxt.load_obj((6, 0)).err().unwrap();
+        assert_eq!(err.kind(), PdfParseErrorKind::Object);
+        assert!(err.msg().starts_with("Cannot parse object (5,0)"));
+    }
+
+    #[test]
+    fn test_parallel() {
+        fn check_send_sync<T: Send + Sync>(_: &T) {}
+        let path = Path::new("parallel.pdf");
+        let mut parallel = ParseOptions::new();
+        parallel.set_parallel(true);
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let mut files = vec![v.clone(), obj_stream_data(v.clone(), "6")];
+        for f in &["rc4_40", "aes_256", "crypt_filters"] {
+            files.push(get_test_data(&format!(
+                "tests/test_files/encryption/{}.pdf",
+                f
+            )))
+        }
+        for v in &files {
+            let (_, eager, root_id) = parse_data(path, v).unwrap();
+            let (_, ctxt, parallel_root_id) = parse_data_with_options(path, v, &parallel).unwrap();
+            assert_eq!(parallel_root_id, root_id);
+            assert_eq!(ctxt.obj_ids(), eager.obj_ids());
+            for id in eager.obj_ids() {
+                assert_eq!(ctxt.lookup_obj(id), eager.lookup_obj(id));
+            }
+        }
+
+        // the first error is that of a sequential parse
+        let w = String::from_utf8_lossy(&v).replace("/Length 55", "/Length 50");
+        let w = w.as_bytes();
+        let err = parse_data(path, w).err();
+        assert_eq!(parse_data_with_options(path, w, &parallel).err(), err);
+        parallel.set_lenient_stream_length(true);
+        let (_, ctxt, _) = parse_data_with_options(path, w, &parallel).unwrap();
+        assert_eq!(
+            ctxt.length_repairs(),
+            &[LengthRepair::new((4, 0), 457, Some(50), 55)]
+        );
+
+        // contexts can be shared between threads, including in lazy
+        // mode where the threads load the same objects
+        let (_, eager, _) = parse_data(path, &files[1]).unwrap();
+        let mut lazy = ParseOptions::new();
+        lazy.set_lazy(true);
+        let (_, ctxt, _) = parse_data_with_options(path, &files[1], &lazy).unwrap();
+        check_send_sync(&ctxt);
+        thread::scope(|s| {
+            for _ in 0 .. 4 {
+                let _ = s.spawn(|| {
+                    for id in eager.obj_ids() {
+                        assert_eq!(ctxt.lookup_obj(id), eager.lookup_obj(id));
+                    }
+                });
+            }
+        });
+    }
 }
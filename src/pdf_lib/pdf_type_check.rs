@@ -20,7 +20,7 @@ use super::super::pcore::parsebuffer::LocatedVal;
 use super::pdf_obj::{DictKey, PDFObjContext, PDFObjT, ReferenceT};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum PDFPrimType {
@@ -50,7 +50,7 @@ pub enum DictKeySpec {
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct DictEntry {
     pub(super) key: Vec<u8>,
-    pub(super) chk: Rc<TypeCheck>,
+    pub(super) chk: Arc<TypeCheck>,
     pub(super) opt: DictKeySpec,
 }
 impl std::fmt::Debug for DictEntry {
@@ -68,7 +68,7 @@ impl std::fmt::Debug for DictEntry {
 }
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct DictStarEntry {
-    pub(super) chk: Rc<TypeCheck>,
+    pub(super) chk: Arc<TypeCheck>,
     pub(super) opt: DictKeySpec,
 }
 impl std::fmt::Debug for DictStarEntry {
@@ -86,17 +86,17 @@ pub enum PDFType {
     Any,
     PrimType(PDFPrimType),
     Array {
-        elem: Rc<TypeCheck>,
+        elem: Arc<TypeCheck>,
         size: Option<usize>,
     },
     HetArray {
-        elems: Vec<Rc<TypeCheck>>,
+        elems: Vec<Arc<TypeCheck>>,
     },
     // The second optional argument corresponds to the '*' key in the PDF DOM,
     // which indicates any key not listed in the first argument.
     Dict(Vec<DictEntry>, Option<DictStarEntry>),
     Stream(Vec<DictEntry>),
-    Disjunct(Vec<Rc<TypeCheck>>),
+    Disjunct(Vec<Arc<TypeCheck>>),
 }
 
 /* Errors reported by the type-checker */
@@ -106,20 +106,21 @@ pub enum TypeCheckError {
     ArraySizeMismatch(/* expected */ usize, /* found */ usize),
     MissingKey(DictKey),
     ForbiddenKey(DictKey),
-    TypeMismatch(/* expected */ Rc<PDFType>, /* found */ PDFType),
-    ValueMismatch(/* found */ Rc<LocatedVal<PDFObjT>>, String),
+    TypeMismatch(/* expected */ Arc<PDFType>, /* found */ PDFType),
+    ValueMismatch(/* found */ Arc<LocatedVal<PDFObjT>>, String),
     PredicateError(String),
     UnknownTypeCheck(String), /* undefined named typecheck */
 }
 
-// trait wrapper around predicate function
-pub trait Predicate {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>>;
+// trait wrapper around predicate function; type checks can be shared
+// between threads along with the objects they check
+pub trait Predicate: Send + Sync {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>>;
 }
 
 // typecheck context containing the named checks
 pub struct TypeCheckContext {
-    map: BTreeMap<String, Rc<TypeCheckRep>>,
+    map: BTreeMap<String, Arc<TypeCheckRep>>,
 }
 
 impl TypeCheckContext {
@@ -128,11 +129,11 @@ impl TypeCheckContext {
             map: BTreeMap::new(),
         }
     }
-    pub fn register(&mut self, chk: &Rc<TypeCheckRep>) {
+    pub fn register(&mut self, chk: &Arc<TypeCheckRep>) {
         // This intentionally does not catch duplicates.
-        self.map.insert(String::from(chk.name()), Rc::clone(chk));
+        self.map.insert(String::from(chk.name()), Arc::clone(chk));
     }
-    pub fn lookup(&self, name: &str) -> Option<Rc<TypeCheckRep>> {
+    pub fn lookup(&self, name: &str) -> Option<Arc<TypeCheckRep>> {
         self.map.get(name).cloned()
     }
 }
@@ -144,8 +145,8 @@ impl Default for TypeCheckContext {
 // the type check representation
 pub struct TypeCheckRep {
     name:     String,
-    typ:      Rc<PDFType>,
-    pred:     Option<Rc<dyn Predicate>>,
+    typ:      Arc<PDFType>,
+    pred:     Option<Arc<dyn Predicate>>,
     indirect: IndirectSpec,
 }
 
@@ -157,10 +158,10 @@ impl TypeCheckRep {
     // efficient once-only normalization.  But for now, since this is
     // called during every check, we don't need to register/replace it
     // in the context.
-    pub fn new_replace_typ(typ: PDFType, chk: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
-        Rc::new(TypeCheckRep {
+    pub fn new_replace_typ(typ: PDFType, chk: &Arc<TypeCheckRep>) -> Arc<TypeCheckRep> {
+        Arc::new(TypeCheckRep {
             name:     String::from(chk.name()),
-            typ:      Rc::new(typ),
+            typ:      Arc::new(typ),
             pred:     chk.pred.as_ref().cloned(),
             indirect: chk.indirect,
         })
@@ -168,15 +169,15 @@ impl TypeCheckRep {
 
     pub fn name(&self) -> &str { &self.name }
     pub fn typ(&self) -> &PDFType { self.typ.as_ref() }
-    pub fn typ_rc(&self) -> &Rc<PDFType> { &self.typ }
-    pub fn pred(&self) -> &Option<Rc<dyn Predicate>> { &self.pred }
+    pub fn typ_rc(&self) -> &Arc<PDFType> { &self.typ }
+    pub fn pred(&self) -> &Option<Arc<dyn Predicate>> { &self.pred }
     pub fn indirect(&self) -> IndirectSpec { self.indirect }
 
     // make an indirect-allowed version of the check.
-    pub fn allow_indirect(&self) -> Rc<Self> {
-        Rc::new(Self {
+    pub fn allow_indirect(&self) -> Arc<Self> {
+        Arc::new(Self {
             name:     String::from(&self.name),
-            typ:      Rc::clone(&self.typ),
+            typ:      Arc::clone(&self.typ),
             pred:     self.pred.as_ref().cloned(),
             indirect: IndirectSpec::Allowed,
         })
@@ -212,67 +213,67 @@ impl std::fmt::Debug for TypeCheckRep {
 // representation.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TypeCheck {
-    Rep(Rc<TypeCheckRep>),
+    Rep(Arc<TypeCheckRep>),
     Named(String),
 }
 
 impl TypeCheck {
     // the most commonly used constructor
-    pub fn new(tctx: &mut TypeCheckContext, name: &str, typ: Rc<PDFType>) -> Rc<Self> {
-        let tc = Rc::new(TypeCheckRep {
+    pub fn new(tctx: &mut TypeCheckContext, name: &str, typ: Arc<PDFType>) -> Arc<Self> {
+        let tc = Arc::new(TypeCheckRep {
             name: String::from(name),
             typ,
             pred: None,
             indirect: IndirectSpec::Allowed,
         });
         tctx.register(&tc);
-        Rc::new(TypeCheck::Rep(tc))
+        Arc::new(TypeCheck::Rep(tc))
     }
 
     // the constructor with a refinement predicate
     pub fn new_refined(
-        tctx: &mut TypeCheckContext, name: &str, typ: Rc<PDFType>, pred: Rc<dyn Predicate>,
-    ) -> Rc<Self> {
-        let tc = Rc::new(TypeCheckRep {
+        tctx: &mut TypeCheckContext, name: &str, typ: Arc<PDFType>, pred: Arc<dyn Predicate>,
+    ) -> Arc<Self> {
+        let tc = Arc::new(TypeCheckRep {
             name: String::from(name),
             typ,
             pred: Some(pred),
             indirect: IndirectSpec::Allowed,
         });
         tctx.register(&tc);
-        Rc::new(TypeCheck::Rep(tc))
+        Arc::new(TypeCheck::Rep(tc))
     }
 
     // the constructor with an indirect specification
     pub fn new_indirect(
-        tctx: &mut TypeCheckContext, name: &str, typ: Rc<PDFType>, indirect: IndirectSpec,
-    ) -> Rc<Self> {
-        let tc = Rc::new(TypeCheckRep {
+        tctx: &mut TypeCheckContext, name: &str, typ: Arc<PDFType>, indirect: IndirectSpec,
+    ) -> Arc<Self> {
+        let tc = Arc::new(TypeCheckRep {
             name: String::from(name),
             typ,
             pred: None,
             indirect,
         });
         tctx.register(&tc);
-        Rc::new(TypeCheck::Rep(tc))
+        Arc::new(TypeCheck::Rep(tc))
     }
 
     pub fn new_all(
-        tctx: &mut TypeCheckContext, name: &str, typ: Rc<PDFType>, pred: Option<Rc<dyn Predicate>>,
+        tctx: &mut TypeCheckContext, name: &str, typ: Arc<PDFType>, pred: Option<Arc<dyn Predicate>>,
         indirect: IndirectSpec,
-    ) -> Rc<Self> {
-        let tc = Rc::new(TypeCheckRep {
+    ) -> Arc<Self> {
+        let tc = Arc::new(TypeCheckRep {
             name: String::from(name),
             typ,
             pred,
             indirect,
         });
         tctx.register(&tc);
-        Rc::new(TypeCheck::Rep(tc))
+        Arc::new(TypeCheck::Rep(tc))
     }
 
     // a type check named after another one, used for recursion
-    pub fn new_named(name: &str) -> Rc<Self> { Rc::new(TypeCheck::Named(String::from(name))) }
+    pub fn new_named(name: &str) -> Arc<Self> { Arc::new(TypeCheck::Named(String::from(name))) }
 }
 
 /* computes the top-most general type of an object without descending into it */
@@ -282,7 +283,7 @@ fn type_of(obj: &PDFObjT) -> PDFType {
     match obj {
         PDFObjT::Dict(_) => PDFType::Dict(Vec::new(), None),
         PDFObjT::Array(_) => PDFType::Array {
-            elem: TypeCheck::new(&mut tctx, "", Rc::new(PDFType::Any)),
+            elem: TypeCheck::new(&mut tctx, "", Arc::new(PDFType::Any)),
             size: None,
         },
         PDFObjT::Stream(_) => PDFType::Stream(Vec::new()),
@@ -290,7 +291,7 @@ fn type_of(obj: &PDFObjT) -> PDFType {
             unreachable!(); // we should never get a raw reference
         },
         PDFObjT::Boolean(_) => PDFType::PrimType(PDFPrimType::Bool),
-        PDFObjT::String(_) => PDFType::PrimType(PDFPrimType::String),
+        PDFObjT::String(_, _) => PDFType::PrimType(PDFPrimType::String),
         PDFObjT::Name(_) => PDFType::PrimType(PDFPrimType::Name),
         PDFObjT::Null(_) => PDFType::PrimType(PDFPrimType::Null),
         PDFObjT::Integer(_) => PDFType::PrimType(PDFPrimType::Integer),
@@ -300,7 +301,7 @@ fn type_of(obj: &PDFObjT) -> PDFType {
 }
 
 fn check_predicate(
-    obj: &Rc<LocatedVal<PDFObjT>>, pred: &Option<Rc<dyn Predicate>>,
+    obj: &Arc<LocatedVal<PDFObjT>>, pred: &Option<Arc<dyn Predicate>>,
 ) -> Option<LocatedVal<TypeCheckError>> {
     match pred {
         None => None,
@@ -317,7 +318,7 @@ fn check_predicate(
  */
 
 /* A pending check */
-type PendingCheck = (Rc<LocatedVal<PDFObjT>>, Rc<TypeCheck>);
+type PendingCheck = (Arc<LocatedVal<PDFObjT>>, Arc<TypeCheck>);
 /* An entry on the stack */
 type PendingSet = VecDeque<PendingCheck>;
 
@@ -331,9 +332,9 @@ struct State {
 type GetResult = Result<Option<PendingCheck>, ()>;
 
 impl State {
-    fn new(obj: &Rc<LocatedVal<PDFObjT>>, chk: &Rc<TypeCheck>) -> State {
+    fn new(obj: &Arc<LocatedVal<PDFObjT>>, chk: &Arc<TypeCheck>) -> State {
         let mut first = VecDeque::new();
-        first.push_back((Rc::clone(obj), Rc::clone(chk)));
+        first.push_back((Arc::clone(obj), Arc::clone(chk)));
         let mut todo = VecDeque::new();
         todo.push_back((first, 0));
         let examined = BTreeSet::new();
@@ -366,13 +367,13 @@ impl State {
     }
 
     /* adds a check to the examined set */
-    fn examine(&mut self, o: &Rc<LocatedVal<PDFObjT>>, c: &Rc<TypeCheck>) {
-        let chk = (Rc::clone(o), Rc::clone(c));
+    fn examine(&mut self, o: &Arc<LocatedVal<PDFObjT>>, c: &Arc<TypeCheck>) {
+        let chk = (Arc::clone(o), Arc::clone(c));
         self.examined.insert(chk);
     }
 
-    fn have_examined(&self, o: &Rc<LocatedVal<PDFObjT>>, c: &Rc<TypeCheck>) -> bool {
-        let chk = (Rc::clone(o), Rc::clone(c));
+    fn have_examined(&self, o: &Arc<LocatedVal<PDFObjT>>, c: &Arc<TypeCheck>) -> bool {
+        let chk = (Arc::clone(o), Arc::clone(c));
         self.examined.contains(&chk)
     }
 
@@ -406,9 +407,9 @@ impl State {
                                         // the disjunct, we adjust the
                                         // index and return the next case.
                                         if *next_idx < set.len() {
-                                            let c = Rc::clone(&set[*next_idx]);
+                                            let c = Arc::clone(&set[*next_idx]);
                                             *next_idx += 1;
-                                            pending.push_front((Rc::clone(&obj), tc));
+                                            pending.push_front((Arc::clone(&obj), tc));
                                             return Ok(Some((obj, c)))
                                         } else {
                                             // if there is an error but
@@ -444,9 +445,9 @@ impl State {
                                     } else {
                                         // Take the first option, and mark
                                         // this disjunct in progress.
-                                        let c = Rc::clone(&set[0]);
+                                        let c = Arc::clone(&set[0]);
                                         *next_idx = 1;
-                                        pending.push_front((Rc::clone(&obj), tc));
+                                        pending.push_front((Arc::clone(&obj), tc));
                                         return Ok(Some((obj, c)))
                                     }
                                 },
@@ -534,14 +535,14 @@ impl State {
 }
 
 /* removes directly nested disjuncts */
-pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
+pub(super) fn normalize_check(typ: &Arc<TypeCheckRep>) -> Arc<TypeCheckRep> {
     match typ.typ() {
-        PDFType::Any | PDFType::PrimType(_) => Rc::clone(typ),
+        PDFType::Any | PDFType::PrimType(_) => Arc::clone(typ),
         PDFType::Array { elem, size } => TypeCheckRep::new_replace_typ(
             PDFType::Array {
                 elem: match elem.as_ref() {
-                    TypeCheck::Rep(r) => Rc::new(TypeCheck::Rep(normalize_check(r))),
-                    _ => Rc::clone(elem),
+                    TypeCheck::Rep(r) => Arc::new(TypeCheck::Rep(normalize_check(r))),
+                    _ => Arc::clone(elem),
                 },
                 size: *size,
             },
@@ -551,8 +552,8 @@ pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
             let mut v = Vec::new();
             for e in elems {
                 v.push(match e.as_ref() {
-                    TypeCheck::Rep(r) => Rc::new(TypeCheck::Rep(normalize_check(r))),
-                    _ => Rc::clone(e),
+                    TypeCheck::Rep(r) => Arc::new(TypeCheck::Rep(normalize_check(r))),
+                    _ => Arc::clone(e),
                 })
             }
             TypeCheckRep::new_replace_typ(PDFType::HetArray { elems: v }, typ)
@@ -563,16 +564,16 @@ pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
                 v.push(DictEntry {
                     key: e.key.clone(),
                     chk: match e.chk.as_ref() {
-                        TypeCheck::Rep(r) => Rc::new(TypeCheck::Rep(normalize_check(r))),
-                        _ => Rc::clone(&e.chk),
+                        TypeCheck::Rep(r) => Arc::new(TypeCheck::Rep(normalize_check(r))),
+                        _ => Arc::clone(&e.chk),
                     },
                     opt: e.opt,
                 })
             }
             let s = star.as_ref().map(|e| DictStarEntry {
                 chk: match e.chk.as_ref() {
-                    TypeCheck::Rep(r) => Rc::new(TypeCheck::Rep(normalize_check(r))),
-                    _ => Rc::clone(&e.chk),
+                    TypeCheck::Rep(r) => Arc::new(TypeCheck::Rep(normalize_check(r))),
+                    _ => Arc::clone(&e.chk),
                 },
                 opt: e.opt,
             });
@@ -584,8 +585,8 @@ pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
                 v.push(DictEntry {
                     key: e.key.clone(),
                     chk: match e.chk.as_ref() {
-                        TypeCheck::Rep(r) => Rc::new(TypeCheck::Rep(normalize_check(r))),
-                        _ => Rc::clone(&e.chk),
+                        TypeCheck::Rep(r) => Arc::new(TypeCheck::Rep(normalize_check(r))),
+                        _ => Arc::clone(&e.chk),
                     },
                     opt: e.opt,
                 })
@@ -600,13 +601,13 @@ pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
                         let flat = normalize_check(r);
                         if let PDFType::Disjunct(nested) = flat.typ() {
                             for n in nested {
-                                v.push(Rc::clone(n))
+                                v.push(Arc::clone(n))
                             }
                         } else {
-                            v.push(Rc::new(TypeCheck::Rep(flat)))
+                            v.push(Arc::new(TypeCheck::Rep(flat)))
                         }
                     },
-                    TypeCheck::Named(_) => v.push(Rc::clone(o)),
+                    TypeCheck::Named(_) => v.push(Arc::clone(o)),
                 }
             }
             TypeCheckRep::new_replace_typ(PDFType::Disjunct(v), typ)
@@ -614,22 +615,22 @@ pub(super) fn normalize_check(typ: &Rc<TypeCheckRep>) -> Rc<TypeCheckRep> {
     }
 }
 
-fn resolve(tctx: &TypeCheckContext, chk: &TypeCheck) -> Result<Rc<TypeCheckRep>, TypeCheckError> {
+fn resolve(tctx: &TypeCheckContext, chk: &TypeCheck) -> Result<Arc<TypeCheckRep>, TypeCheckError> {
     match chk {
-        TypeCheck::Rep(r) => Ok(Rc::clone(r)),
+        TypeCheck::Rep(r) => Ok(Arc::clone(r)),
         TypeCheck::Named(n) => match tctx.lookup(n) {
             None => Err(TypeCheckError::UnknownTypeCheck(format!(
                 "Unknown typecheck {}",
                 n
             ))),
-            Some(rep) => Ok(Rc::clone(&rep)),
+            Some(rep) => Ok(Arc::clone(&rep)),
         },
     }
 }
 
 /* checks a parsed PDF object against its expected type */
 pub fn check_type(
-    ctxt: &PDFObjContext, tctx: &TypeCheckContext, obj: Rc<LocatedVal<PDFObjT>>, chk: Rc<TypeCheck>,
+    ctxt: &PDFObjContext, tctx: &TypeCheckContext, obj: Arc<LocatedVal<PDFObjT>>, chk: Arc<TypeCheck>,
 ) -> Option<LocatedVal<TypeCheckError>> {
     // Resolve the check if needed.
     let rep = match resolve(tctx, &chk) {
@@ -638,7 +639,7 @@ pub fn check_type(
     };
 
     /* normalize the given type */
-    let chk = Rc::new(TypeCheck::Rep(normalize_check(&rep)));
+    let chk = Arc::new(TypeCheck::Rep(normalize_check(&rep)));
 
     /* state initialization */
     let mut state = State::new(&obj, &chk);
@@ -688,26 +689,26 @@ pub fn check_type(
                 match ctxt.lookup_obj(refnc.id()) {
                     Some(obj) => {
                         // Remove any Required indirect from the check.
-                        let chk = Rc::new(TypeCheck::Rep(c.allow_indirect()));
-                        state.return_check((Rc::clone(obj), chk));
+                        let chk = Arc::new(TypeCheck::Rep(c.allow_indirect()));
+                        state.return_check((Arc::clone(obj), chk));
                     },
                     None => {
                         // References to undefined objects are treated
                         // as references to the null object.
                         let obj = o.place(PDFObjT::Null(()));
-                        state.return_check((Rc::new(obj), tc));
+                        state.return_check((Arc::new(obj), tc));
                     },
                 }
             },
             (PDFObjT::Reference(_), _, IndirectSpec::Forbidden) => {
                 result = Some(o.place(TypeCheckError::ValueMismatch(
-                    Rc::clone(&o),
+                    Arc::clone(&o),
                     String::from("An indirect reference was forbidden"),
                 )))
             },
             (_, _, IndirectSpec::Required) => {
                 result = Some(o.place(TypeCheckError::ValueMismatch(
-                    Rc::clone(&o),
+                    Arc::clone(&o),
                     String::from("An indirect reference was required"),
                 )))
             },
@@ -724,7 +725,7 @@ pub fn check_type(
             (PDFObjT::Boolean(_), PDFType::PrimType(PDFPrimType::Bool), _) => {
                 result = check_predicate(&o, c.pred())
             },
-            (PDFObjT::String(_), PDFType::PrimType(PDFPrimType::String), _) => {
+            (PDFObjT::String(_, _), PDFType::PrimType(PDFPrimType::String), _) => {
                 result = check_predicate(&o, c.pred())
             },
             (PDFObjT::Name(_), PDFType::PrimType(PDFPrimType::Name), _) => {
@@ -766,7 +767,7 @@ pub fn check_type(
                 /* non-Any case */
                 let mut chks = Vec::new();
                 for e in ao.objs() {
-                    chks.push((Rc::clone(e), Rc::clone(elem)))
+                    chks.push((Arc::clone(e), Arc::clone(elem)))
                 }
                 state.push_checks(chks);
             },
@@ -783,7 +784,7 @@ pub fn check_type(
                 let mut chks = Vec::new();
                 for (i, tc) in elems.iter().enumerate() {
                     let e = &ao.objs()[i];
-                    chks.push((Rc::clone(e), Rc::clone(tc)))
+                    chks.push((Arc::clone(e), Arc::clone(tc)))
                 }
                 state.push_checks(chks);
             },
@@ -812,7 +813,7 @@ pub fn check_type(
                             break
                         },
                         (Some(_), _, PDFType::Any) => continue,
-                        (Some(v), _, _) => chks.push((Rc::clone(v), Rc::clone(&ent.chk))),
+                        (Some(v), _, _) => chks.push((Arc::clone(v), Arc::clone(&ent.chk))),
                     }
                 }
                 // If we no errors so far and have a '*' specification, match the
@@ -845,7 +846,7 @@ pub fn check_type(
                                     break
                                 },
                                 (Some(_), _, PDFType::Any) => continue,
-                                (Some(v), _, _) => chks.push((Rc::clone(v), Rc::clone(&s.chk))),
+                                (Some(v), _, _) => chks.push((Arc::clone(v), Arc::clone(&s.chk))),
                             }
                         }
                     }
@@ -875,7 +876,7 @@ pub fn check_type(
                             result = Some(o.place(TypeCheckError::ForbiddenKey(key)))
                         },
                         (Some(_), _, PDFType::Any) => continue,
-                        (Some(v), _, _) => chks.push((Rc::clone(v), Rc::clone(&ent.chk))),
+                        (Some(v), _, _) => chks.push((Arc::clone(v), Arc::clone(&ent.chk))),
                     }
                 }
                 if result.is_none() {
@@ -884,7 +885,7 @@ pub fn check_type(
             },
             (obj, _, _) => {
                 result = Some(o.place(TypeCheckError::TypeMismatch(
-                    Rc::clone(&c.typ),
+                    Arc::clone(&c.typ),
                     type_of(obj),
                 )))
             },
@@ -898,13 +899,13 @@ pub fn check_type(
 pub struct ChoicePred(pub String, pub Vec<PDFObjT>);
 
 impl Predicate for ChoicePred {
-    fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+    fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
         let vec = &self.1;
         if vec.iter().any(|c| obj.val() == c) {
             None
         } else {
             Some(obj.place(TypeCheckError::ValueMismatch(
-                Rc::clone(obj),
+                Arc::clone(obj),
                 self.0.clone(),
             )))
         }
@@ -918,29 +919,31 @@ mod test_pdf_types {
         IndirectSpec, PDFPrimType, PDFType, Predicate, TypeCheck, TypeCheckContext, TypeCheckError,
     };
     use crate::pcore::parsebuffer::{LocatedVal, ParseBuffer};
-    use crate::pdf_lib::pdf_obj::{parse_pdf_obj, DictKey, IndirectT, PDFObjContext, PDFObjT};
+    use crate::pdf_lib::pdf_obj::{
+        parse_pdf_obj, DictKey, IndirectT, PDFObjContext, PDFObjT, StringForm,
+    };
     use crate::pdf_lib::pdf_prim::{IntegerT, NameT};
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     fn mk_new_context() -> PDFObjContext { PDFObjContext::new(10) }
 
-    fn mk_rectangle_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
-        let elem = TypeCheck::new(tctx, "", Rc::new(PDFType::PrimType(PDFPrimType::Integer)));
+    fn mk_rectangle_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
+        let elem = TypeCheck::new(tctx, "", Arc::new(PDFType::PrimType(PDFPrimType::Integer)));
         TypeCheck::new(
             tctx,
             "rectangle",
-            Rc::new(PDFType::Array {
+            Arc::new(PDFType::Array {
                 elem,
                 size: Some(4),
             }),
         )
     }
 
-    pub fn mk_date_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+    pub fn mk_date_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
         TypeCheck::new(
             tctx,
             "date",
-            Rc::new(PDFType::PrimType(PDFPrimType::String)),
+            Arc::new(PDFType::PrimType(PDFPrimType::String)),
         )
     }
 
@@ -953,7 +956,7 @@ mod test_pdf_types {
 
         let mut tctx = TypeCheckContext::new();
         let typ = mk_rectangle_typchk(&mut tctx);
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -961,7 +964,7 @@ mod test_pdf_types {
         let mut ctxt = mk_new_context();
         let int = PDFObjT::Integer(IntegerT::new(10));
         let int = LocatedVal::new(int, 0, 1);
-        let obj = IndirectT::new(2, 0, Rc::new(int)); // indirect ref: 2 0 R
+        let obj = IndirectT::new(2, 0, Arc::new(int)); // indirect ref: 2 0 R
         let obj = LocatedVal::new(obj, 0, 1);
         ctxt.register_obj(&obj);
 
@@ -974,9 +977,9 @@ mod test_pdf_types {
         let typ = TypeCheck::new(
             &mut tctx,
             "integer",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // parse a reference pointing to that object
         let v = Vec::from("2 0 R".as_bytes());
@@ -985,9 +988,9 @@ mod test_pdf_types {
         let typ = TypeCheck::new(
             &mut tctx,
             "integer",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // require a referenced object
         let v = Vec::from("2 0 R".as_bytes());
@@ -996,59 +999,59 @@ mod test_pdf_types {
         let typ = TypeCheck::new_all(
             &mut tctx,
             "integer-required",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
             None,
             IndirectSpec::Required,
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // check forbidden error
         let v = Vec::from("2 0 R".as_bytes());
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
-        let obj = Rc::new(obj);
+        let obj = Arc::new(obj);
         let typ = TypeCheck::new_all(
             &mut tctx,
             "integer-forbidden",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
             None,
             IndirectSpec::Forbidden,
         );
         let err = obj.place(TypeCheckError::ValueMismatch(
-            Rc::clone(&obj),
+            Arc::clone(&obj),
             String::from("An indirect reference was forbidden"),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), Some(err));
 
         // check missing reference handling as null
         let v = Vec::from("3 0 R".as_bytes());
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
-        let obj = Rc::new(obj);
+        let obj = Arc::new(obj);
         let typ = TypeCheck::new(
             &mut tctx,
             "missing-reference",
-            Rc::new(PDFType::PrimType(PDFPrimType::Null)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Null)),
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), None);
 
         // check required error.
         let v = Vec::from("10".as_bytes());
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
-        let obj = Rc::new(obj);
+        let obj = Arc::new(obj);
         let typ = TypeCheck::new_all(
             &mut tctx,
             "integer-required",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
             None,
             IndirectSpec::Required,
         );
         let err = obj.place(TypeCheckError::ValueMismatch(
-            Rc::clone(&obj),
+            Arc::clone(&obj),
             String::from("An indirect reference was required"),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), Some(err));
     }
 
     #[test]
@@ -1057,41 +1060,41 @@ mod test_pdf_types {
         let v = Vec::from("[ 1 true ]");
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
-        let obj = Rc::new(obj);
+        let obj = Arc::new(obj);
 
         let mut tctx = TypeCheckContext::new();
         let mut elems = Vec::new();
         elems.push(TypeCheck::new(
             &mut tctx,
             "",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         ));
         elems.push(TypeCheck::new(
             &mut tctx,
             "",
-            Rc::new(PDFType::PrimType(PDFPrimType::Bool)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Bool)),
         ));
-        let typ = TypeCheck::new(&mut tctx, "het-array", Rc::new(PDFType::HetArray { elems }));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), None);
+        let typ = TypeCheck::new(&mut tctx, "het-array", Arc::new(PDFType::HetArray { elems }));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), None);
 
         let mut tctx = TypeCheckContext::new();
         let mut elems = Vec::new();
         elems.push(TypeCheck::new(
             &mut tctx,
             "",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         ));
         elems.push(TypeCheck::new(
             &mut tctx,
             "",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         ));
-        let typ = TypeCheck::new(&mut tctx, "het-array", Rc::new(PDFType::HetArray { elems }));
+        let typ = TypeCheck::new(&mut tctx, "het-array", Arc::new(PDFType::HetArray { elems }));
         let err = obj.place(TypeCheckError::TypeMismatch(
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
             PDFType::PrimType(PDFPrimType::Bool),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), Some(err));
     }
 
     #[test]
@@ -1105,25 +1108,25 @@ mod test_pdf_types {
         let rect = mk_rectangle_typchk(&mut tctx);
         let ent1 = DictEntry {
             key: Vec::from("Entry"),
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Required,
         };
         let ent2 = DictEntry {
             key: Vec::from("Dummy1"),
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Forbidden,
         };
         let ent3 = DictEntry {
             key: Vec::from("Dummy2"),
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Optional,
         };
         let typ = TypeCheck::new(
             &mut tctx,
             "dict",
-            Rc::new(PDFType::Dict(vec![ent1, ent2, ent3], None)),
+            Arc::new(PDFType::Dict(vec![ent1, ent2, ent3], None)),
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -1139,9 +1142,9 @@ mod test_pdf_types {
             chk: mk_rectangle_typchk(&mut tctx),
             opt: DictKeySpec::Required,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
         let err = obj.place(TypeCheckError::MissingKey(DictKey::new(Vec::from("Dummy"))));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     #[test]
@@ -1157,14 +1160,14 @@ mod test_pdf_types {
             chk: mk_rectangle_typchk(&mut tctx),
             opt: DictKeySpec::Forbidden,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
         let err = obj.place(TypeCheckError::ForbiddenKey(DictKey::new(Vec::from(
             "Entry",
         ))));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
-    fn mk_pagemode_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+    fn mk_pagemode_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
         let pred = ChoicePred(
             String::from("Invalid PageMode"),
             vec![
@@ -1175,8 +1178,8 @@ mod test_pdf_types {
         TypeCheck::new_refined(
             tctx,
             "pagemode",
-            Rc::new(PDFType::PrimType(PDFPrimType::Name)),
-            Rc::new(pred),
+            Arc::new(PDFType::PrimType(PDFPrimType::Name)),
+            Arc::new(pred),
         )
     }
 
@@ -1192,11 +1195,11 @@ mod test_pdf_types {
         let pagemode = mk_pagemode_typchk(&mut tctx);
         let ent = DictEntry {
             key: Vec::from("PageMode"),
-            chk: Rc::clone(&pagemode),
+            chk: Arc::clone(&pagemode),
             opt: DictKeySpec::Required,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // valid value for optional key
         let v = Vec::from("<< /PageMode /UseNone >>".as_bytes());
@@ -1204,11 +1207,11 @@ mod test_pdf_types {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let ent = DictEntry {
             key: Vec::from("PageMode"),
-            chk: Rc::clone(&pagemode),
+            chk: Arc::clone(&pagemode),
             opt: DictKeySpec::Optional,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // optional key absent
         let v = Vec::from("<< >>".as_bytes());
@@ -1216,11 +1219,11 @@ mod test_pdf_types {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let ent = DictEntry {
             key: Vec::from("PageMode"),
-            chk: Rc::clone(&pagemode),
+            chk: Arc::clone(&pagemode),
             opt: DictKeySpec::Optional,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
 
         // forbidden key present
         let v = Vec::from("<< /PageMode /UseNone >>".as_bytes());
@@ -1228,14 +1231,14 @@ mod test_pdf_types {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let ent = DictEntry {
             key: Vec::from("PageMode"),
-            chk: Rc::clone(&pagemode),
+            chk: Arc::clone(&pagemode),
             opt: DictKeySpec::Forbidden,
         };
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
         let err = obj.place(TypeCheckError::ForbiddenKey(DictKey::new(Vec::from(
             "PageMode",
         ))));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
 
         // invalid value for optional key
         let v = Vec::from("<< /PageMode /Dummy >>".as_bytes());
@@ -1243,26 +1246,26 @@ mod test_pdf_types {
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         let ent = DictEntry {
             key: Vec::from("PageMode"),
-            chk: Rc::clone(&pagemode),
+            chk: Arc::clone(&pagemode),
             opt: DictKeySpec::Optional,
         };
-        let val = Rc::new(LocatedVal::new(
+        let val = Arc::new(LocatedVal::new(
             PDFObjT::Name(NameT::new(Vec::from("Dummy"))),
             0,
             0,
         ));
-        let typ = TypeCheck::new(&mut tctx, "dict", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ = TypeCheck::new(&mut tctx, "dict", Arc::new(PDFType::Dict(vec![ent], None)));
         let err = obj.place(TypeCheckError::ValueMismatch(
             val,
             String::from("Invalid PageMode"),
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 
     struct AsciiStringPredicate;
     impl Predicate for AsciiStringPredicate {
-        fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
-            if let PDFObjT::String(ref s) = obj.val() {
+        fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+            if let PDFObjT::String(ref s, _) = obj.val() {
                 for c in s {
                     if *c >= 128 {
                         return Some(obj.place(TypeCheckError::PredicateError(
@@ -1279,12 +1282,12 @@ mod test_pdf_types {
         }
     }
 
-    fn mk_ascii_typchk(tctx: &mut TypeCheckContext) -> Rc<TypeCheck> {
+    fn mk_ascii_typchk(tctx: &mut TypeCheckContext) -> Arc<TypeCheck> {
         TypeCheck::new_refined(
             tctx,
             "ascii",
-            Rc::new(PDFType::PrimType(PDFPrimType::String)),
-            Rc::new(AsciiStringPredicate),
+            Arc::new(PDFType::PrimType(PDFPrimType::String)),
+            Arc::new(AsciiStringPredicate),
         )
     }
 
@@ -1297,7 +1300,7 @@ mod test_pdf_types {
         let mut tctx = TypeCheckContext::new();
         let chk = mk_ascii_typchk(&mut tctx);
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             None
         );
 
@@ -1309,7 +1312,7 @@ mod test_pdf_types {
             "Not an ASCII string.".to_string(),
         ));
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             Some(err)
         );
     }
@@ -1319,14 +1322,14 @@ mod test_pdf_types {
         let pred = AsciiStringPredicate;
 
         let v = Vec::from("(ascii)".as_bytes());
-        let obj = LocatedVal::new(PDFObjT::String(v), 0, 0);
-        assert_eq!(pred.check(&Rc::new(obj)), None);
+        let obj = LocatedVal::new(PDFObjT::String(v, StringForm::Literal), 0, 0);
+        assert_eq!(pred.check(&Arc::new(obj)), None);
 
         let obj = LocatedVal::new(PDFObjT::Null(()), 0, 0);
         let err = obj.place(TypeCheckError::PredicateError(
             "Not an ASCII string.".to_string(),
         ));
-        assert_eq!(pred.check(&Rc::new(obj)), Some(err));
+        assert_eq!(pred.check(&Arc::new(obj)), Some(err));
     }
 
     #[test]
@@ -1340,11 +1343,11 @@ mod test_pdf_types {
         let chk = TypeCheck::new_refined(
             &mut tctx,
             "ascii",
-            Rc::new(PDFType::Any),
-            Rc::new(AsciiStringPredicate),
+            Arc::new(PDFType::Any),
+            Arc::new(AsciiStringPredicate),
         );
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             None
         );
 
@@ -1355,15 +1358,15 @@ mod test_pdf_types {
             "Not an ASCII string.".to_string(),
         ));
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             Some(err)
         );
     }
 
     struct OrTestPredicate;
     impl Predicate for OrTestPredicate {
-        fn check(&self, obj: &Rc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
-            if let PDFObjT::String(ref s) = obj.val() {
+        fn check(&self, obj: &Arc<LocatedVal<PDFObjT>>) -> Option<LocatedVal<TypeCheckError>> {
+            if let PDFObjT::String(ref s, _) = obj.val() {
                 for c in s {
                     if *c >= 128 {
                         return Some(obj.place(TypeCheckError::PredicateError(
@@ -1393,11 +1396,11 @@ mod test_pdf_types {
         let chk = TypeCheck::new_refined(
             &mut tctx,
             "or",
-            Rc::new(PDFType::Any),
-            Rc::new(OrTestPredicate),
+            Arc::new(PDFType::Any),
+            Arc::new(OrTestPredicate),
         );
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             None
         );
 
@@ -1405,7 +1408,7 @@ mod test_pdf_types {
         let mut pb = ParseBuffer::new(v);
         let obj = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::new(obj), Rc::clone(&chk)),
+            check_type(&ctxt, &tctx, Arc::new(obj), Arc::clone(&chk)),
             None
         );
     }
@@ -1413,19 +1416,19 @@ mod test_pdf_types {
     #[test]
     fn test_normalize() {
         let mut tctx = TypeCheckContext::new();
-        let t = TypeCheck::new(&mut tctx, "any", Rc::new(PDFType::Any));
-        let opts = vec![Rc::clone(&t), Rc::clone(&t)];
-        let d1 = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        let opts = vec![Rc::clone(&t), Rc::clone(&t)];
-        let d2 = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let t = TypeCheck::new(&mut tctx, "any", Arc::new(PDFType::Any));
+        let opts = vec![Arc::clone(&t), Arc::clone(&t)];
+        let d1 = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&t), Arc::clone(&t)];
+        let d2 = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
 
-        let opts = vec![Rc::clone(&d1), Rc::clone(&d2)];
-        let nd1 = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        let opts = vec![Rc::clone(&d1), Rc::clone(&d2)];
-        let nd2 = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&d1), Arc::clone(&d2)];
+        let nd1 = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&d1), Arc::clone(&d2)];
+        let nd2 = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
 
-        let opts = vec![Rc::clone(&nd1), Rc::clone(&nd2)];
-        let d = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&nd1), Arc::clone(&nd2)];
+        let d = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
 
         // check that normalization flattens the nested disjuncts into
         // a flat set.
@@ -1453,42 +1456,42 @@ mod test_pdf_types {
         let mut ctxt = mk_new_context();
         let v = Vec::from("[1 2 3 4]".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
 
         let mut tctx = TypeCheckContext::new();
         let rect = mk_rectangle_typchk(&mut tctx);
         let int = TypeCheck::new(
             &mut tctx,
             "int",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         );
         let date = mk_date_typchk(&mut tctx);
 
-        let opts = vec![Rc::clone(&rect), Rc::clone(&int), Rc::clone(&date)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), chk), None);
+        let opts = vec![Arc::clone(&rect), Arc::clone(&int), Arc::clone(&date)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), chk), None);
 
-        let opts = vec![Rc::clone(&int), Rc::clone(&rect), Rc::clone(&date)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), chk), None);
+        let opts = vec![Arc::clone(&int), Arc::clone(&rect), Arc::clone(&date)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), chk), None);
 
-        let opts = vec![Rc::clone(&date), Rc::clone(&int), Rc::clone(&rect)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), chk), None);
+        let opts = vec![Arc::clone(&date), Arc::clone(&int), Arc::clone(&rect)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), chk), None);
 
         let v = Vec::from("<</Key [1 2 3 4]>>".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
 
-        let opts = vec![Rc::clone(&rect), Rc::clone(&int), Rc::clone(&date)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&rect), Arc::clone(&int), Arc::clone(&date)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
         let ent = DictEntry {
             key: Vec::from("Key"),
-            chk: Rc::clone(&chk),
+            chk: Arc::clone(&chk),
             opt: DictKeySpec::Required,
         };
-        let typ = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Dict(vec![ent], None)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), typ), None);
+        let typ = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Dict(vec![ent], None)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), typ), None);
     }
 
     #[test]
@@ -1496,27 +1499,27 @@ mod test_pdf_types {
         let mut ctxt = mk_new_context();
         let v = Vec::from("[1 2 3 4]".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
 
         let mut tctx = TypeCheckContext::new();
         let int = TypeCheck::new(
             &mut tctx,
             "int",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         );
         let date = mk_date_typchk(&mut tctx);
 
-        let opts = vec![Rc::clone(&date), Rc::clone(&int)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&date), Arc::clone(&int)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
         // should return the error for the last disjunct, i.e. int
         let err = obj.place(TypeCheckError::TypeMismatch(
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
             PDFType::Array {
-                elem: TypeCheck::new(&mut tctx, "", Rc::new(PDFType::Any)),
+                elem: TypeCheck::new(&mut tctx, "", Arc::new(PDFType::Any)),
                 size: None,
             },
         ));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), chk), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), chk), Some(err));
     }
 
     #[test]
@@ -1524,49 +1527,49 @@ mod test_pdf_types {
         let mut ctxt = mk_new_context();
         let v = Vec::from("<</Key [1 2 3 4]>>".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
 
         let mut tctx = TypeCheckContext::new();
         let int = TypeCheck::new(
             &mut tctx,
             "int",
-            Rc::new(PDFType::PrimType(PDFPrimType::Integer)),
+            Arc::new(PDFType::PrimType(PDFPrimType::Integer)),
         );
         let date = mk_date_typchk(&mut tctx);
         let rect = mk_rectangle_typchk(&mut tctx);
 
         // inner unwind of value match to failure
-        let opts = vec![Rc::clone(&int), Rc::clone(&date)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&int), Arc::clone(&date)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
         let ent = DictEntry {
             key: Vec::from("Key"),
-            chk: Rc::clone(&chk),
+            chk: Arc::clone(&chk),
             opt: DictKeySpec::Required,
         };
-        let typ1 = TypeCheck::new(&mut tctx, "typ1", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ1 = TypeCheck::new(&mut tctx, "typ1", Arc::new(PDFType::Dict(vec![ent], None)));
 
         // unwind up a level
         let ent = DictEntry {
             key: Vec::from("Key"),
-            chk: Rc::clone(&date),
+            chk: Arc::clone(&date),
             opt: DictKeySpec::Required,
         };
-        let typ2 = TypeCheck::new(&mut tctx, "typ2", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ2 = TypeCheck::new(&mut tctx, "typ2", Arc::new(PDFType::Dict(vec![ent], None)));
 
         // inner unwind of value match to success
-        let opts = vec![Rc::clone(&int), Rc::clone(&date), Rc::clone(&rect)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&int), Arc::clone(&date), Arc::clone(&rect)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
         let ent = DictEntry {
             key: Vec::from("Key"),
-            chk: Rc::clone(&chk),
+            chk: Arc::clone(&chk),
             opt: DictKeySpec::Required,
         };
-        let typ3 = TypeCheck::new(&mut tctx, "typ3", Rc::new(PDFType::Dict(vec![ent], None)));
+        let typ3 = TypeCheck::new(&mut tctx, "typ3", Arc::new(PDFType::Dict(vec![ent], None)));
 
         // bound the unwinds to within a single successful top-level match
         let opts = vec![typ1, typ2, typ3];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::clone(&obj), chk), None);
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::clone(&obj), chk), None);
     }
 
     #[test]
@@ -1578,36 +1581,36 @@ mod test_pdf_types {
         let rect = mk_rectangle_typchk(&mut tctx);
         // refer by name to the type that will be created later
         let named = TypeCheck::new_named("rect | dict");
-        let opts = vec![Rc::clone(&rect), Rc::clone(&named)];
-        let chk = TypeCheck::new(&mut tctx, "opt", Rc::new(PDFType::Disjunct(opts)));
+        let opts = vec![Arc::clone(&rect), Arc::clone(&named)];
+        let chk = TypeCheck::new(&mut tctx, "opt", Arc::new(PDFType::Disjunct(opts)));
         let ent = DictEntry {
             key: Vec::from("Key"),
-            chk: Rc::clone(&chk),
+            chk: Arc::clone(&chk),
             opt: DictKeySpec::Required,
         };
         // actually create the concrete type with the specified name
         let typ = TypeCheck::new(
             &mut tctx,
             "rect | dict",
-            Rc::new(PDFType::Dict(vec![ent], None)),
+            Arc::new(PDFType::Dict(vec![ent], None)),
         );
 
         let mut ctxt = mk_new_context();
         // non-recursive case: the value is a rectangle
         let v = Vec::from("<</Key [1 2 3 4]>>".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::clone(&obj), Rc::clone(&typ)),
+            check_type(&ctxt, &tctx, Arc::clone(&obj), Arc::clone(&typ)),
             None
         );
 
         // recursive case: the value is another (rect | dict)
         let v = Vec::from("<</Key <</Key [1 2 3 4]>>>>".as_bytes());
         let mut pb = ParseBuffer::new(v);
-        let obj = Rc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
+        let obj = Arc::new(parse_pdf_obj(&mut ctxt, &mut pb).unwrap());
         assert_eq!(
-            check_type(&ctxt, &tctx, Rc::clone(&obj), Rc::clone(&typ)),
+            check_type(&ctxt, &tctx, Arc::clone(&obj), Arc::clone(&typ)),
             None
         );
     }
@@ -1622,15 +1625,15 @@ mod test_pdf_types {
         let mut tctx = TypeCheckContext::new();
         let rect = mk_rectangle_typchk(&mut tctx);
         let star = DictStarEntry {
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Required,
         };
         let typ = TypeCheck::new(
             &mut tctx,
             "dict",
-            Rc::new(PDFType::Dict(vec![], Some(star))),
+            Arc::new(PDFType::Dict(vec![], Some(star))),
         );
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), None);
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), None);
     }
 
     #[test]
@@ -1644,19 +1647,19 @@ mod test_pdf_types {
         let rect = mk_rectangle_typchk(&mut tctx);
         let ent1 = DictEntry {
             key: Vec::from("First"),
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Optional,
         };
         let star = DictStarEntry {
-            chk: Rc::clone(&rect),
+            chk: Arc::clone(&rect),
             opt: DictKeySpec::Required,
         };
         let typ = TypeCheck::new(
             &mut tctx,
             "dict",
-            Rc::new(PDFType::Dict(vec![ent1], Some(star))),
+            Arc::new(PDFType::Dict(vec![ent1], Some(star))),
         );
         let err = obj.place(TypeCheckError::ArraySizeMismatch(4, 5));
-        assert_eq!(check_type(&ctxt, &tctx, Rc::new(obj), typ), Some(err));
+        assert_eq!(check_type(&ctxt, &tctx, Arc::new(obj), typ), Some(err));
     }
 }
@@ -0,0 +1,659 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Serialization of PDF objects, and of a document given by an object
+// context and the id of its root (catalog) object.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use super::super::pcore::parsebuffer::LocatedVal;
+use super::pdf_obj::{
+    ArrayT, DictKey, DictT, ObjectId, PDFObjContext, PDFObjT, ReferenceT, StreamT, StringForm,
+};
+use super::pdf_prim::{IntegerT, NameT, RealT, StreamContentT};
+use super::pdf_streams::{encode_stream, EncodeFilter, XrefEntStatus};
+
+// Writes a name, with '#xx' escapes for the bytes that cannot appear
+// in a name verbatim.
+pub fn write_name(name: &[u8], out: &mut Vec<u8>) {
+    out.push(b'/');
+    for b in name {
+        match b {
+            b'#' | b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => {
+                out.extend_from_slice(format!("#{:02X}", b).as_bytes())
+            },
+            0x21 ..= 0x7E => out.push(*b),
+            _ => out.extend_from_slice(format!("#{:02X}", b).as_bytes()),
+        }
+    }
+}
+
+// Writes a string in the form it was parsed in.  The parser keeps
+// literal strings in their raw escaped form, which is written as is,
+// and decodes hex strings, whose bytes are written as hex digits.
+pub fn write_string(s: &[u8], form: StringForm, out: &mut Vec<u8>) {
+    match form {
+        StringForm::Literal => {
+            out.push(b'(');
+            out.extend_from_slice(s);
+            out.push(b')')
+        },
+        StringForm::Hex => {
+            out.push(b'<');
+            for b in s {
+                out.extend_from_slice(format!("{:02X}", b).as_bytes())
+            }
+            out.push(b'>')
+        },
+    }
+}
+
+// Writes a real in decimal notation.  The values produced by the
+// parser have a power of ten as denominator, and are written
+// exactly; other values are rounded to ten decimal places.
+pub fn write_real(r: &RealT, out: &mut Vec<u8>) {
+    let (num, den) = r.fraction();
+    let (num, den) = if den < 0 { (-num, -den) } else { (num, den) };
+    if den == 0 {
+        out.push(b'0');
+        return
+    }
+    let (abs, den) = (num.unsigned_abs(), den as u128);
+    let mut digits = String::new();
+    let mut places = 0;
+    let mut d = den;
+    while d % 10 == 0 {
+        d /= 10;
+        places += 1
+    }
+    if d == 1 {
+        if places > 0 {
+            digits = format!("{:0width$}", abs % den, width = places);
+        }
+    } else {
+        // long division of the remainder
+        let mut rem = abs % den;
+        while rem != 0 && digits.len() < 10 {
+            rem *= 10;
+            digits.push((b'0' + (rem / den) as u8) as char);
+            rem %= den
+        }
+    }
+    if num < 0 {
+        out.push(b'-')
+    }
+    out.extend_from_slice(format!("{}", abs / den).as_bytes());
+    if !digits.is_empty() {
+        out.push(b'.');
+        out.extend_from_slice(digits.as_bytes())
+    }
+}
+
+// Objects created by the writer have no location.
+pub fn new_obj(o: PDFObjT) -> Arc<LocatedVal<PDFObjT>> { Arc::new(LocatedVal::new(o, 0, 0)) }
+
+fn write_dict(d: &DictT, out: &mut Vec<u8>) {
+    out.extend_from_slice(b"<<");
+    for (i, (k, v)) in d.map().iter().enumerate() {
+        if i > 0 {
+            out.push(b' ')
+        }
+        write_name(k.as_slice(), out);
+        out.push(b' ');
+        write_obj(v.val(), out);
+    }
+    out.extend_from_slice(b">>");
+}
+
+// Writes an object in PDF syntax.  The /Length of a stream is
+// written as the size of its content.
+pub fn write_obj(obj: &PDFObjT, out: &mut Vec<u8>) {
+    match obj {
+        PDFObjT::Array(a) => {
+            out.push(b'[');
+            for (i, o) in a.objs().iter().enumerate() {
+                if i > 0 {
+                    out.push(b' ')
+                }
+                write_obj(o.val(), out)
+            }
+            out.push(b']')
+        },
+        PDFObjT::Dict(d) => write_dict(d, out),
+        PDFObjT::Stream(s) => {
+            let content = s.stream().val().content();
+            let mut map = s.dict().val().map().clone();
+            let len = PDFObjT::Integer(IntegerT::new(content.len() as i64));
+            map.insert(DictKey::new(b"Length".to_vec()), new_obj(len));
+            write_dict(&DictT::new(map), out);
+            out.extend_from_slice(b"\nstream\n");
+            out.extend_from_slice(content);
+            out.extend_from_slice(b"\nendstream")
+        },
+        PDFObjT::Reference(r) => {
+            out.extend_from_slice(format!("{} {} R", r.num(), r.gen()).as_bytes())
+        },
+        PDFObjT::Boolean(b) => out.extend_from_slice(if *b { b"true" } else { b"false" }),
+        PDFObjT::String(s, form) => write_string(s, *form, out),
+        PDFObjT::Name(n) => write_name(n.val(), out),
+        PDFObjT::Null(_) => out.extend_from_slice(b"null"),
+        PDFObjT::Comment(c) => {
+            out.push(b'%');
+            out.extend_from_slice(c);
+            out.push(b'\n')
+        },
+        PDFObjT::Integer(i) => out.extend_from_slice(format!("{}", i.int_val()).as_bytes()),
+        PDFObjT::Real(r) => write_real(r, out),
+    }
+}
+
+// An object to be written, with its identifier.
+type IndirectObj<'a> = (ObjectId, &'a Arc<LocatedVal<PDFObjT>>);
+
+// Writes a document in the form of a PDF file with a classic xref
+// table.  Only the objects reachable from the root, and from the
+// document information dictionary if any, are written; they keep
+// their object ids.
+pub struct PDFWriter<'a> {
+    ctxt:            &'a PDFObjContext,
+    version:         String,
+    info:            Option<ObjectId>,
+    // the maximum number of objects in an object stream
+    objs_per_stream: usize,
+}
+
+impl<'a> PDFWriter<'a> {
+    pub fn new(ctxt: &'a PDFObjContext) -> PDFWriter<'a> {
+        PDFWriter {
+            ctxt,
+            version: "1.7".to_string(),
+            info: None,
+            objs_per_stream: 100,
+        }
+    }
+    pub fn set_version(&mut self, version: &str) { self.version = version.to_string() }
+    pub fn version(&self) -> &str { &self.version }
+    pub fn set_info(&mut self, info: ObjectId) { self.info = Some(info) }
+    pub fn info(&self) -> Option<ObjectId> { self.info }
+    pub fn set_objs_per_stream(&mut self, n: usize) { self.objs_per_stream = n.max(1) }
+    pub fn objs_per_stream(&self) -> usize { self.objs_per_stream }
+
+    // Collects the ids of the defined objects reachable from the
+    // given roots.  The /Length of a stream is not followed, since it
+    // is written directly.
+    pub fn reachable(&self, roots: &[ObjectId]) -> BTreeSet<ObjectId> {
+        fn visit(o: &PDFObjT, queue: &mut Vec<ObjectId>) {
+            match o {
+                PDFObjT::Reference(r) => queue.push(r.id()),
+                PDFObjT::Array(a) => a.objs().iter().for_each(|o| visit(o.val(), queue)),
+                PDFObjT::Dict(d) => d.map().values().for_each(|o| visit(o.val(), queue)),
+                PDFObjT::Stream(s) => {
+                    for (k, o) in s.dict().val().map() {
+                        if k.as_slice() != b"Length" {
+                            visit(o.val(), queue)
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        let mut ids = BTreeSet::new();
+        let mut queue = roots.to_vec();
+        while let Some(id) = queue.pop() {
+            if ids.contains(&id) {
+                continue
+            }
+            if let Some(o) = self.ctxt.lookup_obj(id) {
+                ids.insert(id);
+                visit(o.val(), &mut queue)
+            }
+        }
+        ids
+    }
+
+    // The header, with a comment of binary bytes to mark the file as
+    // binary.
+    pub fn write_header(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("%PDF-{}\n", self.version).as_bytes());
+        out.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+    }
+
+    // Writes an indirect object.
+    pub fn write_indirect(&self, id: ObjectId, obj: &PDFObjT, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("{} {} obj\n", id.0, id.1).as_bytes());
+        write_obj(obj, out);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    // The trailer entries for a file whose highest object number is
+    // `size - 1`.
+    pub fn trailer_dict(&self, root: ObjectId, size: usize) -> DictT {
+        let mut map = BTreeMap::new();
+        let size = PDFObjT::Integer(IntegerT::new(size as i64));
+        map.insert(DictKey::new(b"Size".to_vec()), new_obj(size));
+        let root = PDFObjT::Reference(ReferenceT::new(root.0, root.1));
+        map.insert(DictKey::new(b"Root".to_vec()), new_obj(root));
+        if let Some(info) = self.info {
+            let info = PDFObjT::Reference(ReferenceT::new(info.0, info.1));
+            map.insert(DictKey::new(b"Info".to_vec()), new_obj(info));
+        }
+        DictT::new(map)
+    }
+
+    // Writes the objects reachable from the root, and returns their
+    // ids with the offsets at which they were written.
+    pub fn write_body(
+        &self, root: ObjectId, out: &mut Vec<u8>,
+    ) -> Result<Vec<(ObjectId, usize)>, String> {
+        let mut offsets = Vec::new();
+        for (id, obj) in self.objects(root)? {
+            offsets.push((id, out.len()));
+            self.write_indirect(id, obj.val(), out);
+        }
+        Ok(offsets)
+    }
+
+    // The objects to be written, in the order of their ids.
+    fn objects(&self, root: ObjectId) -> Result<Vec<IndirectObj<'a>>, String> {
+        let ctxt = self.ctxt;
+        if ctxt.lookup_obj(root).is_none() {
+            return Err(format!(
+                "root object ({},{}) is not defined",
+                root.0, root.1
+            ))
+        }
+        let mut roots = vec![root];
+        roots.extend(self.info);
+        let mut objs: Vec<IndirectObj> = Vec::new();
+        for id in self.reachable(&roots) {
+            if objs.last().map(|(l, _)| l.0) == Some(id.0) {
+                return Err(format!("multiple generations of object {}", id.0))
+            }
+            objs.push((id, ctxt.lookup_obj(id).unwrap()))
+        }
+        Ok(objs)
+    }
+
+    // Writes the complete file.
+    pub fn write_file(&self, root: ObjectId) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        self.write_header(&mut out);
+        let offsets = self.write_body(root, &mut out)?;
+        let size = offsets.last().map_or(1, |(id, _)| id.0 + 1);
+
+        // The xref table, with the free entries linked in increasing
+        // order from object 0.
+        let xref_ofs = out.len();
+        let mut entries = vec![None; size];
+        for (id, ofs) in offsets {
+            entries[id.0] = Some((ofs, id.1))
+        }
+        let mut lines = Vec::with_capacity(size);
+        let mut next_free = 0;
+        for (n, e) in entries.iter().enumerate().rev() {
+            match e {
+                Some((ofs, gen)) => lines.push(format!("{:010} {:05} n\r\n", ofs, gen)),
+                None => {
+                    let gen = if n == 0 { 65535 } else { 0 };
+                    lines.push(format!("{:010} {:05} f\r\n", next_free, gen));
+                    next_free = n
+                },
+            }
+        }
+        out.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+        for line in lines.iter().rev() {
+            out.extend_from_slice(line.as_bytes())
+        }
+        out.extend_from_slice(b"trailer\n");
+        write_dict(&self.trailer_dict(root, size), &mut out);
+        out.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", xref_ofs).as_bytes());
+        Ok(out)
+    }
+
+    // Writes the complete file in the compressed form of PDF 1.5:
+    // objects other than streams are packed into object streams, and
+    // the cross-reference information is written as an xref stream.
+    // The object and xref streams get object numbers following those
+    // of the written objects.
+    pub fn write_compressed_file(&self, root: ObjectId) -> Result<Vec<u8>, String> {
+        let objs = self.objects(root)?;
+        let mut next_num = objs.last().map_or(1, |(id, _)| id.0 + 1);
+        let gens: BTreeMap<usize, usize> = objs.iter().map(|(id, _)| (id.0, id.1)).collect();
+
+        // Only objects with generation 0 can be in object streams.
+        let (packed, direct): (Vec<_>, Vec<_>) = objs
+            .into_iter()
+            .partition(|(id, o)| id.1 == 0 && !matches!(o.val(), PDFObjT::Stream(_)));
+        let mut entries = BTreeMap::new();
+        let mut streams = Vec::new();
+        for chunk in packed.chunks(self.objs_per_stream) {
+            let num = next_num;
+            next_num += 1;
+            for (i, (id, _)) in chunk.iter().enumerate() {
+                entries.insert(
+                    id.0,
+                    XrefEntStatus::InStream {
+                        stream_obj: num,
+                        obj_index:  i,
+                    },
+                );
+            }
+            streams.push(((num, 0), obj_stream(chunk)?));
+        }
+
+        let mut out = Vec::new();
+        self.write_header(&mut out);
+        for (id, obj) in direct {
+            entries.insert(
+                id.0,
+                XrefEntStatus::InUse {
+                    file_ofs: out.len(),
+                },
+            );
+            self.write_indirect(id, obj.val(), &mut out);
+        }
+        for (id, strm) in streams {
+            entries.insert(
+                id.0,
+                XrefEntStatus::InUse {
+                    file_ofs: out.len(),
+                },
+            );
+            self.write_indirect(id, &strm, &mut out);
+        }
+
+        // The xref stream, which has an entry for itself.
+        let xref_num = next_num;
+        let xref_ofs = out.len();
+        entries.insert(xref_num, XrefEntStatus::InUse { file_ofs: xref_ofs });
+        let fields: Vec<(usize, usize, usize)> = entries
+            .iter()
+            .map(|(num, e)| match e {
+                XrefEntStatus::InStream {
+                    stream_obj,
+                    obj_index,
+                } => (2, *stream_obj, *obj_index),
+                XrefEntStatus::InUse { file_ofs } => {
+                    (1, *file_ofs, gens.get(num).cloned().unwrap_or(0))
+                },
+                XrefEntStatus::Free { next } => (0, *next, 0),
+            })
+            .collect();
+        let width = |max: usize| (0 ..).find(|w| w >= &4 || max >> (8 * w) == 0).unwrap();
+        let w2 = width(fields.iter().map(|f| f.1).max().unwrap_or(0)).max(1);
+        let w3 = width(fields.iter().map(|f| f.2).max().unwrap_or(0));
+        if w2 == 4 && fields.iter().any(|f| f.1 >> 32 != 0) {
+            return Err("file too large for the xref stream".to_string())
+        }
+        let mut content = Vec::new();
+        for (t, f2, f3) in fields {
+            content.push(t as u8);
+            content.extend_from_slice(&(f2 as u32).to_be_bytes()[4 - w2 ..]);
+            content.extend_from_slice(&(f3 as u32).to_be_bytes()[4 - w3 ..]);
+        }
+        // The /Index subsections are the runs of consecutive object
+        // numbers.
+        let mut index: Vec<(usize, usize)> = Vec::new();
+        for num in entries.keys() {
+            match index.last_mut() {
+                Some((start, count)) if *start + *count == *num => *count += 1,
+                _ => index.push((*num, 1)),
+            }
+        }
+        let int = |i: usize| new_obj(PDFObjT::Integer(IntegerT::new(i as i64)));
+        let index = index
+            .iter()
+            .flat_map(|(s, c)| vec![int(*s), int(*c)])
+            .collect();
+        let mut dict = self.trailer_dict(root, xref_num + 1);
+        let map = dict.map_mut();
+        map.insert(DictKey::new(b"Type".to_vec()), new_name("XRef"));
+        let w = vec![int(1), int(w2), int(w3)];
+        map.insert(
+            DictKey::new(b"W".to_vec()),
+            new_obj(PDFObjT::Array(ArrayT::new(w))),
+        );
+        let index = new_obj(PDFObjT::Array(ArrayT::new(index)));
+        map.insert(DictKey::new(b"Index".to_vec()), index);
+        let xref = flate_stream(dict, content)?;
+        self.write_indirect((xref_num, 0), &xref, &mut out);
+        out.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_ofs).as_bytes());
+        Ok(out)
+    }
+}
+
+fn new_name(n: &str) -> Arc<LocatedVal<PDFObjT>> {
+    new_obj(PDFObjT::Name(NameT::new(n.as_bytes().to_vec())))
+}
+
+// Creates a stream with the given dictionary entries and content,
+// encoded with FlateDecode.
+fn flate_stream(dict: DictT, content: Vec<u8>) -> Result<PDFObjT, String> {
+    let len = content.len();
+    let strm = StreamT::new(
+        Arc::new(LocatedVal::new(dict, 0, 0)),
+        LocatedVal::new(StreamContentT::new(0, len, content), 0, 0),
+    );
+    let filters = [EncodeFilter::new("FlateDecode", None)];
+    match encode_stream(&strm, &filters) {
+        Ok(s) => Ok(PDFObjT::Stream(s)),
+        Err(e) => Err(format!("cannot encode stream: {:?}", e.val())),
+    }
+}
+
+// Creates an object stream containing the given objects.  The
+// header of object number and offset pairs is followed by the
+// objects, each on a line.
+fn obj_stream(objs: &[IndirectObj]) -> Result<PDFObjT, String> {
+    let mut header = Vec::new();
+    let mut body = Vec::new();
+    for (id, o) in objs {
+        header.extend_from_slice(format!("{} {} ", id.0, body.len()).as_bytes());
+        write_obj(o.val(), &mut body);
+        body.push(b'\n');
+    }
+    header.pop();
+    header.push(b'\n');
+    let mut map = BTreeMap::new();
+    map.insert(DictKey::new(b"Type".to_vec()), new_name("ObjStm"));
+    let n = PDFObjT::Integer(IntegerT::new(objs.len() as i64));
+    map.insert(DictKey::new(b"N".to_vec()), new_obj(n));
+    let first = PDFObjT::Integer(IntegerT::new(header.len() as i64));
+    map.insert(DictKey::new(b"First".to_vec()), new_obj(first));
+    header.extend_from_slice(&body);
+    flate_stream(DictT::new(map), header)
+}
+
+#[cfg(test)]
+mod test_writer {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::{write_obj, PDFWriter};
+    use crate::pcore::parsebuffer::ParseBuffer;
+    use crate::pdf_lib::pdf_obj::{parse_pdf_obj, PDFObjContext, PDFObjT};
+    use crate::pdf_lib::pdf_prim::RealT;
+    use crate::pdf_lib::pdf_traverse_xref::{parse_data, parse_data_with_options, ParseOptions};
+
+    fn get_test_data(test_file: &str) -> Vec<u8> {
+        let mut file = File::open(test_file).unwrap();
+        let mut v = Vec::new();
+        file.read_to_end(&mut v).unwrap();
+        v
+    }
+
+    fn write(o: &PDFObjT) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_obj(o, &mut out);
+        out
+    }
+
+    // Parses the input, and checks that it is written out as
+    // expected, and that the output parses into the same object.
+    fn check(input: &[u8], expected: &[u8]) {
+        let mut ctxt = PDFObjContext::new(10);
+        let mut pb = ParseBuffer::new(input.to_vec());
+        let o = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        let out = write(o.val());
+        assert_eq!(
+            out,
+            expected,
+            "{} written as {}",
+            String::from_utf8_lossy(input),
+            String::from_utf8_lossy(&out)
+        );
+        let mut pb = ParseBuffer::new(out.clone());
+        let p = parse_pdf_obj(&mut ctxt, &mut pb).unwrap();
+        assert_eq!(write(p.val()), out);
+        assert_eq!(p.val(), o.val());
+    }
+
+    #[test]
+    fn test_write_obj() {
+        check(b"/Name", b"/Name");
+        check(b"/A#20B", b"/A#20B");
+        check(b"/A#23#2fB#e9", b"/A#23#2FB#E9");
+        check(b"(a (b) \\) c)", b"(a (b) \\) c)");
+        check(b"(\\\\)", b"(\\\\)");
+        check(b"<48656c6c6f>", b"<48656C6C6F>");
+        check(b"<28>", b"<28>");
+        check(b"<5c>", b"<5C>");
+        // escapes in hex strings are not escapes
+        check(b"<5C6E>", b"<5C6E>");
+        check(b"<615C2862>", b"<615C2862>");
+        check(b"<0D>", b"<0D>");
+        check(b"(\\n)", b"(\\n)");
+        check(b"<00ff>", b"<00FF>");
+        check(b"(\x00\xff)", b"(\x00\xff)");
+        check(b"-12", b"-12");
+        check(b"1.50", b"1.50");
+        check(b"-.5", b"-0.5");
+        check(b"0.001", b"0.001");
+        check(b"5 0 R", b"5 0 R");
+        check(b"[1 /A [true false] null]", b"[1 /A [true false] null]");
+        check(
+            b"<< /Type /Page /Kids [ 1 0 R 2 0 R ] /Media <</A (x)>> >>",
+            b"<</Kids [1 0 R 2 0 R] /Media <</A (x)>> /Type /Page>>",
+        );
+        check(b"[]", b"[]");
+        check(b"<<>>", b"<<>>");
+
+        let mut out = Vec::new();
+        write_obj(&PDFObjT::Real(RealT::new(1, 3)), &mut out);
+        assert_eq!(out, b"0.3333333333");
+        let mut out = Vec::new();
+        write_obj(&PDFObjT::Real(RealT::new(-7, 4)), &mut out);
+        assert_eq!(out, b"-1.75");
+    }
+
+    // Checks that the file written with object streams of at most
+    // `per_stream` objects parses into the objects of the original,
+    // with the object streams and the xref stream numbered after them
+    // and no trailer.
+    fn check_compressed_file(ctxt: &PDFObjContext, root_id: (usize, usize), per_stream: usize) {
+        let mut w = PDFWriter::new(ctxt);
+        w.set_objs_per_stream(per_stream);
+        let out = w.write_compressed_file(root_id).unwrap();
+        assert!(!out.windows(7).any(|s| s == b"trailer"));
+        let ids = w.reachable(&[root_id]);
+        let (_, ctxt2, root_id2) = parse_data(Path::new("written.pdf"), &out).unwrap();
+        assert_eq!(root_id2, root_id);
+        // the object and xref streams follow the written objects
+        let packed = ids
+            .iter()
+            .filter(|id| {
+                let o = ctxt.lookup_obj(**id).unwrap();
+                id.1 == 0 && !matches!(o.val(), PDFObjT::Stream(_))
+            })
+            .count();
+        let n_streams = packed.div_ceil(per_stream);
+        let max = ids.iter().map(|id| id.0).max().unwrap();
+        let mut types = Vec::new();
+        for num in max + 1 ..= max + n_streams + 1 {
+            let o = ctxt2.lookup_obj((num, 0)).unwrap();
+            match o.val() {
+                PDFObjT::Stream(s) => {
+                    types.push(s.dict().val().get_name(b"Type").unwrap().to_vec())
+                },
+                _ => panic!("object {} is not a stream", num),
+            }
+        }
+        let mut expected = vec![b"ObjStm".to_vec(); n_streams];
+        expected.push(b"XRef".to_vec());
+        assert_eq!(types, expected);
+        for id in &ids {
+            let o = ctxt.lookup_obj(*id).unwrap();
+            let o2 = ctxt2.lookup_obj(*id).unwrap();
+            assert_eq!(write(o.val()), write(o2.val()));
+        }
+        assert_eq!(ctxt2.obj_ids().len(), ids.len() + n_streams + 1);
+    }
+
+    // Checks that the written file parses into the objects of the
+    // original, in both the classic and the compressed forms.
+    fn check_file(ctxt: &PDFObjContext, root_id: (usize, usize)) {
+        for n in &[2, 100] {
+            check_compressed_file(ctxt, root_id, *n)
+        }
+        let w = PDFWriter::new(ctxt);
+        let out = w.write_file(root_id).unwrap();
+        let ids = w.reachable(&[root_id]);
+        let (_, ctxt2, root_id2) = parse_data(Path::new("written.pdf"), &out).unwrap();
+        assert_eq!(root_id2, root_id);
+        assert!(!ctxt2.is_encrypted());
+        assert_eq!(ctxt2.obj_ids(), ids.iter().cloned().collect::<Vec<_>>());
+        for id in ids {
+            let o = ctxt.lookup_obj(id).unwrap();
+            let o2 = ctxt2.lookup_obj(id).unwrap();
+            assert_eq!(write(o.val()), write(o2.val()));
+        }
+        // writing is deterministic
+        assert_eq!(PDFWriter::new(&ctxt2).write_file(root_id).unwrap(), out);
+    }
+
+    #[test]
+    fn test_write_file() {
+        for f in &[
+            "tests/test_files/minimal.pdf",
+            "tests/test_files/Rosenthol_example.pdf",
+            "tests/test_files/Rosenthol_example_2pages.pdf",
+        ] {
+            // the example files need their xref to be rebuilt
+            let mut opts = ParseOptions::new();
+            opts.set_recover_xref(true);
+            let v = get_test_data(f);
+            let (_, ctxt, root_id) = parse_data_with_options(Path::new(f), &v, &opts).unwrap();
+            check_file(&ctxt, root_id)
+        }
+        // encrypted files are written out decrypted
+        for f in &["aes_128", "rc4_40"] {
+            let f = format!("tests/test_files/encryption/{}.pdf", f);
+            let v = get_test_data(&f);
+            let opts = ParseOptions::new();
+            let (_, ctxt, root_id) = parse_data_with_options(Path::new(&f), &v, &opts).unwrap();
+            assert!(ctxt.is_encrypted());
+            check_file(&ctxt, root_id)
+        }
+
+        let ctxt = PDFObjContext::new(10);
+        assert!(PDFWriter::new(&ctxt).write_file((1, 0)).is_err());
+        assert!(PDFWriter::new(&ctxt).write_compressed_file((1, 0)).is_err());
+    }
+}
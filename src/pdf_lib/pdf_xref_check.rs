@@ -0,0 +1,434 @@
+// Copyright (c) 2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Checks the consistency of the xref information of a file with the
+// objects in its body.  Unlike the parser, which trusts the xref and
+// fails on the first object it cannot parse, the checker examines
+// every entry in effect after the last revision, and reports all the
+// problems it finds.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use super::super::pcore::parsebuffer::{ParseBuffer, ParseBufferT, ParsleyParser};
+use super::super::pcore::transforms::{BufferTransformT, RestrictViewFrom};
+use super::pdf_obj::{DictP, ObjectId, PDFObjContext};
+use super::pdf_prim::WhitespaceEOL;
+use super::pdf_streams::{XrefEntStatus, XrefEntT};
+use super::pdf_traverse_xref::{merged_ents, parse_revisions, PdfParseResult};
+
+// The generation of the head of the free list.
+const FREE_HEAD_GEN: usize = 65535;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum XrefIssue {
+    // The offset of an in-use object is beyond the end of the file.
+    OutOfBounds {
+        id:  ObjectId,
+        ofs: usize,
+    },
+    // There is no 'n g obj' header at the offset of an in-use object.
+    NoObjectHeader {
+        id:  ObjectId,
+        ofs: usize,
+    },
+    // The header at the offset of an in-use object is for a different
+    // object.
+    HeaderMismatch {
+        id:    ObjectId,
+        ofs:   usize,
+        found: ObjectId,
+    },
+    // Object 0 is missing, is not free, or does not have generation
+    // 65535.
+    BadFreeListHead {
+        status: Option<XrefEntStatus>,
+        gen:    usize,
+    },
+    // A free entry links to an object that is not free.
+    BadFreeLink {
+        from: usize,
+        to:   usize,
+    },
+    // The free list does not return to object 0.
+    FreeListCycle {
+        obj: usize,
+    },
+    // A free entry is not on the free list.
+    NotInFreeList {
+        obj: usize,
+    },
+    // The trailer /Size is missing or is not one more than the highest
+    // object number.
+    SizeMismatch {
+        size:     Option<usize>,
+        expected: usize,
+    },
+    // An entry for an object in an object stream refers to an object
+    // that is not an object stream.
+    NotObjectStream {
+        id:         ObjectId,
+        stream_obj: usize,
+    },
+    // The index of an object in an object stream is not less than the
+    // number of objects in the stream.
+    BadStreamIndex {
+        id:         ObjectId,
+        stream_obj: usize,
+        index:      usize,
+        count:      usize,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct XrefReport {
+    ents:   Vec<XrefEntT>,
+    issues: Vec<XrefIssue>,
+}
+
+impl XrefReport {
+    // The entries that were checked.
+    pub fn ents(&self) -> &[XrefEntT] { &self.ents }
+    pub fn issues(&self) -> &[XrefIssue] { &self.issues }
+    pub fn is_consistent(&self) -> bool { self.issues.is_empty() }
+}
+
+// Parses an 'n g obj' header at the given offset.
+fn obj_header(buf: &[u8], ofs: usize) -> Option<ObjectId> {
+    let mut pos = ofs;
+    let mut number = || {
+        let start = pos;
+        while buf.get(pos).is_some_and(|b| b.is_ascii_digit()) {
+            pos += 1
+        }
+        let n = std::str::from_utf8(&buf[start .. pos])
+            .ok()?
+            .parse::<usize>()
+            .ok()?;
+        let ws = pos;
+        while buf.get(pos).is_some_and(|b| b" \0\t\r\n\x0c".contains(b)) {
+            pos += 1
+        }
+        if ws == pos {
+            return None
+        }
+        Some((n, pos))
+    };
+    let (num, _) = number()?;
+    let (gen, end) = number()?;
+    if buf[end ..].starts_with(b"obj") {
+        Some((num, gen))
+    } else {
+        None
+    }
+}
+
+// Returns the number of objects in the object stream at the given
+// offset, or None if there is no object stream there.  The stream
+// dictionary is parsed from a view into 'pb', which holds 'buf'.
+fn obj_stream_count(pb: &ParseBuffer, buf: &[u8], ofs: usize, num: usize) -> Option<usize> {
+    if obj_header(buf, ofs).map(|id| id.0) != Some(num) {
+        return None
+    }
+    let mut pb = RestrictViewFrom::new(ofs).transform(pb).ok()?;
+    pb.scan(b"obj").ok()?;
+    pb.set_cursor(pb.get_cursor() + 3).ok()?;
+    let mut ws = WhitespaceEOL::new(true);
+    ws.parse(&mut pb).ok()?;
+    let mut ctxt = PDFObjContext::new(50);
+    let d = DictP::new(&mut ctxt).parse(&mut pb).ok()?;
+    ws.parse(&mut pb).ok()?;
+    if !pb.buf().starts_with(b"stream") || d.get_name(b"Type") != Some(b"ObjStm") {
+        return None
+    }
+    d.get_usize(b"N")
+}
+
+fn check_free_list(ents: &BTreeMap<usize, XrefEntT>, issues: &mut Vec<XrefIssue>) {
+    let free: BTreeMap<usize, usize> = ents
+        .iter()
+        .filter_map(|(num, e)| match e.status() {
+            XrefEntStatus::Free { next } => Some((*num, *next)),
+            _ => None,
+        })
+        .collect();
+    match ents.get(&0) {
+        Some(e) if free.contains_key(&0) && e.gen() == FREE_HEAD_GEN => (),
+        e => issues.push(XrefIssue::BadFreeListHead {
+            status: e.map(|e| *e.status()),
+            gen:    e.map_or(0, |e| e.gen()),
+        }),
+    }
+    let mut visited = BTreeSet::new();
+    let mut cur = 0;
+    while let Some(next) = free.get(&cur) {
+        visited.insert(cur);
+        if *next == 0 {
+            break
+        }
+        if !free.contains_key(next) {
+            issues.push(XrefIssue::BadFreeLink {
+                from: cur,
+                to:   *next,
+            });
+            break
+        }
+        if visited.contains(next) {
+            issues.push(XrefIssue::FreeListCycle { obj: *next });
+            break
+        }
+        cur = *next
+    }
+    for num in free.keys() {
+        if !visited.contains(num) {
+            issues.push(XrefIssue::NotInFreeList { obj: *num })
+        }
+    }
+}
+
+// Checks the xref entries in effect after the last revision of the
+// file against the objects in the file.
+pub fn check_xref(path: &Path, data: &[u8]) -> PdfParseResult<XrefReport> {
+    let (fi, revs) = parse_revisions(path, data)?;
+    let buf = &data[fi.file_offset(0) ..];
+    let ents: BTreeMap<usize, XrefEntT> = merged_ents(&revs)
+        .iter()
+        .map(|e| (e.val().obj(), *e.val()))
+        .collect();
+    let mut issues = Vec::new();
+    // The object counts of the object streams, which are shared by
+    // their compressed entries.
+    let pb = ParseBuffer::new(buf.to_vec());
+    let mut counts: BTreeMap<usize, Option<usize>> = BTreeMap::new();
+
+    for (num, e) in &ents {
+        let id = (*num, e.gen());
+        match e.status() {
+            XrefEntStatus::Free { .. } => (),
+            XrefEntStatus::InUse { file_ofs } => {
+                let ofs = *file_ofs;
+                if ofs >= buf.len() {
+                    issues.push(XrefIssue::OutOfBounds { id, ofs })
+                } else {
+                    match obj_header(buf, ofs) {
+                        Some(found) if found == id => (),
+                        Some(found) => issues.push(XrefIssue::HeaderMismatch { id, ofs, found }),
+                        None => issues.push(XrefIssue::NoObjectHeader { id, ofs }),
+                    }
+                }
+            },
+            XrefEntStatus::InStream {
+                stream_obj,
+                obj_index,
+            } => {
+                let stream_obj = *stream_obj;
+                let count = *counts.entry(stream_obj).or_insert_with(|| {
+                    match ents.get(&stream_obj).map(|s| *s.status()) {
+                        Some(XrefEntStatus::InUse { file_ofs }) if file_ofs < buf.len() => {
+                            obj_stream_count(&pb, buf, file_ofs, stream_obj)
+                        },
+                        _ => None,
+                    }
+                });
+                match count {
+                    Some(count) if *obj_index < count => (),
+                    Some(count) => issues.push(XrefIssue::BadStreamIndex {
+                        id,
+                        stream_obj,
+                        index: *obj_index,
+                        count,
+                    }),
+                    None => issues.push(XrefIssue::NotObjectStream { id, stream_obj }),
+                }
+            },
+        }
+    }
+    check_free_list(&ents, &mut issues);
+
+    let expected = ents.keys().next_back().map_or(0, |n| n + 1);
+    let size = revs
+        .last()
+        .and_then(|r| r.trailer())
+        .and_then(|t| t.val().get_usize(b"Size"));
+    if size != Some(expected) {
+        issues.push(XrefIssue::SizeMismatch { size, expected })
+    }
+    Ok(XrefReport {
+        ents: ents.into_values().collect(),
+        issues,
+    })
+}
+
+#[cfg(test)]
+mod test_xref_check {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::{check_xref, XrefIssue};
+    use crate::pdf_lib::pdf_streams::XrefEntStatus;
+
+    fn get_test_data(test_file: &str) -> Vec<u8> {
+        let mut file = match File::open(test_file) {
+            Err(why) => panic!("Cannot open {}: {}", test_file, why),
+            Ok(f) => f,
+        };
+        let mut v = Vec::new();
+        if let Err(why) = file.read_to_end(&mut v) {
+            panic!("Cannot read {}: {}", test_file, why)
+        };
+        v
+    }
+
+    #[test]
+    fn test_xref_check() {
+        let path = Path::new("minimal.pdf");
+        let v = get_test_data("tests/test_files/minimal.pdf");
+        let r = check_xref(path, &v).unwrap();
+        assert_eq!(r.ents().len(), 5);
+        assert!(r.is_consistent(), "{:?}", r.issues());
+
+        // an update with a stale offset, an object in a missing object
+        // stream, and a broken free list
+        let xref = v.len();
+        let mut w = v.clone();
+        w.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000000 65535 f \n2 1\n0000000018 00000 n \n5 3\n\
+                 0000000006 00000 f \n0000000000 00001 f \n0000000001 00000 n \n\
+                 trailer\n<< /Root 1 0 R /Size 9 /Prev 565 >>\nstartxref\n{}\n%%EOF\n",
+                xref
+            )
+            .as_bytes(),
+        );
+        let r = check_xref(path, &w).unwrap();
+        assert_eq!(
+            r.issues(),
+            &[
+                XrefIssue::HeaderMismatch {
+                    id:    (2, 0),
+                    ofs:   18,
+                    found: (1, 0),
+                },
+                XrefIssue::NoObjectHeader {
+                    id:  (7, 0),
+                    ofs: 1,
+                },
+                XrefIssue::NotInFreeList { obj: 5 },
+                XrefIssue::NotInFreeList { obj: 6 },
+                XrefIssue::SizeMismatch {
+                    size:     Some(9),
+                    expected: 8,
+                },
+            ]
+        );
+
+        let mut w = v.clone();
+        w.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000005 65535 f \n5 2\n0000000006 00001 f \n0000000005 00001 f \n\
+                 trailer\n<< /Root 1 0 R /Size 7 /Prev 565 >>\nstartxref\n{}\n%%EOF\n",
+                xref
+            )
+            .as_bytes(),
+        );
+        let r = check_xref(path, &w).unwrap();
+        assert_eq!(r.issues(), &[XrefIssue::FreeListCycle { obj: 5 }]);
+
+        let mut w = v.clone();
+        w.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000003 00000 f \n\
+                 trailer\n<< /Root 1 0 R /Size 5 /Prev 565 >>\nstartxref\n{}\n%%EOF\n",
+                xref
+            )
+            .as_bytes(),
+        );
+        let r = check_xref(path, &w).unwrap();
+        assert_eq!(
+            r.issues(),
+            &[
+                XrefIssue::BadFreeListHead {
+                    status: Some(XrefEntStatus::Free { next: 3 }),
+                    gen:    0,
+                },
+                XrefIssue::BadFreeLink { from: 0, to: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_obj_stream_entries() {
+        let path = Path::new("objstm.pdf");
+        let mut v = get_test_data("tests/test_files/minimal.pdf");
+        let ofs = v.len();
+        v.extend_from_slice(
+            b"5 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Length 7 >>\nstream\n6 0 (x)\nendstream\nendobj\n",
+        );
+        let xref = v.len();
+        v.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000000 65535 f \n5 1\n{:010} 00000 n \n\
+                 trailer\n<< /Root 1 0 R /Size 8 /Prev 565 >>\nstartxref\n{}\n%%EOF\n",
+                ofs, xref
+            )
+            .as_bytes(),
+        );
+        // an xref stream update with entries for objects 6 and 7 in
+        // the object stream, and object 8 in object 3
+        let ents = [(6, 0), (5, 1), (3, 0)];
+        let mut strm = Vec::new();
+        for (so, idx) in ents.iter() {
+            strm.extend_from_slice(&[2, *so, *idx])
+        }
+        let prev = xref;
+        let xref = v.len();
+        v.extend_from_slice(
+            format!(
+                "9 0 obj\n<< /Type /XRef /Size 9 /Index [6 3] /W [1 1 1] /Root 1 0 R /Prev {} /Length {} >>\nstream\n",
+                prev,
+                strm.len()
+            )
+            .as_bytes(),
+        );
+        v.extend_from_slice(&strm);
+        v.extend_from_slice(
+            format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref).as_bytes(),
+        );
+        let r = check_xref(path, &v).unwrap();
+        assert_eq!(
+            r.issues(),
+            &[
+                XrefIssue::NotObjectStream {
+                    id:         (6, 0),
+                    stream_obj: 6,
+                },
+                XrefIssue::BadStreamIndex {
+                    id:         (7, 0),
+                    stream_obj: 5,
+                    index:      1,
+                    count:      1,
+                },
+                XrefIssue::NotObjectStream {
+                    id:         (8, 0),
+                    stream_obj: 3,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,468 @@
+// Copyright (c) 2019-2020 SRI International.
+// All rights reserved.
+//
+//    This file is part of the Parsley parser.
+//
+//    Parsley is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    Parsley is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Reconstruction of the cross-reference information of damaged files.
+//
+// When the xref information of a file is missing or inconsistent
+// with its body, a synthetic xref can be rebuilt by scanning the
+// whole file for 'n g obj ... endobj' spans.  When an object id is
+// defined more than once, the definition found last in the file is
+// considered the newest, as it would be for an incremental update.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use log::{log, Level};
+
+use crate::pcore::parsebuffer::{ErrorKind, LocatedVal, ParseBufferT, ParsleyParser};
+use crate::pcore::transforms::{BufferTransformT, RestrictView};
+use crate::pdf_lib::pdf_file::TrailerP;
+use crate::pdf_lib::pdf_obj::{DictT, IndirectP, ObjectId, PDFObjContext, PDFObjT, ReferenceT};
+use crate::pdf_lib::pdf_streams::{ObjStreamP, XrefEntStatus, XrefEntT};
+use crate::pdf_lib::pdf_traverse_xref::{FileInfo, PdfParseError};
+
+macro_rules! ta3_log {
+    ($lvl:expr, $pos:expr, $($arg:tt)+) => ({
+        log!($lvl, "at {:>10} - {}", $pos, format_args!($($arg)+))
+    })
+}
+
+// The maximum number of digits accepted in the object number and
+// generation of a candidate 'n g obj' header.
+const MAX_NUM_DIGITS: usize = 10;
+const MAX_GEN_DIGITS: usize = 5;
+
+fn is_pdf_whitespace(b: u8) -> bool { b" \0\t\r\n\x0c".contains(&b) }
+fn is_pdf_delimiter(b: u8) -> bool { b"()<>[]{}/%".contains(&b) }
+
+// A disagreement between the original xref information of a file
+// and the xref reconstructed from its body.  A missing original
+// status indicates that the object was not marked in-use in the
+// original xref; a missing found status indicates that the object
+// could not be found in the body.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct XrefMismatch {
+    id:    ObjectId,
+    xref:  Option<XrefEntStatus>,
+    found: Option<XrefEntStatus>,
+}
+
+impl XrefMismatch {
+    pub fn id(&self) -> ObjectId { self.id }
+    pub fn xref(&self) -> Option<XrefEntStatus> { self.xref }
+    pub fn found(&self) -> Option<XrefEntStatus> { self.found }
+}
+
+// Describes how a file was parsed using a reconstructed xref.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecoveryReport {
+    // the failure that triggered the reconstruction
+    cause:      PdfParseError,
+    mismatches: Vec<XrefMismatch>,
+}
+
+impl RecoveryReport {
+    pub fn new(cause: PdfParseError, mismatches: Vec<XrefMismatch>) -> RecoveryReport {
+        RecoveryReport { cause, mismatches }
+    }
+    pub fn cause(&self) -> &PdfParseError { &self.cause }
+    pub fn mismatches(&self) -> &[XrefMismatch] { self.mismatches.as_slice() }
+}
+
+// The synthetic xref built from a scan of the file body.
+pub struct RecoveredXref {
+    ents:    Vec<LocatedVal<XrefEntT>>,
+    root:    Option<Arc<LocatedVal<PDFObjT>>>,
+    // the /Encrypt and /ID entries of the trailer providing the root
+    encrypt: Option<Arc<LocatedVal<PDFObjT>>>,
+    file_id: Option<Arc<LocatedVal<PDFObjT>>>,
+}
+
+impl RecoveredXref {
+    pub fn ents(&self) -> &[LocatedVal<XrefEntT>] { self.ents.as_slice() }
+    pub fn root(&self) -> Option<&Arc<LocatedVal<PDFObjT>>> { self.root.as_ref() }
+    pub fn is_encrypted(&self) -> bool { self.encrypt.is_some() }
+    pub fn encrypt(&self) -> Option<&Arc<LocatedVal<PDFObjT>>> { self.encrypt.as_ref() }
+    pub fn file_id(&self) -> Option<&Arc<LocatedVal<PDFObjT>>> { self.file_id.as_ref() }
+}
+
+// Checks whether the 'obj' keyword at 'ofs' terminates an 'n g obj'
+// object header, and if so, returns the start of the header and the
+// object id.
+fn obj_header_at(buf: &[u8], ofs: usize) -> Option<(usize, ObjectId)> {
+    // The keyword should be properly terminated.
+    if let Some(b) = buf.get(ofs + 3) {
+        if !is_pdf_whitespace(*b) && !is_pdf_delimiter(*b) {
+            return None
+        }
+    }
+    // Scan backwards over: digits, whitespace, digits, whitespace.
+    fn skip_ws(buf: &[u8], mut i: usize) -> Option<usize> {
+        let end = i;
+        while i > 0 && is_pdf_whitespace(buf[i - 1]) {
+            i -= 1
+        }
+        if i == end {
+            None
+        } else {
+            Some(i)
+        }
+    }
+    fn skip_digits(buf: &[u8], mut i: usize, max: usize) -> Option<(usize, usize)> {
+        let end = i;
+        while i > 0 && buf[i - 1].is_ascii_digit() && end - i < max {
+            i -= 1
+        }
+        if i == end || (i > 0 && buf[i - 1].is_ascii_digit()) {
+            return None
+        }
+        let s = std::str::from_utf8(&buf[i .. end]).ok()?;
+        Some((i, s.parse::<usize>().ok()?))
+    }
+    let i = skip_ws(buf, ofs)?;
+    let (i, gen) = skip_digits(buf, i, MAX_GEN_DIGITS)?;
+    let i = skip_ws(buf, i)?;
+    let (start, num) = skip_digits(buf, i, MAX_NUM_DIGITS)?;
+    // The header should not be glued to a preceding token.
+    if start > 0 && !is_pdf_whitespace(buf[start - 1]) && !is_pdf_delimiter(buf[start - 1]) {
+        return None
+    }
+    Some((start, (num, gen)))
+}
+
+// Collects the candidate object headers in the buffer, in file order.
+fn find_obj_headers(buf: &[u8]) -> Vec<(usize, ObjectId)> {
+    let mut cands = Vec::new();
+    let mut ofs = 0;
+    while ofs + 3 <= buf.len() {
+        if &buf[ofs .. ofs + 3] == b"obj" {
+            if let Some(c) = obj_header_at(buf, ofs) {
+                cands.push(c)
+            }
+            ofs += 3
+        } else {
+            ofs += 1
+        }
+    }
+    cands
+}
+
+// Collects the offsets of the 'trailer' keywords in the buffer.
+fn find_trailers(buf: &[u8]) -> Vec<usize> {
+    buf.windows(7)
+        .enumerate()
+        .filter(|(_, w)| *w == b"trailer")
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// A definition found during the scan; 'pos' is used to order
+// definitions of the same object.
+struct Found {
+    pos:    usize,
+    status: XrefEntStatus,
+    start:  usize,
+    end:    usize,
+}
+
+impl Found {
+    fn new(pos: usize, status: XrefEntStatus, start: usize, end: usize) -> Found {
+        Found {
+            pos,
+            status,
+            start,
+            end,
+        }
+    }
+}
+
+// Scans the entire buffer and reconstructs the xref information.
+// The buffer cursor is left in an unspecified position.
+pub fn reconstruct_xref(fi: &FileInfo, pb: &mut dyn ParseBufferT) -> RecoveredXref {
+    pb.set_cursor_unsafe(0);
+    let cands = find_obj_headers(pb.buf());
+    let trailer_ofs = find_trailers(pb.buf());
+
+    // Parse the candidate objects in a scratch context, which allows
+    // stream lengths specified by references to be resolved.
+    let mut ctxt = PDFObjContext::new(50);
+    let mut found: BTreeMap<ObjectId, Found> = BTreeMap::new();
+    // Trailer-like dictionaries and their locations.
+    let mut trailers: Vec<(usize, Arc<LocatedVal<DictT>>)> = Vec::new();
+    let mut obj_streams: Vec<ObjectId> = Vec::new();
+    let mut next = 0;
+    for (start, id) in cands {
+        // Skip headers inside the span of an already parsed object,
+        // e.g. in the content of a stream.
+        if start < next {
+            continue
+        }
+        let _ = ctxt.remove_obj(id);
+        pb.set_cursor_unsafe(start);
+        let mut p = IndirectP::new(&mut ctxt);
+        match p.parse(pb) {
+            Ok(io) => {
+                next = io.end();
+                let obj = io.val().obj();
+                if let PDFObjT::Stream(s) = obj.val() {
+                    match s.dict().val().get_name(b"Type") {
+                        Some(b"XRef") => trailers.push((start, Arc::clone(s.dict()))),
+                        Some(b"ObjStm") => obj_streams.push(id),
+                        _ => (),
+                    }
+                }
+                let status = XrefEntStatus::InUse { file_ofs: start };
+                found.insert(id, Found::new(start, status, start, io.end()));
+            },
+            Err(e) => {
+                if let ErrorKind::InsufficientContext = e.val() {
+                    // The length of this stream will be resolved when
+                    // the objects are parsed using the rebuilt xref.
+                    let status = XrefEntStatus::InUse { file_ofs: start };
+                    found.insert(id, Found::new(start, status, start, start));
+                } else {
+                    ta3_log!(
+                        Level::Info,
+                        fi.file_offset(start),
+                        "skipping unparseable object ({},{}) during xref recovery: {}",
+                        id.0,
+                        id.1,
+                        e.val()
+                    )
+                }
+            },
+        }
+    }
+
+    // Collect the objects in the newest definitions of the object
+    // streams.
+    for sid in obj_streams {
+        let (spos, send) = match found.get(&sid) {
+            Some(f) => (f.start, f.end),
+            None => continue,
+        };
+        let sobj = match ctxt.lookup_obj(sid) {
+            Some(o) => Arc::clone(o),
+            None => continue,
+        };
+        let s = match sobj.val() {
+            PDFObjT::Stream(s) => s,
+            _ => continue, // an older definition was a stream
+        };
+        let content = s.stream().val();
+        let mut view = RestrictView::new(content.start(), content.size());
+        let mut obj_buf = match view.transform(pb) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        let mut sctxt = PDFObjContext::new(50);
+        let mut op = ObjStreamP::new(&mut sctxt, s);
+        let obj_stm = match op.parse(&mut obj_buf) {
+            Ok(o) => o,
+            Err(e) => {
+                ta3_log!(
+                    Level::Info,
+                    fi.file_offset(spos),
+                    "skipping unparseable object stream ({},{}) during xref recovery: {}",
+                    sid.0,
+                    sid.1,
+                    e.val()
+                );
+                continue
+            },
+        };
+        for (idx, o) in obj_stm.val().objs().iter().enumerate() {
+            let id = (o.val().num(), o.val().gen());
+            if let Some(f) = found.get(&id) {
+                if f.pos > spos {
+                    continue
+                }
+            }
+            let status = XrefEntStatus::InStream {
+                stream_obj: sid.0,
+                obj_index:  idx,
+            };
+            found.insert(id, Found::new(spos, status, spos, send));
+        }
+    }
+
+    // Collect the trailers.
+    for ofs in trailer_ofs {
+        pb.set_cursor_unsafe(ofs);
+        let mut p = TrailerP::new(&mut ctxt);
+        if let Ok(t) = p.parse(pb) {
+            let dict = t.place(DictT::new(t.val().dict().map().clone()));
+            trailers.push((ofs, Arc::new(dict)))
+        }
+    }
+    trailers.sort_by_key(|(ofs, _)| *ofs);
+
+    // Use the newest trailer whose root was found, falling back to
+    // the newest catalog object.
+    let mut root = None;
+    let (mut encrypt, mut file_id) = (None, None);
+    for (_, t) in trailers.iter().rev() {
+        if let Some(r) = t.val().get(b"Root") {
+            if let PDFObjT::Reference(rf) = r.val() {
+                if found.contains_key(&rf.id()) {
+                    root = Some(Arc::clone(r));
+                    encrypt = t.val().get(b"Encrypt").cloned();
+                    file_id = t.val().get(b"ID").cloned();
+                    break
+                }
+            }
+        }
+    }
+    if root.is_none() {
+        let mut catalog = None;
+        for (id, f) in found.iter() {
+            if let Some(o) = ctxt.lookup_obj(*id) {
+                if let PDFObjT::Dict(d) = o.val() {
+                    if d.get_name(b"Type") != Some(b"Catalog") {
+                        continue
+                    }
+                    match catalog {
+                        Some((_, pos, _, _)) if pos > f.pos => (),
+                        _ => catalog = Some((*id, f.pos, f.start, f.end)),
+                    }
+                }
+            }
+        }
+        if let Some((id, _, start, end)) = catalog {
+            ta3_log!(
+                Level::Warn,
+                fi.file_offset(start),
+                "no usable trailer found, using catalog ({},{}) as root",
+                id.0,
+                id.1
+            );
+            let r = PDFObjT::Reference(ReferenceT::new(id.0, id.1));
+            root = Some(Arc::new(LocatedVal::new(r, start, end)))
+        }
+    }
+
+    let ents = found
+        .iter()
+        .map(|((num, gen), f)| {
+            let ent = XrefEntT::new(*num, *gen, f.status);
+            LocatedVal::new(ent, f.start, f.end)
+        })
+        .collect();
+    RecoveredXref {
+        ents,
+        root,
+        encrypt,
+        file_id,
+    }
+}
+
+// Compares the in-use entries of the original xref information with
+// those of the reconstructed xref.
+pub fn compare_xref(
+    orig: &[LocatedVal<XrefEntT>], recovered: &[LocatedVal<XrefEntT>],
+) -> Vec<XrefMismatch> {
+    let mut omap = BTreeMap::new();
+    for e in orig {
+        let e = e.val();
+        omap.insert((e.obj(), e.gen()), *e.status());
+    }
+    let mut rmap = BTreeMap::new();
+    for e in recovered {
+        let e = e.val();
+        rmap.insert((e.obj(), e.gen()), *e.status());
+    }
+    let mut mismatches = Vec::new();
+    for (id, st) in omap.iter() {
+        if let XrefEntStatus::Free { .. } = st {
+            continue
+        }
+        let found = rmap.get(id).copied();
+        if found != Some(*st) {
+            mismatches.push(XrefMismatch {
+                id: *id,
+                xref: Some(*st),
+                found,
+            })
+        }
+    }
+    for (id, st) in rmap.iter() {
+        match omap.get(id) {
+            None | Some(XrefEntStatus::Free { .. }) => mismatches.push(XrefMismatch {
+                id:    *id,
+                xref:  None,
+                found: Some(*st),
+            }),
+            Some(_) => (), // already compared above
+        }
+    }
+    mismatches.sort_by_key(|m| m.id);
+    mismatches
+}
+
+#[cfg(test)]
+mod test_xref_recovery {
+    use super::{compare_xref, find_obj_headers, XrefMismatch};
+    use crate::pcore::parsebuffer::LocatedVal;
+    use crate::pdf_lib::pdf_streams::{XrefEntStatus, XrefEntT};
+
+    #[test]
+    fn test_obj_headers() {
+        //                  1         2         3         4
+        //        0123456789012345678901234567890123456789012345
+        let v = b"1 0 obj\n<<>>\nendobj 12 3\r\nobj x4 0 obj 5 0 objx";
+        let hdrs = find_obj_headers(v);
+        assert_eq!(hdrs, vec![(0, (1, 0)), (20, (12, 3))]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mk = |n, g, st| LocatedVal::new(XrefEntT::new(n, g, st), 0, 0);
+        let orig = vec![
+            mk(0, 65535, XrefEntStatus::Free { next: 0 }),
+            mk(1, 0, XrefEntStatus::InUse { file_ofs: 10 }),
+            mk(2, 0, XrefEntStatus::InUse { file_ofs: 20 }),
+            mk(3, 0, XrefEntStatus::InUse { file_ofs: 30 }),
+        ];
+        let rec = vec![
+            mk(1, 0, XrefEntStatus::InUse { file_ofs: 10 }),
+            mk(2, 0, XrefEntStatus::InUse { file_ofs: 25 }),
+            mk(4, 0, XrefEntStatus::InUse { file_ofs: 40 }),
+        ];
+        let m = compare_xref(&orig, &rec);
+        assert_eq!(
+            m,
+            vec![
+                XrefMismatch {
+                    id:    (2, 0),
+                    xref:  Some(XrefEntStatus::InUse { file_ofs: 20 }),
+                    found: Some(XrefEntStatus::InUse { file_ofs: 25 }),
+                },
+                XrefMismatch {
+                    id:    (3, 0),
+                    xref:  Some(XrefEntStatus::InUse { file_ofs: 30 }),
+                    found: None,
+                },
+                XrefMismatch {
+                    id:    (4, 0),
+                    xref:  None,
+                    found: Some(XrefEntStatus::InUse { file_ofs: 40 }),
+                },
+            ]
+        );
+    }
+}
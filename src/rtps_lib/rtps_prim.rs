@@ -121,6 +121,8 @@ impl ParsleyParser for HeaderP {
         let start = buf.get_cursor();
         match buf.exact(b"RTPS") {
             Ok(_) => (),
+            // A truncated magic needs more input.
+            Err(e) if b"RTPS".starts_with(buf.buf()) => return Err(e.place(ErrorKind::EndOfBuffer)),
             Err(e) => {
                 let err = ErrorKind::GuardError("invalid magic".to_string());
                 return Err(e.place(err))